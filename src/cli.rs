@@ -2,7 +2,7 @@
 //!
 //! 使用 `clap` 库定义和解析命令行参数。
 
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand};
 use std::path::PathBuf;
 
 /// 图片批量下载与处理程序
@@ -24,17 +24,59 @@ pub struct Cli {
     #[arg(short = 'l', long, global = true, default_value = "info")]
     pub log_level: String,
 
+    /// 日志格式 (text, json) (默认: text)
+    #[arg(long, global = true, default_value = "text", value_parser = ["text", "json"])]
+    pub log_format: String,
+
+    /// 日志文件路径，日志同时按天轮转写入该文件（覆盖配置中的 log_file）
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// 安静模式：只输出错误日志，不显示进度条
+    #[arg(short = 'q', long, global = true, conflicts_with_all = ["verbose", "log_level"])]
+    pub quiet: bool,
+
+    /// 输出更详细的日志（-v 为 debug，-vv 为 trace）
+    #[arg(short = 'v', long, global = true, action = ArgAction::Count, conflicts_with = "log_level")]
+    pub verbose: u8,
+
+    /// 本次运行使用的输出目录，覆盖配置中的 output_dir（不存在时创建，不会写回配置文件）
+    #[arg(long, global = true, value_name = "PATH")]
+    pub output_dir: Option<PathBuf>,
+
+    /// 控制台输出语言 (zh, en)，覆盖配置中的 lang 和 LANG 环境变量
+    #[arg(long, global = true, value_enum)]
+    pub lang: Option<crate::i18n::Lang>,
+
+    /// 不输出颜色（设置 NO_COLOR 环境变量或输出不是终端时同样不输出颜色）
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
     /// 子命令 (默认: run)
     #[command(subcommand)]
     pub command: Option<Command>,
 }
 
+impl Cli {
+    /// 综合 `--log-level`、`--quiet` 和 `-v` 得到实际使用的日志级别
+    pub fn effective_log_level(&self) -> &str {
+        if self.quiet {
+            return "error";
+        }
+        match self.verbose {
+            0 => &self.log_level,
+            1 => "debug",
+            _ => "trace",
+        }
+    }
+}
+
 /// 子命令
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// 从起始日期批量下载到当前或指定结束日期
     Run {
-        /// 起始日期 (格式: YYYY-MM-DD)
+        /// 起始日期 (格式: YYYY-MM-DD，或 today、yesterday、N-days-ago、last-N-days)
         ///
         /// 如果不指定则使用配置文件中的起始日期
         #[arg(long)]
@@ -46,9 +88,39 @@ pub enum Command {
         #[arg(long)]
         end_date: Option<String>,
 
+        /// 下载整月 (格式: YYYY-MM)，不能与 --start-date/--end-date 同时使用
+        #[arg(long, value_parser = parse_month_arg, conflicts_with_all = ["start_date", "end_date"])]
+        month: Option<String>,
+
+        /// 下载整年 (格式: YYYY)，当年只下载到今天，不能与 --start-date/--end-date/--month 同时使用
+        #[arg(
+            long,
+            value_parser = parse_year_arg,
+            conflicts_with_all = ["start_date", "end_date", "month"]
+        )]
+        year: Option<String>,
+
+        /// 从状态文件记录的上次成功日期的次日开始下载，忽略配置文件中的 start_date
+        ///
+        /// 尚无成功记录时报错，适合写在 cron 等定时任务中
+        #[arg(long, default_value_t = false, conflicts_with_all = ["start_date", "month", "year"])]
+        since_last_success: bool,
+
+        /// 本次运行使用的 URL 模板，覆盖配置文件中的 base_url（不会写回配置文件）
+        #[arg(long, value_name = "TEMPLATE")]
+        base_url: Option<String>,
+
+        /// 本次运行的请求超时时间（秒），优先于配置文件和 CALENDAR_TIMEOUT 环境变量
+        #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: Option<u64>,
+
+        /// 本次运行的并发下载数量，覆盖配置文件中的 max_concurrent
+        #[arg(long, value_name = "N")]
+        concurrency: Option<usize>,
+
         /// 覆盖已存在的文件
         ///
-        /// 默认情况下，已存在的文件会跳过下载，但仍然执行 EXIF 和文件属性更新
+        /// 默认情况下，已存在的文件会跳过下载，但仍会在元数据与日期不一致时更新 EXIF 和文件属性
         #[arg(long, default_value_t = false)]
         overwrite: bool,
 
@@ -57,11 +129,50 @@ pub enum Command {
         /// 适用于只需要下载文件的场景
         #[arg(long, default_value_t = false)]
         download_only: bool,
+
+        /// 已存在的文件即使元数据已是最新也重写 EXIF 和文件时间戳
+        #[arg(long, default_value_t = false, conflicts_with = "download_only")]
+        force_metadata: bool,
+
+        /// 跳过覆盖确认（用于脚本等非交互场景）
+        #[arg(short = 'y', long, default_value_t = false)]
+        yes: bool,
+
+        /// 以 JSON 格式输出结果（不显示进度条和文字统计）
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        /// 进度显示方式：bar 为进度条，json 为在标准输出逐行输出 JSON 进度事件（不输出文字统计）
+        #[arg(
+            long,
+            value_name = "MODE",
+            default_value = "bar",
+            value_parser = ["bar", "json"],
+            conflicts_with = "json"
+        )]
+        progress: String,
+
+        /// EXIF 或文件时间戳更新失败时也以失败状态退出
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+
+        /// 首个日期失败（重试之后）时停止调度剩余日期，等待进行中的任务结束后退出
+        #[arg(long, default_value_t = false)]
+        fail_fast: bool,
+
+        /// 整次运行的时间预算 (如 55m、2h)；剩余时间不足时停止调度新的日期，
+        /// 到期时中止进行中的任务，未完成的日期留待下次运行
+        #[arg(long, value_name = "DURATION", value_parser = date_utils::parse_duration)]
+        max_duration: Option<std::time::Duration>,
+
+        /// 输出目录被其他 calendar 进程锁定时等待，而不是立即退出
+        #[arg(long, default_value_t = false)]
+        wait: bool,
     },
 
     /// 处理指定日期的单个或多个文件
     Process {
-        /// 单个日期 (格式: YYYY-MM-DD)
+        /// 单个日期 (格式: YYYY-MM-DD，或 today、yesterday、N-days-ago、last-N-days)
         ///
         /// 如果需要处理多个日期，建议使用 --dates 参数
         #[arg(long)]
@@ -69,11 +180,57 @@ pub enum Command {
 
         /// 多个日期，使用逗号分隔或多次指定 (格式: YYYY-MM-DD,YYYY-MM-DD)
         ///
+        /// 支持 `起始..结束` 范围（包含两端）
         /// 示例: --dates 2024-06-15,2024-06-20,2024-06-25
         /// 或: --dates 2024-06-15 --dates 2024-06-20
-        #[arg(long, value_delimiter = ',', required_unless_present = "date")]
+        /// 或: --dates 2024-06-01..2024-06-10,2024-07-01
+        #[arg(
+            long,
+            value_delimiter = ',',
+            required_unless_present_any = ["date", "from", "month", "year", "dates_file"]
+        )]
         dates: Option<Vec<String>>,
 
+        /// 从文件读取日期，每行一个 (格式: YYYY-MM-DD)，`-` 表示标准输入
+        ///
+        /// 忽略空行和 `#` 注释，兼容失败记录中的 `日期<TAB>原因` 格式
+        #[arg(long, value_name = "FILE")]
+        dates_file: Option<PathBuf>,
+
+        /// 处理整月 (格式: YYYY-MM)，逗号分隔或多次指定
+        #[arg(long, value_delimiter = ',', value_parser = parse_month_arg)]
+        month: Option<Vec<String>>,
+
+        /// 处理整年 (格式: YYYY)，当年只处理到今天，逗号分隔或多次指定
+        #[arg(long, value_delimiter = ',', value_parser = parse_year_arg)]
+        year: Option<Vec<String>>,
+
+        /// 日期范围的起始日期 (格式: YYYY-MM-DD)，可与 --date/--dates 同时使用
+        #[arg(long)]
+        from: Option<String>,
+
+        /// 日期范围的结束日期 (格式: YYYY-MM-DD)
+        ///
+        /// 需要与 --from 同时使用，如果不指定则使用当前日期
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+
+        /// 按日期从新到旧处理（默认从旧到新）
+        #[arg(long, default_value_t = false)]
+        reverse: bool,
+
+        /// 本次运行使用的 URL 模板，覆盖配置文件中的 base_url（不会写回配置文件）
+        #[arg(long, value_name = "TEMPLATE")]
+        base_url: Option<String>,
+
+        /// 本次运行的请求超时时间（秒），优先于配置文件和 CALENDAR_TIMEOUT 环境变量
+        #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: Option<u64>,
+
+        /// 本次运行的并发处理数量 (默认: 1，不使用配置文件中的 max_concurrent)
+        #[arg(long, value_name = "N")]
+        concurrency: Option<usize>,
+
         /// 覆盖已存在的文件
         #[arg(long, default_value_t = false)]
         overwrite: bool,
@@ -83,6 +240,330 @@ pub enum Command {
         /// 适用于文件已存在但需要更新元数据的场景
         #[arg(long, default_value_t = false)]
         metadata_only: bool,
+
+        /// 已存在的文件即使元数据已是最新也重写 EXIF 和文件时间戳
+        #[arg(long, default_value_t = false)]
+        force_metadata: bool,
+
+        /// 跳过覆盖确认和大范围 --dates 的确认（用于脚本等非交互场景）
+        #[arg(short = 'y', long, default_value_t = false)]
+        yes: bool,
+
+        /// 以 JSON 格式输出结果（不显示进度条和文字统计）
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        /// 进度显示方式：bar 为进度条，json 为在标准输出逐行输出 JSON 进度事件（不输出文字统计）
+        #[arg(
+            long,
+            value_name = "MODE",
+            default_value = "bar",
+            value_parser = ["bar", "json"],
+            conflicts_with = "json"
+        )]
+        progress: String,
+
+        /// EXIF 或文件时间戳更新失败时也以失败状态退出
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+
+        /// 首个日期失败（重试之后）时停止调度剩余日期，等待进行中的任务结束后退出
+        #[arg(long, default_value_t = false)]
+        fail_fast: bool,
+
+        /// 输出目录被其他 calendar 进程锁定时等待，而不是立即退出
+        #[arg(long, default_value_t = false)]
+        wait: bool,
+    },
+
+    /// 重新处理失败日期记录文件中的日期
+    ///
+    /// 处理完成后，记录文件会被改写为仍然失败的日期；全部成功时删除该文件。
+    /// 其余参数与 process 相同
+    Retry {
+        /// 失败日期记录文件 (默认: 配置中的 failed_log_path)
+        ///
+        /// failed_log_path 含日期占位符时必须指定
+        #[arg(long)]
+        file: Option<PathBuf>,
+
+        /// 按日期从新到旧重试（默认从旧到新）
+        #[arg(long, default_value_t = false)]
+        reverse: bool,
+
+        /// 本次运行使用的 URL 模板，覆盖配置文件中的 base_url（不会写回配置文件）
+        #[arg(long, value_name = "TEMPLATE")]
+        base_url: Option<String>,
+
+        /// 本次运行的请求超时时间（秒），优先于配置文件和 CALENDAR_TIMEOUT 环境变量
+        #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: Option<u64>,
+
+        /// 本次运行的并发处理数量 (默认: 1，不使用配置文件中的 max_concurrent)
+        #[arg(long, value_name = "N")]
+        concurrency: Option<usize>,
+
+        /// 覆盖已存在的文件
+        #[arg(long, default_value_t = false)]
+        overwrite: bool,
+
+        /// 仅修改 EXIF 和文件属性，不下载
+        #[arg(long, default_value_t = false)]
+        metadata_only: bool,
+
+        /// 已存在的文件即使元数据已是最新也重写 EXIF 和文件时间戳
+        #[arg(long, default_value_t = false)]
+        force_metadata: bool,
+
+        /// 跳过覆盖确认（用于脚本等非交互场景）
+        #[arg(short = 'y', long, default_value_t = false)]
+        yes: bool,
+
+        /// 以 JSON 格式输出结果（不显示进度条和文字统计）
+        #[arg(long, default_value_t = false)]
+        json: bool,
+
+        /// 进度显示方式：bar 为进度条，json 为在标准输出逐行输出 JSON 进度事件（不输出文字统计）
+        #[arg(
+            long,
+            value_name = "MODE",
+            default_value = "bar",
+            value_parser = ["bar", "json"],
+            conflicts_with = "json"
+        )]
+        progress: String,
+
+        /// EXIF 或文件时间戳更新失败时也以失败状态退出
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+
+        /// 首个日期失败（重试之后）时停止调度剩余日期，等待进行中的任务结束后退出
+        #[arg(long, default_value_t = false)]
+        fail_fast: bool,
+
+        /// 输出目录被其他 calendar 进程锁定时等待，而不是立即退出
+        #[arg(long, default_value_t = false)]
+        wait: bool,
+    },
+
+    /// 常驻运行，按计划定时执行 run（Ctrl-C 或 SIGTERM 退出）
+    ///
+    /// 每个周期都会重新读取配置文件，期间手动执行的 run 推进的起始日期同样生效
+    Watch {
+        /// 每天运行的本地时间 (格式: HH:MM)，不指定则立即开始第一次运行
+        #[arg(long, value_name = "HH:MM", value_parser = parse_time_arg)]
+        at: Option<chrono::NaiveTime>,
+
+        /// 两次运行之间的间隔 (如 30m、12h、1d)
+        #[arg(long, default_value = "24h", value_parser = date_utils::parse_duration)]
+        interval: std::time::Duration,
+    },
+
+    /// 查看图库状态，列出起始日期到今天之间缺失的日期
+    Status {
+        /// 将缺失的日期写入文件（每行一个日期）
+        #[arg(long, value_name = "FILE")]
+        write_dates: Option<PathBuf>,
+
+        /// 以 JSON 格式输出
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// 校验输出目录中的所有图片，报告无效文件
+    Verify {
+        /// 删除无效文件
+        #[arg(long, default_value_t = false)]
+        delete_invalid: bool,
+
+        /// 重新下载无效文件对应的日期
+        #[arg(long, default_value_t = false)]
+        redownload: bool,
+    },
+
+    /// 查找输出目录中的无效文件，按文件名中的日期重新下载（覆盖原文件）
+    Repair {
+        /// 只列出无效文件，不下载
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// 输出目录被其他 calendar 进程锁定时等待，而不是立即退出
+        #[arg(long, default_value_t = false)]
+        wait: bool,
+    },
+
+    /// 统计输出目录中图库的文件数量、大小和日期覆盖情况
+    Stats {
+        /// 以 JSON 格式输出
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// 清理输出目录中的无效文件、临时文件和不符合文件名格式的文件
+    Clean {
+        /// 只列出将被删除的文件，不实际删除
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// 保留不符合文件名格式的文件（可能不是本程序创建的）
+        #[arg(long, default_value_t = false)]
+        keep_unrecognized: bool,
+
+        /// 同时删除写入 EXIF 前留下的备份文件（`*.orig`）
+        #[arg(long, default_value_t = false)]
+        backups: bool,
+    },
+
+    /// 删除保留期之外的旧文件（无法按文件名格式解析日期的文件不会被删除）
+    Prune {
+        /// 保留最近 N 天（含今天）的文件
+        #[arg(
+            long,
+            value_name = "N",
+            value_parser = clap::value_parser!(u32).range(1..),
+            required_unless_present = "before",
+            conflicts_with = "before"
+        )]
+        keep_days: Option<u32>,
+
+        /// 删除日期早于该日期的文件 (格式: YYYY-MM-DD)
+        #[arg(long, value_name = "DATE")]
+        before: Option<String>,
+
+        /// 只列出将被删除的文件，不实际删除
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+
+    /// 查找内容完全相同的文件，并可折叠为链接或删除多余副本
+    ///
+    /// 默认只报告重复文件，不做任何修改
+    Dedupe {
+        /// 保留最早日期的文件，其余替换为指向它的硬链接 (hard) 或符号链接 (sym)
+        #[arg(long, value_parser = ["hard", "sym"], conflicts_with = "delete")]
+        link: Option<String>,
+
+        /// 保留最早日期的文件，删除其余副本
+        #[arg(long, default_value_t = false)]
+        delete: bool,
+
+        /// 与 --link/--delete 同时使用时只列出将要进行的操作
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+
+    /// 将已有图库迁移到新的文件名格式，不重新下载
+    Rename {
+        /// 旧的文件名格式，用于解析已有文件的日期
+        #[arg(long)]
+        from: String,
+
+        /// 新的文件名格式 (默认: 配置文件中的 filename_format)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// 只列出将要进行的重命名，不实际修改
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+
+    /// 按当前的文件名格式和 year_subdirs 设置整理已有文件的目录位置
+    Migrate {
+        /// 只列出将要进行的移动，不实际修改
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// 覆盖已存在的目标文件
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+
+    /// 为已下载的文件重新写入 EXIF 日期和文件时间戳，不下载
+    Touch {
+        /// 起始日期 (格式: YYYY-MM-DD)
+        #[arg(long, required_unless_present = "all")]
+        start_date: Option<String>,
+
+        /// 结束日期 (格式: YYYY-MM-DD)
+        ///
+        /// 如果不指定则使用当前日期
+        #[arg(long)]
+        end_date: Option<String>,
+
+        /// 扫描输出目录，按 filename_format 从文件名解析日期并处理所有文件
+        ///
+        /// 无法解析日期的文件会被列出，不计为失败
+        #[arg(
+            long,
+            visible_alias = "from-filename",
+            default_value_t = false,
+            conflicts_with_all = ["start_date", "end_date"]
+        )]
+        all: bool,
+
+        /// 并发处理的文件数量 (默认: 配置中的 max_concurrent)
+        #[arg(long)]
+        concurrency: Option<usize>,
+    },
+
+    /// 列出日期范围内的下载 URL 和保存路径，不访问网络
+    List {
+        /// 起始日期 (格式: YYYY-MM-DD，或 today、yesterday、N-days-ago、last-N-days)
+        ///
+        /// 如果不指定则使用配置文件中的起始日期
+        #[arg(long)]
+        start_date: Option<String>,
+
+        /// 结束日期 (格式: YYYY-MM-DD)
+        ///
+        /// 如果不指定则使用当前日期
+        #[arg(long)]
+        end_date: Option<String>,
+
+        /// 输出格式 (text, tsv, json) (默认: text)
+        #[arg(long, default_value = "text", value_parser = ["text", "tsv", "json"])]
+        format: String,
+
+        /// 只列出本地不存在的日期
+        #[arg(long, default_value_t = false)]
+        missing_only: bool,
+    },
+
+    /// 打印指定日期的下载 URL，不访问网络
+    Url {
+        /// 日期 (格式: YYYY-MM-DD，或 today、yesterday、N-days-ago、last-N-days、起始..结束)
+        #[arg(required = true)]
+        dates: Vec<String>,
+
+        /// 同时打印保存路径（与 URL 以制表符分隔）
+        #[arg(long, default_value_t = false)]
+        path: bool,
+    },
+
+    /// EXIF 信息相关操作
+    Exif {
+        #[command(subcommand)]
+        command: ExifCommand,
+    },
+
+    /// 检查配置、输出目录、网络连接、EXIF 写入和磁盘空间
+    Doctor,
+
+    /// 生成 man 手册（roff 格式），供打包使用
+    #[command(hide = true)]
+    Man {
+        /// 输出目录，每个命令写入一个 `.1` 文件 (默认: 全部输出到标准输出)
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+
+    /// 生成 shell 补全脚本并输出到标准输出
+    ///
+    /// 示例: calendar completions bash > /etc/bash_completion.d/calendar
+    Completions {
+        /// 目标 shell
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
     },
 
     /// 配置文件验证
@@ -93,7 +574,77 @@ pub enum Command {
     },
 }
 
+/// exif 子命令
+#[derive(Subcommand, Debug)]
+pub enum ExifCommand {
+    /// 查看文件的 EXIF 日期字段和文件修改时间，并与期望日期对比
+    Show {
+        /// 文件路径
+        #[arg(required_unless_present = "date")]
+        path: Option<PathBuf>,
+
+        /// 按日期定位输出目录中的文件 (格式: YYYY-MM-DD)
+        #[arg(long, conflicts_with = "path")]
+        date: Option<String>,
+
+        /// 以 JSON 格式输出
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// 清除文件中嵌入的 EXIF 和 XMP 元数据，图像数据保持不变
+    Strip {
+        /// 文件路径，可指定多个
+        #[arg(required_unless_present_any = ["date", "all"], conflicts_with_all = ["date", "all"])]
+        paths: Vec<PathBuf>,
+
+        /// 按日期定位输出目录中的文件，逗号分隔或多次指定，支持 `起始..结束` 范围
+        #[arg(long, value_delimiter = ',', conflicts_with = "all")]
+        date: Option<Vec<String>>,
+
+        /// 处理输出目录中的所有文件
+        #[arg(long, default_value_t = false)]
+        all: bool,
+
+        /// 保留 DateTimeOriginal、CreateDate、ModifyDate 日期标签
+        #[arg(long, default_value_t = false)]
+        keep_dates: bool,
+
+        /// 不恢复文件的访问和修改时间（默认清除后恢复为原值）
+        #[arg(long, default_value_t = false)]
+        also_reset_mtime: bool,
+
+        /// 仅列出含有元数据的文件，不做修改
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
 impl Command {
+    /// 未指定子命令时使用的默认命令：不带任何覆盖参数的 `run`
+    pub fn default_run() -> Self {
+        Command::Run {
+            start_date: None,
+            end_date: None,
+            month: None,
+            year: None,
+            since_last_success: false,
+            base_url: None,
+            timeout: None,
+            concurrency: None,
+            overwrite: false,
+            download_only: false,
+            force_metadata: false,
+            yes: false,
+            json: false,
+            progress: "bar".to_string(),
+            strict: false,
+            fail_fast: false,
+            max_duration: None,
+            wait: false,
+        }
+    }
+
     /// 获取日期列表
     pub fn get_dates(&self) -> Result<Vec<String>, AppError> {
         match self {
@@ -101,64 +652,342 @@ impl Command {
                 // run 命令的日期由 main.rs 根据 start_date 和 end_date 生成
                 Ok(vec![])
             }
+            Command::Doctor => {
+                // doctor 命令使用今天的日期探测 URL
+                Ok(vec![])
+            }
+            Command::Man { .. } => {
+                // man 命令不需要日期
+                Ok(vec![])
+            }
+            Command::Completions { .. } => {
+                // completions 命令不需要日期
+                Ok(vec![])
+            }
             Command::Config { .. } => {
                 // config 命令不需要日期
                 Ok(vec![])
             }
-            Command::Process { date, dates, .. } => {
+            Command::Status { .. } => {
+                // status 命令的日期范围由配置文件决定
+                Ok(vec![])
+            }
+            Command::Verify { .. } => {
+                // verify 命令的日期从输出目录中的文件推断
+                Ok(vec![])
+            }
+            Command::Repair { .. } => {
+                // repair 命令的日期从无效文件的文件名解析
+                Ok(vec![])
+            }
+            Command::Stats { .. } => {
+                // stats 命令的日期从输出目录中的文件推断
+                Ok(vec![])
+            }
+            Command::Clean { .. } => {
+                // clean 命令不需要日期
+                Ok(vec![])
+            }
+            Command::Prune { .. } => {
+                // prune 命令的日期从已有文件名解析
+                Ok(vec![])
+            }
+            Command::Dedupe { .. } => {
+                // dedupe 命令的日期从已有文件名解析
+                Ok(vec![])
+            }
+            Command::Rename { .. } => {
+                // rename 命令的日期从已有文件名解析
+                Ok(vec![])
+            }
+            Command::Migrate { .. } => {
+                // migrate 命令的日期从已有文件名解析
+                Ok(vec![])
+            }
+            Command::List { .. } => {
+                // list 命令的日期由 main.rs 根据 start_date 和 end_date 生成
+                Ok(vec![])
+            }
+            Command::Url { dates, .. } => {
+                // 按输入顺序展开，保留重复的日期
+                let today = date_utils::today();
                 let mut date_list = vec![];
+                for value in dates {
+                    let (start, end) = date_utils::resolve_date_expr(value, today)?;
+                    date_list.extend(
+                        date_utils::date_range(start, end)
+                            .iter()
+                            .map(date_utils::format_date),
+                    );
+                }
+                Ok(date_list)
+            }
+            Command::Exif { .. } => {
+                // exif 命令自行解析日期
+                Ok(vec![])
+            }
+            Command::Touch { .. } => {
+                // touch 命令的日期由 main.rs 根据日期范围或输出目录生成
+                Ok(vec![])
+            }
+            Command::Watch { .. } => {
+                // watch 命令每个周期按配置文件中的起始日期执行 run
+                Ok(vec![])
+            }
+            Command::Retry { .. } => {
+                // retry 命令的日期从失败日期记录文件读取
+                Ok(vec![])
+            }
+            Command::Process {
+                date,
+                dates,
+                month,
+                year,
+                from,
+                to,
+                dates_file,
+                yes,
+                reverse,
+                ..
+            } => {
+                let mut date_list = vec![];
+                let today = date_utils::today();
+
+                // 展开 today、last-N-days 等相对日期关键字和 起始..结束 范围
+                for value in date.iter().chain(dates.iter().flatten()) {
+                    let (start, end) = date_utils::resolve_date_expr(value, today)?;
+                    let count = (end - start).num_days() as usize + 1;
+                    if count > MAX_RANGE_DATES && !yes {
+                        return Err(AppError::argument_error(format!(
+                            "日期范围 {} 展开为 {} 个日期，超过 {} 个；确认无误请加 --yes",
+                            value, count, MAX_RANGE_DATES
+                        )));
+                    }
+                    date_list.extend(
+                        date_utils::date_range(start, end)
+                            .iter()
+                            .map(date_utils::format_date),
+                    );
+                }
+
+                if let Some(path) = dates_file {
+                    let content = if path.as_os_str() == "-" {
+                        std::io::read_to_string(std::io::stdin())
+                            .map_err(|e| AppError::file_error(path, e.to_string()))?
+                    } else {
+                        std::fs::read_to_string(path)
+                            .map_err(|e| AppError::file_error(path, e.to_string()))?
+                    };
+                    date_list.extend(
+                        date_utils::parse_date_lines(&content)?
+                            .iter()
+                            .map(date_utils::format_date),
+                    );
+                }
 
-                if let Some(d) = date {
-                    date_list.push(d.clone());
+                for month in month.iter().flatten() {
+                    let (start, end) = date_utils::parse_month(month)?;
+                    date_list.extend(
+                        date_utils::date_range(start, end)
+                            .iter()
+                            .map(date_utils::format_date),
+                    );
                 }
 
-                if let Some(d) = dates {
-                    date_list.extend(d.clone());
+                for year in year.iter().flatten() {
+                    let (start, end) = date_utils::parse_year(year, today)?;
+                    date_list.extend(
+                        date_utils::date_range(start, end)
+                            .iter()
+                            .map(date_utils::format_date),
+                    );
+                }
+
+                if let Some(from) = from {
+                    let start = date_utils::resolve_date_arg(from, today)?.0;
+                    let end = match to {
+                        Some(to) => date_utils::resolve_date_arg(to, today)?.1,
+                        None => today,
+                    };
+                    if start > end {
+                        return Err(AppError::argument_error(format!(
+                            "--from ({}) 不能晚于 --to ({})",
+                            date_utils::format_date(&start),
+                            date_utils::format_date(&end)
+                        )));
+                    }
+                    date_list.extend(
+                        date_utils::date_range(start, end)
+                            .iter()
+                            .map(date_utils::format_date),
+                    );
                 }
 
                 if date_list.is_empty() {
                     return Err(AppError::argument_error(
-                        "必须指定 --date 或 --dates 参数",
+                        "必须指定 --date、--dates、--dates-file、--month、--year 或 --from 参数",
                     ));
                 }
 
-                // 去重并验证日期格式
-                date_list.sort();
-                date_list.dedup();
-
-                for d in &date_list {
-                    // 验证日期格式
-                    chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|e| {
-                        AppError::InvalidDate {
-                            input: d.clone(),
-                            details: e.to_string(),
-                        }
-                    })?;
+                // 验证日期格式，按时间排序并去重
+                let mut parsed = date_list
+                    .iter()
+                    .map(|d| {
+                        chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").map_err(|e| {
+                            AppError::InvalidDate {
+                                input: d.clone(),
+                                details: e.to_string(),
+                            }
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                parsed.sort();
+                parsed.dedup();
+                if *reverse {
+                    parsed.reverse();
                 }
 
-                Ok(date_list)
+                Ok(parsed.iter().map(date_utils::format_date).collect())
             }
         }
     }
 }
 
+use crate::date_utils;
 use crate::error::{AppError, Result};
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// `--dates` 中单个范围最多展开的日期数量，超过时需要 `--yes` 确认
+pub const MAX_RANGE_DATES: usize = 10_000;
 
-    #[test]
-    fn test_cli_default_values() {
-        let cli = Cli::try_parse_from(["calendar"]).unwrap();
-        assert_eq!(cli.config, PathBuf::from("config.toml"));
-        assert_eq!(cli.log_level, "info");
+/// 生成主命令和各子命令的 man 手册（roff 格式）
+///
+/// 返回 `(文件名, 内容)` 列表，文件名形如 `calendar.1`、`calendar-run.1`，隐藏的子命令不生成
+pub fn render_man_pages() -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    let mut command = <Cli as clap::CommandFactory>::command();
+    command.build();
+
+    let mut pages = Vec::new();
+    render_man_page(&command, &mut pages)?;
+    Ok(pages)
+}
+
+fn render_man_page(command: &clap::Command, pages: &mut Vec<(String, Vec<u8>)>) -> std::io::Result<()> {
+    // 构建后子命令的 display_name 为 `calendar-run` 形式，同时用作页面标题和文件名
+    let name = command.get_display_name().unwrap_or(command.get_name());
+    let mut buffer = Vec::new();
+    clap_mangen::Man::new(command.clone()).render(&mut buffer)?;
+    pages.push((format!("{}.1", name), buffer));
+
+    for subcommand in command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+    {
+        render_man_page(subcommand, pages)?;
+    }
+    Ok(())
+}
+
+/// 生成指定 shell 的补全脚本，覆盖所有子命令和参数（包括全局参数）
+pub fn render_completions(shell: clap_complete::Shell) -> Vec<u8> {
+    let mut command = <Cli as clap::CommandFactory>::command();
+    let mut buffer = Vec::new();
+    clap_complete::generate(shell, &mut command, "calendar", &mut buffer);
+    buffer
+}
+
+/// 校验 `--month` 参数 (格式: YYYY-MM)
+fn parse_month_arg(value: &str) -> Result<String> {
+    date_utils::parse_month(value)?;
+    Ok(value.to_string())
+}
+
+/// 解析 `--at` 参数 (格式: HH:MM)
+fn parse_time_arg(value: &str) -> Result<chrono::NaiveTime> {
+    chrono::NaiveTime::parse_from_str(value, "%H:%M").map_err(|e| {
+        AppError::argument_error(format!("无效的时间 '{}'，应为 HH:MM: {}", value, e))
+    })
+}
+
+/// 校验 `--year` 参数 (格式: YYYY)
+fn parse_year_arg(value: &str) -> Result<String> {
+    date_utils::parse_year(value, date_utils::today())?;
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_completions() {
+        use clap::ValueEnum;
+        for shell in clap_complete::Shell::value_variants() {
+            let script = String::from_utf8(render_completions(*shell)).unwrap();
+            // fish 以 `-l log-level` 形式列出长参数，这里不带 `--` 检查
+            for word in ["calendar", "process", "retry", "log-level", "no-color", "dates-file"] {
+                assert!(script.contains(word), "{}: {}", shell, word);
+            }
+        }
+
+        let cli = Cli::try_parse_from(["calendar", "completions", "powershell"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Completions { shell: clap_complete::Shell::PowerShell })
+        ));
+        assert!(Cli::try_parse_from(["calendar", "completions", "tcsh"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_default_values() {
+        let cli = Cli::try_parse_from(["calendar"]).unwrap();
+        assert_eq!(cli.config, PathBuf::from("config.toml"));
+        assert_eq!(cli.log_level, "info");
     }
 
     #[test]
     fn test_cli_config_option() {
         let cli = Cli::try_parse_from(["calendar", "-c", "my-config.toml"]).unwrap();
         assert_eq!(cli.config, PathBuf::from("my-config.toml"));
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_cli_bare_defaults_to_run() {
+        let cli = Cli::try_parse_from(["calendar"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let command = Command::default_run();
+        if let Command::Run {
+            start_date,
+            end_date,
+            month,
+            year,
+            since_last_success,
+            base_url,
+            timeout,
+            concurrency,
+            overwrite,
+            download_only,
+            force_metadata,
+            yes,
+            json,
+            progress,
+            strict,
+            fail_fast,
+            max_duration,
+            wait,
+        } = &command
+        {
+            assert!(start_date.is_none() && end_date.is_none() && !since_last_success);
+            assert!(month.is_none() && year.is_none() && base_url.is_none() && timeout.is_none());
+            assert!(concurrency.is_none());
+            assert!(!overwrite && !download_only && !force_metadata && !yes && !json && !strict);
+            assert!(!fail_fast && !wait && max_duration.is_none());
+            assert_eq!(progress, "bar");
+        } else {
+            panic!("Expected Run command");
+        }
+        assert!(command.get_dates().unwrap().is_empty());
     }
 
     #[test]
@@ -232,6 +1061,297 @@ mod tests {
         assert!(dates.contains(&"2024-06-20".to_string()));
     }
 
+    #[test]
+    fn test_cli_process_date_range() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--from",
+            "2024-06-28",
+            "--to",
+            "2024-07-02",
+        ])
+        .unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(
+            dates,
+            vec!["2024-06-28", "2024-06-29", "2024-06-30", "2024-07-01", "2024-07-02"]
+        );
+    }
+
+    #[test]
+    fn test_cli_timeout_must_be_positive() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--timeout", "5"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Run { timeout: Some(5), .. })));
+
+        assert!(Cli::try_parse_from(["calendar", "run", "--timeout", "0"]).is_err());
+        assert!(Cli::try_parse_from(["calendar", "process", "--date", "today", "--timeout", "-1"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_cli_run_max_duration() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--max-duration", "55m"]).unwrap();
+        if let Some(Command::Run { max_duration, .. }) = cli.command {
+            assert_eq!(max_duration, Some(std::time::Duration::from_secs(55 * 60)));
+        } else {
+            panic!("Expected Run command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "run", "--max-duration", "0m"]).is_err());
+        assert!(Cli::try_parse_from(["calendar", "process", "--date", "today", "--max-duration", "1h"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_cli_progress_mode() {
+        let cli = Cli::try_parse_from(["calendar", "process", "--date", "2024-06-01", "--progress", "json"])
+            .unwrap();
+        if let Some(Command::Process { progress, .. }) = cli.command {
+            assert_eq!(progress, "json");
+        } else {
+            panic!("Expected Process command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "run", "--progress", "xml"]).is_err());
+        assert!(Cli::try_parse_from(["calendar", "run", "--progress", "json", "--json"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_fail_fast_flag() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--fail-fast"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Run { fail_fast: true, .. })));
+
+        let cli = Cli::try_parse_from(["calendar", "process", "--date", "2024-06-15", "--fail-fast"])
+            .unwrap();
+        assert!(matches!(cli.command, Some(Command::Process { fail_fast: true, .. })));
+    }
+
+    #[test]
+    fn test_cli_process_dates_with_ranges() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--dates",
+            "2024-06-01..2024-06-03,2024-06-02,2024-07-01",
+        ])
+        .unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(dates, vec!["2024-06-01", "2024-06-02", "2024-06-03", "2024-07-01"]);
+
+        let cli = Cli::try_parse_from(["calendar", "process", "--dates", "2024-06-10..2024-06-01"])
+            .unwrap();
+        assert!(cli.command.unwrap().get_dates().is_err());
+    }
+
+    #[test]
+    fn test_cli_process_large_range_requires_yes() {
+        let cli = Cli::try_parse_from(["calendar", "process", "--dates", "1900-01-01..1999-12-31"])
+            .unwrap();
+        let err = cli.command.unwrap().get_dates().unwrap_err();
+        assert!(err.to_string().contains("--yes"));
+
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--dates",
+            "1900-01-01..1999-12-31",
+            "--yes",
+        ])
+        .unwrap();
+        assert_eq!(cli.command.unwrap().get_dates().unwrap().len(), 36524);
+    }
+
+    #[test]
+    fn test_cli_process_sorts_chronologically() {
+        let args = [
+            "calendar",
+            "process",
+            "--dates",
+            "2024-06-15,2023-12-31,2024-06-01..2024-06-02,2024-06-15",
+            "--date",
+            "2024-01-05",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(dates, vec!["2023-12-31", "2024-01-05", "2024-06-01", "2024-06-02", "2024-06-15"]);
+
+        let cli = Cli::try_parse_from(args.iter().chain(&["--reverse"])).unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(dates, vec!["2024-06-15", "2024-06-02", "2024-06-01", "2024-01-05", "2023-12-31"]);
+
+        let cli = Cli::try_parse_from(["calendar", "retry", "--reverse"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Retry { reverse: true, .. })));
+    }
+
+    #[test]
+    fn test_cli_process_date_range_with_dates() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--dates",
+            "2024-06-01,2024-06-29",
+            "--from",
+            "2024-06-29",
+            "--to",
+            "2024-06-30",
+        ])
+        .unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(dates, vec!["2024-06-01", "2024-06-29", "2024-06-30"]);
+    }
+
+    #[test]
+    fn test_cli_process_date_range_rejects_reversed() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--from",
+            "2024-06-30",
+            "--to",
+            "2024-06-01",
+        ])
+        .unwrap();
+        assert!(cli.command.unwrap().get_dates().is_err());
+    }
+
+    #[test]
+    fn test_cli_process_to_requires_from() {
+        let result = Cli::try_parse_from(["calendar", "process", "--to", "2024-06-30"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_cli_run_month() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--month", "2024-02"]).unwrap();
+        if let Some(Command::Run { month, .. }) = cli.command {
+            assert_eq!(month, Some("2024-02".to_string()));
+        } else {
+            panic!("Expected Run command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "run", "--month", "2024-13"]).is_err());
+        assert!(Cli::try_parse_from([
+            "calendar",
+            "run",
+            "--month",
+            "2024-06",
+            "--start-date",
+            "2024-06-01",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_run_since_last_success() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--since-last-success", "--end-date", "2024-06-30"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Run { since_last_success: true, .. })
+        ));
+
+        assert!(Cli::try_parse_from([
+            "calendar",
+            "run",
+            "--since-last-success",
+            "--start-date",
+            "2024-06-01",
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(["calendar", "run", "--since-last-success", "--month", "2024-06"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_cli_process_multiple_months() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--month",
+            "2024-02",
+            "--month",
+            "2023-02",
+            "--date",
+            "2024-02-10",
+        ])
+        .unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(dates.len(), 29 + 28);
+        assert_eq!(dates.first().unwrap(), "2023-02-01");
+        assert_eq!(dates.last().unwrap(), "2024-02-29");
+    }
+
+    #[test]
+    fn test_cli_run_year() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--year", "2023"]).unwrap();
+        if let Some(Command::Run { year, .. }) = cli.command {
+            assert_eq!(year, Some("2023".to_string()));
+        } else {
+            panic!("Expected Run command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "run", "--year", "2023", "--end-date", "2023-06-01"])
+            .is_err());
+        assert!(Cli::try_parse_from(["calendar", "run", "--year", "2023", "--month", "2023-06"])
+            .is_err());
+        assert!(Cli::try_parse_from(["calendar", "run", "--year", "99999"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_process_year() {
+        let cli = Cli::try_parse_from(["calendar", "process", "--year", "2024"]).unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(dates.len(), 366);
+        assert_eq!(dates.first().unwrap(), "2024-01-01");
+        assert_eq!(dates.last().unwrap(), "2024-12-31");
+    }
+
+    #[test]
+    fn test_cli_process_relative_dates() {
+        let cli = Cli::try_parse_from(["calendar", "process", "--dates", "today,last-3-days"])
+            .unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+
+        let today = date_utils::today();
+        let expected: Vec<String> = date_utils::date_range(today - chrono::Days::new(2), today)
+            .iter()
+            .map(date_utils::format_date)
+            .collect();
+        assert_eq!(dates, expected);
+    }
+
+    #[test]
+    fn test_cli_process_dates_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("dates.txt");
+        std::fs::write(&path, "# 待处理\n2024-06-20\n\n2024-06-15\tHTTP 404\n").unwrap();
+
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--dates-file",
+            path.to_str().unwrap(),
+            "--date",
+            "2024-06-15",
+        ])
+        .unwrap();
+        let dates = cli.command.unwrap().get_dates().unwrap();
+        assert_eq!(dates, vec!["2024-06-15", "2024-06-20"]);
+    }
+
+    #[test]
+    fn test_cli_process_dates_file_reports_line() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("dates.txt");
+        std::fs::write(&path, "2024-06-20\n2024-02-30\n").unwrap();
+
+        let cli = Cli::try_parse_from(["calendar", "process", "--dates-file", path.to_str().unwrap()])
+            .unwrap();
+        let err = cli.command.unwrap().get_dates().unwrap_err();
+        assert!(err.to_string().contains("第 2 行"));
+    }
+
     #[test]
     fn test_cli_process_requires_date_or_dates() {
         let result = Cli::try_parse_from(["calendar", "process"]);
@@ -260,4 +1380,452 @@ mod tests {
         let cli = Cli::try_parse_from(["calendar", "-l", "debug", "run"]).unwrap();
         assert_eq!(cli.log_level, "debug");
     }
+
+    #[test]
+    fn test_cli_log_format() {
+        let cli = Cli::try_parse_from(["calendar"]).unwrap();
+        assert_eq!(cli.log_format, "text");
+
+        let cli = Cli::try_parse_from(["calendar", "run", "--log-format", "json"]).unwrap();
+        assert_eq!(cli.log_format, "json");
+
+        assert!(Cli::try_parse_from(["calendar", "--log-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_log_file() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--log-file", "logs/calendar.log"]).unwrap();
+        assert_eq!(cli.log_file, Some(PathBuf::from("logs/calendar.log")));
+
+        let cli = Cli::try_parse_from(["calendar"]).unwrap();
+        assert!(cli.log_file.is_none());
+    }
+
+    #[test]
+    fn test_cli_quiet_and_verbose() {
+        let cli = Cli::try_parse_from(["calendar", "run", "-q"]).unwrap();
+        assert!(cli.quiet);
+        assert_eq!(cli.effective_log_level(), "error");
+
+        let cli = Cli::try_parse_from(["calendar", "-v", "run"]).unwrap();
+        assert_eq!(cli.effective_log_level(), "debug");
+
+        let cli = Cli::try_parse_from(["calendar", "-vv", "run"]).unwrap();
+        assert_eq!(cli.effective_log_level(), "trace");
+
+        let cli = Cli::try_parse_from(["calendar", "run"]).unwrap();
+        assert_eq!(cli.effective_log_level(), "info");
+
+        assert!(Cli::try_parse_from(["calendar", "-q", "-v", "run"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_retry_command() {
+        let cli = Cli::try_parse_from(["calendar", "retry"]).unwrap();
+        if let Some(Command::Retry {
+            file,
+            overwrite,
+            metadata_only,
+            yes,
+            reverse,
+            wait,
+            concurrency,
+            json,
+            progress,
+            fail_fast,
+            ..
+        }) = cli.command
+        {
+            assert!(file.is_none());
+            assert!(!overwrite);
+            assert!(!metadata_only);
+            assert!(!yes);
+            assert!(concurrency.is_none());
+            assert!(!json);
+            assert_eq!(progress, "bar");
+            assert!(!fail_fast);
+            assert!(!reverse);
+            assert!(!wait);
+        } else {
+            panic!("Expected Retry command");
+        }
+
+        let cli = Cli::try_parse_from(["calendar", "retry", "--file", "failed.txt", "--overwrite", "-y"])
+            .unwrap();
+        if let Some(Command::Retry { file, overwrite, yes, .. }) = cli.command {
+            assert_eq!(file, Some(PathBuf::from("failed.txt")));
+            assert!(overwrite);
+            assert!(yes);
+        } else {
+            panic!("Expected Retry command");
+        }
+
+        // 与 process 相同的流程参数
+        let cli = Cli::try_parse_from([
+            "calendar", "retry", "--concurrency", "4", "--progress", "json", "--fail-fast", "--strict",
+        ])
+        .unwrap();
+        if let Some(Command::Retry { concurrency, progress, fail_fast, strict, .. }) = cli.command {
+            assert_eq!(concurrency, Some(4));
+            assert_eq!(progress, "json");
+            assert!(fail_fast);
+            assert!(strict);
+        } else {
+            panic!("Expected Retry command");
+        }
+        assert!(Cli::try_parse_from(["calendar", "retry", "--json", "--progress", "json"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_status_command() {
+        let cli = Cli::try_parse_from(["calendar", "status", "--write-dates", "missing.txt", "--json"])
+            .unwrap();
+        if let Some(Command::Status { write_dates, json }) = cli.command {
+            assert_eq!(write_dates, Some(PathBuf::from("missing.txt")));
+            assert!(json);
+        } else {
+            panic!("Expected Status command");
+        }
+    }
+
+    #[test]
+    fn test_cli_verify_command() {
+        let cli = Cli::try_parse_from(["calendar", "verify", "--delete-invalid", "--redownload"])
+            .unwrap();
+        if let Some(Command::Verify { delete_invalid, redownload }) = cli.command {
+            assert!(delete_invalid);
+            assert!(redownload);
+        } else {
+            panic!("Expected Verify command");
+        }
+    }
+
+    #[test]
+    fn test_cli_repair_command() {
+        let cli = Cli::try_parse_from(["calendar", "repair", "--dry-run"]).unwrap();
+        if let Some(Command::Repair { dry_run, wait }) = &cli.command {
+            assert!(dry_run);
+            assert!(!wait);
+        } else {
+            panic!("Expected Repair command");
+        }
+        assert!(cli.command.unwrap().get_dates().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cli_rename_command() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "rename",
+            "--from",
+            "{yyyy}{mm}{dd}.jpg",
+            "--dry-run",
+        ])
+        .unwrap();
+        if let Some(Command::Rename { from, to, dry_run }) = cli.command {
+            assert_eq!(from, "{yyyy}{mm}{dd}.jpg");
+            assert!(to.is_none());
+            assert!(dry_run);
+        } else {
+            panic!("Expected Rename command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "rename"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_migrate_command() {
+        let cli = Cli::try_parse_from(["calendar", "migrate", "--dry-run"]).unwrap();
+        if let Some(Command::Migrate { dry_run, force }) = cli.command {
+            assert!(dry_run);
+            assert!(!force);
+        } else {
+            panic!("Expected Migrate command");
+        }
+    }
+
+    #[test]
+    fn test_cli_list_command() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "list",
+            "--start-date",
+            "2024-06-01",
+            "--format",
+            "tsv",
+            "--missing-only",
+        ])
+        .unwrap();
+        if let Some(Command::List {
+            start_date,
+            end_date,
+            format,
+            missing_only,
+        }) = cli.command
+        {
+            assert_eq!(start_date, Some("2024-06-01".to_string()));
+            assert!(end_date.is_none());
+            assert_eq!(format, "tsv");
+            assert!(missing_only);
+        } else {
+            panic!("Expected List command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "list", "--format", "csv"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_stats_command() {
+        let cli = Cli::try_parse_from(["calendar", "stats", "--json"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Stats { json: true })));
+    }
+
+    #[test]
+    fn test_cli_force_metadata_flag() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--force-metadata"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Run { force_metadata: true, .. })));
+
+        let cli =
+            Cli::try_parse_from(["calendar", "process", "--date", "2024-06-15", "--force-metadata"])
+                .unwrap();
+        assert!(matches!(cli.command, Some(Command::Process { force_metadata: true, .. })));
+
+        assert!(
+            Cli::try_parse_from(["calendar", "run", "--force-metadata", "--download-only"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_yes_flag() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--overwrite", "-y"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Run { yes: true, overwrite: true, .. })));
+
+        let cli = Cli::try_parse_from(["calendar", "process", "--date", "2024-06-15", "--yes"])
+            .unwrap();
+        assert!(matches!(cli.command, Some(Command::Process { yes: true, .. })));
+    }
+
+    #[test]
+    fn test_render_man_pages() {
+        let pages = render_man_pages().unwrap();
+        let page = |name: &str| {
+            let (_, content) = pages.iter().find(|(n, _)| n == name).unwrap();
+            String::from_utf8(content.clone()).unwrap()
+        };
+
+        let main = page("calendar.1");
+        assert!(main.contains(".TH calendar"));
+        assert!(main.contains("\\-\\-config"));
+
+        let run = page("calendar-run.1");
+        assert!(run.contains("\\-\\-start\\-date"));
+        assert!(run.contains("\\-\\-overwrite"));
+        assert!(page("calendar-exif-show.1").contains("\\-\\-date"));
+        assert!(!pages.iter().any(|(name, _)| name == "calendar-man.1"));
+    }
+
+    #[test]
+    fn test_cli_url_command() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "url",
+            "2024-06-15",
+            "2024-06-01..2024-06-02",
+            "--path",
+        ])
+        .unwrap();
+        let command = cli.command.unwrap();
+        assert!(matches!(command, Command::Url { path: true, .. }));
+        assert_eq!(
+            command.get_dates().unwrap(),
+            vec!["2024-06-15", "2024-06-01", "2024-06-02"]
+        );
+
+        let cli = Cli::try_parse_from(["calendar", "url", "2024-13-01"]).unwrap();
+        assert!(matches!(
+            cli.command.unwrap().get_dates(),
+            Err(AppError::InvalidDate { .. })
+        ));
+        assert!(Cli::try_parse_from(["calendar", "url"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_prune_command() {
+        let cli = Cli::try_parse_from(["calendar", "prune", "--keep-days", "730", "--dry-run"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Prune { keep_days: Some(730), before: None, dry_run: true })
+        ));
+
+        assert!(Cli::try_parse_from(["calendar", "prune"]).is_err());
+        assert!(Cli::try_parse_from(["calendar", "prune", "--keep-days", "0"]).is_err());
+        assert!(Cli::try_parse_from([
+            "calendar",
+            "prune",
+            "--keep-days",
+            "7",
+            "--before",
+            "2024-01-01"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_cli_dedupe_command() {
+        let cli = Cli::try_parse_from(["calendar", "dedupe"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Dedupe { link: None, delete: false, dry_run: false })
+        ));
+
+        let cli = Cli::try_parse_from(["calendar", "dedupe", "--link", "hard", "--dry-run"]).unwrap();
+        if let Some(Command::Dedupe { link, dry_run, .. }) = cli.command {
+            assert_eq!(link.as_deref(), Some("hard"));
+            assert!(dry_run);
+        } else {
+            panic!("Expected Dedupe command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "dedupe", "--link", "copy"]).is_err());
+        assert!(Cli::try_parse_from(["calendar", "dedupe", "--link", "sym", "--delete"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_watch_command() {
+        let cli = Cli::try_parse_from(["calendar", "watch", "--at", "08:30"]).unwrap();
+        if let Some(Command::Watch { at, interval }) = cli.command {
+            assert_eq!(at, chrono::NaiveTime::from_hms_opt(8, 30, 0));
+            assert_eq!(interval, std::time::Duration::from_secs(86400));
+        } else {
+            panic!("Expected Watch command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "watch", "--at", "25:00"]).is_err());
+        assert!(Cli::try_parse_from(["calendar", "watch", "--interval", "0m"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_doctor_command() {
+        let cli = Cli::try_parse_from(["calendar", "-c", "other.toml", "doctor"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Doctor)));
+        assert_eq!(cli.config, PathBuf::from("other.toml"));
+    }
+
+    #[test]
+    fn test_cli_clean_command() {
+        let cli = Cli::try_parse_from(["calendar", "clean", "--dry-run", "--backups"]).unwrap();
+        if let Some(Command::Clean { dry_run, keep_unrecognized, backups }) = cli.command {
+            assert!(dry_run);
+            assert!(!keep_unrecognized);
+            assert!(backups);
+        } else {
+            panic!("Expected Clean command");
+        }
+    }
+
+    #[test]
+    fn test_cli_exif_show_command() {
+        let cli = Cli::try_parse_from(["calendar", "exif", "show", "--date", "2024-06-15", "--json"])
+            .unwrap();
+        if let Some(Command::Exif {
+            command: ExifCommand::Show { path, date, json },
+        }) = cli.command
+        {
+            assert!(path.is_none());
+            assert_eq!(date, Some("2024-06-15".to_string()));
+            assert!(json);
+        } else {
+            panic!("Expected Exif Show command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "exif", "show"]).is_err());
+        assert!(
+            Cli::try_parse_from(["calendar", "exif", "show", "a.jpg", "--date", "2024-06-15"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_exif_strip_command() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "exif",
+            "strip",
+            "--date",
+            "2024-06-15,2024-06-20..2024-06-22",
+            "--keep-dates",
+            "--dry-run",
+        ])
+        .unwrap();
+        if let Some(Command::Exif {
+            command:
+                ExifCommand::Strip {
+                    paths,
+                    date,
+                    all,
+                    keep_dates,
+                    also_reset_mtime,
+                    dry_run,
+                },
+        }) = cli.command
+        {
+            assert!(paths.is_empty());
+            assert_eq!(
+                date,
+                Some(vec!["2024-06-15".to_string(), "2024-06-20..2024-06-22".to_string()])
+            );
+            assert!(!all && keep_dates && !also_reset_mtime && dry_run);
+        } else {
+            panic!("Expected Exif Strip command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "exif", "strip", "a.jpg", "b.jpg"]).is_ok());
+        assert!(Cli::try_parse_from(["calendar", "exif", "strip"]).is_err());
+        assert!(Cli::try_parse_from(["calendar", "exif", "strip", "a.jpg", "--all"]).is_err());
+        assert!(
+            Cli::try_parse_from(["calendar", "exif", "strip", "--all", "--date", "2024-06-15"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_touch_command() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "touch",
+            "--start-date",
+            "2024-01-01",
+            "--concurrency",
+            "16",
+        ])
+        .unwrap();
+        if let Some(Command::Touch { start_date, end_date, all, concurrency }) = cli.command {
+            assert_eq!(start_date, Some("2024-01-01".to_string()));
+            assert!(end_date.is_none());
+            assert!(!all);
+            assert_eq!(concurrency, Some(16));
+        } else {
+            panic!("Expected Touch command");
+        }
+
+        assert!(Cli::try_parse_from(["calendar", "touch", "--all"]).is_ok());
+        let cli = Cli::try_parse_from(["calendar", "touch", "--from-filename"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Touch { all: true, .. })));
+        assert!(Cli::try_parse_from(["calendar", "touch"]).is_err());
+        assert!(
+            Cli::try_parse_from(["calendar", "touch", "--all", "--start-date", "2024-01-01"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_strict_flag() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--strict"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Run { strict: true, .. })));
+
+        let cli = Cli::try_parse_from(["calendar", "process", "--date", "2024-06-15", "--strict"])
+            .unwrap();
+        assert!(matches!(cli.command, Some(Command::Process { strict: true, .. })));
+    }
 }
@@ -0,0 +1,76 @@
+//! 控制台输出着色
+//!
+//! 统计摘要和校验报告中的成功、失败、跳过分别用绿色、红色、黄色标出。
+//! 指定 `--no-color`、设置 `NO_COLOR` 环境变量或标准输出不是终端时不输出颜色，
+//! 此时文本与未着色前完全一致，不影响脚本解析。
+
+use console::{style, StyledObject};
+use std::io::IsTerminal;
+
+/// 根据命令行参数、`NO_COLOR` 环境变量和标准输出是否为终端决定是否启用颜色
+///
+/// 同时作用于进度条（indicatif 使用同一套 `console` 设置）
+pub fn init(no_color: bool) {
+    console::set_colors_enabled(should_enable(no_color, no_color_env(), std::io::stdout().is_terminal()));
+}
+
+/// 写到标准错误的控制台日志是否使用颜色，规则与 [`init`] 相同，但检查标准错误
+pub fn log_colors_enabled(no_color: bool) -> bool {
+    should_enable(no_color, no_color_env(), std::io::stderr().is_terminal())
+}
+
+/// 是否设置了非空的 `NO_COLOR` 环境变量（见 <https://no-color.org>）
+fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// 是否启用颜色：未通过参数或环境变量禁用，且输出为终端
+fn should_enable(no_color: bool, no_color_env: bool, is_terminal: bool) -> bool {
+    !no_color && !no_color_env && is_terminal
+}
+
+/// 成功（绿色）
+pub fn success<D>(text: D) -> StyledObject<D> {
+    style(text).green()
+}
+
+/// 失败（红色）
+pub fn failure<D>(text: D) -> StyledObject<D> {
+    style(text).red()
+}
+
+/// 跳过或警告（黄色）
+pub fn skipped<D>(text: D) -> StyledObject<D> {
+    style(text).yellow()
+}
+
+/// 数量大于 0 时标为失败色，否则原样输出
+pub fn failure_count(count: usize) -> StyledObject<usize> {
+    if count > 0 {
+        failure(count)
+    } else {
+        style(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_enable() {
+        assert!(should_enable(false, false, true));
+        assert!(!should_enable(true, false, true));
+        assert!(!should_enable(false, true, true));
+        assert!(!should_enable(false, false, false));
+    }
+
+    #[test]
+    fn test_plain_text_when_disabled() {
+        // 强制关闭颜色时输出与原文本一致
+        let plain = format!("{}", success(42).force_styling(false));
+        assert_eq!(plain, "42");
+        let colored = format!("{}", failure("失败").force_styling(true));
+        assert!(colored.contains("失败") && colored.starts_with('\u{1b}'));
+    }
+}
@@ -4,12 +4,22 @@
 
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
 use std::time::Duration as StdDuration;
 
 use crate::cli::Command;
 use crate::date_utils;
 use crate::error::{AppError, Result};
+use crate::filename::{FilenameFormatter, FormatStyle};
+use crate::fileops;
+
+/// 默认的失败日期记录文件名
+const DEFAULT_FAILED_LOG: &str = "failed_downloads.txt";
+
+/// 元数据更新失败的日期记录文件名（位于 output_dir 下）
+const METADATA_FAILED_LOG: &str = "metadata_failures.txt";
 
 /// 应用程序配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,14 +37,50 @@ pub struct Config {
     /// 文件名格式，支持占位符：{yyyy}、{yy}、{mm}、{dd}
     pub filename_format: String,
 
+    /// `filename_format` 和 `base_url` 的语法：braces（默认，`{yyyy}` 占位符）或 strftime（`%Y` 格式符）
+    #[serde(default)]
+    pub format_style: FormatStyle,
+
+    /// `{month_name}` 使用的本地化月份名称（1 月到 12 月共 12 个），未设置时使用英文
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub month_names: Option<Vec<String>>,
+
+    /// 响应缺少 Content-Type 或类型未知时 `{ext}` 使用的扩展名（默认 jpg）
+    #[serde(default = "default_ext")]
+    pub default_ext: String,
+
+    /// 是否按年份建立子目录（默认 true）；为 false 时所有文件直接保存在 output_dir 下
+    #[serde(default = "default_year_subdirs")]
+    pub year_subdirs: bool,
+
+    /// 文件名包含文件系统不允许的字符（`< > : " | ? *`、控制字符、结尾的点号或空格、
+    /// Windows 保留设备名）时替换为 `_`；默认 false，加载配置和生成文件名时报错
+    #[serde(default)]
+    pub sanitize_filenames: bool,
+
+    /// 每天最多下载的图片数量（默认 1）；大于 1 时 `base_url` 和 `filename_format` 都必须包含 `{n}`，
+    /// 下载时 `{n}` 从 1 递增，遇到第一个 404 即停止
+    #[serde(default = "default_images_per_day")]
+    pub images_per_day: u32,
+
+    /// 不同日期生成相同保存路径时的处理方式（默认 error）
+    #[serde(default)]
+    pub on_collision: OnCollision,
+
     /// 最大并发数（仅对 run 命令有效）
     #[serde(default = "default_max_concurrent")]
     pub max_concurrent: usize,
 
     /// HTTP 请求时使用的 User-Agent
+    ///
+    /// 支持占位符：`{version}`（程序版本）、`{contact}`（`contact` 配置项）
     #[serde(default = "default_user_agent")]
     pub user_agent: String,
 
+    /// 联系方式（邮箱或网址），用于 User-Agent 中的 `{contact}` 占位符
+    #[serde(default)]
+    pub contact: Option<String>,
+
     /// 下载超时时间（秒）
     #[serde(default = "default_timeout")]
     pub timeout: u64,
@@ -46,8 +92,292 @@ pub struct Config {
     /// 重试基础延迟（毫秒）
     #[serde(default = "default_retry_delay")]
     pub retry_delay_ms: u64,
+
+    /// 失败日期记录文件路径（绝对路径或相对于 output_dir），支持日期占位符
+    ///
+    /// 未设置时为 `output_dir/failed_downloads.txt`；设为空字符串时不写入
+    #[serde(default)]
+    pub failed_log_path: Option<String>,
+
+    /// `--overwrite` 将覆盖的已存在文件超过该数量时要求确认（默认 10）
+    #[serde(default = "default_overwrite_confirm_threshold")]
+    pub overwrite_confirm_threshold: usize,
+
+    /// 日志文件路径，设置后日志同时按天轮转写入该文件（命令行 `--log-file` 优先）
+    #[serde(default)]
+    pub log_file: Option<String>,
+
+    /// 允许下载的内容类型（Content-Type），支持 `image/*` 通配符
+    ///
+    /// 响应类型不在列表中时视为下载失败；非图片类型跳过 EXIF 修改，仅设置文件时间戳
+    #[serde(default = "default_allowed_content_types")]
+    pub allowed_content_types: Vec<String>,
+
+    /// 控制台输出语言 (`zh`、`en`)，未设置时根据 `LANG` 环境变量推断，默认中文
+    #[serde(default)]
+    pub lang: Option<crate::i18n::Lang>,
+
+    /// EXIF 写入选项（`[exif]` 表）
+    #[serde(default)]
+    pub exif: ExifConfig,
+
+    /// 文件属性选项（`[fileops]` 表）
+    #[serde(default)]
+    pub fileops: FileopsConfig,
+
+    /// 经过 `${VAR}` 插值的字段原始值（字段名 -> 插值前的模板），列表元素的字段名带下标，如 `month_names[0]`
+    ///
+    /// 用于日志脱敏和保存配置，避免把环境变量中的敏感值写回文件
+    #[serde(skip)]
+    interpolated: Vec<(Cow<'static, str>, String)>,
+
+    /// 加载时发现的已弃用配置键（旧键, 新键）
+    #[serde(skip)]
+    deprecated_keys: Vec<(String, String)>,
+
+    /// 被命令行 `--output-dir` 覆盖前的 output_dir，保存配置时写回原值
+    #[serde(skip)]
+    original_output_dir: Option<String>,
+}
+
+/// 文件名冲突（不同日期生成相同保存路径）的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnCollision {
+    /// 将后一个日期记为失败
+    #[default]
+    Error,
+    /// 在扩展名前追加 `-1`、`-2`……直到文件名可用
+    Suffix,
+    /// 不检查冲突，后写入的文件覆盖先写入的文件
+    Overwrite,
+}
+
+/// EXIF 写入选项
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExifConfig {
+    /// 是否覆盖图片中已有的 Artist、ImageDescription、Copyright 标签（默认 false）
+    ///
+    /// 为 false 时只在标签不存在时写入，保留原图自带的作者和描述；日期标签总是写入
+    #[serde(default)]
+    pub overwrite_existing: bool,
+
+    /// 写入 EXIF 的纬度（度，-90 到 90，南纬为负），需与 `gps_longitude` 同时设置
+    #[serde(default)]
+    pub gps_latitude: Option<f64>,
+
+    /// 写入 EXIF 的经度（度，-180 到 180，西经为负），需与 `gps_latitude` 同时设置
+    #[serde(default)]
+    pub gps_longitude: Option<f64>,
+
+    /// 写入 EXIF 的海拔（米，海平面以下为负），需同时设置经纬度
+    #[serde(default)]
+    pub gps_altitude: Option<f64>,
+
+    /// 图片日期所在时区相对 UTC 的偏移，格式 `+08:00`
+    ///
+    /// 设置后写入 OffsetTime、OffsetTimeOriginal、OffsetTimeDigitized，
+    /// 使相册软件跨时区排列照片时不产生偏差
+    #[serde(default)]
+    pub offset_time: Option<String>,
+
+    /// 写入后重新读取 DateTimeOriginal 校验（默认 true），不一致时视为 EXIF 写入失败
+    #[serde(default = "default_exif_verify")]
+    pub verify: bool,
+
+    /// 写入 EXIF 前把原文件复制为 `<文件名>.orig`（默认 false）
+    ///
+    /// 写入或校验失败时自动用备份恢复原文件；备份可用 `calendar clean --backups` 清理
+    #[serde(default)]
+    pub backup: bool,
+
+    /// 下载时把来源 URL 写入 EXIF UserComment 标签（默认 false）
+    ///
+    /// URL 中的密钥参数会被隐藏；与 Artist 等标签一样，已有的 UserComment 只在 `overwrite_existing` 时覆盖
+    #[serde(default)]
+    pub embed_source_url: bool,
+
+    /// 写入日期标签时删除 GPS 位置、机身和镜头序列号、相机主人等敏感标签（默认 false）
+    ///
+    /// 同时配置 `gps_*` 时先删除原有坐标，再写入配置的坐标
+    #[serde(default)]
+    pub strip_sensitive: bool,
+}
+
+impl Default for ExifConfig {
+    fn default() -> Self {
+        Self {
+            overwrite_existing: false,
+            gps_latitude: None,
+            gps_longitude: None,
+            gps_altitude: None,
+            offset_time: None,
+            verify: default_exif_verify(),
+            backup: false,
+            embed_source_url: false,
+            strip_sensitive: false,
+        }
+    }
+}
+
+/// 文件属性选项
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileopsConfig {
+    /// 设置修改时间时保留文件原有的访问时间（默认 false）
+    ///
+    /// 为 false 时访问时间和修改时间一起设为图片日期；依赖访问时间的分层存储等工具需要开启
+    #[serde(default)]
+    pub preserve_atime: bool,
+
+    /// clean、prune、dedupe --delete 等删除操作将文件移入系统回收站而不是直接删除（默认 false）
+    ///
+    /// 回收站不可用时记录警告并直接删除
+    #[serde(default)]
+    pub use_trash: bool,
+
+    /// 写入文件后设置的八进制权限，如 `"0664"`（默认不修改，由 umask 决定）
+    ///
+    /// 非 Unix 平台忽略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_mode: Option<String>,
+
+    /// 写入文件后设置的所属组（组名或组 ID，仅 Unix）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_group: Option<String>,
+
+    /// 写入每个文件并更新元数据后同步文件和所在目录到磁盘（默认 false）
+    ///
+    /// 防止断电后已报告成功的文件为空，会降低下载吞吐
+    #[serde(default)]
+    pub durable_writes: bool,
+
+    /// 文件修改时间的来源（默认 publication）
+    #[serde(default)]
+    pub mtime_source: MtimeSource,
+
+    /// 每次 run 之后在输出目录维护指向最新日期图片的 `latest.<扩展名>` 链接（默认不维护）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_link: Option<LatestLink>,
+}
+
+/// 文件修改时间的来源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MtimeSource {
+    /// 修改时间和创建时间都设为图片日期
+    #[default]
+    Publication,
+    /// 修改时间保留为实际下载（写入）时间，只有创建时间设为图片日期
+    Download,
+}
+
+/// `latest` 链接的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LatestLink {
+    /// 相对路径的符号链接
+    Symlink,
+    /// 硬链接
+    Hardlink,
+}
+
+impl FileopsConfig {
+    /// 校验文件权限格式和所属组是否存在，返回错误信息
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if let Some(mode) = &self.file_mode {
+            fileops::parse_file_mode(mode).map_err(|e| format!("fileops.file_mode: {}", e))?;
+        }
+        #[cfg(unix)]
+        if let Some(group) = &self.file_group {
+            fileops::lookup_group(group).map_err(|e| format!("fileops.file_group: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// 解析后的文件权限，未设置或格式无效时为 None
+    pub fn file_mode(&self) -> Option<u32> {
+        self.file_mode.as_deref().and_then(|mode| fileops::parse_file_mode(mode).ok())
+    }
+
+    /// 将配置的权限和所属组应用到文件，两者都未设置时不做任何操作
+    pub fn apply_permissions(&self, path: &Path) -> Result<()> {
+        fileops::set_file_permissions(path, self.file_mode(), self.file_group.as_deref())
+    }
+
+    /// 删除操作在输出中使用的动词
+    pub fn discard_verb(&self) -> &'static str {
+        if self.use_trash {
+            "移入回收站"
+        } else {
+            "删除"
+        }
+    }
+}
+
+/// 默认在写入 EXIF 后读回校验
+fn default_exif_verify() -> bool {
+    true
 }
 
+impl ExifConfig {
+    /// 配置的 GPS 坐标 (纬度, 经度, 海拔)，未设置经纬度时返回 None
+    pub fn gps(&self) -> Option<(f64, f64, Option<f64>)> {
+        Some((self.gps_latitude?, self.gps_longitude?, self.gps_altitude))
+    }
+
+    /// 校验 GPS 坐标的取值范围和组合，返回错误信息
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        match (self.gps_latitude, self.gps_longitude) {
+            (Some(_), None) | (None, Some(_)) => {
+                return Err("exif.gps_latitude 和 exif.gps_longitude 必须同时设置".to_string());
+            }
+            (None, None) if self.gps_altitude.is_some() => {
+                return Err("设置 exif.gps_altitude 时必须同时设置经纬度".to_string());
+            }
+            _ => {}
+        }
+        let ranges = [
+            ("gps_latitude", self.gps_latitude, 90.0),
+            ("gps_longitude", self.gps_longitude, 180.0),
+            ("gps_altitude", self.gps_altitude, f64::MAX),
+        ];
+        for (name, value, limit) in ranges {
+            if let Some(value) = value {
+                if !value.is_finite() || value.abs() > limit {
+                    return Err(format!("exif.{} 超出范围: {}", name, value));
+                }
+            }
+        }
+        if let Some(offset) = &self.offset_time {
+            if !is_exif_offset(offset) {
+                return Err(format!(
+                    "exif.offset_time 格式无效: '{}'，应为 +HH:MM 或 -HH:MM（如 +08:00）",
+                    offset
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 是否为 EXIF 时区偏移格式 `±HH:MM`（小时不超过 14，分钟小于 60）
+fn is_exif_offset(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    if bytes.len() != 6 || !matches!(bytes[0], b'+' | b'-') || bytes[3] != b':' {
+        return false;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<u32> {
+        let digits = value.get(range)?;
+        digits.bytes().all(|b| b.is_ascii_digit()).then(|| digits.parse().ok())?
+    };
+    matches!((number(1..3), number(4..6)), (Some(hours), Some(minutes)) if hours <= 14 && minutes < 60)
+}
+
+/// 已弃用的配置键：(旧键, 新键)，键名支持 `a.b` 形式的嵌套路径
+///
+/// 重命名配置项时在此登记旧键名，加载配置时会自动迁移到新键名并输出弃用警告。
+const DEPRECATED_KEYS: &[(&str, &str)] = &[];
+
 /// 用于 serde 的日期序列化/反序列化模块
 mod serde_date {
     use super::*;
@@ -71,6 +401,21 @@ mod serde_date {
     }
 }
 
+/// 默认按年份建立子目录
+fn default_year_subdirs() -> bool {
+    true
+}
+
+/// 默认每天下载一张图片
+fn default_images_per_day() -> u32 {
+    1
+}
+
+/// 默认的 `{ext}` 扩展名
+fn default_ext() -> String {
+    "jpg".to_string()
+}
+
 /// 默认最大并发数
 fn default_max_concurrent() -> usize {
     3
@@ -78,7 +423,7 @@ fn default_max_concurrent() -> usize {
 
 /// 默认 User-Agent
 fn default_user_agent() -> String {
-    "Mozilla/5.0".to_string()
+    "calendar/{version}".to_string()
 }
 
 /// 默认超时时间（秒）
@@ -91,28 +436,218 @@ fn default_max_retries() -> u32 {
     3
 }
 
+/// 默认覆盖确认阈值
+fn default_overwrite_confirm_threshold() -> usize {
+    10
+}
+
 /// 默认重试延迟（毫秒）
 fn default_retry_delay() -> u64 {
     1000
 }
 
+/// 默认允许的内容类型
+fn default_allowed_content_types() -> Vec<String> {
+    vec!["image/*".to_string()]
+}
+
 impl Config {
+    /// 在完整加载配置前读取 `log_file` 配置项
+    ///
+    /// 日志需要在加载配置之前初始化，这里只做宽松解析；读取或解析失败时返回 None，
+    /// 错误留给随后的 [`Config::from_file`] 报告
+    pub fn peek_log_file(path: &Path) -> Option<PathBuf> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let table: toml::Table = toml::from_str(&content).ok()?;
+        table
+            .get("log_file")?
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// 在完整加载配置前读取 `lang` 配置项，使加载配置时的错误也能使用该语言输出
+    pub fn peek_lang(path: &Path) -> Option<crate::i18n::Lang> {
+        let content = std::fs::read_to_string(path).ok()?;
+        let table: toml::Table = toml::from_str(&content).ok()?;
+        table.get("lang")?.as_str()?.parse().ok()
+    }
+
     /// 从 TOML 文件加载配置
     pub fn from_file(path: &Path) -> Result<Self> {
+        Self::from_file_with_deprecations(path, DEPRECATED_KEYS)
+    }
+
+    /// 按给定的已弃用键表加载配置，测试中用来验证完整的迁移流程
+    fn from_file_with_deprecations(path: &Path, deprecations: &[(&str, &str)]) -> Result<Self> {
         tracing::info!("加载配置文件: {}", path.display());
 
         let content = std::fs::read_to_string(path).map_err(|e| {
             AppError::config_error(path, format!("无法读取配置文件: {}", e))
         })?;
 
-        let config: Config = toml::from_str(&content).map_err(|e| {
+        let mut table: toml::Table = toml::from_str(&content).map_err(|e| {
+            AppError::config_error(path, format!("TOML 解析失败: {}", e))
+        })?;
+
+        let deprecated_keys = migrate_deprecated_keys(&mut table, deprecations);
+        for (old_key, new_key) in &deprecated_keys {
+            tracing::warn!("配置项 {} 已弃用，请改用 {}", old_key, new_key);
+        }
+
+        // 没有迁移时直接解析原文，保留带行号的错误信息
+        let parsed = if deprecated_keys.is_empty() {
+            toml::from_str::<Config>(&content)
+        } else {
+            toml::Value::Table(table).try_into::<Config>()
+        };
+        let mut config = parsed.map_err(|e| {
             AppError::config_error(path, format!("TOML 解析失败: {}", e))
         })?;
+        config.deprecated_keys = deprecated_keys;
+
+        config
+            .interpolate_env()
+            .map_err(|details| AppError::config_error(path, details))?;
+        config
+            .exif
+            .validate()
+            .map_err(|details| AppError::config_error(path, details))?;
+        config
+            .fileops
+            .validate()
+            .map_err(|details| AppError::config_error(path, details))?;
+        if let Some(names) = &config.month_names {
+            if names.len() != 12 {
+                return Err(AppError::config_error(
+                    path,
+                    format!("month_names 必须为 12 个，实际为 {} 个", names.len()),
+                ));
+            }
+            if let Some(name) = names.iter().find(|name| name.is_empty() || name.contains(['/', '\\'])) {
+                return Err(AppError::config_error(
+                    path,
+                    format!("month_names 中的名称不能为空或包含路径分隔符: {:?}", name),
+                ));
+            }
+        }
+        if config.default_ext.is_empty() || !config.default_ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(AppError::config_error(
+                path,
+                format!("default_ext 只能包含字母和数字（不含点号）: {:?}", config.default_ext),
+            ));
+        }
+        if config.images_per_day == 0 {
+            return Err(AppError::config_error(path, "images_per_day 必须大于 0"));
+        }
+        // 模板中拼写错误的占位符在加载时发现
+        let templates = [
+            ("filename_format", Some(config.filename_format.as_str())),
+            ("base_url", Some(config.base_url.as_str())),
+            ("failed_log_path", config.failed_log_template()),
+        ];
+        for (key, template) in templates {
+            if let Some(template) = template {
+                // failed_log_path 始终使用花括号占位符
+                let style = if key == "failed_log_path" { FormatStyle::Braces } else { config.format_style };
+                let formatter = FilenameFormatter::with_style(template, style)
+                    .map_err(|e| AppError::config_error(path, format!("{}: {}", key, e)))?;
+                if key == "base_url" {
+                    formatter
+                        .ensure_no_urlhash()
+                        .map_err(|e| AppError::config_error(path, format!("{}: {}", key, e)))?;
+                }
+                // 同一天的多张图片需要不同的 URL 和文件名
+                if config.images_per_day > 1 && key != "failed_log_path" && !formatter.uses_index() {
+                    return Err(AppError::config_error(
+                        path,
+                        format!("images_per_day 大于 1 时 {} 必须包含 {{n}} 占位符", key),
+                    ));
+                }
+            }
+        }
 
-        tracing::debug!("配置加载成功: {:?}", config);
+        tracing::debug!("配置加载成功: {:?}", config.redacted());
         Ok(config)
     }
 
+    /// 加载时发现的已弃用配置键（旧键, 新键）
+    pub fn deprecated_keys(&self) -> &[(String, String)] {
+        &self.deprecated_keys
+    }
+
+    /// 所有参与 `${VAR}` 插值的字符串字段，包括字符串列表中的每个元素
+    fn string_fields_mut(&mut self) -> Vec<(Cow<'static, str>, &mut String)> {
+        let mut fields: Vec<(Cow<'static, str>, &mut String)> = vec![
+            ("base_url".into(), &mut self.base_url),
+            ("output_dir".into(), &mut self.output_dir),
+            ("filename_format".into(), &mut self.filename_format),
+            ("user_agent".into(), &mut self.user_agent),
+            ("default_ext".into(), &mut self.default_ext),
+        ];
+        let optional = [
+            ("contact", self.contact.as_mut()),
+            ("failed_log_path", self.failed_log_path.as_mut()),
+            ("log_file", self.log_file.as_mut()),
+            ("exif.offset_time", self.exif.offset_time.as_mut()),
+            ("fileops.file_mode", self.fileops.file_mode.as_mut()),
+            ("fileops.file_group", self.fileops.file_group.as_mut()),
+        ];
+        fields.extend(
+            optional
+                .into_iter()
+                .filter_map(|(field, value)| value.map(|v| (field.into(), v))),
+        );
+        let lists = [
+            ("month_names", self.month_names.as_mut()),
+            ("allowed_content_types", Some(&mut self.allowed_content_types)),
+        ];
+        for (field, values) in lists {
+            for (i, value) in values.into_iter().flatten().enumerate() {
+                fields.push((format!("{}[{}]", field, i).into(), value));
+            }
+        }
+        fields
+    }
+
+    /// 将字符串字段中的 `${NAME}` 替换为环境变量的值
+    ///
+    /// `$${` 转义为字面量 `${`。引用的环境变量未设置时返回错误信息。
+    fn interpolate_env(&mut self) -> std::result::Result<(), String> {
+        let mut interpolated = Vec::new();
+
+        for (field, value) in self.string_fields_mut() {
+            if !value.contains("${") {
+                continue;
+            }
+            let (resolved, secrets) = interpolate_str(value)
+                .map_err(|name| format!("字段 {} 引用的环境变量 {} 未设置", field, name))?;
+            register_secrets(secrets);
+            interpolated.push((field, std::mem::replace(value, resolved)));
+        }
+
+        self.interpolated = interpolated;
+        Ok(())
+    }
+
+    /// 返回用于展示和保存的副本：插值字段恢复为原始模板
+    ///
+    /// 日志、`config --validate` 输出和写回配置文件都应使用此副本，
+    /// 确保环境变量中的敏感值不会泄露。
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        if let Some(output_dir) = config.original_output_dir.take() {
+            config.output_dir = output_dir;
+        }
+        let originals = std::mem::take(&mut config.interpolated);
+        for (field, value) in config.string_fields_mut() {
+            if let Some((_, raw)) = originals.iter().find(|(name, _)| *name == field) {
+                *value = raw.clone();
+            }
+        }
+        config
+    }
+
     /// 获取重试配置
     pub fn retry_config(&self) -> crate::downloader::RetryConfig {
         crate::downloader::RetryConfig {
@@ -129,64 +664,260 @@ impl Config {
             Some(Command::Run {
                 start_date,
                 end_date,
+                month,
+                year,
+                since_last_success,
+                base_url,
+                timeout,
+                concurrency,
                 overwrite,
                 download_only,
+                force_metadata,
+                yes,
+                json,
+                progress,
+                strict,
+                fail_fast,
+                max_duration,
+                wait,
+            }) => {
+                // --month/--year 已在解析命令行时校验，这里展开为起止日期
+                let range = month
+                    .as_deref()
+                    .and_then(|m| date_utils::parse_month(m).ok())
+                    .or_else(|| {
+                        year.as_deref()
+                            .and_then(|y| date_utils::parse_year(y, date_utils::today()).ok())
+                    })
+                    .map(|(first, last)| {
+                        (date_utils::format_date(&first), date_utils::format_date(&last))
+                    });
+                let (start_date, end_date) = match range {
+                    Some((first, last)) => (Some(first), Some(last)),
+                    None => (start_date.clone(), end_date.clone()),
+                };
+
+                ConfigWithDefaults {
+                    start_date_override: start_date,
+                    end_date,
+                    since_last_success: *since_last_success,
+                    base_url_override: base_url.clone(),
+                    timeout_override: *timeout,
+                    concurrency_override: *concurrency,
+                    overwrite: *overwrite,
+                    download_only: *download_only,
+                    force_metadata: *force_metadata,
+                    yes: *yes,
+                    json: *json,
+                    progress_json: progress == "json",
+                    strict: *strict,
+                    fail_fast: *fail_fast,
+                    max_duration: *max_duration,
+                    wait: *wait,
+                    ..Default::default()
+                }
+            }
+            Some(Command::Process {
+                base_url,
+                timeout,
+                overwrite,
+                metadata_only,
+                force_metadata,
+                yes,
+                json,
+                progress,
+                strict,
+                fail_fast,
+                wait,
+                reverse,
+                concurrency,
+                ..
+            }) => ConfigWithDefaults {
+                base_url_override: base_url.clone(),
+                timeout_override: *timeout,
+                concurrency_override: *concurrency,
+                overwrite: *overwrite,
+                metadata_only: *metadata_only,
+                force_metadata: *force_metadata,
+                yes: *yes,
+                json: *json,
+                progress_json: progress == "json",
+                strict: *strict,
+                fail_fast: *fail_fast,
+                wait: *wait,
+                reverse: *reverse,
+                ..Default::default()
+            },
+            Some(Command::Touch {
+                start_date,
+                end_date,
+                ..
             }) => ConfigWithDefaults {
                 start_date_override: start_date.clone(),
                 end_date: end_date.clone(),
-                overwrite: *overwrite,
-                download_only: *download_only,
-                metadata_only: false,
+                metadata_only: true,
+                ..Default::default()
             },
-            Some(Command::Process {
+            Some(Command::List {
+                start_date,
+                end_date,
+                ..
+            }) => ConfigWithDefaults {
+                start_date_override: start_date.clone(),
+                end_date: end_date.clone(),
+                ..Default::default()
+            },
+            Some(Command::Retry {
+                base_url,
+                timeout,
+                concurrency,
                 overwrite,
                 metadata_only,
+                force_metadata,
+                yes,
+                json,
+                progress,
+                strict,
+                fail_fast,
+                wait,
+                reverse,
                 ..
             }) => ConfigWithDefaults {
-                start_date_override: None,
-                end_date: None,
+                base_url_override: base_url.clone(),
+                timeout_override: *timeout,
+                concurrency_override: *concurrency,
                 overwrite: *overwrite,
-                download_only: false,
                 metadata_only: *metadata_only,
+                force_metadata: *force_metadata,
+                yes: *yes,
+                json: *json,
+                progress_json: progress == "json",
+                strict: *strict,
+                fail_fast: *fail_fast,
+                wait: *wait,
+                reverse: *reverse,
+                ..Default::default()
             },
-            Some(Command::Config { .. }) => ConfigWithDefaults {
-                start_date_override: None,
-                end_date: None,
-                overwrite: false,
-                download_only: false,
-                metadata_only: false,
-            },
-            None => ConfigWithDefaults {
-                // 默认执行 run 命令的配置
-                start_date_override: None,
-                end_date: None,
-                overwrite: false,
-                download_only: false,
-                metadata_only: false,
+            Some(Command::Repair { wait, .. }) => ConfigWithDefaults {
+                wait: *wait,
+                ..Default::default()
             },
+            Some(Command::Config { .. })
+            | Some(Command::Doctor)
+            | Some(Command::Man { .. })
+            | Some(Command::Completions { .. })
+            | Some(Command::Status { .. })
+            | Some(Command::Verify { .. })
+            | Some(Command::Stats { .. })
+            | Some(Command::Clean { .. })
+            | Some(Command::Watch { .. })
+            | Some(Command::Prune { .. })
+            | Some(Command::Dedupe { .. })
+            | Some(Command::Rename { .. })
+            | Some(Command::Migrate { .. })
+            | Some(Command::Url { .. })
+            | Some(Command::Exif { .. }) => ConfigWithDefaults::default(),
+            // 未指定子命令时等同于不带参数的 run
+            None => self.merge_cli_defaults(Some(&Command::default_run())),
         }
     }
 
     /// 获取有效的起始日期
+    ///
+    /// 命令行日期支持 `today`、`last-N-days` 等相对关键字，范围关键字取起始日期
     pub fn get_effective_start_date(&self, override_date: &Option<String>) -> Result<NaiveDate> {
         if let Some(date_str) = override_date {
-            date_utils::parse_date(date_str)
+            Ok(date_utils::resolve_date_arg(date_str, date_utils::today())?.0)
         } else {
             Ok(self.start_date)
         }
     }
 
     /// 获取有效的结束日期
+    ///
+    /// 范围关键字（如 `last-N-days`）取结束日期
     pub fn get_effective_end_date(
         &self,
         override_date: &Option<String>,
     ) -> Result<Option<NaiveDate>> {
         override_date
             .as_ref()
-            .map(|d| date_utils::parse_date(d))
+            .map(|d| Ok(date_utils::resolve_date_arg(d, date_utils::today())?.1))
             .transpose()
     }
 
+    /// 获取实际发送的 User-Agent
+    ///
+    /// 将 `{version}` 替换为程序版本，`{contact}` 替换为 `contact` 配置项；
+    /// 不含占位符的字符串原样返回。
+    pub fn resolved_user_agent(&self) -> Result<String> {
+        let mut agent = self.user_agent.replace("{version}", env!("CARGO_PKG_VERSION"));
+
+        if agent.contains("{contact}") {
+            let contact = self.contact.as_deref().ok_or_else(|| {
+                AppError::HeaderError("user_agent 使用了 {contact} 占位符，但未配置 contact".to_string())
+            })?;
+            agent = agent.replace("{contact}", contact);
+        }
+
+        Ok(agent)
+    }
+
+    /// 按 `format_style` 解析格式，并使用配置中的本地化月份名称和默认扩展名创建格式化器
+    pub fn formatter(&self, format: &str) -> Result<FilenameFormatter> {
+        self.localized(FilenameFormatter::with_style(format, self.format_style)?)
+    }
+
+    /// 为格式化器设置配置中的本地化月份名称、默认扩展名和文件名清理选项
+    pub fn localized(&self, formatter: FilenameFormatter) -> Result<FilenameFormatter> {
+        let formatter =
+            formatter.with_default_ext(&self.default_ext).with_sanitize(self.sanitize_filenames);
+        match &self.month_names {
+            Some(names) => formatter.with_month_names(names),
+            None => Ok(formatter),
+        }
+    }
+
+    /// `filename_format` 对应的格式化器，`{urlhash}` 按 `base_url` 生成的 URL 计算
+    pub fn filename_formatter(&self) -> Result<FilenameFormatter> {
+        self.formatter(&self.filename_format)?
+            .with_url_template(self.formatter(&self.base_url)?)
+    }
+
+    /// 获取失败日期记录文件的路径
+    ///
+    /// `date` 用于填充路径中的日期占位符（通常为运行当天）；
+    /// 相对路径基于 `output_dir`，配置为空字符串时返回 `None` 表示不写入。
+    pub fn failed_log_path(&self, date: &NaiveDate) -> Result<Option<PathBuf>> {
+        let Some(template) = self.failed_log_template() else {
+            return Ok(None);
+        };
+
+        let formatted = self.localized(FilenameFormatter::new(template)?)?.format(date);
+        let path = PathBuf::from(formatted);
+        if path.is_absolute() {
+            Ok(Some(path))
+        } else {
+            Ok(Some(Path::new(&self.output_dir).join(path)))
+        }
+    }
+
+    /// 获取失败日期记录文件的路径模板，配置为空字符串时返回 `None`
+    pub fn failed_log_template(&self) -> Option<&str> {
+        match self.failed_log_path.as_deref() {
+            None => Some(DEFAULT_FAILED_LOG),
+            Some(t) if t.trim().is_empty() => None,
+            Some(t) => Some(t),
+        }
+    }
+
+    /// 元数据（EXIF、文件时间戳）更新失败的日期记录文件路径
+    ///
+    /// 每行 `日期<TAB>原因`，可直接用作 `process --metadata-only --dates-file` 的输入
+    pub fn metadata_failed_log_path(&self) -> PathBuf {
+        Path::new(&self.output_dir).join(METADATA_FAILED_LOG)
+    }
+
     /// 获取超时时长
     pub fn timeout_duration(&self) -> StdDuration {
         StdDuration::from_secs(self.timeout)
@@ -199,6 +930,7 @@ impl Config {
 
         if let Ok(agent) = std::env::var("CALENDAR_USER_AGENT") {
             config.user_agent = agent;
+            config.interpolated.retain(|(field, _)| *field != "user_agent");
             tracing::debug!("从环境变量覆盖 User-Agent");
         }
 
@@ -212,15 +944,43 @@ impl Config {
         config
     }
 
+    /// 使用命令行指定的输出目录（`--output-dir`）
+    ///
+    /// 下载、失败记录和状态文件都使用新目录；`redacted` 和写回配置文件时仍使用原来的 output_dir
+    pub fn with_output_dir(mut self, dir: &Path) -> Self {
+        let original = std::mem::replace(&mut self.output_dir, dir.to_string_lossy().into_owned());
+        self.original_output_dir.get_or_insert(original);
+        self
+    }
+
+    /// 是否使用了命令行指定的输出目录
+    pub fn output_dir_overridden(&self) -> bool {
+        self.original_output_dir.is_some()
+    }
+
+    /// 返回应用了命令行覆盖项的副本，用于创建下载器
+    ///
+    /// 命令行参数的优先级高于配置文件和环境变量；返回的副本不应写回配置文件
+    pub fn with_cli_overrides(&self, defaults: &ConfigWithDefaults) -> Config {
+        let mut config = self.clone();
+        if let Some(secs) = defaults.timeout_override {
+            config.timeout = secs;
+        }
+        if let Some(concurrency) = defaults.concurrency_override {
+            config.max_concurrent = concurrency;
+        }
+        config
+    }
+
     /// 保存配置到文件
     pub fn save_to_file(&self, path: &Path) -> Result<()> {
         tracing::info!("保存配置文件: {}", path.display());
 
-        let toml_content = toml::to_string_pretty(self).map_err(|e| {
+        let toml_content = toml::to_string_pretty(&self.redacted()).map_err(|e| {
             AppError::config_error(path, format!("TOML 序列化失败: {}", e))
         })?;
 
-        std::fs::write(path, toml_content).map_err(|e| {
+        fileops::write_atomic(path, toml_content).map_err(|e| {
             AppError::config_error(path, format!("写入配置文件失败: {}", e))
         })?;
 
@@ -237,14 +997,192 @@ impl Config {
     }
 }
 
+/// 将原始 TOML 中的已弃用键迁移到新键名
+///
+/// 返回实际出现的已弃用键。新旧键同时存在时以新键为准，旧键被丢弃。
+fn migrate_deprecated_keys(
+    table: &mut toml::Table,
+    deprecations: &[(&str, &str)],
+) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+
+    for (old_key, new_key) in deprecations {
+        let Some(value) = remove_key_path(table, old_key) else {
+            continue;
+        };
+        found.push((old_key.to_string(), new_key.to_string()));
+
+        if get_key_path(table, new_key).is_some() {
+            tracing::warn!("配置项 {} 与 {} 同时存在，忽略 {}", old_key, new_key, old_key);
+            continue;
+        }
+        insert_key_path(table, new_key, value);
+    }
+
+    found
+}
+
+/// 按 `a.b.c` 路径读取嵌套键
+fn get_key_path<'a>(table: &'a toml::Table, key_path: &str) -> Option<&'a toml::Value> {
+    let (parents, last) = split_key_path(key_path);
+    let mut current = table;
+    for part in parents {
+        current = current.get(part)?.as_table()?;
+    }
+    current.get(last)
+}
+
+/// 按 `a.b.c` 路径移除嵌套键
+fn remove_key_path(table: &mut toml::Table, key_path: &str) -> Option<toml::Value> {
+    let (parents, last) = split_key_path(key_path);
+    let mut current = table;
+    for part in parents {
+        current = current.get_mut(part)?.as_table_mut()?;
+    }
+    current.remove(last)
+}
+
+/// 按 `a.b.c` 路径插入嵌套键，必要时创建中间表
+fn insert_key_path(table: &mut toml::Table, key_path: &str, value: toml::Value) {
+    let (parents, last) = split_key_path(key_path);
+    let mut current = table;
+    for part in parents {
+        let entry = current
+            .entry(part.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        if !entry.is_table() {
+            *entry = toml::Value::Table(toml::Table::new());
+        }
+        current = entry.as_table_mut().unwrap();
+    }
+    current.insert(last.to_string(), value);
+}
+
+fn split_key_path(key_path: &str) -> (Vec<&str>, &str) {
+    let mut parts: Vec<&str> = key_path.split('.').collect();
+    let last = parts.pop().unwrap_or(key_path);
+    (parts, last)
+}
+
+/// 通过插值引入的敏感值，用于日志脱敏
+static SECRETS: OnceLock<RwLock<Vec<String>>> = OnceLock::new();
+
+fn secrets() -> &'static RwLock<Vec<String>> {
+    SECRETS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+fn register_secrets(values: Vec<String>) {
+    let mut registry = secrets().write().unwrap_or_else(|e| e.into_inner());
+    for value in values {
+        if !value.is_empty() && !registry.contains(&value) {
+            registry.push(value);
+        }
+    }
+}
+
+/// 将文本中出现的插值敏感值替换为 `***`
+///
+/// 记录包含插值结果的 URL 等信息前调用。
+pub fn redact_secrets(text: &str) -> String {
+    let registry = secrets().read().unwrap_or_else(|e| e.into_inner());
+    let mut result = text.to_string();
+    for secret in registry.iter() {
+        result = result.replace(secret.as_str(), "***");
+    }
+    result
+}
+
+/// 对单个字符串执行 `${NAME}` 插值
+///
+/// 成功时返回插值结果和引用到的环境变量值；变量未设置时返回变量名。
+fn interpolate_str(input: &str) -> std::result::Result<(String, Vec<String>), String> {
+    let mut result = String::with_capacity(input.len());
+    let mut secrets = Vec::new();
+    let mut rest = input;
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+
+        if let Some(after) = tail.strip_prefix("$${") {
+            // 转义：$${ -> ${
+            result.push_str("${");
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix("${") {
+            match after.find('}') {
+                Some(end) => {
+                    let name = &after[..end];
+                    let value = std::env::var(name).map_err(|_| name.to_string())?;
+                    result.push_str(&value);
+                    secrets.push(value);
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    // 未闭合的 ${ 按字面量保留
+                    result.push_str(tail);
+                    rest = "";
+                }
+            }
+        } else {
+            result.push('$');
+            rest = &tail[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok((result, secrets))
+}
+
+/// `--concurrency` 允许的最大并发数
+pub const MAX_CONCURRENCY: usize = 256;
+
+/// 校验命令行指定的并发数（1–[`MAX_CONCURRENCY`]）
+pub fn validate_concurrency(concurrency: usize) -> Result<usize> {
+    if concurrency == 0 || concurrency > MAX_CONCURRENCY {
+        return Err(AppError::argument_error(format!(
+            "--concurrency 必须在 1 到 {} 之间，实际为 {}",
+            MAX_CONCURRENCY, concurrency
+        )));
+    }
+    Ok(concurrency)
+}
+
 /// 带有命令行参数默认值的配置
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ConfigWithDefaults {
     pub start_date_override: Option<String>,
     pub end_date: Option<String>,
+    /// 从状态文件记录的上次成功日期之后开始
+    pub since_last_success: bool,
+    /// 命令行指定的 URL 模板，覆盖配置中的 base_url
+    pub base_url_override: Option<String>,
+    /// 命令行指定的超时时间（秒），覆盖配置文件和环境变量
+    pub timeout_override: Option<u64>,
+    /// 命令行指定的并发数，覆盖配置中的 max_concurrent
+    pub concurrency_override: Option<usize>,
     pub overwrite: bool,
     pub download_only: bool,
     pub metadata_only: bool,
+    /// 已存在文件的元数据即使已是最新也重写
+    pub force_metadata: bool,
+    /// 跳过覆盖确认
+    pub yes: bool,
+    /// 以 JSON 格式输出结果
+    pub json: bool,
+    /// 以 JSON 行输出进度事件（`--progress json`）
+    pub progress_json: bool,
+    /// 元数据更新失败也视为失败
+    pub strict: bool,
+    /// 首个失败后停止调度剩余日期
+    pub fail_fast: bool,
+    /// 整次运行的时间预算
+    pub max_duration: Option<std::time::Duration>,
+    /// 输出目录被锁定时等待
+    pub wait: bool,
+    /// 按日期从新到旧处理（process、retry）
+    pub reverse: bool,
+    /// 安静模式：不显示进度条，只输出最终统计（由全局 `--quiet` 设置）
+    pub quiet: bool,
 }
 
 #[cfg(test)]
@@ -254,14 +1192,13 @@ mod tests {
     use chrono::Datelike;
     use clap::Parser;
     use std::fs;
-    use std::path::PathBuf;
-    use tempfile::tempdir;
+    use tempfile::{tempdir, TempDir};
 
-    fn create_test_config(contents: &str) -> PathBuf {
+    fn create_test_config(contents: &str) -> (TempDir, PathBuf) {
         let dir = tempdir().unwrap();
         let config_path = dir.path().join("config.toml");
         fs::write(&config_path, contents).unwrap();
-        config_path
+        (dir, config_path)
     }
 
     #[test]
@@ -275,7 +1212,7 @@ max_concurrent = 5
 user_agent = "TestAgent/1.0"
 timeout = 60
 "#;
-        let config_path = create_test_config(contents);
+        let (_dir, config_path) = create_test_config(contents);
         let config = Config::from_file(&config_path).unwrap();
 
         assert_eq!(config.start_date.year(), 2024);
@@ -292,11 +1229,15 @@ base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
 output_dir = "./images"
 filename_format = "{yyyy}{mm}{dd}.jpg"
 "#;
-        let config_path = create_test_config(contents);
+        let (_dir, config_path) = create_test_config(contents);
         let config = Config::from_file(&config_path).unwrap();
 
         assert_eq!(config.max_concurrent, 3);
-        assert_eq!(config.user_agent, "Mozilla/5.0");
+        assert_eq!(config.user_agent, "calendar/{version}");
+        assert_eq!(
+            config.resolved_user_agent().unwrap(),
+            format!("calendar/{}", env!("CARGO_PKG_VERSION"))
+        );
         assert_eq!(config.timeout, 30);
     }
 
@@ -308,42 +1249,207 @@ base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
 output_dir = "./images"
 filename_format = "{yyyy}{mm}{dd}.jpg"
 "#;
-        let config_path = create_test_config(contents);
+        let (_dir, config_path) = create_test_config(contents);
         let result = Config::from_file(&config_path);
         assert!(result.is_err());
     }
 
     #[test]
-    fn test_missing_required_field() {
-        let contents = r#"
+    fn test_unknown_placeholder_in_templates() {
+        let (_dir, config_path) = create_test_config(
+            r#"
+start_date = "2024-01-01"
 base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
 output_dir = "./images"
-"#;
-        let config_path = create_test_config(contents);
-        let result = Config::from_file(&config_path);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_merge_cli_defaults() {
-        let cli = Cli::try_parse_from([
-            "calendar",
-            "run",
-            "--start-date",
-            "2024-06-01",
-            "--end-date",
-            "2024-06-30",
-            "--overwrite",
-        ])
-        .unwrap();
+filename_format = "{yyy}{mm}{dd}.jpg"
+"#,
+        );
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("filename_format"), "{}", err);
+        assert!(err.contains("`{yyyy}`"), "{}", err);
 
-        let contents = r#"
+        let (_dir, config_path) = create_test_config(
+            r#"
 start_date = "2024-01-01"
-base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+base_url = "https://example.com/images/{year}/{mnth:02}/{day:02}.jpg"
 output_dir = "./images"
 filename_format = "{yyyy}{mm}{dd}.jpg"
-"#;
-        let config_path = create_test_config(contents);
+"#,
+        );
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("base_url"), "{}", err);
+    }
+
+    #[test]
+    fn test_month_names() {
+        let base = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{year}/{month_name}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}/{month_name}/{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(base);
+        let config = Config::from_file(&config_path).unwrap();
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        assert_eq!(config.filename_formatter().unwrap().format(&june_15), "2024/June/15.jpg");
+
+        let names = r#"["一月", "二月", "三月", "四月", "五月", "六月", "七月", "八月", "九月", "十月", "十一月", "十二月"]"#;
+        let (_dir, config_path) = create_test_config(&format!("month_names = {}\n{}", names, base));
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.filename_formatter().unwrap().format(&june_15), "2024/六月/15.jpg");
+
+        let (_dir, config_path) =
+            create_test_config(&format!("month_names = [\"一月\", \"二月\"]\n{}", base));
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("month_names 必须为 12 个"), "{}", err);
+    }
+
+    #[test]
+    fn test_urlhash_uses_base_url() {
+        let base = r#"
+start_date = "2024-01-01"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}_{urlhash:6}.jpg"
+"#;
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let (_dir, config_path) =
+            create_test_config(&format!("base_url = \"https://example.com/{{yyyy}}{{mm}}{{dd}}.jpg\"\n{}", base));
+        let config = Config::from_file(&config_path).unwrap();
+        let hash = crate::fileops::sha256_bytes(b"https://example.com/20240615.jpg");
+        assert_eq!(
+            config.filename_formatter().unwrap().format(&june_15),
+            format!("20240615_{}.jpg", &hash[..6])
+        );
+
+        let (_dir, config_path) =
+            create_test_config(&format!("base_url = \"https://example.com/{{urlhash}}.jpg\"\n{}", base));
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("base_url") && err.contains("不能用于 URL 模板"), "{}", err);
+    }
+
+    #[test]
+    fn test_images_per_day() {
+        let base = r#"
+start_date = "2024-01-01"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}_{n}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(&format!(
+            "images_per_day = 3\nbase_url = \"https://example.com/{{yyyy}}-{{mm}}-{{dd}}_{{n}}.jpg\"\n{}",
+            base
+        ));
+        assert_eq!(Config::from_file(&config_path).unwrap().images_per_day, 3);
+
+        // 默认每天一张，`{n}` 取 1
+        let (_dir, config_path) =
+            create_test_config(&format!("base_url = \"https://example.com/{{yyyy}}{{mm}}{{dd}}.jpg\"\n{}", base));
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.images_per_day, 1);
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        assert_eq!(config.filename_formatter().unwrap().format(&june_15), "20240615_1.jpg");
+
+        let (_dir, config_path) = create_test_config(&format!(
+            "images_per_day = 3\nbase_url = \"https://example.com/{{yyyy}}{{mm}}{{dd}}.jpg\"\n{}",
+            base
+        ));
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("base_url 必须包含 {n}"), "{}", err);
+
+        let (_dir, config_path) = create_test_config(&format!(
+            "images_per_day = 0\nbase_url = \"https://example.com/{{n}}.jpg\"\n{}",
+            base
+        ));
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("images_per_day 必须大于 0"), "{}", err);
+    }
+
+    #[test]
+    fn test_strftime_format_style() {
+        let base = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/%Y/%m/%d.jpg"
+output_dir = "./images"
+filename_format = "%Y/%m/%d.jpg"
+failed_log_path = "failed_{yyyy}.txt"
+"#;
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let (_dir, config_path) = create_test_config(&format!("format_style = \"strftime\"\n{}", base));
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.filename_formatter().unwrap().format(&june_15), "2024/06/15.jpg");
+        assert_eq!(
+            config.formatter(&config.base_url).unwrap().format_url(&june_15),
+            "https://example.com/2024/06/15.jpg"
+        );
+        // failed_log_path 不受 format_style 影响
+        assert!(config.failed_log_path(&june_15).unwrap().unwrap().ends_with("failed_2024.txt"));
+
+        // 默认的花括号语法中出现 strftime 格式符时提示设置 format_style
+        let (_dir, config_path) = create_test_config(base);
+        let config = Config::from_file(&config_path).unwrap();
+        let err = config.filename_formatter().unwrap().validate_path().unwrap_err().to_string();
+        assert!(err.contains("format_style"), "{}", err);
+
+        let (_dir, config_path) = create_test_config(&format!(
+            "format_style = \"strftime\"\n{}",
+            base.replace("%Y/%m/%d.jpg\"\nfailed", "%Y/{mm}/%d.jpg\"\nfailed")
+        ));
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("filename_format") && err.contains("不能混用"), "{}", err);
+    }
+
+    #[test]
+    fn test_default_ext() {
+        let base = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}{mm}{dd}"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.{ext}"
+"#;
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let (_dir, config_path) = create_test_config(base);
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.filename_formatter().unwrap().format(&june_15), "20240615.jpg");
+
+        let (_dir, config_path) = create_test_config(&format!("default_ext = \"png\"\n{}", base));
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.filename_formatter().unwrap().format(&june_15), "20240615.png");
+
+        let (_dir, config_path) = create_test_config(&format!("default_ext = \".png\"\n{}", base));
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("default_ext"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_required_field() {
+        let contents = r#"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let result = Config::from_file(&config_path);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_merge_cli_defaults() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "run",
+            "--start-date",
+            "2024-06-01",
+            "--end-date",
+            "2024-06-30",
+            "--overwrite",
+        ])
+        .unwrap();
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
         let config = Config::from_file(&config_path).unwrap();
 
         let defaults = config.merge_cli_defaults(cli.command.as_ref());
@@ -356,6 +1462,164 @@ filename_format = "{yyyy}{mm}{dd}.jpg"
         assert!(defaults.overwrite);
     }
 
+    #[test]
+    fn test_merge_cli_defaults_concurrency() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+max_concurrent = 3
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        for args in [
+            &["calendar", "run", "--concurrency", "8"][..],
+            &["calendar", "process", "--date", "2024-06-15", "--concurrency", "8"][..],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            let defaults = config.merge_cli_defaults(cli.command.as_ref());
+            assert_eq!(defaults.concurrency_override, Some(8));
+            assert_eq!(config.with_cli_overrides(&defaults).max_concurrent, 8);
+        }
+        let cli = Cli::try_parse_from(["calendar", "run"]).unwrap();
+        let defaults = config.merge_cli_defaults(cli.command.as_ref());
+        assert_eq!(config.with_cli_overrides(&defaults).max_concurrent, 3);
+
+        assert_eq!(validate_concurrency(1).unwrap(), 1);
+        assert_eq!(validate_concurrency(MAX_CONCURRENCY).unwrap(), MAX_CONCURRENCY);
+        for value in [0, MAX_CONCURRENCY + 1] {
+            assert!(matches!(validate_concurrency(value), Err(AppError::ArgumentError(_))));
+        }
+    }
+
+    #[test]
+    fn test_with_output_dir() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+        assert!(!config.output_dir_overridden());
+
+        let scratch = dir.path().join("scratch");
+        let mut config = config.with_output_dir(&scratch);
+        assert!(config.output_dir_overridden());
+        assert_eq!(Path::new(&config.output_dir), scratch);
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        assert_eq!(config.failed_log_path(&date).unwrap(), Some(scratch.join(DEFAULT_FAILED_LOG)));
+        assert_eq!(config.metadata_failed_log_path(), scratch.join(METADATA_FAILED_LOG));
+
+        // 写回配置文件时保留原来的 output_dir
+        assert_eq!(config.redacted().output_dir, "./images");
+        config.update_start_date(date, &config_path).unwrap();
+        let saved = Config::from_file(&config_path).unwrap();
+        assert_eq!(saved.output_dir, "./images");
+        assert_eq!(saved.start_date, date);
+
+        // 再次覆盖时仍记住配置文件中的值
+        let config = config.with_output_dir(Path::new("other"));
+        assert_eq!(config.output_dir, "other");
+        assert_eq!(config.redacted().output_dir, "./images");
+    }
+
+    #[test]
+    fn test_merge_cli_defaults_without_subcommand() {
+        let cli = Cli::try_parse_from(["calendar", "-c", "other.toml"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        let defaults = config.merge_cli_defaults(cli.command.as_ref());
+
+        assert!(defaults.start_date_override.is_none());
+        assert!(defaults.end_date.is_none());
+        assert!(!defaults.overwrite);
+        assert!(!defaults.download_only);
+        assert!(!defaults.metadata_only);
+        assert!(!defaults.json);
+        assert!(defaults.base_url_override.is_none());
+    }
+
+    #[test]
+    fn test_merge_cli_defaults_base_url_override() {
+        let cli = Cli::try_parse_from([
+            "calendar",
+            "process",
+            "--date",
+            "2024-06-15",
+            "--base-url",
+            "https://special.example.com/{yyyy}{mm}{dd}.jpg",
+        ])
+        .unwrap();
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        let defaults = config.merge_cli_defaults(cli.command.as_ref());
+
+        assert_eq!(
+            defaults.base_url_override.as_deref(),
+            Some("https://special.example.com/{yyyy}{mm}{dd}.jpg")
+        );
+        assert_eq!(config.base_url, "https://example.com/images/{year}/{month:02}/{day:02}.jpg");
+    }
+
+    #[test]
+    fn test_merge_cli_defaults_month() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--month", "2024-02"]).unwrap();
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        let defaults = config.merge_cli_defaults(cli.command.as_ref());
+
+        assert_eq!(defaults.start_date_override, Some("2024-02-01".to_string()));
+        assert_eq!(defaults.end_date, Some("2024-02-29".to_string()));
+    }
+
+    #[test]
+    fn test_merge_cli_defaults_year() {
+        let cli = Cli::try_parse_from(["calendar", "run", "--year", "2023"]).unwrap();
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/images/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        let defaults = config.merge_cli_defaults(cli.command.as_ref());
+
+        assert_eq!(defaults.start_date_override, Some("2023-01-01".to_string()));
+        assert_eq!(defaults.end_date, Some("2023-12-31".to_string()));
+    }
+
     #[test]
     fn test_apply_env_overrides() {
         std::env::set_var("CALENDAR_USER_AGENT", "EnvAgent/2.0");
@@ -370,7 +1634,7 @@ max_concurrent = 3
 user_agent = "OriginalAgent/1.0"
 timeout = 30
 "#;
-        let config_path = create_test_config(contents);
+        let (_dir, config_path) = create_test_config(contents);
         let config = Config::from_file(&config_path).unwrap();
         let config = config.apply_env_overrides();
 
@@ -378,7 +1642,547 @@ timeout = 30
         assert_eq!(config.timeout, 120);
         assert_eq!(config.max_concurrent, 3); // 保持原值
 
+        // 命令行 --timeout 优先于环境变量
+        let cli = Cli::try_parse_from(["calendar", "run", "--timeout", "5"]).unwrap();
+        let defaults = config.merge_cli_defaults(cli.command.as_ref());
+        assert_eq!(config.with_cli_overrides(&defaults).timeout, 5);
+        let cli = Cli::try_parse_from(["calendar", "run"]).unwrap();
+        let defaults = config.merge_cli_defaults(cli.command.as_ref());
+        assert_eq!(config.with_cli_overrides(&defaults).timeout, 120);
+
         std::env::remove_var("CALENDAR_USER_AGENT");
         std::env::remove_var("CALENDAR_TIMEOUT");
     }
+
+    #[test]
+    fn test_interpolate_env_vars() {
+        std::env::set_var("CALENDAR_TEST_CDN_TOKEN", "s3cr3t");
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://cdn.example.com/${CALENDAR_TEST_CDN_TOKEN}/{year}/{month:02}/{day:02}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        assert_eq!(
+            config.base_url,
+            "https://cdn.example.com/s3cr3t/{year}/{month:02}/{day:02}.jpg"
+        );
+        // 展示副本保留原始模板
+        assert!(config.redacted().base_url.contains("${CALENDAR_TEST_CDN_TOKEN}"));
+        assert!(!format!("{:?}", config.redacted()).contains("s3cr3t"));
+        assert_eq!(redact_secrets(&config.base_url), "https://cdn.example.com/***/{year}/{month:02}/{day:02}.jpg");
+
+        std::env::remove_var("CALENDAR_TEST_CDN_TOKEN");
+    }
+
+    #[test]
+    fn test_interpolate_unset_var() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/${CALENDAR_TEST_UNSET_VAR}/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let err = Config::from_file(&config_path).unwrap_err();
+        assert!(err.to_string().contains("CALENDAR_TEST_UNSET_VAR"));
+        assert!(err.to_string().contains("base_url"));
+    }
+
+    #[test]
+    fn test_interpolate_month_names() {
+        std::env::set_var("CALENDAR_TEST_MONTH_SUFFIX", "-calendar-test");
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+month_names = ["一${CALENDAR_TEST_MONTH_SUFFIX}", "二", "三", "四", "五", "六", "七", "八", "九", "十", "十一", "十二${CALENDAR_TEST_MONTH_SUFFIX}"]
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        let names = config.month_names.as_ref().unwrap();
+        assert_eq!(names[0], "一-calendar-test");
+        assert_eq!(names[11], "十二-calendar-test");
+        // 展示副本按下标恢复原始模板
+        let redacted = config.redacted().month_names.unwrap();
+        assert_eq!(redacted[0], "一${CALENDAR_TEST_MONTH_SUFFIX}");
+        assert_eq!(redacted[1], "二");
+        assert_eq!(redacted[11], "十二${CALENDAR_TEST_MONTH_SUFFIX}");
+
+        std::env::remove_var("CALENDAR_TEST_MONTH_SUFFIX");
+    }
+
+    #[test]
+    fn test_interpolate_allowed_content_types() {
+        std::env::set_var("CALENDAR_TEST_EXTRA_TYPE", "application/x-calendar-test");
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+allowed_content_types = ["image/*", "${CALENDAR_TEST_EXTRA_TYPE}"]
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.allowed_content_types, vec!["image/*", "application/x-calendar-test"]);
+        assert_eq!(config.redacted().allowed_content_types[1], "${CALENDAR_TEST_EXTRA_TYPE}");
+
+        // 未设置的变量报错时指出列表元素的位置
+        let contents = contents.replace("CALENDAR_TEST_EXTRA_TYPE", "CALENDAR_TEST_UNSET_TYPE");
+        let (_dir, config_path) = create_test_config(&contents);
+        let err = Config::from_file(&config_path).unwrap_err().to_string();
+        assert!(err.contains("allowed_content_types[1]"), "{}", err);
+        assert!(err.contains("CALENDAR_TEST_UNSET_TYPE"), "{}", err);
+
+        std::env::remove_var("CALENDAR_TEST_EXTRA_TYPE");
+    }
+
+    #[test]
+    fn test_interpolate_escape() {
+        assert_eq!(
+            interpolate_str("a$${NAME}b").unwrap(),
+            ("a${NAME}b".to_string(), vec![])
+        );
+        assert_eq!(
+            interpolate_str("cost $5 ${").unwrap(),
+            ("cost $5 ${".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_save_keeps_env_references() {
+        std::env::set_var("CALENDAR_TEST_SAVE_TOKEN", "hidden-value");
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/${CALENDAR_TEST_SAVE_TOKEN}/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let mut config = Config::from_file(&config_path).unwrap();
+        config
+            .update_start_date(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(), &config_path)
+            .unwrap();
+
+        let saved = fs::read_to_string(&config_path).unwrap();
+        assert!(saved.contains("${CALENDAR_TEST_SAVE_TOKEN}"));
+        assert!(!saved.contains("hidden-value"));
+
+        std::env::remove_var("CALENDAR_TEST_SAVE_TOKEN");
+    }
+
+    #[test]
+    fn test_user_agent_template() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+user_agent = "calendar/{version} (+{contact})"
+contact = "mailto:me@example.com"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        assert_eq!(
+            config.resolved_user_agent().unwrap(),
+            format!("calendar/{} (+mailto:me@example.com)", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn test_user_agent_literal_unchanged() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+user_agent = "Mozilla/5.0 (X11; Linux x86_64)"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        assert_eq!(config.resolved_user_agent().unwrap(), "Mozilla/5.0 (X11; Linux x86_64)");
+    }
+
+    #[test]
+    fn test_user_agent_contact_missing() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+user_agent = "calendar/{version} (+{contact})"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+
+        assert!(matches!(config.resolved_user_agent(), Err(AppError::HeaderError(_))));
+    }
+
+    #[test]
+    fn test_migrate_deprecated_keys() {
+        let mut table: toml::Table = toml::from_str(
+            r#"
+retry_delay_ms = 500
+file_name = "{yyyy}.jpg"
+"#,
+        )
+        .unwrap();
+
+        let found = migrate_deprecated_keys(
+            &mut table,
+            &[
+                ("retry_delay_ms", "retry.base_delay_ms"),
+                ("file_name", "filename_format"),
+                ("not_present", "whatever"),
+            ],
+        );
+
+        assert_eq!(
+            found,
+            vec![
+                ("retry_delay_ms".to_string(), "retry.base_delay_ms".to_string()),
+                ("file_name".to_string(), "filename_format".to_string()),
+            ]
+        );
+        assert!(table.get("retry_delay_ms").is_none());
+        assert_eq!(
+            get_key_path(&table, "retry.base_delay_ms").and_then(|v| v.as_integer()),
+            Some(500)
+        );
+        assert_eq!(
+            table.get("filename_format").and_then(|v| v.as_str()),
+            Some("{yyyy}.jpg")
+        );
+    }
+
+    #[test]
+    fn test_migrate_deprecated_keys_new_key_wins() {
+        let mut table: toml::Table = toml::from_str(
+            r#"
+old_name = "old"
+new_name = "new"
+"#,
+        )
+        .unwrap();
+
+        let found = migrate_deprecated_keys(&mut table, &[("old_name", "new_name")]);
+
+        assert_eq!(found.len(), 1);
+        assert!(table.get("old_name").is_none());
+        assert_eq!(table.get("new_name").and_then(|v| v.as_str()), Some("new"));
+    }
+
+    #[test]
+    fn test_from_file_migrates_deprecated_keys() {
+        use std::sync::{Arc, Mutex};
+
+        /// 收集日志输出的写入器
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "./images"
+file_name_format = "{yyyy}{mm}{dd}.jpg"
+retry_delay = 250
+
+[exif]
+keep_backup = true
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let deprecations = [
+            ("file_name_format", "filename_format"),
+            ("retry_delay", "retry_delay_ms"),
+            ("exif.keep_backup", "exif.backup"),
+        ];
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::WARN)
+            .finish();
+        let config = tracing::subscriber::with_default(subscriber, || {
+            Config::from_file_with_deprecations(&config_path, &deprecations)
+        })
+        .unwrap();
+
+        // 旧键的值迁移到新键
+        assert_eq!(config.filename_format, "{yyyy}{mm}{dd}.jpg");
+        assert_eq!(config.retry_delay_ms, 250);
+        assert!(config.exif.backup);
+
+        // 每个旧键输出一条弃用警告，并可供 config --validate 列出
+        let logs = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        for (old_key, new_key) in &deprecations {
+            assert!(
+                logs.contains(&format!("配置项 {} 已弃用，请改用 {}", old_key, new_key)),
+                "{}",
+                logs
+            );
+        }
+        let listed: Vec<(&str, &str)> = config
+            .deprecated_keys()
+            .iter()
+            .map(|(old_key, new_key)| (old_key.as_str(), new_key.as_str()))
+            .collect();
+        assert_eq!(listed, deprecations);
+
+        // 未登记弃用键时旧键名不被识别，缺少必填的 filename_format
+        assert!(Config::from_file(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_no_deprecated_keys_in_current_format() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let config = Config::from_file(&config_path).unwrap();
+        assert!(config.deprecated_keys().is_empty());
+    }
+
+    #[test]
+    fn test_peek_log_file() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}{mm}{dd}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+log_file = "logs/calendar.log"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        assert_eq!(
+            Config::peek_log_file(&config_path),
+            Some(PathBuf::from("logs/calendar.log"))
+        );
+        assert_eq!(
+            Config::from_file(&config_path).unwrap().log_file.as_deref(),
+            Some("logs/calendar.log")
+        );
+
+        // 配置文件不存在或未设置时返回 None
+        assert!(Config::peek_log_file(Path::new("/nonexistent/config.toml")).is_none());
+    }
+
+    #[test]
+    fn test_lang_config() {
+        let base = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}{mm}{dd}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(&format!("{}lang = \"en\"\n", base));
+        assert_eq!(Config::peek_lang(&config_path), Some(crate::i18n::Lang::En));
+        assert_eq!(
+            Config::from_file(&config_path).unwrap().lang,
+            Some(crate::i18n::Lang::En)
+        );
+
+        // 未设置时为 None，不支持的语言在加载时报错
+        let (_dir, config_path) = create_test_config(base);
+        assert!(Config::peek_lang(&config_path).is_none());
+        let (_dir, config_path) = create_test_config(&format!("{}lang = \"fr\"\n", base));
+        assert!(Config::peek_lang(&config_path).is_none());
+        assert!(Config::from_file(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_exif_config() {
+        let base = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}{mm}{dd}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        // 未设置 [exif] 表时不覆盖已有标签，写入后读回校验
+        let (_dir, config_path) = create_test_config(base);
+        let config = Config::from_file(&config_path).unwrap();
+        assert!(!config.exif.overwrite_existing);
+        assert!(config.exif.verify);
+        assert!(!config.exif.embed_source_url);
+        assert!(!config.exif.strip_sensitive);
+
+        let (_dir, config_path) = create_test_config(&format!(
+            "{}\n[exif]\noverwrite_existing = true\nembed_source_url = true\nstrip_sensitive = true\n",
+            base
+        ));
+        let config = Config::from_file(&config_path).unwrap();
+        assert!(config.exif.overwrite_existing);
+        assert!(config.exif.embed_source_url);
+        assert!(config.exif.strip_sensitive);
+
+        // 嵌套表能写回 TOML
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("[exif]"));
+    }
+
+    #[test]
+    fn test_fileops_config() {
+        let base = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}{mm}{dd}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(base);
+        let config = Config::from_file(&config_path).unwrap();
+        assert!(!config.fileops.preserve_atime);
+        assert!(!config.fileops.use_trash);
+        assert!(!config.fileops.durable_writes);
+        assert_eq!(config.fileops.mtime_source, MtimeSource::Publication);
+        assert_eq!(config.fileops.discard_verb(), "删除");
+
+        let (_dir, config_path) = create_test_config(&format!(
+            "{}\n[fileops]\npreserve_atime = true\nuse_trash = true\ndurable_writes = true\nmtime_source = \"download\"\n",
+            base
+        ));
+        let config = Config::from_file(&config_path).unwrap();
+        assert!(config.fileops.preserve_atime);
+        assert!(config.fileops.use_trash);
+        assert!(config.fileops.durable_writes);
+        assert_eq!(config.fileops.mtime_source, MtimeSource::Download);
+        assert_eq!(config.fileops.discard_verb(), "移入回收站");
+        assert_eq!(config.fileops.file_mode(), None);
+
+        let (_dir, config_path) =
+            create_test_config(&format!("{}\n[fileops]\nfile_mode = \"0664\"\n", base));
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.fileops.file_mode(), Some(0o664));
+        assert_eq!(config.fileops.latest_link, None);
+
+        let (_dir, config_path) =
+            create_test_config(&format!("{}\n[fileops]\nlatest_link = \"symlink\"\n", base));
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.fileops.latest_link, Some(LatestLink::Symlink));
+
+        let (_dir, config_path) =
+            create_test_config(&format!("{}\n[fileops]\nfile_mode = \"rw-rw-r--\"\n", base));
+        let err = Config::from_file(&config_path).unwrap_err();
+        assert!(err.to_string().contains("fileops.file_mode"));
+
+        #[cfg(unix)]
+        {
+            let (_dir, config_path) = create_test_config(&format!(
+                "{}\n[fileops]\nfile_group = \"no-such-group-calendar\"\n",
+                base
+            ));
+            let err = Config::from_file(&config_path).unwrap_err();
+            assert!(err.to_string().contains("fileops.file_group"));
+        }
+    }
+
+    #[test]
+    fn test_exif_gps_config() {
+        let base = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}{mm}{dd}.jpg"
+output_dir = "./images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+
+[exif]
+"#;
+        let (_dir, config_path) = create_test_config(&format!(
+            "{}gps_latitude = -33.8568\ngps_longitude = 151.2153\ngps_altitude = -5.5\n",
+            base
+        ));
+        let config = Config::from_file(&config_path).unwrap();
+        assert_eq!(config.exif.gps(), Some((-33.8568, 151.2153, Some(-5.5))));
+
+        // 超出范围或只设置一部分时加载失败
+        for invalid in [
+            "gps_latitude = 91.0\ngps_longitude = 0.0\n",
+            "gps_latitude = 0.0\ngps_longitude = -180.5\n",
+            "gps_latitude = 30.0\n",
+            "gps_altitude = 10.0\n",
+        ] {
+            let (_dir, config_path) = create_test_config(&format!("{}{}", base, invalid));
+            let err = Config::from_file(&config_path).unwrap_err();
+            assert!(err.to_string().contains("exif.gps_"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_exif_offset_time() {
+        assert!(is_exif_offset("+08:00"));
+        assert!(is_exif_offset("-05:30"));
+        assert!(is_exif_offset("+00:00"));
+        assert!(!is_exif_offset("08:00"));
+        assert!(!is_exif_offset("+8:00"));
+        assert!(!is_exif_offset("+08:60"));
+        assert!(!is_exif_offset("+15:00"));
+        assert!(!is_exif_offset("+0a:00"));
+        assert!(!is_exif_offset("+08:+0"));
+
+        let config = ExifConfig {
+            offset_time: Some("UTC+8".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validate().unwrap_err().contains("exif.offset_time"));
+    }
+
+    #[test]
+    fn test_failed_log_path() {
+        let contents = r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{yyyy}.jpg"
+output_dir = "/data/images"
+filename_format = "{yyyy}{mm}{dd}.jpg"
+"#;
+        let (_dir, config_path) = create_test_config(contents);
+        let mut config = Config::from_file(&config_path).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+
+        // 默认写入 output_dir
+        assert_eq!(
+            config.failed_log_path(&date).unwrap(),
+            Some(PathBuf::from("/data/images/failed_downloads.txt"))
+        );
+
+        // 相对路径 + 日期占位符
+        config.failed_log_path = Some("logs/failed_{yyyy}-{mm}-{dd}.txt".to_string());
+        assert_eq!(
+            config.failed_log_path(&date).unwrap(),
+            Some(PathBuf::from("/data/images/logs/failed_2024-06-15.txt"))
+        );
+
+        // 绝对路径
+        config.failed_log_path = Some("/var/log/calendar_failed.txt".to_string());
+        assert_eq!(
+            config.failed_log_path(&date).unwrap(),
+            Some(PathBuf::from("/var/log/calendar_failed.txt"))
+        );
+
+        // 空字符串表示不写入
+        config.failed_log_path = Some(String::new());
+        assert_eq!(config.failed_log_path(&date).unwrap(), None);
+    }
 }
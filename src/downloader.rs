@@ -2,27 +2,32 @@
 //!
 //! 负责从指定的 URL 下载图片，支持并发下载和错误重试。
 
-use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use chrono::{NaiveDate, TimeZone, Utc};
 use reqwest::{
-    header::{HeaderMap, USER_AGENT},
-    Client, StatusCode,
+    header::{HeaderMap, CONTENT_TYPE, USER_AGENT},
+    Client, Response, StatusCode,
 };
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 
 use crate::{
-    build_year_path,
-    config::Config,
+    build_file_path_with, ensure_within_output_dir, plan_file_path,
+    config::{self, Config, ExifConfig, FileopsConfig, MtimeSource, OnCollision},
     date_utils,
     error::{AppError, Result, RetryableError},
     exif,
     fileops,
-    filename::FilenameFormatter,
-    validator::ImageValidator,
-    DownloadStats,
+    filename::{FilenameFormatter, FormatContext},
+    i18n::Msg,
+    progress::{self, ProgressEvent},
+    validator::{self, ImageValidator},
+    DownloadStats, ProcessResult,
 };
 
 /// 下载重试配置
@@ -49,18 +54,347 @@ impl Default for RetryConfig {
     }
 }
 
+/// 单个文件的元数据更新结果
+#[derive(Debug, Clone, PartialEq)]
+enum MetadataOutcome {
+    /// 未更新元数据（`--download-only` 或下载失败）
+    NotApplied,
+    /// 已写入 EXIF 和文件时间戳
+    Written,
+    /// EXIF 日期和修改时间已是最新，跳过写入
+    UpToDate,
+    /// 写入失败及原因
+    Failed(String),
+}
+
+impl MetadataOutcome {
+    /// 写入失败的原因
+    fn error(&self) -> Option<&str> {
+        match self {
+            MetadataOutcome::Failed(reason) => Some(reason),
+            _ => None,
+        }
+    }
+}
+
+/// 批量下载中各任务共享的下载选项
+struct BatchJob {
+    client: Client,
+    allowed_content_types: Arc<Vec<String>>,
+    /// 格式包含 `{ext}` 时的默认扩展名
+    default_ext: Option<String>,
+    force_metadata: bool,
+    exif: Arc<ExifConfig>,
+    fileops: Arc<FileopsConfig>,
+    overwrite: bool,
+    download_only: bool,
+    /// 只更新已存在文件的元数据，不下载（process/retry --metadata-only）
+    metadata_only: bool,
+}
+
+impl BatchJob {
+    /// 下载单张图片（带重试）并更新元数据，返回保存路径、是否已存在以及元数据更新结果
+    ///
+    /// `index` 为 `{n}` 的取值，第 2 张及之后的图片不存在时只记录调试日志
+    async fn fetch(
+        &self,
+        date: &NaiveDate,
+        index: u32,
+        url: &str,
+        path: PathBuf,
+    ) -> (Result<(PathBuf, bool)>, MetadataOutcome) {
+        let date_str = date_utils::format_date(date);
+        let shown_url = config::redact_secrets(url);
+
+        // 检查文件是否已存在
+        if fileops::file_exists(&path) && (!self.overwrite || self.metadata_only) {
+            tracing::debug!(date = %date_str, path = %path.display(), "文件已存在，跳过下载");
+
+            let metadata = if self.download_only {
+                MetadataOutcome::NotApplied
+            } else {
+                Downloader::refresh_metadata(&path, date, self.force_metadata, &self.exif, &self.fileops)
+            };
+            return (Ok((path, true)), metadata);
+        }
+        if self.metadata_only {
+            tracing::warn!(date = %date_str, path = %path.display(), "文件不存在，--metadata-only 不下载");
+            return (
+                Err(AppError::file_error(&path, "文件不存在（--metadata-only 不下载）")),
+                MetadataOutcome::NotApplied,
+            );
+        }
+
+        // 创建格式中 `/` 对应的子目录
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fileops::ensure_dir_exists(parent) {
+                return (Err(e), MetadataOutcome::NotApplied);
+            }
+        }
+
+        // 下载文件（带重试）
+        const MAX_RETRIES: u32 = 3;
+        const BASE_DELAY_MS: u64 = 1000;
+        const MAX_DELAY_MS: u64 = 30000;
+
+        let download_result = async {
+            for attempt in 0..=MAX_RETRIES {
+                // 检查是否需要重试（不是第一次尝试）
+                if attempt > 0 {
+                    let delay_ms = (BASE_DELAY_MS * (2_u64.pow(attempt.min(10))))
+                        .min(MAX_DELAY_MS);
+                    // 检查是否是 decoding 错误，增加额外延迟
+                    if attempt == 1 {
+                        tokio::time::sleep(Duration::from_millis(2000)).await;
+                    } else {
+                        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    }
+                    tracing::warn!(
+                        date = %date_str,
+                        url = %shown_url,
+                        attempt = attempt + 1,
+                        max_attempts = MAX_RETRIES + 1,
+                        "重试下载"
+                    );
+                }
+
+                // 发送请求
+                let response = match self.client.get(url).send().await {
+                    Ok(r) => r,
+                    Err(e) => {
+                        // reqwest 错误信息会带上完整 URL，其中可能含有插值的敏感值
+                        let e = e.without_url();
+                        // 只有最后一次才记录错误
+                        if attempt == MAX_RETRIES {
+                            tracing::error!(
+                                date = %date_str,
+                                url = %shown_url,
+                                attempt = attempt + 1,
+                                error = %e,
+                                "下载失败"
+                            );
+                            return Err(AppError::NetworkError {
+                                url: shown_url.clone(),
+                                details: e.to_string(),
+                            });
+                        }
+                        continue;
+                    }
+                };
+
+                // 检查响应状态码
+                if !response.status().is_success() {
+                    // 404 不重试
+                    if response.status() == StatusCode::NOT_FOUND {
+                        if index > 1 {
+                            tracing::debug!(date = %date_str, url = %shown_url, index, "该日期没有更多图片");
+                        } else {
+                            tracing::error!(
+                                date = %date_str,
+                                url = %shown_url,
+                                status = response.status().as_u16(),
+                                "资源不存在"
+                            );
+                        }
+                        return Err(AppError::HttpError {
+                            url: shown_url.clone(),
+                            status: response.status(),
+                        });
+                    }
+
+                    // 只有最后一次才记录错误
+                    if attempt == MAX_RETRIES {
+                        tracing::error!(
+                            date = %date_str,
+                            url = %shown_url,
+                            attempt = attempt + 1,
+                            status = response.status().as_u16(),
+                            "HTTP 错误"
+                        );
+                        return Err(AppError::HttpError {
+                            url: shown_url.clone(),
+                            status: response.status(),
+                        });
+                    }
+                    continue;
+                }
+
+                // 检查内容类型，不在允许列表中的响应不重试
+                let content_type = match Downloader::check_content_type(
+                    &response,
+                    &self.allowed_content_types,
+                    &shown_url,
+                ) {
+                    Ok(ct) => ct,
+                    Err(e) => {
+                        tracing::error!(date = %date_str, url = %shown_url, error = %e, "内容类型不允许");
+                        return Err(e);
+                    }
+                };
+
+                // 读取响应体
+                match response.bytes().await {
+                    Ok(b) => {
+                        // 验证是否为空响应
+                        if b.is_empty() {
+                            if attempt == MAX_RETRIES {
+                                tracing::error!(
+                                    date = %date_str,
+                                    url = %shown_url,
+                                    attempt = attempt + 1,
+                                    "服务器返回空响应"
+                                );
+                                return Err(AppError::NetworkError {
+                                    url: shown_url.clone(),
+                                    details: "服务器返回空响应".to_string(),
+                                });
+                            }
+                            continue;
+                        }
+                        return Ok((b, content_type));
+                    }
+                    Err(e) => {
+                        let e = e.without_url();
+                        let err_msg = e.to_string().to_lowercase();
+                        // decoding 错误可重试
+                        let is_retryable = err_msg.contains("decode")
+                            || err_msg.contains("stream")
+                            || err_msg.contains("connection")
+                            || err_msg.contains("timeout");
+
+                        if !is_retryable || attempt == MAX_RETRIES {
+                            tracing::error!(
+                                date = %date_str,
+                                url = %shown_url,
+                                attempt = attempt + 1,
+                                error = %e,
+                                "读取响应体失败"
+                            );
+                            return Err(AppError::NetworkError {
+                                url: shown_url.clone(),
+                                details: e.to_string(),
+                            });
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            unreachable!()
+        }.await;
+
+        // 处理下载结果
+        let (bytes, content_type) = match download_result {
+            Ok(result) => result,
+            Err(e) => return (Err(e), MetadataOutcome::NotApplied),
+        };
+
+        // 按 Content-Type 确定 `{ext}`，覆盖扩展名不同的原文件时在验证通过后删除原文件
+        let replaced = path;
+        let path = match Downloader::path_for_content_type(
+            &replaced,
+            self.default_ext.as_deref(),
+            content_type.as_deref(),
+            &shown_url,
+        ) {
+            Ok(path) => path,
+            Err(e) => return (Err(e), MetadataOutcome::NotApplied),
+        };
+
+        // 先写入临时文件，验证通过后再替换目标文件，无效的响应不会覆盖已有的文件
+        let temp = match fileops::write_atomic_temp_async(&path, bytes).await {
+            Ok(temp) => temp,
+            Err(e) => {
+                tracing::error!(date = %date_str, path = %path.display(), error = %e, "写入文件失败");
+                return (Err(e), MetadataOutcome::NotApplied);
+            }
+        };
+
+        // 验证文件完整性（按目标文件名的扩展名检查格式）
+        match ImageValidator::validate_download_as(&temp, &path, content_type.as_deref()) {
+            Ok(validation_result) => {
+                if validation_result != crate::validator::ValidationResult::Valid {
+                    tracing::warn!(
+                        date = %date_str,
+                        path = %path.display(),
+                        result = ?validation_result,
+                        "图片验证失败"
+                    );
+                    // 丢弃无效的图片，保留原有文件
+                    fileops::discard_atomic_temp(&temp);
+                    return (
+                        Err(AppError::file_error(&path, format!("图片验证失败: {:?}", validation_result))),
+                        MetadataOutcome::NotApplied,
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::warn!(date = %date_str, error = %e, "图片验证出错");
+            }
+        }
+
+        if let Err(e) = fileops::commit_atomic_temp(&temp, &path) {
+            tracing::error!(date = %date_str, path = %path.display(), error = %e, "写入文件失败");
+            return (Err(e), MetadataOutcome::NotApplied);
+        }
+        Downloader::remove_replaced(&replaced, &path).await;
+
+        tracing::info!(date = %date_str, url = %shown_url, path = %path.display(), "下载成功");
+
+        let metadata = if self.download_only {
+            MetadataOutcome::NotApplied
+        } else {
+            Downloader::apply_metadata(
+                &path,
+                date,
+                content_type.as_deref(),
+                Some(&shown_url),
+                &self.exif,
+                &self.fileops,
+            )
+        };
+        (Ok((path, false)), metadata)
+    }
+}
+
 /// 下载器
 pub struct Downloader {
     /// HTTP 客户端
     client: Client,
     /// 文件名格式化器
     formatter: FilenameFormatter,
+    /// 配置中 `base_url` 的格式化器，避免每次下载重新解析模板
+    url_formatter: FilenameFormatter,
     /// 输出目录
     output_dir: String,
+    /// 是否按年份建立子目录
+    year_subdirs: bool,
+    /// 文件名冲突的处理方式
+    on_collision: OnCollision,
+    /// 每天最多下载的图片数量（`{n}` 的上限）
+    images_per_day: u32,
     /// 用户代理（保留字段，用于未来功能扩展）
     _user_agent: String,
     /// 重试配置
     retry_config: RetryConfig,
+    /// 允许下载的内容类型
+    allowed_content_types: Arc<Vec<String>>,
+    /// 是否显示进度条
+    show_progress: bool,
+    /// 已存在文件的元数据即使已是最新也重写
+    force_metadata: bool,
+    /// 首个失败后停止调度新的日期
+    fail_fast: bool,
+    /// 以 JSON 行输出进度事件代替进度条
+    progress_events: bool,
+    /// 单次请求超时，用于计算停止调度新日期的时间
+    request_timeout: Duration,
+    /// 整批处理的截止时间，到达后中止仍在进行的任务
+    deadline: Option<tokio::time::Instant>,
+    /// EXIF 写入选项
+    exif: Arc<ExifConfig>,
+    /// 文件属性选项
+    fileops: Arc<FileopsConfig>,
 }
 
 impl Downloader {
@@ -69,8 +403,9 @@ impl Downloader {
     /// # 参数
     /// - `config`: 配置
     pub fn new(config: &Config) -> Result<Self> {
+        let user_agent = config.resolved_user_agent()?;
         let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, config.user_agent.parse()?);
+        headers.insert(USER_AGENT, user_agent.parse()?);
 
         let client = Client::builder()
             .timeout(config.timeout_duration())
@@ -81,21 +416,37 @@ impl Downloader {
             .pool_idle_timeout(Duration::from_secs(90))
             .build()?;
 
-        let formatter = FilenameFormatter::new(&config.filename_format)?;
+        let formatter = config.filename_formatter()?;
+        formatter.validate_path()?;
+        let url_formatter = config.formatter(&config.base_url)?;
 
         Ok(Self {
             client,
             formatter,
+            url_formatter,
             output_dir: config.output_dir.clone(),
-            _user_agent: config.user_agent.clone(),
+            year_subdirs: config.year_subdirs,
+            on_collision: config.on_collision,
+            images_per_day: config.images_per_day,
+            allowed_content_types: Arc::new(config.allowed_content_types.clone()),
+            _user_agent: user_agent,
             retry_config: RetryConfig::default(),
+            show_progress: true,
+            force_metadata: false,
+            fail_fast: false,
+            progress_events: false,
+            request_timeout: config.timeout_duration(),
+            deadline: None,
+            exif: Arc::new(config.exif.clone()),
+            fileops: Arc::new(config.fileops.clone()),
         })
     }
 
     /// 使用自定义重试配置创建下载器
     pub fn with_retry_config(config: &Config, retry_config: RetryConfig) -> Result<Self> {
+        let user_agent = config.resolved_user_agent()?;
         let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, config.user_agent.parse()?);
+        headers.insert(USER_AGENT, user_agent.parse()?);
 
         let client = Client::builder()
             .timeout(config.timeout_duration())
@@ -106,20 +457,68 @@ impl Downloader {
             .pool_idle_timeout(Duration::from_secs(90))
             .build()?;
 
-        let formatter = FilenameFormatter::new(&config.filename_format)?;
+        let formatter = config.filename_formatter()?;
+        formatter.validate_path()?;
+        let url_formatter = config.formatter(&config.base_url)?;
 
         Ok(Self {
             client,
             formatter,
+            url_formatter,
             output_dir: config.output_dir.clone(),
-            _user_agent: config.user_agent.clone(),
+            year_subdirs: config.year_subdirs,
+            on_collision: config.on_collision,
+            images_per_day: config.images_per_day,
+            allowed_content_types: Arc::new(config.allowed_content_types.clone()),
+            _user_agent: user_agent,
             retry_config,
+            show_progress: true,
+            force_metadata: false,
+            fail_fast: false,
+            progress_events: false,
+            request_timeout: config.timeout_duration(),
+            deadline: None,
+            exif: Arc::new(config.exif.clone()),
+            fileops: Arc::new(config.fileops.clone()),
         })
     }
 
+    /// 设置是否显示进度条（默认显示）
+    pub fn with_progress(mut self, show_progress: bool) -> Self {
+        self.show_progress = show_progress;
+        self
+    }
+
+    /// 设置是否强制重写已存在文件的元数据（默认元数据已是最新时跳过）
+    pub fn with_force_metadata(mut self, force_metadata: bool) -> Self {
+        self.force_metadata = force_metadata;
+        self
+    }
+
+    /// 设置是否在首个失败（重试之后）后停止调度新的日期（默认处理全部日期）
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// 设置是否在标准输出逐行输出 JSON 进度事件（启用时不显示进度条）
+    pub fn with_progress_events(mut self, progress_events: bool) -> Self {
+        self.progress_events = progress_events;
+        self
+    }
+
+    /// 设置整批处理的截止时间（默认不限制）
+    ///
+    /// 剩余时间不足一次请求超时时停止调度新的日期，到达截止时间时中止仍在进行的任务；
+    /// 两种情况下未完成的日期都计入 `DownloadStats::cancelled`，不算作失败
+    pub fn with_deadline(mut self, deadline: Option<std::time::Instant>) -> Self {
+        self.deadline = deadline.map(tokio::time::Instant::from_std);
+        self
+    }
+
     /// 计算指数退避延迟时间
     fn calculate_delay(&self, attempt: u32, base_delay: u64, max_delay: u64) -> u64 {
-        let delay = base_delay * (2_u64.pow(attempt.min(10) as u32));
+        let delay = base_delay * (2_u64.pow(attempt.min(10)));
         delay.min(max_delay)
     }
 
@@ -128,18 +527,366 @@ impl Downloader {
         tokio::time::sleep(Duration::from_millis(ms)).await
     }
 
+    /// 检查响应的 Content-Type 是否在允许列表中
+    ///
+    /// 返回响应的 Content-Type；缺少该头部时返回 `None`，交由文件验证判断
+    fn check_content_type(
+        response: &Response,
+        allowed: &[String],
+        url: &str,
+    ) -> Result<Option<String>> {
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        if let Some(ct) = &content_type {
+            if !validator::content_type_allowed(ct, allowed) {
+                return Err(AppError::ContentTypeError {
+                    url: url.to_string(),
+                    content_type: ct.clone(),
+                });
+            }
+        }
+
+        Ok(content_type)
+    }
+
+    /// 按响应的 Content-Type 确定 `{ext}` 后的保存路径
+    ///
+    /// `default_ext` 为 `None`（格式不含 `{ext}`）时路径不变；未知的图片类型或缺少 Content-Type 时使用默认扩展名。
+    /// 无法确定扩展名的非图片类型（如允许了 `application/*`）返回 [`AppError::UnknownExtension`]，
+    /// 避免把 PDF 等文件保存为 `.jpg`。
+    fn path_for_content_type(
+        path: &Path,
+        default_ext: Option<&str>,
+        content_type: Option<&str>,
+        url: &str,
+    ) -> Result<PathBuf> {
+        let Some(default_ext) = default_ext else {
+            return Ok(path.to_path_buf());
+        };
+        let ext = match content_type {
+            Some(ct) => match validator::content_type_extension(ct) {
+                Some(ext) => ext,
+                None if validator::is_image_content_type(ct) => default_ext,
+                None => {
+                    return Err(AppError::UnknownExtension {
+                        url: url.to_string(),
+                        content_type: ct.to_string(),
+                    })
+                }
+            },
+            None => default_ext,
+        };
+        Ok(path.with_extension(ext))
+    }
+
+    /// 覆盖下载的文件扩展名与原文件不同时，删除被取代的原文件
+    async fn remove_replaced(old: &Path, new: &Path) {
+        if old == new || !fileops::file_exists(old) {
+            return;
+        }
+        match tokio::fs::remove_file(fileops::long_path(old)).await {
+            Ok(()) => tracing::debug!(old = %old.display(), new = %new.display(), "扩展名已变化，删除原文件"),
+            Err(e) => tracing::warn!(path = %old.display(), error = %e, "无法删除扩展名不同的原文件"),
+        }
+    }
+
+    /// 更新文件的 EXIF 和时间戳
+    ///
+    /// 非图片类型（根据 Content-Type 判断）跳过 EXIF，仅设置文件时间戳
+    ///
+    /// 失败已在 `stamp_metadata` 中记录警告，不影响下载结果
+    fn apply_metadata(
+        path: &Path,
+        date: &NaiveDate,
+        content_type: Option<&str>,
+        source_url: Option<&str>,
+        exif: &ExifConfig,
+        fileops: &FileopsConfig,
+    ) -> MetadataOutcome {
+        match Self::stamp_metadata(path, date, content_type, source_url, exif, fileops) {
+            Ok(()) => MetadataOutcome::Written,
+            Err(e) => MetadataOutcome::Failed(e.to_string()),
+        }
+    }
+
+    /// 探测 URL 是否可访问，返回响应状态码
+    ///
+    /// 发送 HEAD 请求；服务器不支持 HEAD（405）时改用 GET
+    pub async fn probe_url(&self, url: &str) -> Result<StatusCode> {
+        let shown_url = config::redact_secrets(url);
+        let to_error =
+            |e: reqwest::Error| AppError::network_error(&shown_url, e.without_url().to_string());
+
+        let status = self.client.head(url).send().await.map_err(to_error)?.status();
+        if status != StatusCode::METHOD_NOT_ALLOWED {
+            return Ok(status);
+        }
+        Ok(self.client.get(url).send().await.map_err(to_error)?.status())
+    }
+
+    /// 更新已存在文件的元数据
+    ///
+    /// EXIF 日期和修改时间已与目标日期一致时跳过写入，`force` 为 true 时总是重写
+    fn refresh_metadata(
+        path: &Path,
+        date: &NaiveDate,
+        force: bool,
+        exif: &ExifConfig,
+        fileops: &FileopsConfig,
+    ) -> MetadataOutcome {
+        if !force && Self::metadata_is_current(path, date, exif, fileops) {
+            tracing::debug!(date = %date, path = %path.display(), "元数据已是最新，跳过");
+            return MetadataOutcome::UpToDate;
+        }
+        Self::apply_metadata(path, date, None, None, exif, fileops)
+    }
+
+    /// 文件的修改时间和 EXIF 的三个日期标签是否已与目标日期一致
+    ///
+    /// 不支持 EXIF 的文件只检查修改时间；`mtime_source = "download"` 时改为检查创建时间
+    /// （无法设置创建时间的平台不检查）；开启 `exif.strip_sensitive` 时还要求没有敏感标签
+    fn metadata_is_current(
+        path: &Path,
+        date: &NaiveDate,
+        exif: &ExifConfig,
+        fileops: &FileopsConfig,
+    ) -> bool {
+        let datetime = date.and_hms_opt(0, 0, 0).unwrap();
+        let expected = Utc.from_utc_datetime(&datetime);
+        let time_matches = match fileops.mtime_source {
+            MtimeSource::Publication => {
+                matches!(fileops::get_file_mtime(path), Ok(Some(mtime)) if mtime == expected)
+            }
+            MtimeSource::Download if fileops::CAN_SET_CREATION_TIME => {
+                matches!(fileops::get_file_ctime(path), Ok(Some(ctime)) if ctime == expected)
+            }
+            MtimeSource::Download => true,
+        };
+        if !time_matches {
+            return false;
+        }
+        if !exif::supports_exif(path) {
+            return true;
+        }
+        // 配置了 GPS 坐标时 GPS 标签是本工具写入的，不视为需要删除
+        let unwanted = |name: &String| exif.gps().is_none() || !name.starts_with("GPS");
+        matches!(
+            exif::get_exif_info(path),
+            Ok(info) if info.dates().all_match(&datetime)
+                && (!exif.strip_sensitive || !info.sensitive_tags.iter().any(unwanted))
+        )
+    }
+
+    /// 写入 EXIF 日期和文件时间戳
+    ///
+    /// 两项都会尝试执行，失败时记录警告并返回遇到的第一个错误；
+    /// `source_url` 为本次下载的来源（已隐藏密钥），重新处理已有文件时为 None
+    fn stamp_metadata(
+        path: &Path,
+        date: &NaiveDate,
+        content_type: Option<&str>,
+        source_url: Option<&str>,
+        exif: &ExifConfig,
+        fileops: &FileopsConfig,
+    ) -> Result<()> {
+        let datetime = date.and_hms_opt(0, 0, 0).unwrap();
+        let datetime_utc = Utc.from_utc_datetime(&datetime);
+        let mut result = Ok(());
+
+        // 写入 EXIF 需要读取文件，可能更新访问时间；保留下载时间作为修改时间时还会改变修改时间，因此先记录
+        let original_times = if fileops.preserve_atime || fileops.mtime_source == MtimeSource::Download {
+            Some(fileops::FileTimes::read(path)?)
+        } else {
+            None
+        };
+
+        // 更新 EXIF
+        if content_type.is_none_or(validator::is_image_content_type) {
+            if let Err(e) = exif::set_exif_datetime(path, &datetime, exif, source_url) {
+                tracing::warn!(date = %date, path = %path.display(), error = %e, "更新 EXIF 失败");
+                result = Err(e);
+            }
+        } else {
+            tracing::debug!(date = %date, path = %path.display(), "非图片类型，跳过 EXIF");
+        }
+
+        // 更新文件时间戳
+        let timestamps = match original_times {
+            Some(times) => {
+                let times = match fileops.mtime_source {
+                    MtimeSource::Publication => times.with_mtime(datetime_utc),
+                    MtimeSource::Download => times,
+                };
+                let created = fileops::TimestampOptions {
+                    created: Some(datetime_utc),
+                    ..Default::default()
+                };
+                times.restore(path).and_then(|()| fileops::set_file_timestamps(path, &created))
+            }
+            None => fileops::set_file_timestamps(path, &fileops::TimestampOptions::all(datetime_utc)),
+        };
+        if let Err(e) = timestamps {
+            tracing::warn!(date = %date, path = %path.display(), error = %e, "更新文件时间戳失败");
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+
+        // 改写 EXIF 可能替换文件，权限和所属组最后设置
+        if let Err(e) = fileops.apply_permissions(path) {
+            tracing::warn!(date = %date, path = %path.display(), error = %e, "设置文件权限失败");
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+
+        if fileops.durable_writes {
+            if let Err(e) = fileops::sync_durable(path) {
+                tracing::warn!(date = %date, path = %path.display(), error = %e, "同步文件到磁盘失败");
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// 重新写入已有文件的 EXIF 日期和文件时间戳，不访问网络
+    ///
+    /// # 参数
+    /// - `files`: 日期及对应的文件路径
+    /// - `concurrency`: 并发处理的文件数量
+    ///
+    /// # 返回
+    /// 统计信息；文件不存在的日期记为跳过
+    pub async fn touch_files(
+        files: Vec<(NaiveDate, PathBuf)>,
+        concurrency: usize,
+        exif: &ExifConfig,
+        fileops: &FileopsConfig,
+    ) -> DownloadStats {
+        let exif = Arc::new(exif.clone());
+        let fileops = Arc::new(fileops.clone());
+        let mut stats = DownloadStats::new(files.len());
+        let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+
+        for (date, path) in files {
+            let semaphore = semaphore.clone();
+            let exif = exif.clone();
+            let fileops = fileops.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let date_str = date_utils::format_date(&date);
+
+                if !fileops::file_exists(&path) {
+                    tracing::debug!(date = %date_str, path = %path.display(), "文件不存在，跳过");
+                    return (date_str, None);
+                }
+
+                let result = tokio::task::spawn_blocking(move || {
+                    Self::stamp_metadata(&path, &date, None, None, &exif, &fileops)
+                })
+                .await;
+                (date_str, Some(matches!(result, Ok(Ok(())))))
+            });
+        }
+
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok((date_str, None)) => stats.record_skip_with_date(&date_str),
+                Ok((date_str, Some(true))) => stats.record_success_with_date(&date_str),
+                Ok((date_str, Some(false))) => stats.record_failure(&date_str),
+                Err(e) => tracing::error!("任务执行失败: {}", e),
+            }
+        }
+        if fileops.durable_writes {
+            tracing::debug!(elapsed = ?fileops::sync_time(), "同步到磁盘累计耗时");
+        }
+
+        stats
+    }
+
     /// 获取给定日期的 URL
-    fn build_url(&self, base_url: &str, date: &NaiveDate) -> String {
-        let url_formatter =
-            FilenameFormatter::new(base_url).unwrap_or_else(|_| self.formatter.clone());
-        url_formatter.format_url(date)
+    pub fn build_url(&self, base_url: &str, date: &NaiveDate) -> String {
+        self.url_formatter(base_url).format_url(date)
+    }
+
+    /// 获取给定日期的 URL，URL 中仍有未替换的占位符时返回错误
+    fn try_build_url(&self, base_url: &str, date: &NaiveDate) -> Result<String> {
+        self.url_formatter(base_url).try_format_url(date, &FormatContext::default())
+    }
+
+    /// `base_url` 模板的格式化器，与配置中的 `base_url` 相同时使用缓存
+    fn url_formatter(&self, base_url: &str) -> Cow<'_, FilenameFormatter> {
+        if base_url == self.url_formatter.format_str() {
+            return Cow::Borrowed(&self.url_formatter);
+        }
+        Cow::Owned(self.formatter.with_format(base_url).unwrap_or_else(|_| self.formatter.clone()))
     }
 
-    /// 构建文件路径
-    fn build_path(&self, date: &NaiveDate) -> std::path::PathBuf {
-        let filename = self.formatter.format(date);
-        let year_dir = build_year_path(Path::new(&self.output_dir), date.year());
-        year_dir.join(&filename)
+    /// 构建文件路径，不创建年份目录（用于 list、url 等只读命令）
+    pub fn build_path(&self, date: &NaiveDate) -> Result<std::path::PathBuf> {
+        plan_file_path(Path::new(&self.output_dir), date, &self.formatter, self.year_subdirs)
+    }
+
+    /// 构建文件路径，`{urlhash}` 按上下文中实际请求的 URL 计算
+    fn build_path_with(&self, date: &NaiveDate, context: &FormatContext) -> Result<PathBuf> {
+        build_file_path_with(Path::new(&self.output_dir), date, &self.formatter, context, self.year_subdirs)
+    }
+
+    /// 确定日期的保存路径，按 `on_collision` 处理与其他日期的文件名冲突
+    ///
+    /// `context` 包含该日期实际请求的 URL 和 `{n}` 序号；`claimed` 为本批次已分配给其他日期的路径；
+    /// 已存在的文件根据修改时间判断所属日期，无法判断时视为该日期自己的文件（保持跳过已存在文件的行为）
+    fn resolve_path(
+        &self,
+        date: &NaiveDate,
+        context: &FormatContext,
+        claimed: &HashMap<PathBuf, NaiveDate>,
+    ) -> Result<PathBuf> {
+        let path = self.build_path_with(date, context)?;
+        ensure_within_output_dir(Path::new(&self.output_dir), &path, &self.formatter)?;
+        if self.on_collision == OnCollision::Overwrite {
+            return Ok(path);
+        }
+
+        let occupant = |candidate: &Path| {
+            claimed
+                .get(candidate)
+                .copied()
+                .or_else(|| fileops::stamped_date(candidate))
+                .filter(|other| other != date)
+        };
+        let Some(other) = occupant(&path) else {
+            return Ok(path);
+        };
+        if self.on_collision == OnCollision::Error {
+            return Err(AppError::FilenameCollision {
+                path,
+                other_date: date_utils::format_date(&other),
+            });
+        }
+
+        let mut n = 1;
+        loop {
+            let candidate = fileops::collision_suffix_path(&path, n);
+            if occupant(&candidate).is_none() {
+                tracing::debug!(
+                    date = %date,
+                    other_date = %other,
+                    path = %candidate.display(),
+                    "文件名冲突，使用带序号的文件名"
+                );
+                return Ok(candidate);
+            }
+            n += 1;
+        }
     }
 
     /// 下载单个日期的图片
@@ -149,6 +896,7 @@ impl Downloader {
     /// - `date`: 下载日期
     /// - `overwrite`: 是否覆盖已存在的文件
     /// - `download_only`: 是否仅下载（不修改 EXIF 和文件属性）
+    /// - `stats`: 记录处理结果和元数据更新结果的统计信息
     ///
     /// # 返回
     /// 返回下载结果和文件路径
@@ -158,9 +906,37 @@ impl Downloader {
         date: &NaiveDate,
         overwrite: bool,
         download_only: bool,
+        stats: &mut DownloadStats,
     ) -> Result<(std::path::PathBuf, bool)> {
-        self.download_with_retry(base_url, date, overwrite, download_only)
+        let date_str = date_utils::format_date(date);
+        match self
+            .download_with_retry(base_url, date, overwrite, download_only)
             .await
+        {
+            Ok((path, existed, metadata)) => {
+                Self::record_metadata(stats, &date_str, &metadata);
+                let result = if existed {
+                    ProcessResult::AlreadyExists(path.clone())
+                } else {
+                    ProcessResult::Downloaded(path.clone())
+                };
+                stats.record_result(&date_str, &result);
+                Ok((path, existed))
+            }
+            Err(e) => {
+                stats.record_result(&date_str, &ProcessResult::Failed(e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    /// 将元数据更新结果计入统计信息
+    fn record_metadata(stats: &mut DownloadStats, date: &str, metadata: &MetadataOutcome) {
+        match metadata {
+            MetadataOutcome::Failed(reason) => stats.record_metadata_failure(date, reason),
+            MetadataOutcome::UpToDate => stats.metadata_up_to_date += 1,
+            MetadataOutcome::NotApplied | MetadataOutcome::Written => {}
+        }
     }
 
     /// 带重试的下载实现
@@ -170,34 +946,32 @@ impl Downloader {
         date: &NaiveDate,
         overwrite: bool,
         download_only: bool,
-    ) -> Result<(std::path::PathBuf, bool)> {
-        let url = self.build_url(base_url, date);
-        let path = self.build_path(date);
+    ) -> Result<(std::path::PathBuf, bool, MetadataOutcome)> {
+        let url = self.try_build_url(base_url, date)?;
+        let context = FormatContext { url: Some(&url), ..Default::default() };
+        let path = self.resolve_path(date, &context, &HashMap::new())?;
         let date_str = date_utils::format_date(date);
 
-        tracing::debug!("处理日期: {} -> {:?}", date_str, path);
+        tracing::debug!(date = %date_str, path = %path.display(), "处理日期");
 
         // 检查文件是否已存在
-        if path.exists() && !overwrite {
-            tracing::debug!("文件已存在，跳过下载: {:?}", path);
+        if fileops::file_exists(&path) && !overwrite {
+            tracing::debug!(date = %date_str, path = %path.display(), "文件已存在，跳过下载");
 
             // 即使文件已存在，也要更新 EXIF 和文件属性（除非 --download-only）
-            if !download_only {
-                let datetime = date.and_hms_opt(0, 0, 0).unwrap();
-                let datetime_utc = Utc.from_utc_datetime(&datetime);
-
-                // 更新 EXIF
-                if let Err(e) = exif::set_exif_datetime(&path, &datetime) {
-                    tracing::warn!("更新 EXIF 失败: {:?}: {}", path, e);
-                }
-
-                // 更新文件时间戳
-                if let Err(e) = fileops::set_file_timestamps(&path, datetime_utc) {
-                    tracing::warn!("更新文件时间戳失败: {:?}: {}", path, e);
-                }
-            }
+            let metadata = if download_only {
+                MetadataOutcome::NotApplied
+            } else {
+                Self::refresh_metadata(
+                    &path,
+                    date,
+                    self.force_metadata,
+                    &self.exif,
+                    &self.fileops,
+                )
+            };
 
-            return Ok((path, true)); // true 表示已存在
+            return Ok((path, true, metadata)); // true 表示已存在
         }
 
         // 如果文件不存在，创建目录
@@ -237,17 +1011,25 @@ impl Downloader {
                         }
 
                         tracing::warn!(
-                            "下载失败 (尝试 {}/{}): {} - {}ms 后重试",
-                            attempt + 1,
-                            max_retries + 1,
-                            url,
-                            delay
+                            date = %date_str,
+                            url = %config::redact_secrets(&url),
+                            attempt = attempt + 1,
+                            max_attempts = max_retries + 1,
+                            delay_ms = delay,
+                            error = %e,
+                            "下载失败，稍后重试"
                         );
                         Self::sleep_ms(delay).await;
                         last_error = Some(e);
                     } else {
                         // 不可重试错误或已达最大重试次数
-                        tracing::error!("下载失败: {} - {}", url, e);
+                        tracing::error!(
+                            date = %date_str,
+                            url = %config::redact_secrets(&url),
+                            attempt = attempt + 1,
+                            error = %e,
+                            "下载失败"
+                        );
                         return Err(e);
                     }
                 }
@@ -256,7 +1038,7 @@ impl Downloader {
 
         // 所有重试都失败
         if let Some(e) = last_error {
-            tracing::error!("下载失败，已重试 {} 次: {}", max_retries + 1, e);
+            tracing::error!(date = %date_str, attempt = max_retries + 1, error = %e, "下载失败，重试次数已用尽");
             Err(e)
         } else {
             unreachable!()
@@ -289,18 +1071,20 @@ impl Downloader {
     async fn execute_download(
         &self,
         url: &str,
-        path: &std::path::PathBuf,
+        path: &Path,
         date: &NaiveDate,
         download_only: bool,
-    ) -> Result<(std::path::PathBuf, bool)> {
-        tracing::debug!("开始下载: {}", url);
+    ) -> Result<(std::path::PathBuf, bool, MetadataOutcome)> {
+        let shown_url = config::redact_secrets(url);
+        tracing::debug!(date = %date, url = %shown_url, "开始下载");
 
         let response = match self.client.get(url).send().await {
             Ok(r) => r,
             Err(e) => {
-                tracing::warn!("请求失败: {} - {}", url, e);
+                let e = e.without_url();
+                tracing::warn!(date = %date, url = %shown_url, error = %e, "请求失败");
                 return Err(AppError::NetworkError {
-                    url: url.to_string(),
+                    url: shown_url.clone(),
                     details: e.to_string(),
                 });
             }
@@ -310,53 +1094,61 @@ impl Downloader {
         if !response.status().is_success() {
             if response.status() == StatusCode::NOT_FOUND {
                 return Err(AppError::HttpError {
-                    url: url.to_string(),
+                    url: shown_url.clone(),
                     status: StatusCode::NOT_FOUND,
                 });
             }
-            tracing::warn!("HTTP 错误 {}: {}", response.status(), url);
+            tracing::warn!(date = %date, url = %shown_url, status = response.status().as_u16(), "HTTP 错误");
             return Err(AppError::HttpError {
-                url: url.to_string(),
+                url: shown_url.clone(),
                 status: response.status(),
             });
         }
 
+        let content_type =
+            Self::check_content_type(&response, &self.allowed_content_types, &shown_url)?;
+        let target = Self::path_for_content_type(
+            path,
+            self.formatter.default_ext(),
+            content_type.as_deref(),
+            &shown_url,
+        )?;
+
         // 读取响应体
         let bytes = match response.bytes().await {
             Ok(b) => b,
             Err(e) => {
-                tracing::warn!("读取响应体失败: {} - {}", url, e);
+                let e = e.without_url();
+                tracing::warn!(date = %date, url = %shown_url, error = %e, "读取响应体失败");
                 return Err(AppError::NetworkError {
-                    url: url.to_string(),
+                    url: shown_url.clone(),
                     details: format!("读取响应体失败: {}", e),
                 });
             }
         };
 
         // 写入文件
-        tokio::fs::write(path, bytes)
-            .await
-            .map_err(|e| AppError::file_error(path, e.to_string()))?;
+        fileops::write_atomic_async(&target, bytes).await?;
+        Self::remove_replaced(path, &target).await;
+        let path = target.as_path();
 
-        tracing::info!("下载成功: {:?}", path);
+        tracing::info!(date = %date, url = %shown_url, path = %path.display(), "下载成功");
 
         // 更新 EXIF 和文件属性（除非 --download-only）
-        if !download_only {
-            let datetime = date.and_hms_opt(0, 0, 0).unwrap();
-            let datetime_utc = Utc.from_utc_datetime(&datetime);
-
-            // 更新 EXIF
-            if let Err(e) = exif::set_exif_datetime(path, &datetime) {
-                tracing::warn!("更新 EXIF 失败: {:?}: {}", path, e);
-            }
-
-            // 更新文件时间戳
-            if let Err(e) = fileops::set_file_timestamps(path, datetime_utc) {
-                tracing::warn!("更新文件时间戳失败: {:?}: {}", path, e);
-            }
-        }
+        let metadata = if download_only {
+            MetadataOutcome::NotApplied
+        } else {
+            Self::apply_metadata(
+                path,
+                date,
+                content_type.as_deref(),
+                Some(&shown_url),
+                &self.exif,
+                &self.fileops,
+            )
+        };
 
-        Ok((path.clone(), false)) // false 表示新下载
+        Ok((path.to_path_buf(), false, metadata)) // false 表示新下载
     }
 
     /// 批量下载多个日期的图片
@@ -377,283 +1169,256 @@ impl Downloader {
         max_concurrent: usize,
         overwrite: bool,
         download_only: bool,
+    ) -> DownloadStats {
+        self.run_batch(base_url, dates, max_concurrent, overwrite, download_only, false).await
+    }
+
+    /// 批量处理的实现，`metadata_only` 为 true 时只更新已存在文件的元数据，缺失的文件记为失败
+    async fn run_batch(
+        &self,
+        base_url: &str,
+        dates: &[NaiveDate],
+        max_concurrent: usize,
+        overwrite: bool,
+        download_only: bool,
+        metadata_only: bool,
     ) -> DownloadStats {
         let semaphore = Arc::new(Semaphore::new(max_concurrent));
         let mut tasks = JoinSet::new();
 
         let mut stats = DownloadStats::new(dates.len());
 
-        // 创建进度条
-        let progress = indicatif::ProgressBar::new(dates.len() as u64);
+        // 创建进度条（输出 JSON 进度事件时隐藏）
+        let progress =
+            progress::create_bar(dates.len() as u64, self.show_progress && !self.progress_events);
+        if self.progress_events {
+            progress::emit_event(&ProgressEvent::Start { total: dates.len() });
+        }
         progress.set_style(
             indicatif::ProgressStyle::default_bar()
-                .template(
-                    "[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} \
-                     成功: {green} 失败: {red} 跳过: {yellow}",
-                )
+                .template(Msg::ProgressTemplate.text())
                 .unwrap()
                 .progress_chars("##-"),
         );
 
-        for date in dates {
-            let permit = semaphore.clone().acquire_owned().await;
+        // --fail-fast: 任务失败时在释放许可前设置停止标记，调度循环拿到许可后即可看到
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut path_error_reported = false;
+        // 本批次已分配的保存路径，用于发现不同日期生成相同文件名的冲突
+        let mut claimed = HashMap::new();
+        let url_formatter = self.url_formatter(base_url);
+        let job = Arc::new(BatchJob {
+            client: self.client.clone(),
+            allowed_content_types: self.allowed_content_types.clone(),
+            default_ext: self.formatter.default_ext().map(str::to_string),
+            force_metadata: self.force_metadata,
+            exif: self.exif.clone(),
+            fileops: self.fileops.clone(),
+            overwrite,
+            download_only,
+            metadata_only,
+        });
+        // --max-duration: 剩余时间不足一次请求超时（最多为剩余预算的一半）时不再开始新的日期
+        let schedule_until = self.deadline.map(|deadline| {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            deadline - self.request_timeout.min(remaining / 2)
+        });
+
+        for (index, date) in dates.iter().enumerate() {
+            let acquire = semaphore.clone().acquire_owned();
+            let permit = match schedule_until {
+                Some(until) => tokio::time::timeout_at(until, acquire).await.ok(),
+                None => Some(acquire.await),
+            };
+            let out_of_time =
+                schedule_until.is_some_and(|until| tokio::time::Instant::now() >= until);
+            let Some(permit) = permit.filter(|_| !out_of_time) else {
+                stats.cancelled = dates.len() - index;
+                tracing::warn!(remaining = stats.cancelled, "时间预算即将用尽，停止调度剩余日期 (--max-duration)");
+                break;
+            };
             if permit.is_err() {
                 tracing::error!("未能获取信号量许可");
                 break;
             }
+            if stop.load(Ordering::SeqCst) {
+                stats.cancelled = dates.len() - index;
+                tracing::warn!(remaining = stats.cancelled, "出现失败，停止调度剩余日期 (--fail-fast)");
+                break;
+            }
 
-            let formatter = self.formatter.clone();
-            let url = self.build_url(base_url, date);
-            let client = self.client.clone();
-            let output_dir = self.output_dir.clone();
+            // URL 和保存路径在调度时确定：年份目录无法创建（如输出目录没有写权限）时后续日期都会失败，错误日志只输出一次；
+            // 每天有多张图片时为每个 `{n}` 分配 URL 和路径
+            let resolved: Result<Vec<(u32, String, PathBuf)>> = (1..=self.images_per_day)
+                .map(|index| {
+                    let context = FormatContext { index: Some(index), ..Default::default() };
+                    let url = url_formatter.try_format_url(date, &context)?;
+                    let path =
+                        self.resolve_path(date, &FormatContext { url: Some(&url), ..context }, &claimed)?;
+                    Ok((index, url, path))
+                })
+                .collect();
+            let images = match resolved {
+                Ok(images) => {
+                    for (_, _, path) in &images {
+                        claimed.insert(path.clone(), *date);
+                    }
+                    images
+                }
+                Err(e) => {
+                    let date_str = date_utils::format_date(date);
+                    if matches!(e, AppError::FilenameCollision { .. }) {
+                        tracing::error!(date = %date_str, error = %e, "文件名冲突");
+                    } else if matches!(e, AppError::FilenameFormatError { .. }) {
+                        tracing::error!(date = %date_str, error = %e, "无法生成 URL 或文件名");
+                    } else if !path_error_reported {
+                        tracing::error!(error = %e, "无法确定保存路径");
+                        path_error_reported = true;
+                    }
+                    stats.record_result(&date_str, &ProcessResult::Failed(e.to_string()));
+                    progress.inc(1);
+                    progress.set_message(Msg::ProgressFailed.format(&[&date_str]));
+                    if self.progress_events {
+                        Self::emit_result_event(&stats, None);
+                    }
+                    if self.fail_fast {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    continue;
+                }
+            };
+
+            let job = job.clone();
             let date_clone = *date;
             let progress = progress.clone();
+            let fail_fast = self.fail_fast;
+            let stop = stop.clone();
 
             tasks.spawn(async move {
                 let date_str = date_utils::format_date(&date_clone);
-                let filename = formatter.format(&date_clone);
-                let year_dir = build_year_path(Path::new(&output_dir), date_clone.year());
-                let path = year_dir.join(&filename);
 
                 // permit 在此作用域结束时自动释放，确保整个下载过程都受信号量控制
+                let _permit = permit;
 
-                // 检查文件是否已存在
-                if path.exists() && !overwrite {
-                    tracing::debug!("文件已存在，跳过下载: {:?}", path);
-
-                    let datetime =
-                        date_clone.and_hms_opt(0, 0, 0).unwrap();
-                    let datetime_utc = Utc.from_utc_datetime(&datetime);
-
-                    if !download_only {
-                        // 更新 EXIF
-                        if let Err(e) = exif::set_exif_datetime(&path, &datetime) {
-                            tracing::warn!("更新 EXIF 失败: {:?}: {}", path, e);
-                        }
-
-                        // 更新文件时间戳
-                        if let Err(e) = fileops::set_file_timestamps(&path, datetime_utc) {
-                            tracing::warn!("更新文件时间戳失败: {:?}: {}", path, e);
-                        }
-                    }
-
-                    progress.inc(1);
-                    progress.set_message(format!("跳过: {}", date_str));
-                    return (date_str, Ok((path, true)));
-                }
-
-                // 创建目录
-                if let Some(parent) = path.parent() {
-                    let _ = fileops::ensure_dir_exists(parent);
-                }
-
-                // 下载文件（带重试）
-                const MAX_RETRIES: u32 = 3;
-                const BASE_DELAY_MS: u64 = 1000;
-                const MAX_DELAY_MS: u64 = 30000;
-
-                let download_result = async {
-                    for attempt in 0..=MAX_RETRIES {
-                        // 检查是否需要重试（不是第一次尝试）
-                        if attempt > 0 {
-                            let delay_ms = (BASE_DELAY_MS * (2_u64.pow(attempt.min(10) as u32)))
-                                .min(MAX_DELAY_MS);
-                            // 检查是否是 decoding 错误，增加额外延迟
-                            if attempt == 1 {
-                                tokio::time::sleep(Duration::from_millis(2000)).await;
-                            } else {
-                                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                            }
-                            tracing::warn!(
-                                "重试下载 (尝试 {}/{}): {}",
-                                attempt + 1,
-                                MAX_RETRIES + 1,
-                                url
-                            );
-                        }
-
-                        // 发送请求
-                        let response = match client.get(&url).send().await {
-                            Ok(r) => r,
-                            Err(e) => {
-                                // 只有最后一次才记录错误
-                                if attempt == MAX_RETRIES {
-                                    tracing::error!("下载失败: {}: {}", date_str, e);
-                                    return Err(AppError::NetworkError {
-                                        url: url.clone(),
-                                        details: e.to_string(),
-                                    });
-                                }
-                                continue;
-                            }
-                        };
-
-                        // 检查响应状态码
-                        if !response.status().is_success() {
-                            // 404 不重试
-                            if response.status() == StatusCode::NOT_FOUND {
-                                tracing::error!("资源不存在: {}", url);
-                                return Err(AppError::HttpError {
-                                    url: url.clone(),
-                                    status: response.status(),
-                                });
-                            }
-
-                            // 只有最后一次才记录错误
-                            if attempt == MAX_RETRIES {
-                                tracing::error!(
-                                    "HTTP 错误: {} 返回状态码 {}",
-                                    url,
-                                    response.status()
-                                );
-                                return Err(AppError::HttpError {
-                                    url: url.clone(),
-                                    status: response.status(),
-                                });
-                            }
-                            continue;
-                        }
-
-                        // 读取响应体
-                        match response.bytes().await {
-                            Ok(b) => {
-                                // 验证是否为空响应
-                                if b.is_empty() {
-                                    if attempt == MAX_RETRIES {
-                                        tracing::error!("服务器返回空响应: {}", url);
-                                        return Err(AppError::NetworkError {
-                                            url: url.clone(),
-                                            details: "服务器返回空响应".to_string(),
-                                        });
-                                    }
-                                    continue;
-                                }
-                                return Ok(b);
-                            }
-                            Err(e) => {
-                                let err_msg = e.to_string().to_lowercase();
-                                // decoding 错误可重试
-                                let is_retryable = err_msg.contains("decode")
-                                    || err_msg.contains("stream")
-                                    || err_msg.contains("connection")
-                                    || err_msg.contains("timeout");
-
-                                if !is_retryable || attempt == MAX_RETRIES {
-                                    tracing::error!("读取响应体失败: {}: {}", date_str, e);
-                                    return Err(AppError::NetworkError {
-                                        url: url.clone(),
-                                        details: e.to_string(),
-                                    });
-                                }
-                                continue;
-                            }
-                        }
+                let mut outcomes = Vec::new();
+                for (index, url, path) in images {
+                    if index > 1 {
+                        progress.inc_length(1);
                     }
-
-                    unreachable!()
-                }.await;
-
-                // 处理下载结果
-                let bytes = match download_result {
-                    Ok(b) => b,
-                    Err(e) => {
-                        progress.inc(1);
-                        progress.set_message(format!("失败: {}", date_str));
-                        return (date_str, Err(e));
+                    let (result, metadata) = job.fetch(&date_clone, index, &url, path).await;
+
+                    // 第 2 张及之后的图片不存在表示该日期没有更多图片，不计入失败
+                    if index > 1
+                        && matches!(result, Err(AppError::HttpError { status: StatusCode::NOT_FOUND, .. }))
+                    {
+                        progress.dec_length(1);
+                        break;
                     }
-                };
 
-                // 写入文件
-                match tokio::fs::write(&path, bytes).await {
-                    Ok(_) => {
-                        // 验证图片完整性
-                        match ImageValidator::validate(&path) {
-                            Ok(validation_result) => {
-                                if validation_result != crate::validator::ValidationResult::Valid {
-                                    tracing::warn!("图片验证失败: {:?} - {:?}", path, validation_result);
-                                    // 删除无效的图片
-                                    let _ = tokio::fs::remove_file(&path).await;
-                                    return (date_str, Err(AppError::file_error(
-                                        &path,
-                                        format!("图片验证失败: {:?}", validation_result)
-                                    )));
-                                }
-                            }
-                            Err(e) => {
-                                tracing::warn!("图片验证出错: {:?}", e);
-                            }
-                        }
-
-                        tracing::info!("下载成功: {:?}", path);
-
-                        let datetime =
-                            date_clone.and_hms_opt(0, 0, 0).unwrap();
-                        let datetime_utc = Utc.from_utc_datetime(&datetime);
-
-                        if !download_only {
-                            // 更新 EXIF
-                            if let Err(e) = exif::set_exif_datetime(&path, &datetime) {
-                                tracing::warn!("更新 EXIF 失败: {:?}: {}", path, e);
-                            }
-
-                            // 更新文件时间戳
-                            if let Err(e) = fileops::set_file_timestamps(&path, datetime_utc) {
-                                tracing::warn!("更新文件时间戳失败: {:?}: {}", path, e);
-                            }
+                    progress.inc(1);
+                    let message = match &result {
+                        Ok((_, true)) => Msg::ProgressSkipped,
+                        Ok((_, false)) => Msg::ProgressSucceeded,
+                        Err(_) => Msg::ProgressFailed,
+                    };
+                    progress.set_message(message.format(&[&date_str]));
+                    let failed = result.is_err();
+                    outcomes.push((result, metadata));
+                    if failed {
+                        if fail_fast {
+                            stop.store(true, Ordering::SeqCst);
                         }
-
-                        progress.inc(1);
-                        progress.set_message(format!("成功: {}", date_str));
-
-                        drop(permit);
-
-                        (date_str, Ok((path, false)))
-                    }
-                    Err(e) => {
-                        progress.inc(1);
-                        progress.set_message(format!("失败: {}", date_str));
-                        tracing::error!("写入文件失败: {:?}: {}", path, e);
-                        (
-                            date_str,
-                            Err(AppError::file_error(&path, e.to_string())),
-                        )
+                        break;
                     }
                 }
+                (date_str, outcomes)
             });
         }
 
-        // 等待所有任务完成
-        while let Some(result) = tasks.join_next().await {
-            match result {
-                Ok((date_str, result)) => match result {
-                    Ok((_, existed)) => {
-                        if existed {
-                            stats.record_skip();
-                        } else {
-                            stats.record_success_with_date(&date_str);
-                        }
-                    }
+        // 等待所有任务完成；到达截止时间时中止仍在进行的任务
+        loop {
+            let next = match self.deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, tasks.join_next()).await {
+                    Ok(next) => next,
                     Err(_) => {
-                        stats.record_failure(&date_str);
+                        let aborted = tasks.len();
+                        tasks.abort_all();
+                        stats.cancelled += aborted;
+                        tracing::warn!(aborted, "到达截止时间，中止进行中的任务 (--max-duration)");
+                        break;
                     }
                 },
+                None => tasks.join_next().await,
+            };
+            let Some(result) = next else {
+                break;
+            };
+            match result {
+                Ok((date_str, outcomes)) => {
+                    // 每天有多张图片时按图片计数
+                    stats.total += outcomes.len().saturating_sub(1);
+                    for (result, metadata) in outcomes {
+                        Self::record_metadata(&mut stats, &date_str, &metadata);
+                        let result = match result {
+                            Ok((path, true)) => ProcessResult::AlreadyExists(path),
+                            Ok((path, false)) => ProcessResult::Downloaded(path),
+                            Err(e) => ProcessResult::Failed(e.to_string()),
+                        };
+                        stats.record_result(&date_str, &result);
+                        if self.progress_events {
+                            Self::emit_result_event(&stats, metadata.error());
+                        }
+                    }
+                }
                 Err(e) => {
                     tracing::error!("任务执行失败: {}", e);
                 }
             }
         }
 
-        progress.finish_with_message("完成");
+        progress::finish_bar(&progress, Msg::ProgressDone.text());
+        if self.fileops.durable_writes {
+            tracing::debug!(elapsed = ?fileops::sync_time(), "同步到磁盘累计耗时");
+        }
+        if self.progress_events {
+            progress::emit_event(&ProgressEvent::Summary {
+                total: stats.total,
+                succeeded: stats.succeeded,
+                failed: stats.failed,
+                skipped: stats.skipped,
+                cancelled: stats.cancelled,
+                metadata_failures: stats.metadata_failures.len(),
+                metadata_up_to_date: stats.metadata_up_to_date,
+            });
+        }
         stats
     }
 
+    /// 输出刚记录的日期结果对应的进度事件
+    fn emit_result_event(stats: &DownloadStats, metadata_error: Option<&str>) {
+        let Some(result) = stats.results.last() else {
+            return;
+        };
+        let event = match &result.error {
+            Some(reason) => ProgressEvent::Failed { date: &result.date, reason },
+            None => ProgressEvent::Done {
+                date: &result.date,
+                result: result.status,
+                path: result.path.as_deref(),
+                metadata_error,
+            },
+        };
+        progress::emit_event(&event);
+    }
+
     /// 处理指定日期的文件（process 命令）
     ///
     /// # 参数
     /// - `base_url`: 基础 URL 模板
     /// - `dates`: 日期列表
+    /// - `concurrency`: 并发数（process 命令默认为 1，可用 `--concurrency` 指定）
     /// - `overwrite`: 是否覆盖已存在的文件
-    /// - `metadata_only`: 是否仅修改元数据（不下载）
+    /// - `metadata_only`: 是否仅修改元数据（不下载，文件不存在的日期记为失败）
     ///
     /// # 返回
     /// 返回下载统计信息
@@ -661,19 +1426,14 @@ impl Downloader {
         &self,
         base_url: &str,
         dates: &[NaiveDate],
+        concurrency: usize,
         overwrite: bool,
         metadata_only: bool,
     ) -> DownloadStats {
         let download_only = false; // process 命令默认需要修改元数据
 
-        self.download_batch(
-            base_url,
-            dates,
-            1, // process 命令不使用并发
-            overwrite,
-            if metadata_only { true } else { download_only },
-        )
-        .await
+        self.run_batch(base_url, dates, concurrency, overwrite, download_only, metadata_only)
+            .await
     }
 }
 
@@ -683,19 +1443,23 @@ mod tests {
     use chrono::NaiveDate;
     use std::path::PathBuf;
 
+    fn test_config(output_dir: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+start_date = "2024-01-01"
+base_url = "https://example.com/{{year}}/{{month:02}}/{{day:02}}.jpg"
+output_dir = "{}"
+filename_format = "{{yyyy}}{{mm}}{{dd}}.jpg"
+user_agent = "Test"
+"#,
+            output_dir
+        ))
+        .unwrap()
+    }
+
     #[test]
     fn test_build_url() {
-        let config = Config {
-            start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
-            base_url: "https://example.com/{year}/{month:02}/{day:02}.jpg".to_string(),
-            output_dir: "./images".to_string(),
-            filename_format: "{yyyy}{mm}{dd}.jpg".to_string(),
-            max_concurrent: 3,
-            user_agent: "Test".to_string(),
-            timeout: 30,
-            max_retries: 3,
-            retry_delay_ms: 1000,
-        };
+        let config = test_config("./images");
 
         let downloader = Downloader::new(&config).unwrap();
         let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
@@ -706,22 +1470,520 @@ mod tests {
 
     #[test]
     fn test_build_path() {
-        let config = Config {
-            start_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
-            base_url: "https://example.com/{year}/{month:02}/{day:02}.jpg".to_string(),
-            output_dir: "/tmp/images".to_string(),
-            filename_format: "{yyyy}{mm}{dd}.jpg".to_string(),
-            max_concurrent: 3,
-            user_agent: "Test".to_string(),
-            timeout: 30,
-            max_retries: 3,
-            retry_delay_ms: 1000,
-        };
+        let config = test_config("/tmp/images");
 
         let downloader = Downloader::new(&config).unwrap();
         let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
 
-        let path = downloader.build_path(&date);
+        let path = downloader.build_path(&date).unwrap();
         assert_eq!(path, PathBuf::from("/tmp/images/2024/20240615.jpg"));
     }
+
+    #[tokio::test]
+    async fn test_touch_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let existing = temp_dir.path().join("20240615.txt");
+        std::fs::write(&existing, b"content").unwrap();
+        let missing = temp_dir.path().join("20240616.txt");
+
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let june_16 = NaiveDate::from_ymd_opt(2024, 6, 16).unwrap();
+        let stats =
+            Downloader::touch_files(
+                vec![(june_15, existing.clone()), (june_16, missing)],
+                2,
+                &ExifConfig::default(),
+                &FileopsConfig::default(),
+            )
+            .await;
+
+        assert_eq!(stats.total, 2);
+        assert_eq!(stats.succeeded, 1);
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.skipped_dates, vec!["2024-06-16".to_string()]);
+
+        let mtime = fileops::get_file_mtime(&existing).unwrap().unwrap();
+        assert_eq!(mtime.date_naive(), june_15);
+    }
+
+    #[test]
+    fn test_metadata_is_current() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.txt");
+        std::fs::write(&path, b"content").unwrap();
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+
+        let exif = ExifConfig::default();
+        let fileops = FileopsConfig::default();
+        assert!(!Downloader::metadata_is_current(&path, &june_15, &exif, &fileops));
+
+        Downloader::stamp_metadata(&path, &june_15, None, None, &exif, &fileops).unwrap();
+        assert!(Downloader::metadata_is_current(&path, &june_15, &exif, &fileops));
+        assert!(!Downloader::metadata_is_current(
+            &path,
+            &NaiveDate::from_ymd_opt(2024, 6, 16).unwrap(),
+            &exif,
+            &fileops
+        ));
+    }
+
+    #[test]
+    fn test_refresh_metadata_preserves_atime() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let atime = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_atime(&path, atime).unwrap();
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+
+        let fileops = FileopsConfig { preserve_atime: true, ..Default::default() };
+        let outcome =
+            Downloader::refresh_metadata(&path, &june_15, false, &ExifConfig::default(), &fileops);
+        assert!(matches!(outcome, MetadataOutcome::Written));
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(filetime::FileTime::from_last_access_time(&metadata), atime);
+        let mtime = fileops::get_file_mtime(&path).unwrap().unwrap();
+        assert_eq!(mtime.date_naive(), june_15);
+    }
+
+    #[test]
+    fn test_stamp_metadata_mtime_source_download() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let downloaded = filetime::FileTime::from_unix_time(1_700_000_123, 0);
+        filetime::set_file_mtime(&path, downloaded).unwrap();
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+
+        // 修改时间保留为下载时间，创建时间设为图片日期（平台支持时）
+        let fileops = FileopsConfig { mtime_source: MtimeSource::Download, ..Default::default() };
+        Downloader::stamp_metadata(&path, &june_15, None, None, &ExifConfig::default(), &fileops)
+            .unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(filetime::FileTime::from_last_modification_time(&metadata), downloaded);
+        if fileops::CAN_SET_CREATION_TIME {
+            let created = fileops::get_file_ctime(&path).unwrap().unwrap();
+            assert_eq!(created, Utc.from_utc_datetime(&june_15.and_hms_opt(0, 0, 0).unwrap()));
+        }
+        assert!(Downloader::metadata_is_current(&path, &june_15, &ExifConfig::default(), &fileops));
+    }
+
+    #[test]
+    fn test_metadata_is_current_strip_sensitive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let with_gps = ExifConfig {
+            gps_latitude: Some(39.9042),
+            gps_longitude: Some(116.4074),
+            ..Default::default()
+        };
+        Downloader::stamp_metadata(&path, &june_15, None, None, &with_gps, &FileopsConfig::default()).unwrap();
+
+        // 日期已是最新，但仍有 GPS 标签时需要重写才能删除
+        let strip = ExifConfig { strip_sensitive: true, ..Default::default() };
+        assert!(Downloader::metadata_is_current(
+            &path,
+            &june_15,
+            &ExifConfig::default(),
+            &FileopsConfig::default()
+        ));
+        assert!(!Downloader::metadata_is_current(&path, &june_15, &strip, &FileopsConfig::default()));
+
+        let outcome = Downloader::refresh_metadata(&path, &june_15, false, &strip, &FileopsConfig::default());
+        assert!(matches!(outcome, MetadataOutcome::Written));
+        assert!(exif::get_exif_info(&path).unwrap().sensitive_tags.is_empty());
+        assert!(Downloader::metadata_is_current(&path, &june_15, &strip, &FileopsConfig::default()));
+
+        // 同时配置坐标时，写入的 GPS 标签不会导致每次都重写
+        let strip_with_gps = ExifConfig { strip_sensitive: true, ..with_gps };
+        Downloader::stamp_metadata(&path, &june_15, None, None, &strip_with_gps, &FileopsConfig::default()).unwrap();
+        assert!(Downloader::metadata_is_current(&path, &june_15, &strip_with_gps, &FileopsConfig::default()));
+    }
+
+    #[test]
+    fn test_build_path_flat_layout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(temp_dir.path().to_str().unwrap());
+        config.year_subdirs = false;
+
+        let downloader = Downloader::new(&config).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+
+        let path = downloader.build_path(&date).unwrap();
+        assert_eq!(path, temp_dir.path().join("20240615.jpg"));
+        assert!(!temp_dir.path().join("2024").exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_deadline_defers_dates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = test_config(temp_dir.path().to_str().unwrap());
+        let dates: Vec<NaiveDate> = (1..=3)
+            .map(|day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap())
+            .collect();
+
+        // 截止时间已过：不调度任何日期，全部计为未处理而不是失败
+        let downloader = Downloader::new(&config)
+            .unwrap()
+            .with_progress(false)
+            .with_deadline(Some(std::time::Instant::now()));
+        let stats = downloader
+            .download_batch("http://127.0.0.1:1/{yyyy}{mm}{dd}.jpg", &dates, 2, false, true)
+            .await;
+
+        assert_eq!(stats.cancelled, 3);
+        assert_eq!(stats.failed, 0);
+        assert!(stats.results.is_empty());
+        assert_eq!(stats.contiguous_success_end(&dates), None);
+    }
+
+    #[test]
+    fn test_resolve_path_collision() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(temp_dir.path().to_str().unwrap());
+        // 按月命名：同一个月的日期生成相同的文件名
+        config.filename_format = "{yyyy}{mm}.jpg".to_string();
+        let june_1 = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let june_2 = NaiveDate::from_ymd_opt(2024, 6, 2).unwrap();
+        let context = FormatContext { url: Some("https://example.com/2024/06.jpg"), ..Default::default() };
+        let base = temp_dir.path().join("2024").join("202406.jpg");
+        let claimed = HashMap::from([(base.clone(), june_1)]);
+
+        let downloader = Downloader::new(&config).unwrap();
+        let err = downloader.resolve_path(&june_2, &context, &claimed).unwrap_err();
+        assert!(matches!(
+            err,
+            AppError::FilenameCollision { ref path, ref other_date }
+                if path == &base && other_date == "2024-06-01"
+        ));
+        assert_eq!(downloader.resolve_path(&june_1, &context, &claimed).unwrap(), base);
+
+        config.on_collision = OnCollision::Overwrite;
+        let downloader = Downloader::new(&config).unwrap();
+        assert_eq!(downloader.resolve_path(&june_2, &context, &claimed).unwrap(), base);
+
+        config.on_collision = OnCollision::Suffix;
+        let downloader = Downloader::new(&config).unwrap();
+        let first = temp_dir.path().join("2024").join("202406-1.jpg");
+        assert_eq!(downloader.resolve_path(&june_2, &context, &claimed).unwrap(), first);
+
+        // 已存在的文件按修改时间判断所属日期：属于其他日期时继续递增序号，属于本日期时沿用
+        let june_3 = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        std::fs::write(&first, b"image").unwrap();
+        let june_2_midnight = Utc.from_utc_datetime(&june_2.and_hms_opt(0, 0, 0).unwrap());
+        fileops::set_file_timestamps(&first, &fileops::TimestampOptions::all(june_2_midnight)).unwrap();
+        assert_eq!(downloader.resolve_path(&june_2, &context, &claimed).unwrap(), first);
+        assert_eq!(
+            downloader.resolve_path(&june_3, &context, &claimed).unwrap(),
+            temp_dir.path().join("2024").join("202406-2.jpg")
+        );
+
+        // 修改时间无法判断所属日期的已有文件视为本日期的文件
+        std::fs::write(&base, b"image").unwrap();
+        assert_eq!(downloader.resolve_path(&june_3, &context, &HashMap::new()).unwrap(), base);
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_filename_collision() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(temp_dir.path().to_str().unwrap());
+        config.filename_format = "{yyyy}{mm}.jpg".to_string();
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let dates: Vec<NaiveDate> = (1..=2)
+            .map(|day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap())
+            .collect();
+        // 第一个日期的文件已存在（跳过），第二个日期生成相同的文件名
+        std::fs::create_dir_all(temp_dir.path().join("2024")).unwrap();
+        std::fs::write(temp_dir.path().join("2024").join("202406.jpg"), b"image").unwrap();
+
+        let stats = downloader
+            .download_batch("http://127.0.0.1:1/{yyyy}{mm}{dd}.jpg", &dates, 2, false, true)
+            .await;
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.failed, 1);
+        let collision = stats.results.iter().find(|r| r.date == "2024-06-02").unwrap();
+        assert!(collision.error.as_deref().unwrap().contains("文件名冲突"));
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_unwritable_output_dir() {
+        // 输出目录位置是普通文件，无法创建年份目录：不发起请求，所有日期记为失败
+        let temp_dir = tempfile::tempdir().unwrap();
+        let blocker = temp_dir.path().join("images");
+        std::fs::write(&blocker, b"").unwrap();
+        let config = test_config(blocker.to_str().unwrap());
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let dates: Vec<NaiveDate> = (1..=3)
+            .map(|day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap())
+            .collect();
+
+        // 只计算路径时不创建年份目录，下载时才报错
+        assert_eq!(downloader.build_path(&dates[0]).unwrap(), blocker.join("2024/20240601.jpg"));
+        let stats = downloader
+            .download_batch("http://127.0.0.1:1/{yyyy}{mm}{dd}.jpg", &dates, 2, false, true)
+            .await;
+        assert_eq!(stats.failed, 3);
+        assert!(stats.results[0].error.as_deref().unwrap().contains("无法创建年份目录"));
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_counts_up_to_date_metadata() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = test_config(temp_dir.path().to_str().unwrap());
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let dates: Vec<NaiveDate> = (1..=2)
+            .map(|day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap())
+            .collect();
+
+        // 两个已存在的文件，只有第一个的元数据已与日期一致
+        for date in &dates {
+            let path = downloader.build_path(date).unwrap();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image::RgbImage::new(1, 1)
+                .save_with_format(&path, image::ImageFormat::Jpeg)
+                .unwrap();
+        }
+        let current = downloader.build_path(&dates[0]).unwrap();
+        Downloader::stamp_metadata(&current, &dates[0], None, None, &ExifConfig::default(), &FileopsConfig::default()).unwrap();
+
+        let base_url = "http://127.0.0.1:1/{yyyy}{mm}{dd}.jpg";
+        let stats = downloader.download_batch(base_url, &dates, 2, false, false).await;
+        assert_eq!(stats.skipped, 2);
+        assert_eq!(stats.metadata_up_to_date, 1);
+
+        // 第二次运行两个文件都已是最新；--force-metadata 时全部重写
+        let stats = downloader.download_batch(base_url, &dates, 2, false, false).await;
+        assert_eq!(stats.metadata_up_to_date, 2);
+        let forced = Downloader::new(&config)
+            .unwrap()
+            .with_progress(false)
+            .with_force_metadata(true);
+        let stats = forced.download_batch(base_url, &dates, 2, false, false).await;
+        assert_eq!(stats.metadata_up_to_date, 0);
+    }
+
+    #[tokio::test]
+    async fn test_process_dates_metadata_only() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = test_config(temp_dir.path().to_str().unwrap());
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let dates: Vec<NaiveDate> = (1..=2)
+            .map(|day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap())
+            .collect();
+        let existing = downloader.build_path(&dates[0]).unwrap();
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        image::RgbImage::new(1, 1)
+            .save_with_format(&existing, image::ImageFormat::Jpeg)
+            .unwrap();
+
+        // 已存在的文件写入元数据（即使指定了 overwrite），缺失的文件不下载、记为失败
+        let base_url = "http://127.0.0.1:1/{yyyy}{mm}{dd}.jpg";
+        let stats = downloader.process_dates(base_url, &dates, 1, true, true).await;
+        assert_eq!((stats.skipped, stats.failed), (1, 1));
+        assert_eq!(stats.failed_dates, vec!["2024-06-02".to_string()]);
+        assert!(stats.results.iter().any(|r| r.error.as_deref().is_some_and(|e| e.contains("--metadata-only"))));
+        assert!(Downloader::metadata_is_current(
+            &existing,
+            &dates[0],
+            &ExifConfig::default(),
+            &FileopsConfig::default()
+        ));
+    }
+
+    /// 在本地端口上对每个连接返回同一个 HTTP 响应，返回监听地址
+    fn serve_fixed_response(content_type: &'static str, body: &'static [u8]) -> std::net::SocketAddr {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    content_type,
+                    body.len()
+                );
+                let _ = stream.write_all(header.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_with_invalid_response_keeps_existing_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = test_config(temp_dir.path().to_str().unwrap());
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+        let existing = downloader.build_path(&date).unwrap();
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        image::RgbImage::new(64, 64)
+            .save_with_format(&existing, image::ImageFormat::Jpeg)
+            .unwrap();
+        let original = std::fs::read(&existing).unwrap();
+
+        // 声称是 JPEG 的 HTML 错误页面
+        let addr = serve_fixed_response("image/jpeg", b"<html><body>502 Bad Gateway</body></html>");
+        let base_url = format!("http://{}/{{yyyy}}{{mm}}{{dd}}.jpg", addr);
+        let stats = downloader.download_batch(&base_url, &[date], 1, true, true).await;
+
+        assert_eq!(stats.failed, 1);
+        assert!(stats.results[0].error.as_deref().is_some_and(|e| e.contains("图片验证失败")));
+        assert_eq!(std::fs::read(&existing).unwrap(), original);
+        // 没有遗留临时文件
+        let entries: Vec<_> = std::fs::read_dir(existing.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(entries, vec![existing]);
+    }
+
+    #[test]
+    fn test_path_for_content_type() {
+        let path = Path::new("/tmp/images/2024/20240615.jpg");
+        let url = "http://example.com/20240615";
+        let resolve = |default_ext, content_type| {
+            Downloader::path_for_content_type(path, default_ext, content_type, url)
+        };
+        assert_eq!(
+            resolve(Some("jpg"), Some("image/webp")).unwrap(),
+            PathBuf::from("/tmp/images/2024/20240615.webp")
+        );
+        assert_eq!(
+            resolve(Some("jpg"), Some("image/x-unknown")).unwrap(),
+            PathBuf::from("/tmp/images/2024/20240615.jpg")
+        );
+        assert_eq!(resolve(Some("png"), None).unwrap(), path.with_extension("png"));
+        // 允许的媒体类型使用实际的扩展名，无法确定扩展名的非图片类型报错
+        assert_eq!(
+            resolve(Some("jpg"), Some("application/pdf")).unwrap(),
+            PathBuf::from("/tmp/images/2024/20240615.pdf")
+        );
+        assert!(matches!(
+            resolve(Some("jpg"), Some("application/zip")),
+            Err(AppError::UnknownExtension { .. })
+        ));
+        // 格式不含 {ext} 时不改变路径
+        assert_eq!(resolve(None, Some("image/png")).unwrap(), path);
+        assert_eq!(resolve(None, Some("application/zip")).unwrap(), path);
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_multiple_images_per_day() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(temp_dir.path().to_str().unwrap());
+        config.filename_format = "{yyyy}{mm}{dd}_{n}.jpg".to_string();
+        config.images_per_day = 3;
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+        let dir = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&dir).unwrap();
+        for n in 1..=3 {
+            image::RgbImage::new(1, 1)
+                .save_with_format(dir.join(format!("20240601_{}.jpg", n)), image::ImageFormat::Jpeg)
+                .unwrap();
+        }
+
+        // 已存在的图片不发请求，统计按图片计数
+        let base_url = "http://127.0.0.1:1/{yyyy}{mm}{dd}_{n}.jpg";
+        let stats = downloader.download_batch(base_url, &[date], 1, false, true).await;
+        assert_eq!((stats.total, stats.skipped, stats.failed), (3, 3, 0));
+        let paths: Vec<_> = stats.results.iter().filter_map(|r| r.path.clone()).collect();
+        assert_eq!(paths.len(), 3);
+        assert!(paths.contains(&dir.join("20240601_3.jpg")));
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_ext_finds_existing_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(temp_dir.path().to_str().unwrap());
+        config.filename_format = "{yyyy}{mm}{dd}.{ext}".to_string();
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+        // 之前以 png 保存的文件同样视为已存在
+        let existing = temp_dir.path().join("2024/20240601.png");
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        image::RgbImage::new(1, 1)
+            .save_with_format(&existing, image::ImageFormat::Png)
+            .unwrap();
+        assert_eq!(downloader.build_path(&date).unwrap(), existing);
+
+        let base_url = "http://127.0.0.1:1/{yyyy}{mm}{dd}.jpg";
+        let stats = downloader.download_batch(base_url, &[date], 1, false, false).await;
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.failed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_batch_unresolved_placeholder() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(temp_dir.path().to_str().unwrap());
+        config.filename_format = "{yyyy}{mm}{dd}_{lunar_month}{lunar_day}.jpg".to_string();
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+
+        // 1989 年超出农历数据表范围，不发请求直接记为失败
+        let date = NaiveDate::from_ymd_opt(1989, 6, 1).unwrap();
+        let base_url = "http://127.0.0.1:1/{yyyy}{mm}{dd}.jpg";
+        let stats = downloader.download_batch(base_url, &[date], 1, false, false).await;
+        assert_eq!(stats.failed, 1);
+        assert!(fileops::list_files(temp_dir.path()).unwrap().is_empty());
+
+        let mut stats = DownloadStats::new(1);
+        let err = downloader.download(base_url, &date, false, false, &mut stats).await.unwrap_err();
+        assert!(err.to_string().contains("{lunar_month}"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_download_error_redacts_secret_url() {
+        std::env::set_var("CALENDAR_TEST_DL_TOKEN", "dl-s3cr3t");
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            format!(
+                r#"
+start_date = "2024-01-01"
+base_url = "http://127.0.0.1:1/${{CALENDAR_TEST_DL_TOKEN}}/{{yyyy}}{{mm}}{{dd}}.jpg"
+output_dir = "{}"
+filename_format = "{{yyyy}}{{mm}}{{dd}}.jpg"
+max_retries = 0
+"#,
+                temp_dir.path().join("images").display()
+            ),
+        )
+        .unwrap();
+        let config = Config::from_file(&config_path).unwrap();
+        let downloader = Downloader::new(&config).unwrap().with_progress(false);
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+        // 连接失败：reqwest 错误信息中的 URL 不能带出插值的敏感值
+        let mut stats = DownloadStats::new(1);
+        let err = downloader
+            .download(&config.base_url, &date, false, true, &mut stats)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::NetworkError { .. }), "{:?}", err);
+        assert!(!format!("{} {:?}", err, err).contains("dl-s3cr3t"), "{:?}", err);
+        assert!(err.to_string().contains("***"), "{}", err);
+
+        let reqwest_err = reqwest::get("http://127.0.0.1:1/dl-s3cr3t/a.jpg").await.unwrap_err();
+        let err = AppError::from(reqwest_err);
+        assert!(!format!("{} {:?}", err, err).contains("dl-s3cr3t"), "{:?}", err);
+
+        std::env::remove_var("CALENDAR_TEST_DL_TOKEN");
+    }
 }
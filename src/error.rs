@@ -1,7 +1,10 @@
 //! 应用程序错误类型定义
 //!
 //! 使用 `thiserror` 为应用程序定义结构化的错误类型，确保所有错误都能被正确处理。
+//! 错误信息按 [`crate::i18n`] 选择的语言输出。
 
+use crate::i18n::{self, Lang};
+use std::fmt;
 use std::path::PathBuf;
 use thiserror::Error;
 use reqwest::header::InvalidHeaderValue;
@@ -115,80 +118,260 @@ impl RetryableError {
 #[derive(Error, Debug)]
 pub enum AppError {
     /// 配置文件加载错误
-    #[error("配置文件错误: {path}: {details}")]
     ConfigError {
         path: PathBuf,
         details: String,
     },
 
     /// TOML 解析错误
-    #[error("TOML 解析错误: {0}")]
     TomlError(#[from] toml::de::Error),
 
     /// 日期解析错误
-    #[error("无效的日期格式 '{input}': {details}")]
     InvalidDate {
         input: String,
         details: String,
     },
 
     /// 网络请求错误
-    #[error("网络请求错误: {url} - {details}")]
     NetworkError {
         url: String,
         details: String,
     },
 
     /// HTTP 状态码错误
-    #[error("HTTP 错误: {url} 返回状态码 {status}")]
     HttpError {
         url: String,
         status: reqwest::StatusCode,
     },
 
     /// 文件操作错误
-    #[error("文件操作错误: {path} - {details}")]
     FileError {
         path: PathBuf,
         details: String,
     },
 
     /// IO 错误
-    #[error("IO 错误: {0}")]
     IoError(#[from] std::io::Error),
 
     /// EXIF 修改错误
-    #[error("EXIF 修改错误: {path} - {details}")]
     ExifError {
         path: PathBuf,
         details: String,
     },
 
     /// 文件名格式错误
-    #[error("文件名格式错误: {format} - {details}")]
     FilenameFormatError {
         format: String,
         details: String,
     },
 
     /// 正则表达式错误
-    #[error("正则表达式错误: {0}")]
     RegexError(#[from] regex::Error),
 
     /// URL 构建错误
-    #[error("URL 构建错误: {template} - {details}")]
     UrlBuildError {
         template: String,
         details: String,
     },
 
     /// 参数错误
-    #[error("参数错误: {0}")]
     ArgumentError(String),
 
     /// HTTP 头部错误
-    #[error("HTTP 头部错误: {0}")]
     HeaderError(String),
+
+    /// 图库中存在无效文件
+    InvalidFiles {
+        count: usize,
+    },
+
+    /// 输出目录已被其他进程锁定
+    Locked {
+        details: String,
+    },
+
+    /// 响应类型不在允许列表中
+    ContentTypeError {
+        url: String,
+        content_type: String,
+    },
+
+    /// 文件名格式包含 `{ext}`，但无法确定响应类型对应的扩展名
+    UnknownExtension {
+        url: String,
+        content_type: String,
+    },
+
+    /// 目标文件已存在，拒绝覆盖
+    DestinationExists {
+        path: PathBuf,
+    },
+
+    /// 保存路径已被其他日期的文件占用
+    FilenameCollision {
+        path: PathBuf,
+        other_date: String,
+    },
+
+    /// 日期范围内不同日期生成相同的保存路径
+    DuplicateFilenames {
+        format: String,
+        first: String,
+        second: String,
+        /// 相对输出目录的路径
+        path: String,
+    },
+
+    /// 文件名格式缺少日期成分，无法从文件名反向解析日期
+    AmbiguousFilenameFormat {
+        format: String,
+        /// 缺少的成分：`year`、`month`、`day`
+        missing: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message(i18n::current()))
+    }
+}
+
+impl AppError {
+    /// 指定语言下的错误信息
+    pub fn message(&self, lang: Lang) -> String {
+        match (self, lang) {
+            (Self::ConfigError { path, details }, Lang::Zh) => {
+                format!("配置文件错误: {}: {}", path.display(), details)
+            }
+            (Self::ConfigError { path, details }, Lang::En) => {
+                format!("config file error: {}: {}", path.display(), details)
+            }
+            (Self::TomlError(e), Lang::Zh) => format!("TOML 解析错误: {}", e),
+            (Self::TomlError(e), Lang::En) => format!("TOML parse error: {}", e),
+            (Self::InvalidDate { input, details }, Lang::Zh) => {
+                format!("无效的日期格式 '{}': {}", input, details)
+            }
+            (Self::InvalidDate { input, details }, Lang::En) => {
+                format!("invalid date '{}': {}", input, details)
+            }
+            (Self::NetworkError { url, details }, Lang::Zh) => {
+                format!("网络请求错误: {} - {}", url, details)
+            }
+            (Self::NetworkError { url, details }, Lang::En) => {
+                format!("network error: {} - {}", url, details)
+            }
+            (Self::HttpError { url, status }, Lang::Zh) => {
+                format!("HTTP 错误: {} 返回状态码 {}", url, status)
+            }
+            (Self::HttpError { url, status }, Lang::En) => {
+                format!("HTTP error: {} returned status {}", url, status)
+            }
+            (Self::FileError { path, details }, Lang::Zh) => {
+                format!("文件操作错误: {} - {}", path.display(), details)
+            }
+            (Self::FileError { path, details }, Lang::En) => {
+                format!("file error: {} - {}", path.display(), details)
+            }
+            (Self::IoError(e), Lang::Zh) => format!("IO 错误: {}", e),
+            (Self::IoError(e), Lang::En) => format!("I/O error: {}", e),
+            (Self::ExifError { path, details }, Lang::Zh) => {
+                format!("EXIF 修改错误: {} - {}", path.display(), details)
+            }
+            (Self::ExifError { path, details }, Lang::En) => {
+                format!("EXIF error: {} - {}", path.display(), details)
+            }
+            (Self::FilenameFormatError { format, details }, Lang::Zh) => {
+                format!("文件名格式错误: {} - {}", format, details)
+            }
+            (Self::FilenameFormatError { format, details }, Lang::En) => {
+                format!("invalid filename format: {} - {}", format, details)
+            }
+            (Self::RegexError(e), Lang::Zh) => format!("正则表达式错误: {}", e),
+            (Self::RegexError(e), Lang::En) => format!("regex error: {}", e),
+            (Self::UrlBuildError { template, details }, Lang::Zh) => {
+                format!("URL 构建错误: {} - {}", template, details)
+            }
+            (Self::UrlBuildError { template, details }, Lang::En) => {
+                format!("cannot build URL: {} - {}", template, details)
+            }
+            (Self::ArgumentError(msg), Lang::Zh) => format!("参数错误: {}", msg),
+            (Self::ArgumentError(msg), Lang::En) => format!("invalid argument: {}", msg),
+            (Self::HeaderError(msg), Lang::Zh) => format!("HTTP 头部错误: {}", msg),
+            (Self::HeaderError(msg), Lang::En) => format!("invalid HTTP header: {}", msg),
+            (Self::InvalidFiles { count }, Lang::Zh) => {
+                format!("图库校验失败: {} 个无效文件", count)
+            }
+            (Self::InvalidFiles { count }, Lang::En) => {
+                format!("library verification failed: {} invalid file(s)", count)
+            }
+            (Self::Locked { details }, Lang::Zh) => format!(
+                "另一个 calendar 进程正在处理该输出目录 ({})，可使用 --wait 等待其结束",
+                details
+            ),
+            (Self::Locked { details }, Lang::En) => format!(
+                "another calendar process is using the output directory ({}); pass --wait to wait for it",
+                details
+            ),
+            (Self::ContentTypeError { url, content_type }, Lang::Zh) => {
+                format!("不允许的内容类型: {} 返回 {}", url, content_type)
+            }
+            (Self::ContentTypeError { url, content_type }, Lang::En) => {
+                format!("content type not allowed: {} returned {}", url, content_type)
+            }
+            (Self::UnknownExtension { url, content_type }, Lang::Zh) => format!(
+                "无法确定内容类型的扩展名: {} 返回 {}（文件名格式中的 {{ext}} 只支持图片和常见媒体类型）",
+                url, content_type
+            ),
+            (Self::UnknownExtension { url, content_type }, Lang::En) => format!(
+                "cannot determine a file extension for {} returned by {} ({{ext}} supports images and common media types only)",
+                content_type, url
+            ),
+            (Self::DestinationExists { path }, Lang::Zh) => {
+                format!("目标文件已存在: {}", path.display())
+            }
+            (Self::DestinationExists { path }, Lang::En) => {
+                format!("destination already exists: {}", path.display())
+            }
+            (Self::FilenameCollision { path, other_date }, Lang::Zh) => format!(
+                "文件名冲突: {} 已被 {} 的文件占用（可设置 on_collision = \"suffix\"）",
+                path.display(),
+                other_date
+            ),
+            (Self::FilenameCollision { path, other_date }, Lang::En) => format!(
+                "filename collision: {} is already used by the file for {} (set on_collision = \"suffix\" to keep both)",
+                path.display(),
+                other_date
+            ),
+            (Self::DuplicateFilenames { format, first, second, path }, Lang::Zh) => format!(
+                "文件名格式 {} 不能区分所有日期: {} 和 {} 都映射到 {}（可在格式中加入 {{dd}}，或设置 on_collision = \"suffix\"）",
+                format, first, second, path
+            ),
+            (Self::DuplicateFilenames { format, first, second, path }, Lang::En) => format!(
+                "filename format {} does not distinguish all dates: {} and {} both map to {} (add {{dd}} to the format, or set on_collision = \"suffix\")",
+                format, first, second, path
+            ),
+            (Self::AmbiguousFilenameFormat { format, missing }, Lang::Zh) => {
+                let missing: Vec<&str> = missing
+                    .iter()
+                    .map(|part| match *part {
+                        "year" => "年份",
+                        "month" => "月份",
+                        _ => "日期",
+                    })
+                    .collect();
+                format!(
+                    "文件名格式 {} 缺少{}占位符，无法从文件名解析日期",
+                    format,
+                    missing.join("、")
+                )
+            }
+            (Self::AmbiguousFilenameFormat { format, missing }, Lang::En) => format!(
+                "filename format {} has no {} placeholder, dates cannot be parsed from filenames",
+                format,
+                missing.join("/")
+            ),
+        }
+    }
 }
 
 impl From<InvalidHeaderValue> for AppError {
@@ -250,11 +433,32 @@ impl AppError {
     pub fn argument_error(msg: impl Into<String>) -> Self {
         Self::ArgumentError(msg.into())
     }
+
+    /// 是否为配置类错误（配置文件、命令行参数、格式模板等），用于区分退出码
+    pub fn is_config_error(&self) -> bool {
+        matches!(
+            self,
+            Self::ConfigError { .. }
+                | Self::TomlError(_)
+                | Self::InvalidDate { .. }
+                | Self::ArgumentError(_)
+                | Self::FilenameFormatError { .. }
+                | Self::AmbiguousFilenameFormat { .. }
+                | Self::DuplicateFilenames { .. }
+                | Self::RegexError(_)
+                | Self::UrlBuildError { .. }
+                | Self::HeaderError(_)
+        )
+    }
 }
 
 impl From<reqwest::Error> for AppError {
     fn from(err: reqwest::Error) -> Self {
-        let url = err.url().map(|u| u.to_string()).unwrap_or_else(|| "<unknown>".to_string());
+        let url = err
+            .url()
+            .map(|u| crate::config::redact_secrets(u.as_str()))
+            .unwrap_or_else(|| "<unknown>".to_string());
+        let err = err.without_url();
         if let Some(status) = err.status() {
             Self::HttpError { url, status }
         } else {
@@ -277,9 +481,24 @@ mod tests {
         assert!(err.to_string().contains("missing field"));
     }
 
+    #[test]
+    fn test_error_message_lang() {
+        let err = AppError::file_error("a.jpg", "permission denied");
+        assert_eq!(err.message(Lang::Zh), "文件操作错误: a.jpg - permission denied");
+        assert_eq!(err.message(Lang::En), "file error: a.jpg - permission denied");
+    }
+
     #[test]
     fn test_network_error() {
         let err = AppError::network_error("https://example.com", "connection refused");
         assert!(matches!(err, AppError::NetworkError { .. }));
     }
+
+    #[test]
+    fn test_is_config_error() {
+        assert!(AppError::config_error("config.toml", "missing field").is_config_error());
+        assert!(AppError::argument_error("--from 晚于 --to").is_config_error());
+        assert!(!AppError::network_error("https://example.com", "timeout").is_config_error());
+        assert!(!AppError::file_error("a.jpg", "permission denied").is_config_error());
+    }
 }
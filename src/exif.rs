@@ -4,11 +4,14 @@
 //! 主要功能是将 `DateTimeOriginal` 等日期字段设置为指定日期。
 
 use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
 use std::path::Path as StdPath;
 
 use little_exif::metadata::Metadata;
 use little_exif::exif_tag::ExifTag;
+use little_exif::rational::uR64;
 
+use crate::config::ExifConfig;
 use crate::Result;
 
 /// 检查文件是否支持 EXIF
@@ -25,94 +28,685 @@ pub fn supports_exif(path: &StdPath) -> bool {
     }
 }
 
+/// 写入 Artist 和 Copyright 标签时使用的署名
+const ATTRIBUTION: &str = "OWSPACE";
+
+/// GPS 秒数和海拔的有理数分母（秒精确到 1/10000，约 3 毫米）
+const GPS_SECONDS_DENOMINATOR: u32 = 10_000;
+const GPS_ALTITUDE_DENOMINATOR: u32 = 100;
+
+/// UserComment 的字符编码标识（8 字节，见 EXIF 2.3 表 9）
+const USER_COMMENT_ASCII: &[u8; 8] = b"ASCII\0\0\0";
+const USER_COMMENT_UNICODE: &[u8; 8] = b"UNICODE\0";
+const USER_COMMENT_UNDEFINED: &[u8; 8] = &[0; 8];
+
 /// 设置图片的 EXIF DateTimeOriginal 字段
 ///
 /// 使用 `little_exif` 库将图片的 `DateTimeOriginal` 等日期字段设置为指定日期。
-/// 注意：此实现会创建新的 EXIF 数据并追加到文件，原始 EXIF 数据会被保留。
-pub fn set_exif_datetime(path: &StdPath, date: &NaiveDateTime) -> Result<()> {
+/// 原有的 EXIF 数据会被保留：日期标签（含 SubSecTime* 和配置时区时的 OffsetTime*）总是写入，Artist、ImageDescription、Copyright
+/// 以及配置的 GPS 坐标只在不存在时补全，除非 `options.overwrite_existing` 为 true。
+/// `source_url` 为下载来源，`options.embed_source_url` 开启时按同样规则写入 UserComment。
+pub fn set_exif_datetime(
+    path: &StdPath,
+    date: &NaiveDateTime,
+    options: &ExifConfig,
+    source_url: Option<&str>,
+) -> Result<()> {
     // 检查文件是否支持 EXIF
     if !supports_exif(path) {
         tracing::debug!("文件类型不支持 EXIF: {:?}", path);
         return Ok(());
     }
 
-    // 格式化日期时间字符串 (EXIF 标准格式: "YYYY:MM:DD HH:MM:SS")
-    let datetime_str = date.format("%Y:%m:%d %H:%M:%S").to_string();
-
     tracing::info!(
         "设置 EXIF 时间: {:?} -> {}",
         path,
-        datetime_str
+        date.format("%Y:%m:%d %H:%M:%S")
     );
 
-    // 读取 EXIF 元数据并设置新标签
-    let mut metadata = Metadata::new_from_path(path).unwrap_or_else(|_| Metadata::new());
-    metadata.set_tag(ExifTag::DateTimeOriginal(datetime_str.clone()));
-    metadata.set_tag(ExifTag::CreateDate(datetime_str.clone()));
-    metadata.set_tag(ExifTag::ModifyDate(datetime_str.clone()));
-    metadata.set_tag(ExifTag::Artist("OWSPACE".to_string()));
-    metadata.set_tag(ExifTag::ImageDescription(date.format("%Y-%m-%d").to_string()));
-        
-
-    // 写入 EXIF 数据到文件
-    metadata.write_to_file(path).map_err(|e| {
-        crate::AppError::file_error(
-            path,
-            format!("写入 EXIF 数据失败: {}", e),
-        )
+    // 读取 EXIF 元数据并设置新标签；EXIF 段损坏时从空白元数据重建
+    let (mut metadata, rebuilt) = match load_metadata(path) {
+        LoadedMetadata::Parsed(metadata) => (metadata, None),
+        LoadedMetadata::Corrupt(stripped) => (Metadata::new(), Some(stripped)),
+    };
+    apply_tags(&mut metadata, date, options);
+    if let Some(url) = source_url.filter(|_| options.embed_source_url) {
+        apply_source_url(&mut metadata, url, options.overwrite_existing);
+    }
+
+    with_backup(path, options.backup, || {
+        if let Some(stripped) = &rebuilt {
+            tracing::warn!("删除损坏的 EXIF 段并重新写入: {:?}", path);
+            crate::fileops::write_atomic(path, stripped).map_err(|e| {
+                crate::AppError::file_error(path, format!("删除损坏的 EXIF 段失败: {}", e))
+            })?;
+        }
+
+        // 写入 EXIF 数据到文件
+        metadata.write_to_file(path).map_err(|e| {
+            let action = if rebuilt.is_some() { "重建" } else { "写入" };
+            crate::AppError::file_error(
+                path,
+                format!("{} EXIF 数据失败: {}", action, e),
+            )
+        })?;
+
+        if options.verify {
+            verify_exif_datetime(path, date)?;
+        }
+        Ok(())
     })?;
 
     tracing::debug!("EXIF 日期设置成功: {:?}", path);
     Ok(())
 }
 
-/// 获取图片的 EXIF DateTimeOriginal 字段
-pub fn get_exif_datetime(path: &StdPath) -> Result<Option<NaiveDate>> {
-    tracing::debug!("获取 EXIF 时间: {:?}", path);
+/// 相机主人和序列号标签的编号（CameraOwnerName、BodySerialNumber、LensSerialNumber）
+const SENSITIVE_TAG_CODES: [u16; 3] = [0xa430, 0xa431, 0xa435];
 
-    // 检查文件是否存在且支持 EXIF
-    if !supports_exif(path) || !path.exists() {
-        return Ok(None);
+/// JPEG APP1 段中 EXIF 数据的标识
+const EXIF_SIGNATURE: &[u8] = b"Exif\0\0";
+
+/// 读取 EXIF 元数据的结果
+enum LoadedMetadata {
+    /// 解析成功；文件没有 EXIF 时为空
+    Parsed(Metadata),
+    /// JPEG 含 EXIF 段但无法解析，附带删除该段后的文件内容
+    Corrupt(Vec<u8>),
+}
+
+/// 读取文件的 EXIF 元数据，区分"没有 EXIF"和"EXIF 段已损坏"
+///
+/// 只有 JPEG 能定位并删除损坏的 EXIF 段；其他格式解析失败时按没有 EXIF 处理
+fn load_metadata(path: &StdPath) -> LoadedMetadata {
+    match Metadata::new_from_path(path) {
+        Ok(metadata) => LoadedMetadata::Parsed(metadata),
+        Err(e) => match read_jpeg_without_exif(path) {
+            Some(stripped) => {
+                tracing::warn!("EXIF 数据已损坏，无法解析: {:?}: {}", path, e);
+                LoadedMetadata::Corrupt(stripped)
+            }
+            None => {
+                tracing::debug!("没有可读取的 EXIF 数据: {:?}: {}", path, e);
+                LoadedMetadata::Parsed(Metadata::new())
+            }
+        },
+    }
+}
+
+/// 在改写文件前备份原文件，`write` 失败时用备份恢复
+///
+/// 备份为同目录下的 `<文件名>.orig`，成功后保留，由 `clean --backups` 统一清理
+fn with_backup(path: &StdPath, enabled: bool, write: impl FnOnce() -> Result<()>) -> Result<()> {
+    if !enabled {
+        return write();
+    }
+
+    let backup = crate::fileops::backup_path(path);
+    crate::fileops::copy_file(path, &backup)?;
+
+    let result = write();
+    if let Err(e) = &result {
+        tracing::warn!("写入 EXIF 失败，从备份恢复: {:?}: {}", path, e);
+        if let Err(restore_error) = crate::fileops::copy_file(&backup, path) {
+            tracing::error!("从备份恢复失败: {:?}: {}", backup, restore_error);
+        }
     }
+    result
+}
 
-    // 从文件读取 EXIF 元数据
-    let metadata = Metadata::new_from_path(path).map_err(|e| {
-        crate::AppError::file_error(
+/// 重新读取文件的三个日期标签，任一与期望时间不一致时返回 `ExifError`
+///
+/// 用于发现 `write_to_file` 成功返回但实际没有写入可读标签的情况（如部分 PNG）
+pub fn verify_exif_datetime(path: &StdPath, expected: &NaiveDateTime) -> Result<()> {
+    let dates = get_exif_dates(path)
+        .map_err(|e| crate::AppError::exif_error(path, format!("写入后无法读回 EXIF: {}", e)))?;
+    if let Some((tag, actual)) = dates.mismatches(expected).into_iter().next() {
+        return Err(crate::AppError::exif_error(
             path,
-            format!("读取 EXIF 数据失败: {}", e),
-        )
-    })?;
+            format!(
+                "写入后读回的 {} 不一致: 期望 {}，实际 {}",
+                tag,
+                expected,
+                actual.map_or_else(|| "(无)".to_string(), |d| d.to_string())
+            ),
+        ));
+    }
+    Ok(())
+}
 
-    // 尝试获取 DateTimeOriginal
-    // get_tag 返回迭代器，使用 next() 获取第一个匹配项
-    if let Some(datetime_tag) = metadata.get_tag(&ExifTag::DateTimeOriginal(String::new())).next() {
-        // 通过模式匹配获取 DateTimeOriginal 中的值
-        match datetime_tag {
-            ExifTag::DateTimeOriginal(datetime_str) => {
-                tracing::debug!("原始 EXIF DateTimeOriginal: {}", datetime_str);
-                if let Some(date) = parse_exif_datetime(datetime_str) {
-                    return Ok(Some(date));
+/// 在元数据中设置日期标签，并按配置补全或覆盖署名标签
+fn apply_tags(metadata: &mut Metadata, date: &NaiveDateTime, options: &ExifConfig) {
+    if options.strip_sensitive {
+        strip_sensitive_tags(metadata);
+    }
+
+    // 格式化日期时间字符串 (EXIF 标准格式: "YYYY:MM:DD HH:MM:SS")
+    let datetime_str = date.format("%Y:%m:%d %H:%M:%S").to_string();
+    metadata.set_tag(ExifTag::DateTimeOriginal(datetime_str.clone()));
+    metadata.set_tag(ExifTag::CreateDate(datetime_str.clone()));
+    metadata.set_tag(ExifTag::ModifyDate(datetime_str));
+    // 日期不含秒以下的精度，写入 000 使读取方得到确定的排序
+    metadata.set_tag(ExifTag::SubSecTime("000".to_string()));
+    metadata.set_tag(ExifTag::SubSecTimeOriginal("000".to_string()));
+    metadata.set_tag(ExifTag::SubSecTimeDigitized("000".to_string()));
+    if let Some(offset) = &options.offset_time {
+        metadata.set_tag(ExifTag::OffsetTime(offset.clone()));
+        metadata.set_tag(ExifTag::OffsetTimeOriginal(offset.clone()));
+        metadata.set_tag(ExifTag::OffsetTimeDigitized(offset.clone()));
+    }
+
+    let attribution = [
+        ExifTag::Artist(ATTRIBUTION.to_string()),
+        ExifTag::ImageDescription(date.format("%Y-%m-%d").to_string()),
+        ExifTag::Copyright(ATTRIBUTION.to_string()),
+    ];
+    for tag in attribution {
+        if !options.overwrite_existing {
+            if let Some(existing) = string_tag(metadata, tag.clone()) {
+                tracing::debug!("保留已有的 EXIF 标签: {:?} = {}", tag, existing);
+                continue;
+            }
+        }
+        metadata.set_tag(tag);
+    }
+
+    if let Some((latitude, longitude, altitude)) = options.gps() {
+        let has_gps = metadata.get_tag(&ExifTag::GPSLatitude(Vec::new())).next().is_some();
+        if has_gps && !options.overwrite_existing {
+            tracing::debug!("保留已有的 GPS 坐标");
+        } else {
+            apply_gps_tags(metadata, latitude, longitude, altitude);
+        }
+    }
+}
+
+/// 是否为 GPS 位置、序列号或相机主人标签
+fn is_sensitive_tag(tag: &ExifTag) -> bool {
+    // little_exif 中 GPS IFD 的标签变体名均以 GPS 开头
+    SENSITIVE_TAG_CODES.contains(&tag.as_u16()) || tag_name(tag).starts_with("GPS")
+}
+
+/// 标签的变体名，如 `GPSLatitude`
+fn tag_name(tag: &ExifTag) -> String {
+    let debug = format!("{:?}", tag);
+    debug.split('(').next().unwrap_or(&debug).to_string()
+}
+
+/// 删除元数据中的敏感标签
+fn strip_sensitive_tags(metadata: &mut Metadata) {
+    let sensitive: Vec<ExifTag> =
+        (&*metadata).into_iter().filter(|tag| is_sensitive_tag(tag)).cloned().collect();
+    let names: Vec<String> = sensitive.iter().map(tag_name).collect();
+    for tag in sensitive {
+        metadata.remove_tag(tag);
+    }
+    if !names.is_empty() {
+        tracing::debug!("删除敏感 EXIF 标签: {}", names.join(", "));
+    }
+}
+
+/// 把来源 URL 写入 UserComment；已有注释时只在 `overwrite` 为 true 时替换
+///
+/// 已有的值与 URL 相同时不重写，重复处理同一文件不会改变标签
+fn apply_source_url(metadata: &mut Metadata, url: &str, overwrite: bool) {
+    if let Some(existing) = user_comment(metadata) {
+        if existing == url || !overwrite {
+            tracing::debug!("保留已有的 UserComment: {}", existing);
+            return;
+        }
+    }
+    metadata.set_tag(ExifTag::UserComment(encode_user_comment(url)));
+}
+
+/// 按 UserComment 的字符编码规则编码文本
+///
+/// 使用 ASCII 字符编码；非 ASCII 字符按 UTF-8 字节百分号编码，URL 语义不变
+fn encode_user_comment(text: &str) -> Vec<u8> {
+    let mut bytes = USER_COMMENT_ASCII.to_vec();
+    for byte in text.bytes() {
+        if byte.is_ascii() && !byte.is_ascii_control() {
+            bytes.push(byte);
+        } else {
+            bytes.extend(format!("%{:02X}", byte).into_bytes());
+        }
+    }
+    bytes
+}
+
+/// 解码 UserComment，支持 ASCII、UNICODE（UTF-16，按首字节猜测字节序）和未定义编码
+///
+/// 其他编码（如 JIS）或空注释返回 None
+fn decode_user_comment(bytes: &[u8]) -> Option<String> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let (code, body) = bytes.split_at(8);
+    let text = if code == USER_COMMENT_ASCII || code == USER_COMMENT_UNDEFINED {
+        String::from_utf8_lossy(body).into_owned()
+    } else if code == USER_COMMENT_UNICODE {
+        let big_endian = body.first() == Some(&0);
+        let units: Vec<u16> = body
+            .chunks_exact(2)
+            .map(|pair| {
+                if big_endian {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_le_bytes([pair[0], pair[1]])
                 }
+            })
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        return None;
+    };
+    let text = text.trim_end_matches(['\0', ' ']);
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// 读取并解码 UserComment 标签
+fn user_comment(metadata: &Metadata) -> Option<String> {
+    match metadata.get_tag(&ExifTag::UserComment(Vec::new())).next()? {
+        ExifTag::UserComment(bytes) => decode_user_comment(bytes),
+        _ => None,
+    }
+}
+
+/// 写入 GPS 坐标，方向由 Ref 标签表示，坐标值本身取绝对值
+fn apply_gps_tags(metadata: &mut Metadata, latitude: f64, longitude: f64, altitude: Option<f64>) {
+    metadata.set_tag(ExifTag::GPSVersionID(vec![2, 3, 0, 0]));
+    let latitude_ref = if latitude < 0.0 { "S" } else { "N" };
+    metadata.set_tag(ExifTag::GPSLatitudeRef(latitude_ref.to_string()));
+    metadata.set_tag(ExifTag::GPSLatitude(to_dms(latitude)));
+    let longitude_ref = if longitude < 0.0 { "W" } else { "E" };
+    metadata.set_tag(ExifTag::GPSLongitudeRef(longitude_ref.to_string()));
+    metadata.set_tag(ExifTag::GPSLongitude(to_dms(longitude)));
+
+    match altitude {
+        Some(altitude) => {
+            // AltitudeRef: 0 为海平面以上，1 为海平面以下
+            metadata.set_tag(ExifTag::GPSAltitudeRef(vec![u8::from(altitude < 0.0)]));
+            metadata.set_tag(ExifTag::GPSAltitude(vec![uR64 {
+                nominator: (altitude.abs() * GPS_ALTITUDE_DENOMINATOR as f64).round() as u32,
+                denominator: GPS_ALTITUDE_DENOMINATOR,
+            }]));
+        }
+        None => {
+            metadata.remove_tag(ExifTag::GPSAltitudeRef(Vec::new()));
+            metadata.remove_tag(ExifTag::GPSAltitude(Vec::new()));
+        }
+    }
+}
+
+/// 将十进制度数转换为 EXIF 的度、分、秒有理数
+fn to_dms(value: f64) -> Vec<uR64> {
+    let value = value.abs();
+    let degrees = value.trunc();
+    let minutes = ((value - degrees) * 60.0).trunc();
+    let seconds = (value - degrees - minutes / 60.0) * 3600.0;
+    vec![
+        uR64 { nominator: degrees as u32, denominator: 1 },
+        uR64 { nominator: minutes as u32, denominator: 1 },
+        uR64 {
+            nominator: (seconds * GPS_SECONDS_DENOMINATOR as f64).round() as u32,
+            denominator: GPS_SECONDS_DENOMINATOR,
+        },
+    ]
+}
+
+/// 将 EXIF 的度、分、秒有理数转换为十进制度数
+fn from_dms(values: &[uR64]) -> Option<f64> {
+    let mut result = 0.0;
+    for (value, unit) in values.iter().zip([1.0, 60.0, 3600.0]) {
+        result += rational_to_f64(value)? / unit;
+    }
+    Some(result)
+}
+
+/// 有理数转换为浮点数，分母为 0 时返回 None
+fn rational_to_f64(value: &uR64) -> Option<f64> {
+    (value.denominator != 0).then(|| value.nominator as f64 / value.denominator as f64)
+}
+
+/// JPEG APP1 段中 XMP 数据的标识（标准 XMP 和扩展 XMP）
+const XMP_SIGNATURES: [&[u8]; 2] = [
+    b"http://ns.adobe.com/xap/1.0/\0",
+    b"http://ns.adobe.com/xmp/extension/\0",
+];
+
+/// 文件中是否嵌入了 EXIF 或 XMP 元数据
+pub fn has_embedded_metadata(path: &StdPath) -> bool {
+    if !supports_exif(path) {
+        return false;
+    }
+    let has_exif = match load_metadata(path) {
+        LoadedMetadata::Parsed(metadata) => (&metadata).into_iter().next().is_some(),
+        LoadedMetadata::Corrupt(_) => true,
+    };
+    has_exif || read_jpeg_without_xmp(path).is_some()
+}
+
+/// 清除文件中嵌入的 EXIF 和 XMP 元数据，图像数据保持不变
+///
+/// `keep_dates` 为 true 时清除后重新写入原有的 DateTimeOriginal、CreateDate、ModifyDate。
+/// 返回文件是否被修改（没有元数据时不改动文件）。
+pub fn strip_metadata(path: &StdPath, keep_dates: bool) -> Result<bool> {
+    if !has_embedded_metadata(path) {
+        return Ok(false);
+    }
+    tracing::info!("清除元数据: {:?}", path);
+
+    let metadata = match load_metadata(path) {
+        LoadedMetadata::Parsed(metadata) => {
+            if (&metadata).into_iter().next().is_some() {
+                Metadata::file_clear_metadata(path).map_err(|e| {
+                    crate::AppError::exif_error(path, format!("清除 EXIF 数据失败: {}", e))
+                })?;
             }
-            _ => {}
+            metadata
+        }
+        // 损坏的 EXIF 段无法由 little_exif 清除，按字节删除；其中的日期无法保留
+        LoadedMetadata::Corrupt(stripped) => {
+            crate::fileops::write_atomic(path, stripped).map_err(|e| {
+                crate::AppError::file_error(path, format!("删除损坏的 EXIF 段失败: {}", e))
+            })?;
+            Metadata::new()
+        }
+    };
+
+    // little_exif 只处理 EXIF 段，XMP 段按字节删除
+    if let Some(stripped) = read_jpeg_without_xmp(path) {
+        crate::fileops::write_atomic(path, stripped)
+            .map_err(|e| crate::AppError::file_error(path, format!("清除 XMP 数据失败: {}", e)))?;
+    }
+
+    if keep_dates {
+        let mut kept = Metadata::new();
+        for tag in [
+            ExifTag::DateTimeOriginal(String::new()),
+            ExifTag::CreateDate(String::new()),
+            ExifTag::ModifyDate(String::new()),
+        ] {
+            if let Some(existing) = metadata.get_tag(&tag).next() {
+                kept.set_tag(existing.clone());
+            }
+        }
+        if (&kept).into_iter().next().is_some() {
+            kept.write_to_file(path).map_err(|e| {
+                crate::AppError::exif_error(path, format!("写回日期标签失败: {}", e))
+            })?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// 读取 JPEG 文件并删除其中的 EXIF 段；不是 JPEG 或没有 EXIF 段时返回 None
+fn read_jpeg_without_exif(path: &StdPath) -> Option<Vec<u8>> {
+    let data = std::fs::read(path).ok()?;
+    remove_jpeg_app1_segments(&data, |payload| payload.starts_with(EXIF_SIGNATURE))
+}
+
+/// 读取 JPEG 文件并删除其中的 XMP 段；不是 JPEG 或没有 XMP 时返回 None
+fn read_jpeg_without_xmp(path: &StdPath) -> Option<Vec<u8>> {
+    let data = std::fs::read(path).ok()?;
+    remove_jpeg_app1_segments(&data, |payload| {
+        XMP_SIGNATURES.iter().any(|signature| payload.starts_with(signature))
+    })
+}
+
+/// 删除 JPEG 中内容满足条件的 APP1 段，其余字节原样保留
+///
+/// 不是 JPEG、段结构无法识别或没有匹配的段时返回 None
+fn remove_jpeg_app1_segments(data: &[u8], matches: impl Fn(&[u8]) -> bool) -> Option<Vec<u8>> {
+    const APP1: u8 = 0xE1;
+    const SOS: u8 = 0xDA;
+
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(&data[..2]);
+    let mut pos = 2;
+    let mut removed = false;
+
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // SOS 之后是压缩的图像数据，剩余部分原样复制
+        if marker == SOS {
+            break;
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let end = pos + 2 + length;
+        if length < 2 || end > data.len() {
+            return None;
+        }
+        if marker == APP1 && matches(&data[pos + 4..end]) {
+            removed = true;
+        } else {
+            out.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+
+    if !removed {
+        return None;
+    }
+    out.extend_from_slice(&data[pos..]);
+    Some(out)
+}
+
+/// 图片中与日期相关的 EXIF 字段（原始字符串）
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExifInfo {
+    pub date_time_original: Option<String>,
+    pub create_date: Option<String>,
+    pub modify_date: Option<String>,
+    pub artist: Option<String>,
+    pub image_description: Option<String>,
+    pub copyright: Option<String>,
+    /// DateTimeOriginal 所在时区的偏移，如 `+08:00`
+    pub offset_time_original: Option<String>,
+    /// DateTimeOriginal 的秒以下部分
+    pub sub_sec_time_original: Option<String>,
+    /// GPS 纬度（度，南纬为负）
+    pub gps_latitude: Option<f64>,
+    /// GPS 经度（度，西经为负）
+    pub gps_longitude: Option<f64>,
+    /// GPS 海拔（米，海平面以下为负）
+    pub gps_altitude: Option<f64>,
+    /// UserComment 中的文本（启用 `exif.embed_source_url` 时为来源 URL）
+    pub user_comment: Option<String>,
+    /// GPS 位置、序列号、相机主人等敏感标签名，按名称排序
+    pub sensitive_tags: Vec<String>,
+}
+
+impl ExifInfo {
+    /// 解析 DateTimeOriginal 中的日期
+    pub fn date_time_original_date(&self) -> Option<NaiveDate> {
+        self.date_time_original.as_deref().and_then(parse_exif_datetime)
+    }
+
+    /// 解析三个日期标签，保留时间部分
+    pub fn dates(&self) -> ExifDates {
+        let parse = |value: &Option<String>| value.as_deref().and_then(parse_exif_naive_datetime);
+        ExifDates {
+            date_time_original: parse(&self.date_time_original),
+            create_date: parse(&self.create_date),
+            modify_date: parse(&self.modify_date),
+            artist: self.artist.clone(),
+            image_description: self.image_description.clone(),
+        }
+    }
+}
+
+/// 图片中解析后的 EXIF 日期标签
+///
+/// 标签不存在或无法解析时为 None
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ExifDates {
+    pub date_time_original: Option<NaiveDateTime>,
+    pub create_date: Option<NaiveDateTime>,
+    pub modify_date: Option<NaiveDateTime>,
+    pub artist: Option<String>,
+    pub image_description: Option<String>,
+}
+
+impl ExifDates {
+    /// 与期望时间不一致（含缺失）的日期标签及其实际值
+    pub fn mismatches(&self, expected: &NaiveDateTime) -> Vec<(&'static str, Option<NaiveDateTime>)> {
+        [
+            ("DateTimeOriginal", self.date_time_original),
+            ("CreateDate", self.create_date),
+            ("ModifyDate", self.modify_date),
+        ]
+        .into_iter()
+        .filter(|(_, actual)| actual.as_ref() != Some(expected))
+        .collect()
+    }
+
+    /// 三个日期标签是否都等于期望时间
+    pub fn all_match(&self, expected: &NaiveDateTime) -> bool {
+        self.mismatches(expected).is_empty()
+    }
+}
+
+/// 读取图片中与日期相关的 EXIF 字段
+///
+/// 文件不存在或不支持 EXIF 时返回空的 `ExifInfo`
+pub fn get_exif_info(path: &StdPath) -> Result<ExifInfo> {
+    tracing::debug!("读取 EXIF 信息: {:?}", path);
+
+    // 检查文件是否存在且支持 EXIF
+    if !supports_exif(path) || !path.exists() {
+        return Ok(ExifInfo::default());
+    }
+
+    // 从文件读取 EXIF 元数据；损坏的 EXIF 段已记录警告，按没有标签处理
+    let metadata = match load_metadata(path) {
+        LoadedMetadata::Parsed(metadata) => metadata,
+        LoadedMetadata::Corrupt(_) => return Ok(ExifInfo::default()),
+    };
+
+    Ok(ExifInfo {
+        date_time_original: string_tag(&metadata, ExifTag::DateTimeOriginal(String::new())),
+        create_date: string_tag(&metadata, ExifTag::CreateDate(String::new())),
+        modify_date: string_tag(&metadata, ExifTag::ModifyDate(String::new())),
+        artist: string_tag(&metadata, ExifTag::Artist(String::new())),
+        image_description: string_tag(&metadata, ExifTag::ImageDescription(String::new())),
+        copyright: string_tag(&metadata, ExifTag::Copyright(String::new())),
+        offset_time_original: string_tag(&metadata, ExifTag::OffsetTimeOriginal(String::new())),
+        sub_sec_time_original: string_tag(&metadata, ExifTag::SubSecTimeOriginal(String::new())),
+        gps_latitude: gps_coordinate(&metadata, ExifTag::GPSLatitude(Vec::new()), "S"),
+        gps_longitude: gps_coordinate(&metadata, ExifTag::GPSLongitude(Vec::new()), "W"),
+        gps_altitude: gps_altitude(&metadata),
+        user_comment: user_comment(&metadata),
+        sensitive_tags: sensitive_tag_names(&metadata),
+    })
+}
+
+/// 收集敏感标签名并排序（little_exif 的遍历顺序取决于标签所在的 IFD，不固定）
+fn sensitive_tag_names(metadata: &Metadata) -> Vec<String> {
+    let mut names: Vec<String> = metadata
+        .into_iter()
+        .filter(|tag| is_sensitive_tag(tag))
+        .map(tag_name)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// 读取字符串类型的 EXIF 标签，去掉末尾的 NUL 字符；空值视为不存在
+fn string_tag(metadata: &Metadata, tag: ExifTag) -> Option<String> {
+    // get_tag 返回迭代器，使用 next() 获取第一个匹配项
+    let value = match metadata.get_tag(&tag).next()? {
+        ExifTag::DateTimeOriginal(value)
+        | ExifTag::CreateDate(value)
+        | ExifTag::ModifyDate(value)
+        | ExifTag::Artist(value)
+        | ExifTag::ImageDescription(value)
+        | ExifTag::Copyright(value)
+        | ExifTag::OffsetTimeOriginal(value)
+        | ExifTag::SubSecTimeOriginal(value) => value.trim_end_matches('\0'),
+        _ => return None,
+    };
+    (!value.trim().is_empty()).then(|| value.to_string())
+}
+
+/// 读取 GPS 纬度或经度，参考方向为 `negative_ref`（`S` 或 `W`）时取负值
+fn gps_coordinate(metadata: &Metadata, tag: ExifTag, negative_ref: &str) -> Option<f64> {
+    let (value, ref_tag) = match metadata.get_tag(&tag).next()? {
+        ExifTag::GPSLatitude(value) => (value, ExifTag::GPSLatitudeRef(String::new())),
+        ExifTag::GPSLongitude(value) => (value, ExifTag::GPSLongitudeRef(String::new())),
+        _ => return None,
+    };
+    let degrees = from_dms(value)?;
+    let negative = match metadata.get_tag(&ref_tag).next() {
+        Some(ExifTag::GPSLatitudeRef(r) | ExifTag::GPSLongitudeRef(r)) => {
+            r.trim_end_matches('\0').eq_ignore_ascii_case(negative_ref)
         }
+        _ => false,
+    };
+    Some(if negative { -degrees } else { degrees })
+}
+
+/// 读取 GPS 海拔，AltitudeRef 为 1 时取负值
+fn gps_altitude(metadata: &Metadata) -> Option<f64> {
+    let altitude = match metadata.get_tag(&ExifTag::GPSAltitude(Vec::new())).next()? {
+        ExifTag::GPSAltitude(value) => rational_to_f64(value.first()?)?,
+        _ => return None,
+    };
+    let below_sea_level = matches!(
+        metadata.get_tag(&ExifTag::GPSAltitudeRef(Vec::new())).next(),
+        Some(ExifTag::GPSAltitudeRef(r)) if r.first() == Some(&1)
+    );
+    Some(if below_sea_level { -altitude } else { altitude })
+}
+
+/// 获取图片的 EXIF DateTimeOriginal 字段
+pub fn get_exif_datetime(path: &StdPath) -> Result<Option<NaiveDate>> {
+    Ok(get_exif_dates(path)?.date_time_original.map(|dt| dt.date()))
+}
+
+/// 获取图片的 DateTimeOriginal、CreateDate、ModifyDate（含时间部分）以及 Artist、ImageDescription
+///
+/// 文件不存在或不支持 EXIF 时返回空的 `ExifDates`
+pub fn get_exif_dates(path: &StdPath) -> Result<ExifDates> {
+    tracing::debug!("获取 EXIF 时间: {:?}", path);
+
+    let info = get_exif_info(path)?;
+    if let Some(datetime_str) = &info.date_time_original {
+        tracing::debug!("原始 EXIF DateTimeOriginal: {}", datetime_str);
     }
 
-    Ok(None)
+    Ok(info.dates())
 }
 
-/// 解析 EXIF 日期时间字符串
+/// 解析 EXIF 日期时间字符串中的日期
 ///
 /// EXIF 标准格式为 `YYYY:MM:DD HH:MM:SS`
-fn parse_exif_datetime(datetime_str: &str) -> Option<NaiveDate> {
+pub fn parse_exif_datetime(datetime_str: &str) -> Option<NaiveDate> {
+    parse_exif_naive_datetime(datetime_str).map(|dt| dt.date())
+}
+
+/// 解析 EXIF 日期时间字符串，保留时间部分
+///
+/// 支持标准格式 `YYYY:MM:DD HH:MM:SS` 和部分软件写入的 `YYYY-MM-DD HH:MM:SS`
+pub fn parse_exif_naive_datetime(datetime_str: &str) -> Option<NaiveDateTime> {
+    let datetime_str = datetime_str.trim();
+
     // 尝试标准 EXIF 格式: "YYYY:MM:DD HH:MM:SS"
-    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(datetime_str, "%Y:%m:%d %H:%M:%S") {
-        return Some(dt.date());
+    if let Ok(dt) = NaiveDateTime::parse_from_str(datetime_str, "%Y:%m:%d %H:%M:%S") {
+        return Some(dt);
     }
 
     // 尝试替代格式: "YYYY-MM-DD HH:MM:SS"
-    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M:%S") {
-        return Some(dt.date());
+    if let Ok(dt) = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M:%S") {
+        return Some(dt);
     }
 
     tracing::warn!("无法解析 EXIF 日期时间: {}", datetime_str);
@@ -121,9 +715,6 @@ fn parse_exif_datetime(datetime_str: &str) -> Option<NaiveDate> {
 
 #[cfg(test)]
 mod tests {
-
-    use little_exif::metadata;
-
     use super::*;
 
     #[test]
@@ -155,18 +746,456 @@ mod tests {
         assert!(!supports_exif(StdPath::new("test.pdf")));
     }
 
+    #[test]
+    fn test_get_exif_info_unsupported_file() {
+        let info = get_exif_info(StdPath::new("notes.txt")).unwrap();
+        assert_eq!(info, ExifInfo::default());
+        assert_eq!(info.date_time_original_date(), None);
+    }
+
+    #[test]
+    fn test_exif_info_date_time_original_date() {
+        let info = ExifInfo {
+            date_time_original: Some("2024:06:15 00:00:00".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(info.date_time_original_date(), NaiveDate::from_ymd_opt(2024, 6, 15));
+    }
+
+    /// 生成带有相机自带署名标签的 JPEG 测试图片
+    fn fixture_jpeg_with_tags(dir: &StdPath) -> std::path::PathBuf {
+        let path = dir.join("camera.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let mut metadata = Metadata::new();
+        metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+        metadata.set_tag(ExifTag::ImageDescription("Sunrise over the harbour".to_string()));
+        metadata.set_tag(ExifTag::Copyright("(c) Jane Doe".to_string()));
+        metadata.set_tag(ExifTag::DateTimeOriginal("2010:01:01 12:00:00".to_string()));
+        metadata.write_to_file(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_set_exif_datetime_preserves_existing_tags() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = fixture_jpeg_with_tags(temp_dir.path());
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        set_exif_datetime(&path, &date, &ExifConfig::default(), None).unwrap();
+
+        let info = get_exif_info(&path).unwrap();
+        // 日期标签总是写入，署名标签保持原样
+        assert_eq!(info.date_time_original.as_deref(), Some("2024:06:15 00:00:00"));
+        assert_eq!(info.create_date.as_deref(), Some("2024:06:15 00:00:00"));
+        assert_eq!(info.modify_date.as_deref(), Some("2024:06:15 00:00:00"));
+        assert_eq!(info.artist.as_deref(), Some("Jane Doe"));
+        assert_eq!(info.image_description.as_deref(), Some("Sunrise over the harbour"));
+        assert_eq!(info.copyright.as_deref(), Some("(c) Jane Doe"));
+    }
+
+    #[test]
+    fn test_set_exif_datetime_overwrite_existing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = fixture_jpeg_with_tags(temp_dir.path());
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let options = ExifConfig { overwrite_existing: true, ..Default::default() };
+
+        set_exif_datetime(&path, &date, &options, None).unwrap();
+
+        let info = get_exif_info(&path).unwrap();
+        assert_eq!(info.artist.as_deref(), Some(ATTRIBUTION));
+        assert_eq!(info.image_description.as_deref(), Some("2024-06-15"));
+        assert_eq!(info.copyright.as_deref(), Some(ATTRIBUTION));
+    }
+
+    #[test]
+    fn test_apply_tags_fills_missing_tags() {
+        let mut metadata = Metadata::new();
+        metadata.set_tag(ExifTag::Artist("Jane Doe".to_string()));
+        metadata.set_tag(ExifTag::Copyright("\0".to_string()));
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        apply_tags(&mut metadata, &date, &ExifConfig::default());
+
+        // 缺失或为空的标签被补全，已有的 Artist 保留
+        let tag = |t| string_tag(&metadata, t);
+        assert_eq!(tag(ExifTag::Artist(String::new())).as_deref(), Some("Jane Doe"));
+        assert_eq!(tag(ExifTag::ImageDescription(String::new())).as_deref(), Some("2024-06-15"));
+        assert_eq!(tag(ExifTag::Copyright(String::new())).as_deref(), Some(ATTRIBUTION));
+    }
+
+    #[test]
+    fn test_set_exif_datetime_gps_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        // 南纬、西经、海平面以下，验证 Ref 标签处理负值
+        let options = ExifConfig {
+            gps_latitude: Some(-22.951916),
+            gps_longitude: Some(-43.210487),
+            gps_altitude: Some(-12.5),
+            ..Default::default()
+        };
+        set_exif_datetime(&path, &date, &options, None).unwrap();
+
+        let info = get_exif_info(&path).unwrap();
+        assert!((info.gps_latitude.unwrap() - -22.951916).abs() < 1e-6);
+        assert!((info.gps_longitude.unwrap() - -43.210487).abs() < 1e-6);
+        assert_eq!(info.gps_altitude, Some(-12.5));
+
+        // 北纬、东经
+        let options = ExifConfig {
+            gps_latitude: Some(39.9042),
+            gps_longitude: Some(116.4074),
+            overwrite_existing: true,
+            ..Default::default()
+        };
+        set_exif_datetime(&path, &date, &options, None).unwrap();
+        let info = get_exif_info(&path).unwrap();
+        assert!((info.gps_latitude.unwrap() - 39.9042).abs() < 1e-6);
+        assert!((info.gps_longitude.unwrap() - 116.4074).abs() < 1e-6);
+        assert_eq!(info.gps_altitude, None);
+    }
+
+    #[test]
+    fn test_set_exif_datetime_embed_source_url() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let url = "https://img.owspace.com/Public/uploads/Download/2024/0615.jpg";
+
+        // 未开启时不写入
+        set_exif_datetime(&path, &date, &ExifConfig::default(), Some(url)).unwrap();
+        assert_eq!(get_exif_info(&path).unwrap().user_comment, None);
+
+        let options = ExifConfig { embed_source_url: true, ..Default::default() };
+        set_exif_datetime(&path, &date, &options, Some(url)).unwrap();
+        assert_eq!(get_exif_info(&path).unwrap().user_comment.as_deref(), Some(url));
+
+        // 重新处理（无来源 URL）和再次写入都不改变已有的值
+        set_exif_datetime(&path, &date, &options, None).unwrap();
+        set_exif_datetime(&path, &date, &options, Some(url)).unwrap();
+        let metadata = Metadata::new_from_path(&path).unwrap();
+        assert_eq!(
+            metadata.get_tag(&ExifTag::UserComment(Vec::new())).next(),
+            Some(&ExifTag::UserComment(encode_user_comment(url)))
+        );
+
+        // 已有其他注释时只在 overwrite_existing 下替换
+        let other = "https://example.com/0615.jpg";
+        set_exif_datetime(&path, &date, &options, Some(other)).unwrap();
+        assert_eq!(get_exif_info(&path).unwrap().user_comment.as_deref(), Some(url));
+        let options = ExifConfig { overwrite_existing: true, ..options };
+        set_exif_datetime(&path, &date, &options, Some(other)).unwrap();
+        assert_eq!(get_exif_info(&path).unwrap().user_comment.as_deref(), Some(other));
+    }
+
+    #[test]
+    fn test_set_exif_datetime_strip_sensitive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = fixture_jpeg_with_tags(temp_dir.path());
+        let mut metadata = Metadata::new_from_path(&path).unwrap();
+        metadata.set_tag(ExifTag::GPSLatitudeRef("N".to_string()));
+        metadata.set_tag(ExifTag::GPSLatitude(to_dms(31.2304)));
+        metadata.set_tag(ExifTag::GPSDateStamp("2010:01:01".to_string()));
+        metadata.set_tag(ExifTag::LensSerialNumber("LS-0042".to_string()));
+        metadata.write_to_file(&path).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        // 默认保留所有标签
+        set_exif_datetime(&path, &date, &ExifConfig::default(), None).unwrap();
+        let info = get_exif_info(&path).unwrap();
+        assert_eq!(
+            info.sensitive_tags,
+            vec!["GPSDateStamp", "GPSLatitude", "GPSLatitudeRef", "LensSerialNumber"]
+        );
+
+        let options = ExifConfig { strip_sensitive: true, ..Default::default() };
+        set_exif_datetime(&path, &date, &options, None).unwrap();
+        let info = get_exif_info(&path).unwrap();
+        assert!(info.sensitive_tags.is_empty());
+        assert_eq!(info.gps_latitude, None);
+        assert_eq!(info.date_time_original.as_deref(), Some("2024:06:15 00:00:00"));
+        assert_eq!(info.artist.as_deref(), Some("Jane Doe"));
+
+        // 配置的坐标在删除原有坐标后写入
+        let options = ExifConfig {
+            gps_latitude: Some(39.9042),
+            gps_longitude: Some(116.4074),
+            ..options
+        };
+        set_exif_datetime(&path, &date, &options, None).unwrap();
+        assert!((get_exif_info(&path).unwrap().gps_latitude.unwrap() - 39.9042).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_user_comment_encoding() {
+        let encoded = encode_user_comment("https://example.com/日历.jpg");
+        assert!(encoded.starts_with(USER_COMMENT_ASCII));
+        assert!(encoded.is_ascii());
+        assert_eq!(
+            decode_user_comment(&encoded).as_deref(),
+            Some("https://example.com/%E6%97%A5%E5%8E%86.jpg")
+        );
+
+        let mut unicode = USER_COMMENT_UNICODE.to_vec();
+        unicode.extend("日历".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode_user_comment(&unicode).as_deref(), Some("日历"));
+        let mut unicode = USER_COMMENT_UNICODE.to_vec();
+        unicode.extend("abc".encode_utf16().flat_map(u16::to_be_bytes));
+        assert_eq!(decode_user_comment(&unicode).as_deref(), Some("abc"));
+
+        // 空注释（常见的全空格或全 NUL 填充）和未知编码视为不存在
+        assert_eq!(decode_user_comment(b"ASCII\0\0\0    \0\0"), None);
+        assert_eq!(decode_user_comment(b"JIS\0\0\0\0\0abc"), None);
+        assert_eq!(decode_user_comment(b"ASC"), None);
+    }
+
+    #[test]
+    fn test_set_exif_datetime_offset_and_subsec() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        // 未配置时区时只写 SubSecTime*
+        set_exif_datetime(&path, &date, &ExifConfig::default(), None).unwrap();
+        let info = get_exif_info(&path).unwrap();
+        assert_eq!(info.sub_sec_time_original.as_deref(), Some("000"));
+        assert_eq!(info.offset_time_original, None);
+
+        let options = ExifConfig {
+            offset_time: Some("+08:00".to_string()),
+            ..Default::default()
+        };
+        set_exif_datetime(&path, &date, &options, None).unwrap();
+        let info = get_exif_info(&path).unwrap();
+        assert_eq!(info.offset_time_original.as_deref(), Some("+08:00"));
+        assert_eq!(info.sub_sec_time_original.as_deref(), Some("000"));
+        assert_eq!(info.date_time_original.as_deref(), Some("2024:06:15 00:00:00"));
+    }
+
+    #[test]
+    fn test_verify_exif_datetime() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .unwrap();
+        let june_15 = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+
+        let midnight = june_15.and_hms_opt(0, 0, 0).unwrap();
+
+        // 没有写入过标签时校验失败，并报告为 EXIF 错误
+        let err = verify_exif_datetime(&path, &midnight).unwrap_err();
+        assert!(matches!(err, crate::AppError::ExifError { .. }), "{}", err);
+
+        set_exif_datetime(&path, &midnight, &ExifConfig::default(), None).unwrap();
+        verify_exif_datetime(&path, &midnight).unwrap();
+        let june_16 = NaiveDate::from_ymd_opt(2024, 6, 16).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert!(verify_exif_datetime(&path, &june_16).is_err());
+
+        // 只有 ModifyDate 不一致时同样报告，并指出标签名
+        let mut metadata = Metadata::new_from_path(&path).unwrap();
+        metadata.set_tag(ExifTag::ModifyDate("2024:06:15 09:30:00".to_string()));
+        metadata.write_to_file(&path).unwrap();
+        let err = verify_exif_datetime(&path, &midnight).unwrap_err();
+        assert!(err.to_string().contains("ModifyDate"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_exif_naive_datetime_formats() {
+        let expected = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(8, 30, 5).unwrap();
+        assert_eq!(parse_exif_naive_datetime("2024:06:15 08:30:05"), Some(expected));
+        assert_eq!(parse_exif_naive_datetime("2024-06-15 08:30:05"), Some(expected));
+        assert_eq!(parse_exif_naive_datetime(" 2024:06:15 08:30:05 "), Some(expected));
+        assert_eq!(parse_exif_datetime("2024-06-15 08:30:05"), Some(expected.date()));
+        assert_eq!(parse_exif_naive_datetime("2024:06:15"), None);
+        assert_eq!(parse_exif_naive_datetime("    :  :     :  :  "), None);
+    }
+
+    #[test]
+    fn test_get_exif_dates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = fixture_jpeg_with_tags(temp_dir.path());
+        let mut metadata = Metadata::new_from_path(&path).unwrap();
+        metadata.set_tag(ExifTag::CreateDate("2010-01-01 12:00:00".to_string()));
+        metadata.write_to_file(&path).unwrap();
+
+        let dates = get_exif_dates(&path).unwrap();
+        let noon = NaiveDate::from_ymd_opt(2010, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        assert_eq!(dates.date_time_original, Some(noon));
+        assert_eq!(dates.create_date, Some(noon));
+        assert_eq!(dates.modify_date, None);
+        assert_eq!(dates.artist.as_deref(), Some("Jane Doe"));
+        assert_eq!(dates.image_description.as_deref(), Some("Sunrise over the harbour"));
+        assert_eq!(dates.mismatches(&noon), vec![("ModifyDate", None)]);
+        assert!(!dates.all_match(&noon));
+        assert_eq!(get_exif_datetime(&path).unwrap(), Some(noon.date()));
+
+        // 不支持 EXIF 的文件返回空值
+        let text = temp_dir.path().join("notes.txt");
+        std::fs::write(&text, b"x").unwrap();
+        assert_eq!(get_exif_dates(&text).unwrap(), ExifDates::default());
+    }
+
+    /// 在 JPEG 的 SOI 之后插入一个 APP1 段
+    fn insert_app1(jpeg: &[u8], payload: &[u8]) -> Vec<u8> {
+        let length = (payload.len() + 2) as u16;
+        let mut out = jpeg[..2].to_vec();
+        out.extend_from_slice(&[0xFF, 0xE1]);
+        out.extend_from_slice(&length.to_be_bytes());
+        out.extend_from_slice(payload);
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    #[test]
+    fn test_remove_jpeg_xmp_segment() {
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(2, 2)
+            .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+        let jpeg = jpeg.into_inner();
+        let xmp = [XMP_SIGNATURES[0], b"<x:xmpmeta/>"].concat();
+        let with_xmp = insert_app1(&jpeg, &xmp);
+
+        let is_xmp = |payload: &[u8]| payload.starts_with(XMP_SIGNATURES[0]);
+        // 删除 XMP 段后与原始文件逐字节一致
+        assert_eq!(remove_jpeg_app1_segments(&with_xmp, is_xmp), Some(jpeg.clone()));
+        // 没有匹配的段或不是 JPEG 时不改动
+        assert_eq!(remove_jpeg_app1_segments(&jpeg, is_xmp), None);
+        assert_eq!(remove_jpeg_app1_segments(b"GIF89a", is_xmp), None);
+    }
+
+    /// 含截断 EXIF 段的 JPEG：段长度自洽，但 IFD 声明的条目超出段末尾
+    fn truncated_exif_jpeg() -> (Vec<u8>, Vec<u8>) {
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(2, 2)
+            .write_to(&mut jpeg, image::ImageFormat::Jpeg)
+            .unwrap();
+        let jpeg = jpeg.into_inner();
+        // TIFF 头（小端，IFD0 偏移 8）+ 声明 12 个条目的 IFD0，只剩半个条目
+        let payload = [EXIF_SIGNATURE, b"II*\0\x08\0\0\0", b"\x0c\0\x32\x01\x02\0"].concat();
+        (insert_app1(&jpeg, &payload), jpeg)
+    }
+
+    #[test]
+    fn test_remove_truncated_exif_segment() {
+        let (corrupt, jpeg) = truncated_exif_jpeg();
+        let is_exif = |payload: &[u8]| payload.starts_with(EXIF_SIGNATURE);
+        assert_eq!(remove_jpeg_app1_segments(&corrupt, is_exif), Some(jpeg));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        std::fs::write(&path, &corrupt).unwrap();
+        let stripped = read_jpeg_without_exif(&path).unwrap();
+        assert!(image::load_from_memory(&stripped).is_ok());
+    }
+
+    #[test]
+    fn test_set_exif_datetime_corrupt_exif() {
+        let (corrupt, _) = truncated_exif_jpeg();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        std::fs::write(&path, &corrupt).unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+        // 读取不报错，写入后能读回日期，图像数据保持可解码
+        get_exif_info(&path).unwrap();
+        set_exif_datetime(&path, &date, &ExifConfig::default(), None).unwrap();
+        assert!(get_exif_dates(&path).unwrap().all_match(&date));
+        assert!(image::open(&path).is_ok());
+    }
+
+    #[test]
+    fn test_strip_metadata() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = fixture_jpeg_with_tags(temp_dir.path());
+        let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        set_exif_datetime(&path, &date, &ExifConfig::default(), None).unwrap();
+
+        // --keep-dates 只保留日期标签
+        assert!(strip_metadata(&path, true).unwrap());
+        let info = get_exif_info(&path).unwrap();
+        assert_eq!(info.date_time_original.as_deref(), Some("2024:06:15 00:00:00"));
+        assert_eq!(info.modify_date.as_deref(), Some("2024:06:15 00:00:00"));
+        assert_eq!(info.artist, None);
+        assert_eq!(info.copyright, None);
+
+        assert!(strip_metadata(&path, false).unwrap());
+        assert_eq!(get_exif_info(&path).unwrap(), ExifInfo::default());
+        // 已没有元数据时不再改动文件
+        assert!(!strip_metadata(&path, false).unwrap());
+    }
+
+    #[test]
+    fn test_with_backup_restores_on_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        std::fs::write(&path, b"original").unwrap();
+        let backup = crate::fileops::backup_path(&path);
+
+        // 写入中途失败：文件被恢复为原内容
+        let result = with_backup(&path, true, || {
+            std::fs::write(&path, b"torn").unwrap();
+            Err(crate::AppError::exif_error(&path, "写入失败"))
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"original");
+
+        // 写入成功：保留新内容和备份
+        with_backup(&path, true, || {
+            std::fs::write(&path, b"updated").unwrap();
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"updated");
+        assert_eq!(std::fs::read(&backup).unwrap(), b"original");
+
+        // 未启用时不创建备份
+        std::fs::remove_file(&backup).unwrap();
+        with_backup(&path, false, || Ok(())).unwrap();
+        assert!(!backup.exists());
+    }
+
+    #[test]
+    fn test_dms_conversion() {
+        let dms = to_dms(-33.8568);
+        assert_eq!(dms[0], uR64 { nominator: 33, denominator: 1 });
+        assert_eq!(dms[1], uR64 { nominator: 51, denominator: 1 });
+        assert!((from_dms(&dms).unwrap() - 33.8568).abs() < 1e-6);
+        assert_eq!(from_dms(&[uR64 { nominator: 1, denominator: 0 }]), None);
+    }
+
     #[test]
     fn test_parse_exif_datetime() {
-        let p = StdPath::new("/mnt/d/WorkSpace/copilot/calendar/owspace_20150218.jpg");
+        let temp_dir = tempfile::tempdir().unwrap();
+        let p = temp_dir.path().join("owspace_20150218.jpg");
+        image::RgbImage::new(1, 1)
+            .save_with_format(&p, image::ImageFormat::Jpeg)
+            .unwrap();
         let date=NaiveDate::from_ymd_opt(2015, 2, 18).unwrap().and_hms_opt(8, 0, 0).unwrap();
-        println!("{}", date.format("%Y:%m:%d  %H:%M:%S").to_string());
-        let mut metadata = metadata::Metadata::new_from_path(p).unwrap();
+        let mut metadata = Metadata::new();
         metadata.set_tag(ExifTag::DateTimeOriginal(date.format("%Y:%m:%d %H:%M:%S").to_string()));
         metadata.set_tag(ExifTag::CreateDate(date.format("%Y:%m:%d %H:%M:%S").to_string()));
         metadata.set_tag(ExifTag::ModifyDate(date.format("%Y:%m:%d %H:%M:%S").to_string()));
         metadata.set_tag(ExifTag::Artist("OWSPACE".to_string()));
         metadata.set_tag(ExifTag::ImageDescription(date.format("%Y-%m-%d").to_string()));
-        metadata.write_to_file(p).unwrap();
+        metadata.write_to_file(&p).unwrap();
 
+        let info = get_exif_info(&p).unwrap();
+        assert_eq!(info.date_time_original_date(), Some(date.date()));
     }
 }
@@ -9,24 +9,396 @@
 //! - `{m}` 或 `{month}` → 不补位的月份 (如: 1, 6, 12)
 //! - `{dd}` 或 `{day:02}` → 两位日期 (如: 01, 15, 31)
 //! - `{d}` 或 `{day}` → 不补位的日期 (如: 1, 15, 31)
+//! - `{month_name}` → 月份名称，默认为英文全称，可在配置中用 `month_names` 本地化 (如: June)
+//! - `{Mmm}` / `{mmm}` → 三个字母的英文月份缩写，首字母大写 / 小写 (如: Jun / jun)
+//! - `{doy}` → 三位年内第几天 (如: 001, 167, 366)
+//! - `{ww}` → 两位 ISO 周数 (如: 01, 24, 53)
+//! - `{weekday}` / `{Weekday}` → 英文星期名称，小写 / 首字母大写 (如: monday / Monday)
+//! - `{wd}` → 三个字母的小写星期缩写 (如: mon)
+//! - `{wd_num}` → ISO 星期序号，周一为 1、周日为 7
+//! - `{lunar_year}` / `{lunar_month}` / `{lunar_day}` → 农历年、两位农历月（闰月加 `r` 前缀，如 r04）、
+//!   两位农历日，支持 1990 年春节到 2100 年末，超出范围时保留占位符原样
+//! - `{isoyear}` → ISO 周所属的年份，1 月初和 12 月末可能与日历年份不同 (如: 2021-01-01 → 2020)
+//! - `{ext}` → 文件扩展名，下载时按响应的 Content-Type 确定（jpg/png/webp/gif，
+//!   以及 pdf/mp4 等允许的媒体类型），缺少 Content-Type 时使用默认扩展名；只能出现在文件名格式末尾（如 `{yyyy}{mm}{dd}.{ext}`）
+//! - `{urlhash}` / `{urlhash:N}` → 该日期 URL 的 SHA-256（小写十六进制）前 N 位，默认 8 位，不能用于 URL 模板
+//! - `{n}` → 同一天的第几张图片，从 1 开始，配合 `images_per_day` 使用；下载以外的场景取 1
+//!
+//! `{year}`、`{month}`、`{day}`、`{doy}`、`{mm}`、`{dd}`、`{n}` 可带宽度修饰符（如 `{day:03}`）；
+//! `{year:N}` 的宽度小于 4 时只保留末 N 位（`{year:2}` 与 `{yy}` 相同）。其他 `{...}` 在创建格式化器时报错。
+//!
+//! 也可以使用 strftime 语法（见 [`FormatStyle::Strftime`]），如 `%Y/%m/%d.jpg`，
+//! 格式符转换为对应的占位符，结果与 `NaiveDate::format` 相同。
+
+use std::fmt::Write;
 
 use chrono::{Datelike, NaiveDate};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use crate::error::{AppError, Result};
+use crate::lunar::LunarDate;
+
+/// 反向解析时占位符对应的日期字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateField {
+    /// 四位年份
+    Year,
+    /// 两位年份（按 2000 年后处理）
+    ShortYear,
+    /// 月份
+    Month,
+    /// 月份名称（全称，可本地化）
+    MonthName,
+    /// 三个字母的英文月份缩写
+    MonthAbbr,
+    /// 日期
+    Day,
+    /// 年内第几天
+    DayOfYear,
+    /// 星期（周一为 1，只用于校验）
+    Weekday,
+    /// 农历年（只用于校验）
+    LunarYear,
+    /// 农历月，闰月加 100（只用于校验）
+    LunarMonth,
+    /// 农历日（只用于校验）
+    LunarDay,
+    /// ISO 周所属年份（只用于校验）
+    IsoYear,
+    /// ISO 周数（只用于校验）
+    IsoWeek,
+}
+
+/// 占位符种类，同义的写法（如 `{yyyy}` 与 `{year}`）对应同一种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// `{yyyy}`、`{year}`
+    Year,
+    /// `{yy}`
+    ShortYear,
+    /// `{mm}`、`{m}`、`{month}`
+    Month,
+    /// `{month_name}`
+    MonthName,
+    /// `{Mmm}`
+    MonthAbbr,
+    /// `{mmm}`
+    MonthAbbrLower,
+    /// `{dd}`、`{d}`、`{day}`
+    Day,
+    /// `{doy}`
+    DayOfYear,
+    /// `{weekday}`
+    Weekday,
+    /// `{Weekday}`
+    WeekdayCapitalized,
+    /// `{wd}`
+    WeekdayAbbr,
+    /// `{wd_num}`
+    WeekdayNumber,
+    /// `{lunar_year}`
+    LunarYear,
+    /// `{lunar_month}`
+    LunarMonth,
+    /// `{lunar_day}`
+    LunarDay,
+    /// `{ww}`
+    IsoWeek,
+    /// `{isoyear}`
+    IsoYear,
+    /// `{ext}`
+    Ext,
+    /// `{urlhash}`
+    UrlHash,
+    /// `{n}`
+    Index,
+}
+
+/// 格式字符串在创建格式化器时解析出的片段
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// 原样输出的文本
+    Literal(String),
+    /// 占位符及补零宽度（`{mm}` 与 `{month:02}` 的宽度都是 2）
+    Placeholder(Placeholder, Option<usize>),
+    /// 带大小写修饰符的文本占位符（如 `{wd:upper}`）
+    Cased(Placeholder, Case),
+}
+
+/// 文本占位符的大小写修饰符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    /// `:upper`，全部大写
+    Upper,
+    /// `:lower`，全部小写
+    Lower,
+    /// `:title`，首字母大写、其余小写
+    Title,
+}
+
+impl Case {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "upper" => Some(Self::Upper),
+            "lower" => Some(Self::Lower),
+            "title" => Some(Self::Title),
+            _ => None,
+        }
+    }
+
+    fn apply(self, value: &str) -> String {
+        match self {
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Title => capitalize(&value.to_lowercase()),
+        }
+    }
+}
+
+impl Token {
+    /// 解析占位符（不含花括号），名称或修饰符无效时返回 `None`
+    fn placeholder(placeholder: &str) -> Option<Self> {
+        let (kind, width) = match placeholder.split_once(':') {
+            // 冒号后不是数字时按大小写修饰符处理，只用于文本占位符
+            Some((name, modifier)) if modifier.parse::<usize>().is_err() => {
+                let case = Case::from_name(modifier)?;
+                if !CASE_PLACEHOLDER_NAMES.contains(&name) {
+                    return None;
+                }
+                let Some(Self::Placeholder(kind, _)) = Self::placeholder(name) else {
+                    return None;
+                };
+                return Some(Self::Cased(kind, case));
+            }
+            Some((name, width)) => {
+                let width: usize = width.parse().ok()?;
+                let kind = match name {
+                    "year" => Placeholder::Year,
+                    "month" | "mm" => Placeholder::Month,
+                    "day" | "dd" => Placeholder::Day,
+                    "doy" => Placeholder::DayOfYear,
+                    "n" => Placeholder::Index,
+                    "urlhash" if (1..=URL_HASH_MAX_WIDTH).contains(&width) => Placeholder::UrlHash,
+                    _ => return None,
+                };
+                (kind, Some(width))
+            }
+            None => match placeholder {
+                "yyyy" | "year" => (Placeholder::Year, None),
+                "yy" => (Placeholder::ShortYear, None),
+                "mm" => (Placeholder::Month, Some(2)),
+                "m" | "month" => (Placeholder::Month, None),
+                "month_name" => (Placeholder::MonthName, None),
+                "Mmm" => (Placeholder::MonthAbbr, None),
+                "mmm" => (Placeholder::MonthAbbrLower, None),
+                "dd" => (Placeholder::Day, Some(2)),
+                "d" | "day" => (Placeholder::Day, None),
+                "doy" => (Placeholder::DayOfYear, Some(3)),
+                "weekday" => (Placeholder::Weekday, None),
+                "Weekday" => (Placeholder::WeekdayCapitalized, None),
+                "wd" => (Placeholder::WeekdayAbbr, None),
+                "wd_num" => (Placeholder::WeekdayNumber, None),
+                "lunar_year" => (Placeholder::LunarYear, None),
+                "lunar_month" => (Placeholder::LunarMonth, None),
+                "lunar_day" => (Placeholder::LunarDay, None),
+                "ww" => (Placeholder::IsoWeek, None),
+                "isoyear" => (Placeholder::IsoYear, None),
+                "ext" => (Placeholder::Ext, None),
+                "urlhash" => (Placeholder::UrlHash, Some(URL_HASH_WIDTH)),
+                "n" => (Placeholder::Index, None),
+                _ => return None,
+            },
+        };
+        Some(Self::Placeholder(kind, width))
+    }
+
+    /// 解析 strftime 格式符（不含 `%`），不支持的格式符返回 `None`
+    fn strftime(spec: &str) -> Option<Vec<Self>> {
+        let placeholder = |kind, width| vec![Self::Placeholder(kind, width)];
+        Some(match spec {
+            "Y" => placeholder(Placeholder::Year, Some(4)),
+            "y" => placeholder(Placeholder::ShortYear, None),
+            "m" => placeholder(Placeholder::Month, Some(2)),
+            "-m" => placeholder(Placeholder::Month, None),
+            "d" => placeholder(Placeholder::Day, Some(2)),
+            "-d" => placeholder(Placeholder::Day, None),
+            "j" => placeholder(Placeholder::DayOfYear, Some(3)),
+            "-j" => placeholder(Placeholder::DayOfYear, None),
+            "B" => placeholder(Placeholder::MonthName, None),
+            "b" | "h" => placeholder(Placeholder::MonthAbbr, None),
+            "A" => placeholder(Placeholder::WeekdayCapitalized, None),
+            "u" => placeholder(Placeholder::WeekdayNumber, None),
+            "V" => placeholder(Placeholder::IsoWeek, None),
+            "G" => placeholder(Placeholder::IsoYear, None),
+            "F" => vec![
+                Self::Placeholder(Placeholder::Year, Some(4)),
+                Self::Literal("-".to_string()),
+                Self::Placeholder(Placeholder::Month, Some(2)),
+                Self::Literal("-".to_string()),
+                Self::Placeholder(Placeholder::Day, Some(2)),
+            ],
+            "%" => vec![Self::Literal("%".to_string())],
+            _ => return None,
+        })
+    }
+}
+
+/// 格式字符串的语法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormatStyle {
+    /// `{yyyy}`、`{mm}` 等花括号占位符
+    #[default]
+    Braces,
+    /// `%Y`、`%m` 等 strftime 格式符，支持 `%Y %y %m %d %j %B %b %h %A %u %V %G %F %%`，
+    /// `%-m`、`%-d`、`%-j` 不补零；`%B` 同样使用 `month_names` 本地化
+    Strftime,
+}
+
+impl DateField {
+    /// 反向解析正则中命名捕获组的名称前缀
+    fn group_name(self) -> &'static str {
+        match self {
+            Self::Year => "year",
+            Self::ShortYear => "short_year",
+            Self::Month => "month",
+            Self::MonthName => "month_name",
+            Self::MonthAbbr => "month_abbr",
+            Self::Day => "day",
+            Self::DayOfYear => "doy",
+            Self::Weekday => "weekday",
+            Self::LunarYear => "lunar_year",
+            Self::LunarMonth => "lunar_month",
+            Self::LunarDay => "lunar_day",
+            Self::IsoYear => "isoyear",
+            Self::IsoWeek => "ww",
+        }
+    }
+}
+
+/// 支持的占位符名称，按给出拼写建议时的优先顺序排列
+const PLACEHOLDER_NAMES: [&str; 25] = [
+    "yyyy", "year", "yy", "mm", "m", "month", "month_name", "Mmm", "mmm", "dd", "d", "day", "doy",
+    "ww", "isoyear", "weekday", "Weekday", "wd", "wd_num", "lunar_year", "lunar_month", "lunar_day",
+    "ext", "urlhash", "n",
+];
+
+/// `{ext}` 可能取到的扩展名，查找已下载的文件时逐个尝试
+pub const KNOWN_EXTENSIONS: [&str; 9] = ["jpg", "png", "webp", "gif", "pdf", "mp4", "webm", "mov", "mp3"];
+
+/// `{ext}` 未指定时使用的扩展名
+const DEFAULT_EXT: &str = "jpg";
+
+/// 英文月份名称
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September",
+    "October", "November", "December",
+];
+
+/// 小写英文星期名称，从周一开始
+const WEEKDAY_NAMES: [&str; 7] =
+    ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// 可带宽度修饰符（`{name:width}`）的占位符名称
+const WIDTH_PLACEHOLDER_NAMES: [&str; 8] = ["year", "month", "day", "doy", "mm", "dd", "urlhash", "n"];
+
+/// 可带大小写修饰符（`{name:upper}`、`{name:lower}`、`{name:title}`）的文本占位符名称
+const CASE_PLACEHOLDER_NAMES: [&str; 6] = ["month_name", "Mmm", "mmm", "weekday", "Weekday", "wd"];
+
+/// 大小写修饰符名称
+const CASE_MODIFIERS: [&str; 3] = ["upper", "lower", "title"];
+
+/// `{urlhash}` 的默认位数
+const URL_HASH_WIDTH: usize = 8;
+
+/// `{urlhash:N}` 的最大位数（SHA-256 的十六进制长度）
+const URL_HASH_MAX_WIDTH: usize = 64;
+
+/// Windows 文件名中不允许的字符（控制字符另行检查）
+const ILLEGAL_FILENAME_CHARS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+/// Windows 保留的设备名，不区分大小写，带扩展名时同样不能使用
+const RESERVED_FILENAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// URL 中占位符取值需要编码的字符：除 RFC 3986 非保留字符以外全部编码
+const URL_VALUE_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// URL 模板文本中需要编码的字符：保留 `/`、`?`、`&`、`=`、`%` 等结构字符，只编码空格和不安全的字符
+const URL_LITERAL_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'|')
+    .add(b'\\')
+    .add(b'^');
+
+/// 支持的 strftime 格式符，用于错误提示
+const STRFTIME_SPECIFIERS: &str = "%Y %y %m %-m %d %-d %j %-j %B %b %h %A %u %V %G %F %%";
 
 /// 文件名格式化器
 #[derive(Debug, Clone)]
 pub struct FilenameFormatter {
     /// 格式字符串
     format: String,
+    /// 格式字符串的语法
+    style: FormatStyle,
     /// 占位符正则表达式
     placeholder_regex: Regex,
+    /// 格式字符串解析出的片段
+    tokens: Vec<Token>,
+    /// 用于反向解析的正则表达式
+    parse_regex: Regex,
+    /// 反向解析正则中各捕获组对应的日期字段
+    parse_fields: Vec<DateField>,
+    /// `{month_name}` 使用的本地化月份名称（12 个），未设置时使用英文
+    month_names: Option<Vec<String>>,
+    /// 没有 Content-Type 时 `{ext}` 使用的扩展名
+    default_ext: String,
+    /// 上下文中没有 URL 时，`{urlhash}` 用于生成 URL 的模板
+    url_template: Option<Box<FilenameFormatter>>,
+    /// 文件名中有文件系统不允许的字符时替换为 `_`，而不是报错
+    sanitize: bool,
+}
+
+/// 格式化时日期以外的上下文
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormatContext<'a> {
+    /// `{ext}` 的取值，为 `None` 时使用默认扩展名
+    pub ext: Option<&'a str>,
+    /// 该日期实际请求的 URL，为 `None` 时按 URL 模板生成
+    pub url: Option<&'a str>,
+    /// `{n}` 的取值（同一天的第几张图片），为 `None` 时为 1
+    pub index: Option<u32>,
 }
 
 impl FilenameFormatter {
     /// 创建新的格式化器
     pub fn new(format: &str) -> Result<Self> {
+        Self::with_style(format, FormatStyle::Braces)
+    }
+
+    /// 按指定语法创建格式化器
+    ///
+    /// strftime 语法中出现花括号占位符或不支持的格式符时返回错误
+    ///
+    /// # 示例
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use calendar::filename::{FilenameFormatter, FormatStyle};
+    /// let formatter = FilenameFormatter::with_style("%Y/%m/%d.jpg", FormatStyle::Strftime).unwrap();
+    /// let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+    /// assert_eq!(formatter.format(&date), "2024/06/15.jpg");
+    /// ```
+    pub fn with_style(format: &str, style: FormatStyle) -> Result<Self> {
         // 验证格式字符串
         if format.is_empty() {
             return Err(AppError::FilenameFormatError {
@@ -35,17 +407,516 @@ impl FilenameFormatter {
             });
         }
 
-        // 构建匹配占位符的正则表达式
         // 匹配类似 {year}、{month:02}、{dd} 等模式
-        let regex_str = r"\{([^}]+)\}";
-        let placeholder_regex = Regex::new(regex_str).map_err(|e| AppError::RegexError(e))?;
+        let placeholder_regex = Regex::new(r"\{([^}]+)\}").map_err(AppError::RegexError)?;
+        let tokens = match style {
+            FormatStyle::Braces => Self::tokenize(format, &placeholder_regex)?,
+            FormatStyle::Strftime => Self::tokenize_strftime(format, &placeholder_regex)?,
+        };
+        let (parse_regex, parse_fields) = Self::build_parse_regex(&tokens, None)?;
 
         Ok(Self {
             format: format.to_string(),
+            style,
             placeholder_regex,
+            tokens,
+            parse_regex,
+            parse_fields,
+            month_names: None,
+            default_ext: DEFAULT_EXT.to_string(),
+            url_template: None,
+            sanitize: false,
         })
     }
 
+    /// 设置 `{urlhash}` 在上下文中没有 URL 时使用的 URL 模板（通常为 `base_url`）
+    ///
+    /// URL 模板本身包含 `{urlhash}` 时返回错误
+    pub fn with_url_template(mut self, url_template: Self) -> Result<Self> {
+        url_template.ensure_no_urlhash()?;
+        self.url_template = Some(Box::new(url_template));
+        Ok(self)
+    }
+
+    /// 用于 URL 模板时检查不包含 `{urlhash}`（URL 的哈希不能出现在 URL 自身中）
+    pub fn ensure_no_urlhash(&self) -> Result<()> {
+        if self.tokens.iter().any(|token| matches!(token, Token::Placeholder(Placeholder::UrlHash, _))) {
+            return Err(AppError::FilenameFormatError {
+                format: self.format.clone(),
+                details: "`{urlhash}` 由 URL 计算，不能用于 URL 模板".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// 将格式字符串解析为文本和占位符片段
+    fn tokenize(format: &str, placeholder_regex: &Regex) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        let mut last_end = 0;
+        for cap in placeholder_regex.captures_iter(format) {
+            let full_match = cap.get(0).unwrap();
+            let placeholder = cap.get(1).unwrap().as_str();
+
+            // 拼写错误的占位符会原样出现在每个文件名中，创建时即报错
+            let token = Token::placeholder(placeholder).ok_or_else(|| AppError::FilenameFormatError {
+                format: format.to_string(),
+                details: Self::unknown_placeholder(placeholder),
+            })?;
+
+            if full_match.start() > last_end {
+                tokens.push(Token::Literal(format[last_end..full_match.start()].to_string()));
+            }
+            tokens.push(token);
+            last_end = full_match.end();
+        }
+        if last_end < format.len() {
+            tokens.push(Token::Literal(format[last_end..].to_string()));
+        }
+        Ok(tokens)
+    }
+
+    /// 将 strftime 格式字符串解析为文本和占位符片段
+    fn tokenize_strftime(format: &str, placeholder_regex: &Regex) -> Result<Vec<Token>> {
+        let error = |details: String| AppError::FilenameFormatError {
+            format: format.to_string(),
+            details,
+        };
+        if let Some(placeholder) = placeholder_regex.find(format) {
+            return Err(error(format!(
+                "strftime 格式中不能混用花括号占位符: {}",
+                placeholder.as_str()
+            )));
+        }
+
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                literal.push(c);
+                continue;
+            }
+            let mut spec: String = chars.next().into_iter().collect();
+            if spec == "-" {
+                spec.extend(chars.next());
+            }
+            let parsed = Token::strftime(&spec).ok_or_else(|| {
+                error(format!("不支持的 strftime 格式符 %{}，可用: {}", spec, STRFTIME_SPECIFIERS))
+            })?;
+            for token in parsed {
+                match token {
+                    Token::Literal(text) => literal.push_str(&text),
+                    token => {
+                        if !literal.is_empty() {
+                            tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                        }
+                        tokens.push(token);
+                    }
+                }
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        Ok(tokens)
+    }
+
+    /// 设置是否将文件名中文件系统不允许的字符替换为 `_`（默认报错）
+    pub fn with_sanitize(mut self, sanitize: bool) -> Self {
+        self.sanitize = sanitize;
+        self
+    }
+
+    /// 检查格式化得到的文件名（可含 `/` 分隔的子目录）能否在各平台上创建
+    ///
+    /// 包含 `< > : " | ? *` 或控制字符、以点号或空格结尾、或为 Windows 保留设备名（如 `CON`、`NUL.jpg`）时，
+    /// 启用清理则替换为 `_` 后返回，否则返回指出问题字符的错误。
+    ///
+    /// # 示例
+    /// ```
+    /// # use calendar::filename::FilenameFormatter;
+    /// let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+    /// assert!(formatter.check_file_name("2024:06.jpg".to_string()).is_err());
+    /// let formatter = formatter.with_sanitize(true);
+    /// assert_eq!(formatter.check_file_name("2024:06.jpg".to_string()).unwrap(), "2024_06.jpg");
+    /// ```
+    pub fn check_file_name(&self, name: String) -> Result<String> {
+        if self.sanitize {
+            return Ok(sanitize_file_name(&name));
+        }
+        check_file_name_chars(&name).map_err(|details| AppError::FilenameFormatError {
+            format: self.format.clone(),
+            details,
+        })?;
+        Ok(name)
+    }
+
+    /// 格式是否不含任何占位符（所有日期生成相同的结果）
+    pub fn is_literal(&self) -> bool {
+        self.tokens.iter().all(|token| matches!(token, Token::Literal(_)))
+    }
+
+    /// 格式是否包含 `{n}`
+    pub fn uses_index(&self) -> bool {
+        self.tokens.iter().any(|token| matches!(token, Token::Placeholder(Placeholder::Index, _)))
+    }
+
+    /// 设置 `{ext}` 的默认扩展名（默认 `jpg`）
+    pub fn with_default_ext(mut self, ext: &str) -> Self {
+        self.default_ext = ext.to_string();
+        self
+    }
+
+    /// 格式包含 `{ext}` 时返回默认扩展名，否则返回 `None`
+    pub fn default_ext(&self) -> Option<&str> {
+        self.tokens
+            .contains(&Token::Placeholder(Placeholder::Ext, None))
+            .then_some(self.default_ext.as_str())
+    }
+
+    /// 使用本地化的月份名称（1 月到 12 月）填充 `{month_name}`
+    ///
+    /// 名称数量不是 12 个时返回错误
+    pub fn with_month_names(mut self, names: &[String]) -> Result<Self> {
+        if names.len() != 12 {
+            return Err(AppError::FilenameFormatError {
+                format: self.format,
+                details: format!("月份名称必须为 12 个，实际为 {} 个", names.len()),
+            });
+        }
+        let (parse_regex, parse_fields) = Self::build_parse_regex(&self.tokens, Some(names))?;
+        self.parse_regex = parse_regex;
+        self.parse_fields = parse_fields;
+        self.month_names = Some(names.to_vec());
+        Ok(self)
+    }
+
+    /// 使用相同的本地化设置创建另一个格式的格式化器（如 URL 模板）
+    pub fn with_format(&self, format: &str) -> Result<Self> {
+        let formatter = Self::with_style(format, self.style)?.with_default_ext(&self.default_ext);
+        match &self.month_names {
+            Some(names) => formatter.with_month_names(names),
+            None => Ok(formatter),
+        }
+    }
+
+    /// 月份对应的 `{month_name}` 名称
+    fn month_name(&self, month0: usize) -> &str {
+        match &self.month_names {
+            Some(names) => &names[month0],
+            None => MONTH_NAMES[month0],
+        }
+    }
+
+    /// 不支持的占位符（不含花括号）的错误原因，包含拼写建议
+    fn unknown_placeholder(placeholder: &str) -> String {
+        let (name, width) = match placeholder.split_once(':') {
+            Some((name, width)) => (name, Some(width)),
+            None => (placeholder, None),
+        };
+
+        if let Some(modifier) = width.filter(|width| width.parse::<usize>().is_err()) {
+            if CASE_PLACEHOLDER_NAMES.contains(&name) {
+                let names = CASE_MODIFIERS.map(|case| format!("`{}`", case)).join("、");
+                return match closest_name(modifier, &CASE_MODIFIERS) {
+                    Some(case) => format!(
+                        "未知修饰符 `{}`，是否应为 `{{{}:{}}}`？支持的修饰符: {}",
+                        modifier, name, case, names
+                    ),
+                    None => format!("未知修饰符 `{}`，支持的修饰符: {}", modifier, names),
+                };
+            }
+            if Case::from_name(modifier).is_some() && PLACEHOLDER_NAMES.contains(&name) {
+                return format!(
+                    "`{{{}}}` 不是文本占位符，不能使用大小写修饰符 `{}`；可用于 {}",
+                    name,
+                    modifier,
+                    CASE_PLACEHOLDER_NAMES.map(|name| format!("{{{}}}", name)).join("、")
+                );
+            }
+        }
+
+        let suggestion = match width {
+            None => closest_name(name, &PLACEHOLDER_NAMES).map(|name| format!("{{{}}}", name)),
+            Some(_) if name == "urlhash" => Some(format!("{{urlhash:{}}}", URL_HASH_WIDTH)),
+            // 名称正确但宽度不是数字
+            Some(_) if WIDTH_PLACEHOLDER_NAMES.contains(&name) => Some(format!("{{{}:02}}", name)),
+            Some(width) => closest_name(name, &WIDTH_PLACEHOLDER_NAMES)
+                .map(|name| format!("{{{}:{}}}", name, width)),
+        };
+
+        match suggestion {
+            Some(suggestion) => format!("未知占位符 `{{{}}}`，是否应为 `{}`？", placeholder, suggestion),
+            None => format!(
+                "未知占位符 `{{{}}}`，支持的占位符: {}",
+                placeholder,
+                PLACEHOLDER_NAMES.map(|name| format!("{{{}}}", name)).join("、")
+            ),
+        }
+    }
+
+    /// 将格式片段编译为反向解析用的正则表达式
+    ///
+    /// 日期占位符转换为命名捕获组（如 `year_0`），其余文本按字面匹配
+    fn build_parse_regex(
+        tokens: &[Token],
+        month_names: Option<&[String]>,
+    ) -> Result<(Regex, Vec<DateField>)> {
+        let mut pattern = String::from("^");
+        let mut fields = Vec::new();
+
+        for token in tokens {
+            let (kind, width) = match token {
+                Token::Literal(text) => {
+                    pattern.push_str(&regex::escape(text));
+                    continue;
+                }
+                Token::Cased(kind, case) => {
+                    let (field, group) = Self::cased_pattern(*kind, *case, month_names);
+                    pattern.push_str(&format!("(?P<{}_{}>{})", field.group_name(), fields.len(), group));
+                    fields.push(field);
+                    continue;
+                }
+                Token::Placeholder(kind, width) => (*kind, *width),
+            };
+
+            let (field, group) = match kind {
+                // 截断的年份按 2000 年之后处理
+                Placeholder::Year => match width {
+                    Some(width @ 1..=3) => (DateField::ShortYear, format!(r"\d{{{}}}", width)),
+                    Some(width) if width > 4 => (DateField::Year, format!(r"\d{{{}}}", width)),
+                    _ => (DateField::Year, r"\d{4}".to_string()),
+                },
+                Placeholder::ShortYear => (DateField::ShortYear, r"\d{2}".to_string()),
+                Placeholder::Month => (DateField::Month, Self::width_pattern(width)),
+                Placeholder::MonthName => (
+                    DateField::MonthName,
+                    match month_names {
+                        Some(names) => Self::alternation_pattern(names.iter().map(String::as_str)),
+                        None => Self::alternation_pattern(MONTH_NAMES),
+                    },
+                ),
+                Placeholder::MonthAbbr => (DateField::MonthAbbr, r"[A-Z][a-z]{2}".to_string()),
+                Placeholder::MonthAbbrLower => (DateField::MonthAbbr, r"[a-z]{3}".to_string()),
+                Placeholder::Day => (DateField::Day, Self::width_pattern(width)),
+                Placeholder::DayOfYear => match width {
+                    Some(width) if width >= 3 => (DateField::DayOfYear, format!(r"\d{{{}}}", width)),
+                    _ => (DateField::DayOfYear, r"\d{1,3}".to_string()),
+                },
+                Placeholder::LunarYear => (DateField::LunarYear, r"\d{4}".to_string()),
+                Placeholder::LunarMonth => (DateField::LunarMonth, r"r?\d{2}".to_string()),
+                Placeholder::LunarDay => (DateField::LunarDay, r"\d{2}".to_string()),
+                Placeholder::IsoWeek => (DateField::IsoWeek, r"\d{2}".to_string()),
+                Placeholder::IsoYear => (DateField::IsoYear, r"\d{4}".to_string()),
+                Placeholder::Weekday => (DateField::Weekday, r"[a-z]+".to_string()),
+                Placeholder::WeekdayCapitalized => (DateField::Weekday, r"[A-Z][a-z]+".to_string()),
+                Placeholder::WeekdayAbbr => (DateField::Weekday, r"[a-z]{3}".to_string()),
+                Placeholder::WeekdayNumber => (DateField::Weekday, r"[1-7]".to_string()),
+                // 扩展名和 URL 哈希不影响日期，不需要捕获
+                Placeholder::Ext => {
+                    pattern.push_str("[A-Za-z0-9]+");
+                    continue;
+                }
+                Placeholder::UrlHash => {
+                    pattern.push_str(&format!("[0-9a-f]{{{}}}", width.unwrap_or(URL_HASH_WIDTH)));
+                    continue;
+                }
+                Placeholder::Index => {
+                    pattern.push_str(r"\d+");
+                    continue;
+                }
+            };
+            pattern.push_str(&format!("(?P<{}_{}>{})", field.group_name(), fields.len(), group));
+            fields.push(field);
+        }
+
+        pattern.push('$');
+
+        let regex = Regex::new(&pattern).map_err(AppError::RegexError)?;
+        Ok((regex, fields))
+    }
+
+    /// 带大小写修饰符的文本占位符对应的字段和模式
+    fn cased_pattern(kind: Placeholder, case: Case, month_names: Option<&[String]>) -> (DateField, String) {
+        let (field, abbr) = match kind {
+            Placeholder::MonthName => {
+                let names: Vec<String> = match month_names {
+                    Some(names) => names.iter().map(|name| case.apply(name)).collect(),
+                    None => MONTH_NAMES.iter().map(|name| case.apply(name)).collect(),
+                };
+                let group = Self::alternation_pattern(names.iter().map(String::as_str));
+                return (DateField::MonthName, group);
+            }
+            Placeholder::MonthAbbr | Placeholder::MonthAbbrLower => (DateField::MonthAbbr, true),
+            Placeholder::WeekdayAbbr => (DateField::Weekday, true),
+            _ => (DateField::Weekday, false),
+        };
+        let group = match (case, abbr) {
+            (Case::Upper, true) => r"[A-Z]{3}",
+            (Case::Upper, false) => r"[A-Z]+",
+            (Case::Lower, true) => r"[a-z]{3}",
+            (Case::Lower, false) => r"[a-z]+",
+            (Case::Title, true) => r"[A-Z][a-z]{2}",
+            (Case::Title, false) => r"[A-Z][a-z]+",
+        };
+        (field, group.to_string())
+    }
+
+    /// 匹配任一名称的模式（按字面匹配，较长的名称优先）
+    fn alternation_pattern<'a>(names: impl IntoIterator<Item = &'a str>) -> String {
+        let mut names: Vec<&str> = names.into_iter().collect();
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        let alternatives: Vec<String> = names.into_iter().map(regex::escape).collect();
+        alternatives.join("|")
+    }
+
+    /// 月份、日期的数字模式，没有宽度或宽度不足两位时按不补位处理
+    fn width_pattern(width: Option<usize>) -> String {
+        match width {
+            Some(width) if width >= 2 => format!(r"\d{{{}}}", width),
+            _ => r"\d{1,2}".to_string(),
+        }
+    }
+
+    /// 检查格式能否从文件名唯一确定日期
+    ///
+    /// 需要年份占位符，以及月份和日期占位符（或 `{doy}`）；缺少时返回
+    /// [`AppError::AmbiguousFilenameFormat`]，列出缺少的成分。只按月份命名的格式可以正常下载，
+    /// 但无法用于扫描图库。
+    ///
+    /// # 示例
+    /// ```
+    /// # use calendar::filename::FilenameFormatter;
+    /// assert!(FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap().ensure_parseable().is_ok());
+    /// assert!(FilenameFormatter::new("{yyyy}-{mm}.jpg").unwrap().ensure_parseable().is_err());
+    /// ```
+    pub fn ensure_parseable(&self) -> Result<()> {
+        let has = |fields: &[DateField]| self.parse_fields.iter().any(|field| fields.contains(field));
+
+        let mut missing = Vec::new();
+        if !has(&[DateField::Year, DateField::ShortYear]) {
+            missing.push("year");
+        }
+        if !has(&[DateField::DayOfYear]) {
+            if !has(&[DateField::Month, DateField::MonthName, DateField::MonthAbbr]) {
+                missing.push("month");
+            }
+            if !has(&[DateField::Day]) {
+                missing.push("day");
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::AmbiguousFilenameFormat {
+                format: self.format.clone(),
+                missing,
+            })
+        }
+    }
+
+    /// 从文件名反向解析日期
+    ///
+    /// 整个名称必须与格式匹配，且格式中包含年、月、日占位符（或年份和年内第几天），否则返回 `None`；
+    /// 可先用 [`ensure_parseable`](Self::ensure_parseable) 检查格式。
+    /// 两位年份按 2000 年之后处理；ISO 周数和 ISO 年份不参与确定日期，但必须与解析出的日期一致。
+    ///
+    /// # 示例
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use calendar::filename::FilenameFormatter;
+    /// let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+    /// let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+    /// assert_eq!(formatter.parse("20240615.jpg"), Some(date));
+    /// assert_eq!(formatter.parse("20240615.png"), None);
+    /// ```
+    pub fn parse(&self, name: &str) -> Option<NaiveDate> {
+        let captures = self.parse_regex.captures(name)?;
+
+        let mut year = None;
+        let mut month = None;
+        let mut day = None;
+        let mut day_of_year = None;
+        let mut weekday = None;
+        let mut lunar = (None, None, None);
+        let mut iso_year = None;
+        let mut iso_week = None;
+
+        for (index, field) in self.parse_fields.iter().enumerate() {
+            let text = captures.name(&format!("{}_{}", field.group_name(), index))?.as_str();
+            let value: i32 = match field {
+                DateField::Weekday => weekday_number(text)?,
+                // 正则已限定大小写形式，这里不区分大小写（`{month_name:upper}` 等）
+                DateField::MonthName => {
+                    let text = text.to_lowercase();
+                    (0..12).position(|month0| self.month_name(month0).to_lowercase() == text)? as i32 + 1
+                }
+                DateField::LunarMonth => match text.strip_prefix('r') {
+                    Some(month) => month.parse::<i32>().ok()? + 100,
+                    None => text.parse().ok()?,
+                },
+                DateField::MonthAbbr => {
+                    MONTH_NAMES.iter().position(|name| name[..3].eq_ignore_ascii_case(text))? as i32 + 1
+                }
+                _ => text.parse().ok()?,
+            };
+            let (slot, value) = match field {
+                DateField::Year => (&mut year, value),
+                DateField::ShortYear => (&mut year, 2000 + value),
+                DateField::Month | DateField::MonthName | DateField::MonthAbbr => (&mut month, value),
+                DateField::Day => (&mut day, value),
+                DateField::DayOfYear => (&mut day_of_year, value),
+                DateField::Weekday => (&mut weekday, value),
+                DateField::LunarYear => (&mut lunar.0, value),
+                DateField::LunarMonth => (&mut lunar.1, value),
+                DateField::LunarDay => (&mut lunar.2, value),
+                DateField::IsoYear => (&mut iso_year, value),
+                DateField::IsoWeek => (&mut iso_week, value),
+            };
+
+            // 同一字段出现多次时取值必须一致
+            match slot {
+                Some(existing) if *existing != value => return None,
+                _ => *slot = Some(value),
+            }
+        }
+
+        let date = match (month, day, day_of_year) {
+            (None, None, Some(ordinal)) => NaiveDate::from_yo_opt(year?, u32::try_from(ordinal).ok()?)?,
+            (month, day, _) => NaiveDate::from_ymd_opt(
+                year?,
+                u32::try_from(month?).ok()?,
+                u32::try_from(day?).ok()?,
+            )?,
+        };
+        if day_of_year.is_some_and(|ordinal| ordinal != date.ordinal() as i32)
+            || weekday.is_some_and(|w| w != date.weekday().number_from_monday() as i32)
+        {
+            return None;
+        }
+        if lunar != (None, None, None) {
+            let actual = LunarDate::from_solar(&date)?;
+            let month = actual.month as i32 + if actual.is_leap_month { 100 } else { 0 };
+            if lunar.0.is_some_and(|y| y != actual.year)
+                || lunar.1.is_some_and(|m| m != month)
+                || lunar.2.is_some_and(|d| d != actual.day as i32)
+            {
+                return None;
+            }
+        }
+        let week = date.iso_week();
+        if iso_year.is_some_and(|y| y != week.year()) || iso_week.is_some_and(|w| w != week.week() as i32) {
+            return None;
+        }
+        Some(date)
+    }
+
+    /// 格式中包含的路径层级数（以 `/` 分隔）
+    pub fn path_depth(&self) -> usize {
+        self.format.split('/').count()
+    }
+
     /// 格式化日期为文件名
     ///
     /// # 示例
@@ -57,7 +928,380 @@ impl FilenameFormatter {
     /// assert_eq!(formatter.format(&date), "20240615.jpg");
     /// ```
     pub fn format(&self, date: &NaiveDate) -> String {
-        let mut result = self.format.clone();
+        self.format_with(date, &FormatContext::default())
+    }
+
+    /// 使用日期和额外的上下文（如 `{ext}` 的扩展名）格式化文件名
+    ///
+    /// # 示例
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use calendar::filename::{FilenameFormatter, FormatContext};
+    /// let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.{ext}").unwrap();
+    /// let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+    /// assert_eq!(formatter.format(&date), "20240615.jpg");
+    /// let context = FormatContext { ext: Some("webp"), ..Default::default() };
+    /// assert_eq!(formatter.format_with(&date, &context), "20240615.webp");
+    /// ```
+    pub fn format_with(&self, date: &NaiveDate, context: &FormatContext) -> String {
+        self.render(date, context, false)
+    }
+
+    /// 生成格式化结果，`encode` 为 true 时按 URL 规则对占位符取值和模板文本进行百分号编码
+    ///
+    /// 未替换的占位符（如超出范围的农历日期）不编码，以便 `try_format_url` 报告
+    fn render(&self, date: &NaiveDate, context: &FormatContext, encode: bool) -> String {
+        let lunar = self
+            .tokens
+            .iter()
+            .any(|token| {
+                matches!(
+                    token,
+                    Token::Placeholder(Placeholder::LunarYear | Placeholder::LunarMonth | Placeholder::LunarDay, _)
+                )
+            })
+            .then(|| LunarDate::from_solar(date))
+            .flatten();
+
+        let mut result = String::with_capacity(self.format.len() + 16);
+        for token in &self.tokens {
+            match token {
+                Token::Literal(text) if encode => result.extend(utf8_percent_encode(text, URL_LITERAL_ENCODE_SET)),
+                Token::Literal(text) => result.push_str(text),
+                Token::Placeholder(kind, width) if encode => {
+                    let mut value = String::new();
+                    self.write_placeholder(&mut value, *kind, width.unwrap_or(0), date, context, lunar.as_ref())
+                        .expect("写入 String 不会失败");
+                    if value.starts_with('{') {
+                        result.push_str(&value);
+                    } else {
+                        result.extend(utf8_percent_encode(&value, URL_VALUE_ENCODE_SET));
+                    }
+                }
+                Token::Placeholder(kind, width) => {
+                    self.write_placeholder(&mut result, *kind, width.unwrap_or(0), date, context, lunar.as_ref())
+                        .expect("写入 String 不会失败");
+                }
+                Token::Cased(kind, case) => {
+                    let mut value = String::new();
+                    self.write_placeholder(&mut value, *kind, 0, date, context, None)
+                        .expect("写入 String 不会失败");
+                    let value = case.apply(&value);
+                    if encode {
+                        result.extend(utf8_percent_encode(&value, URL_VALUE_ENCODE_SET));
+                    } else {
+                        result.push_str(&value);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// 写入单个占位符的值
+    fn write_placeholder(
+        &self,
+        out: &mut String,
+        kind: Placeholder,
+        width: usize,
+        date: &NaiveDate,
+        context: &FormatContext,
+        lunar: Option<&LunarDate>,
+    ) -> std::fmt::Result {
+        let weekday = WEEKDAY_NAMES[date.weekday().num_days_from_monday() as usize];
+        let month_abbr = &MONTH_NAMES[date.month0() as usize][..3];
+        match kind {
+            // 宽度小于 4 时只保留末几位（`{year:2}` 与 `{yy}` 相同）
+            Placeholder::Year => match width {
+                1..=3 => write!(out, "{:0width$}", date.year().unsigned_abs() % 10u32.pow(width as u32), width = width),
+                _ => write!(out, "{:0width$}", date.year(), width = width),
+            },
+            Placeholder::ShortYear => write!(out, "{:02}", (date.year() % 100).abs()),
+            Placeholder::Month => write!(out, "{:0width$}", date.month(), width = width),
+            Placeholder::MonthName => out.write_str(self.month_name(date.month0() as usize)),
+            Placeholder::MonthAbbr => out.write_str(month_abbr),
+            Placeholder::MonthAbbrLower => out.write_str(&month_abbr.to_lowercase()),
+            Placeholder::Day => write!(out, "{:0width$}", date.day(), width = width),
+            Placeholder::DayOfYear => write!(out, "{:0width$}", date.ordinal(), width = width),
+            Placeholder::Weekday => out.write_str(weekday),
+            Placeholder::WeekdayCapitalized => out.write_str(&capitalize(weekday)),
+            Placeholder::WeekdayAbbr => out.write_str(&weekday[..3]),
+            Placeholder::WeekdayNumber => write!(out, "{}", date.weekday().number_from_monday()),
+            // 超出农历数据表范围时保留占位符原样
+            Placeholder::LunarYear => match lunar {
+                Some(lunar) => write!(out, "{}", lunar.year),
+                None => out.write_str("{lunar_year}"),
+            },
+            Placeholder::LunarMonth => match lunar {
+                Some(lunar) => out.write_str(&lunar.month_code()),
+                None => out.write_str("{lunar_month}"),
+            },
+            Placeholder::LunarDay => match lunar {
+                Some(lunar) => write!(out, "{:02}", lunar.day),
+                None => out.write_str("{lunar_day}"),
+            },
+            Placeholder::IsoWeek => write!(out, "{:02}", date.iso_week().week()),
+            Placeholder::IsoYear => write!(out, "{}", date.iso_week().year()),
+            Placeholder::Ext => out.write_str(context.ext.unwrap_or(&self.default_ext)),
+            // 既没有 URL 也没有 URL 模板时保留占位符原样
+            Placeholder::UrlHash => {
+                let url = match (context.url, &self.url_template) {
+                    (Some(url), _) => url.to_string(),
+                    (None, Some(template)) => template.format_url(date),
+                    (None, None) => return out.write_str("{urlhash}"),
+                };
+                out.write_str(&crate::fileops::sha256_bytes(url.as_bytes())[..width])
+            }
+            Placeholder::Index => write!(out, "{:0width$}", context.index.unwrap_or(1), width = width),
+        }
+    }
+
+    /// 格式化日期，结果中仍有未替换的占位符时返回错误
+    ///
+    /// 未知占位符在创建时即被拒绝，但超出范围的农历日期、缺少 URL 的 `{urlhash}` 等会保留原样；
+    /// 下载时使用此方法，避免生成带花括号的 URL 或文件名。
+    ///
+    /// # 示例
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use calendar::filename::{FilenameFormatter, FormatContext};
+    /// let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}_{urlhash}.jpg").unwrap();
+    /// let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+    /// assert!(formatter.try_format(&date, &FormatContext::default()).is_err());
+    /// let context = FormatContext { url: Some("https://example.com/a.jpg"), ..Default::default() };
+    /// assert!(formatter.try_format(&date, &context).is_ok());
+    /// ```
+    pub fn try_format(&self, date: &NaiveDate, context: &FormatContext) -> Result<String> {
+        self.ensure_resolved(date, self.format_with(date, context))
+    }
+
+    /// 格式化日期为 URL（与 [`format_url`](Self::format_url) 相同的编码），仍有未替换的占位符时返回错误
+    pub fn try_format_url(&self, date: &NaiveDate, context: &FormatContext) -> Result<String> {
+        self.ensure_resolved(date, self.render(date, context, true))
+    }
+
+    /// 检查格式化结果中没有未替换的占位符
+    fn ensure_resolved(&self, date: &NaiveDate, result: String) -> Result<String> {
+        let unresolved: Vec<&str> =
+            self.placeholder_regex.find_iter(&result).map(|m| m.as_str()).collect();
+        if unresolved.is_empty() {
+            return Ok(result);
+        }
+        Err(AppError::FilenameFormatError {
+            format: self.format.clone(),
+            details: format!(
+                "{} 的结果 {} 中有未替换的占位符: {}",
+                crate::date_utils::format_date(date),
+                result,
+                unresolved.join("、")
+            ),
+        })
+    }
+
+    /// 格式化日期为 URL
+    ///
+    /// 占位符的取值（如本地化的月份名称）按 URL 组件编码，`/`、`?`、`&`、`=` 等也会被编码；
+    /// 模板文本中的这些结构字符和已有的 `%XX` 保持不变，只编码空格、非 ASCII 和其他不安全的字符。
+    ///
+    /// # 示例
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use calendar::filename::FilenameFormatter;
+    /// let names: Vec<String> = (1..=12).map(|month| format!("{}月", month)).collect();
+    /// let formatter = FilenameFormatter::new("https://example.com/{month_name}/{dd} x.jpg?q=1")
+    ///     .unwrap()
+    ///     .with_month_names(&names)
+    ///     .unwrap();
+    /// let date = NaiveDate::from_ymd_opt(2024, 6, 15).unwrap();
+    /// assert_eq!(formatter.format_url(&date), "https://example.com/6%E6%9C%88/15%20x.jpg?q=1");
+    /// ```
+    pub fn format_url(&self, date: &NaiveDate) -> String {
+        self.render(date, &FormatContext::default(), true)
+    }
+
+    /// 获取格式字符串
+    pub fn format_str(&self) -> &str {
+        &self.format
+    }
+
+    /// 检查格式生成的是输出目录内的相对路径
+    ///
+    /// 用于文件名格式（URL 模板不需要）；拒绝绝对路径、`..` 路径段以及 Windows 盘符和 UNC 前缀。
+    /// 花括号语法的格式中出现 strftime 格式符（如 `%Y`）时同样报错，避免两种语法混用。
+    pub fn validate_path(&self) -> Result<()> {
+        if self.style == FormatStyle::Braces {
+            let strftime = Regex::new(r"%-?[YymdjBbhAuVGF]").map_err(AppError::RegexError)?;
+            if let Some(spec) = strftime.find(&self.format) {
+                return Err(AppError::FilenameFormatError {
+                    format: self.format.clone(),
+                    details: format!(
+                        "包含 strftime 格式符 {}，使用 strftime 语法时请设置 format_style = \"strftime\"",
+                        spec.as_str()
+                    ),
+                });
+            }
+        }
+        // 查找已下载的文件时按扩展名替换，`{ext}` 必须是文件扩展名
+        if self.format.contains("{ext}")
+            && (self.format.matches("{ext}").count() > 1 || !self.format.ends_with(".{ext}"))
+        {
+            return Err(AppError::FilenameFormatError {
+                format: self.format.clone(),
+                details: "`{ext}` 只能作为扩展名出现在格式末尾（如 `{yyyy}{mm}{dd}.{ext}`）".to_string(),
+            });
+        }
+        let sample = self.format(&NaiveDate::from_ymd_opt(2000, 1, 1).unwrap());
+        check_relative_path(&sample).map_err(|details| AppError::FilenameFormatError {
+            format: self.format.clone(),
+            details,
+        })?;
+
+        // 月份、星期名称等随日期变化，检查一整年（含闰日）的文件名
+        let mut date = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+        while date.year() == 2000 {
+            self.check_file_name(self.format(&date))?;
+            date = date.succ_opt().unwrap();
+        }
+        Ok(())
+    }
+}
+
+/// 检查文件名的各个路径段是否包含文件系统不允许的字符，返回错误原因
+///
+/// 按 Windows 的规则检查，保证同一配置在各平台上行为一致；`/` 和 `\` 按分隔符处理
+pub fn check_file_name_chars(name: &str) -> std::result::Result<(), String> {
+    for segment in name.split(['/', '\\']) {
+        if let Some(c) = segment.chars().find(|c| ILLEGAL_FILENAME_CHARS.contains(c) || c.is_control()) {
+            return Err(format!("文件名 {} 包含文件系统不允许的字符 {:?}", name, c));
+        }
+        // `.`、`..` 是路径段而不是文件名，由 `check_relative_path` 检查
+        if segment.ends_with(['.', ' ']) && segment != "." && segment != ".." {
+            return Err(format!("文件名 {} 中的 {:?} 不能以点号或空格结尾", name, segment));
+        }
+        if is_reserved_file_name(segment) {
+            return Err(format!("文件名 {} 中的 {:?} 是 Windows 保留的设备名", name, segment));
+        }
+    }
+    Ok(())
+}
+
+/// 将文件名中文件系统不允许的字符、结尾的点号和空格替换为 `_`，保留设备名后追加 `_`
+pub fn sanitize_file_name(name: &str) -> String {
+    let segments: Vec<String> = name
+        .split('/')
+        .map(|segment| {
+            let mut segment: String = segment
+                .chars()
+                .map(|c| if ILLEGAL_FILENAME_CHARS.contains(&c) || c.is_control() { '_' } else { c })
+                .collect();
+            let trimmed = segment.trim_end_matches(['.', ' ']).len();
+            if trimmed < segment.len() && segment != "." && segment != ".." {
+                let count = segment[trimmed..].chars().count();
+                segment.truncate(trimmed);
+                segment.extend(std::iter::repeat_n('_', count));
+            }
+            if is_reserved_file_name(&segment) {
+                let stem_len = segment.find('.').unwrap_or(segment.len());
+                segment.insert(stem_len, '_');
+            }
+            segment
+        })
+        .collect();
+    segments.join("/")
+}
+
+/// 是否为 Windows 保留的设备名（忽略大小写和扩展名）
+fn is_reserved_file_name(segment: &str) -> bool {
+    let stem = segment.split('.').next().unwrap_or(segment).trim_end();
+    RESERVED_FILENAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// 检查路径是否为不会离开所在目录的相对路径，返回错误原因
+///
+/// `/` 和 `\` 都按分隔符处理，盘符和 UNC 前缀在所有平台上都拒绝，保证同一配置在各平台上行为一致
+pub fn check_relative_path(path: &str) -> std::result::Result<(), String> {
+    let bytes = path.as_bytes();
+    if path.starts_with("\\\\") || path.starts_with("//") {
+        return Err(format!("不能使用 UNC 路径: {}", path));
+    }
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        return Err(format!("不能包含盘符: {}", path));
+    }
+    if path.starts_with(['/', '\\']) || std::path::Path::new(path).is_absolute() {
+        return Err(format!("不能是绝对路径: {}", path));
+    }
+    if path.split(['/', '\\']).any(|segment| segment == "..") {
+        return Err(format!("不能包含 `..` 路径段: {}", path));
+    }
+    Ok(())
+}
+
+/// 星期名称（全称或三个字母的缩写，不区分大小写）或序号对应的星期序号，周一为 1
+fn weekday_number(text: &str) -> Option<i32> {
+    if let Ok(number) = text.parse::<i32>() {
+        return (1..=7).contains(&number).then_some(number);
+    }
+    let text = text.to_lowercase();
+    let index = WEEKDAY_NAMES
+        .iter()
+        .position(|name| *name == text || (text.len() == 3 && name.starts_with(&text)))?;
+    Some(index as i32 + 1)
+}
+
+/// 首字母大写
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// 与 `name` 编辑距离最近（不区分大小写，最多相差 2 个字符）的候选名称
+fn closest_name<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(&name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// 两个字符串的 Levenshtein 编辑距离
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl TryFrom<&str> for FilenameFormatter {
+    type Error = AppError;
+
+    fn try_from(format: &str) -> Result<Self> {
+        Self::new(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_date(year: i32, month: u32, day: u32) -> NaiveDate {
+        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// 重构为片段之前逐个 `String::replace` 的实现，用于确认输出不变
+    fn replace_format(formatter: &FilenameFormatter, date: &NaiveDate) -> String {
+        let mut result = formatter.format.clone();
 
         // 处理常见的占位符格式
         // 必须按照特定顺序处理，避免部分替换
@@ -84,27 +1328,54 @@ impl FilenameFormatter {
         result = result.replace("{d}", &date.day().to_string());
         result = result.replace("{day}", &date.day().to_string());
 
+        // {doy} -> 三位年内第几天
+        result = result.replace("{doy}", &format!("{:03}", date.ordinal()));
+
+        // {weekday}、{Weekday}、{wd} -> 英文星期名称，{wd_num} -> 周一为 1 的星期序号
+        let weekday = WEEKDAY_NAMES[date.weekday().num_days_from_monday() as usize];
+        result = result.replace("{weekday}", weekday);
+        result = result.replace("{Weekday}", &capitalize(weekday));
+        result = result.replace("{wd}", &weekday[..3]);
+        result = result.replace("{wd_num}", &date.weekday().number_from_monday().to_string());
+
+        // {lunar_year}、{lunar_month}、{lunar_day} -> 农历日期，超出数据表范围时保留原样
+        if result.contains("{lunar_") {
+            if let Some(lunar) = LunarDate::from_solar(date) {
+                result = result.replace("{lunar_year}", &lunar.year.to_string());
+                result = result.replace("{lunar_month}", &lunar.month_code());
+                result = result.replace("{lunar_day}", &format!("{:02}", lunar.day));
+            }
+        }
+
+        // {ww} -> 两位 ISO 周数，{isoyear} -> ISO 周所属年份
+        let iso_week = date.iso_week();
+        result = result.replace("{ww}", &format!("{:02}", iso_week.week()));
+        result = result.replace("{isoyear}", &iso_week.year().to_string());
+
         // 处理带格式化修饰符的占位符 (如 {month:02}, {day:02})
-        result = self.format_variable_width_placeholders(&result, date);
+        result = replace_width_placeholders(&result, date);
 
-        result
-    }
+        // {month_name} -> 月份名称，{Mmm}/{mmm} -> 英文月份缩写
+        // 最后替换，本地化名称中的字符不会再被当作占位符处理
+        let month0 = date.month0() as usize;
+        let abbr = &MONTH_NAMES[month0][..3];
+        result = result.replace("{Mmm}", abbr);
+        result = result.replace("{mmm}", &abbr.to_lowercase());
+        result = result.replace("{month_name}", formatter.month_name(month0));
 
-    /// 格式化日期为 URL
-    ///
-    /// 与 `format` 类似，但针对 URL 使用场景进行优化
-    pub fn format_url(&self, date: &NaiveDate) -> String {
-        self.format(date)
+        // {ext} -> 文件扩展名
+        result = result.replace("{ext}", &formatter.default_ext);
+
+        result
     }
 
-    /// 处理带宽度修饰符的占位符
-    ///
-    /// 支持格式：{name:02}、{name:03} 等
-    fn format_variable_width_placeholders(&self, format_str: &str, date: &NaiveDate) -> String {
+    fn replace_width_placeholders(format_str: &str, date: &NaiveDate) -> String {
         let mut result = format_str.to_string();
 
         // 查找所有符合 {name:width} 模式的占位符
-        let captures = self.placeholder_regex.captures_iter(format_str);
+        static PLACEHOLDER_REGEX: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        let placeholder_regex = PLACEHOLDER_REGEX.get_or_init(|| Regex::new(r"\{([^}]+)\}").unwrap());
+        let captures = placeholder_regex.captures_iter(format_str);
 
         for cap in captures {
             let full_match = cap.get(0).unwrap().as_str();
@@ -121,6 +1392,7 @@ impl FilenameFormatter {
                         "year" => date.year().to_string(),
                         "month" => format!("{:0width$}", date.month(), width = width),
                         "day" => format!("{:0width$}", date.day(), width = width),
+                        "doy" => format!("{:0width$}", date.ordinal(), width = width),
                         _ => full_match.to_string(),
                     };
 
@@ -133,26 +1405,45 @@ impl FilenameFormatter {
         result
     }
 
-    /// 获取格式字符串
-    pub fn format_str(&self) -> &str {
-        &self.format
-    }
-}
-
-impl TryFrom<&str> for FilenameFormatter {
-    type Error = AppError;
-
-    fn try_from(format: &str) -> Result<Self> {
-        Self::new(format)
-    }
-}
+    #[test]
+    fn test_tokens_match_replace_implementation() {
+        let formats = [
+            "{yyyy}{mm}{dd}.jpg",
+            "{year}_{month}_{day}.png",
+            "{yy}{mm}{dd}.jpg",
+            "{year}_{month:02}_{day:02}.png",
+            "{year}/{month:3}/{day:1}-{doy:5}-{doy:1}",
+            "photo_{yyyy}-{m}-{d}.jpg",
+            "{yy}/{mm}/{dd}.jpg",
+            "{yyyy}{doy}_{ww}_{isoyear}.jpg",
+            "{yyyy}_{month_name}_{Mmm}_{mmm}_{dd}.jpg",
+            "{weekday}_{Weekday}_{wd}_{wd_num}.jpg",
+            "{lunar_year}{lunar_month}{lunar_day}.jpg",
+            "{yyyy}{mm}{dd}.{ext}",
+            "img(1).{yyyy}{mm}{dd}+[x] {} {.jpg",
+            "{d}{dd}{m}{mm}{day}{month}{yy}{yyyy}",
+        ];
+        let names: Vec<String> = ["一月", "二月", "三月", "四月", "五月", "六月", "七月", "八月", "九月", "十月", "十一月", "十二月"]
+            .map(String::from)
+            .to_vec();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let start = test_date(1989, 12, 20);
+        let dates = start
+            .iter_days()
+            .take(450)
+            .chain(test_date(2019, 12, 25).iter_days().take(450))
+            .chain([test_date(2101, 3, 1), test_date(1, 1, 1), test_date(9999, 12, 31)]);
+        let dates: Vec<NaiveDate> = dates.collect();
 
-    fn test_date(year: i32, month: u32, day: u32) -> NaiveDate {
-        chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+        for format in formats {
+            let plain = FilenameFormatter::new(format).unwrap();
+            let localized = plain.clone().with_month_names(&names).unwrap().with_default_ext("png");
+            for formatter in [&plain, &localized] {
+                for date in &dates {
+                    assert_eq!(formatter.format(date), replace_format(formatter, date), "{} {}", format, date);
+                }
+            }
+        }
     }
 
     #[test]
@@ -195,10 +1486,226 @@ mod tests {
 
     #[test]
     fn test_without_zero_padding() {
-        let formatter = FilenameFormatter::new("{y}{m}{d}.jpg").unwrap();
+        let formatter = FilenameFormatter::new("{yyyy}{m}{d}.jpg").unwrap();
         let date = test_date(2024, 12, 31);
-        // {y} 会保持 {y} 不变，因为没有这个占位符定义
-        assert_eq!(formatter.format(&date), "{y}1231.jpg");
+        assert_eq!(formatter.format(&date), "20241231.jpg");
+    }
+
+    #[test]
+    fn test_iso_week_placeholders() {
+        let formatter = FilenameFormatter::new("{isoyear}/W{ww}/{yyyy}{mm}{dd}.jpg").unwrap();
+        for (date, expected) in [
+            // 2021-01-01（周五）属于 2020 年第 53 周
+            (test_date(2021, 1, 1), "2020/W53/20210101.jpg"),
+            (test_date(2021, 1, 3), "2020/W53/20210103.jpg"),
+            (test_date(2021, 1, 4), "2021/W01/20210104.jpg"),
+            // 2024-12-30（周一）属于 2025 年第 1 周
+            (test_date(2024, 12, 29), "2024/W52/20241229.jpg"),
+            (test_date(2024, 12, 30), "2025/W01/20241230.jpg"),
+            (test_date(2024, 6, 10), "2024/W24/20240610.jpg"),
+        ] {
+            assert_eq!(formatter.format(&date), expected);
+            assert_eq!(formatter.parse(expected), Some(date));
+        }
+        // 周数或 ISO 年份与日期不一致时不是该格式生成的名称
+        assert_eq!(formatter.parse("2021/W53/20210101.jpg"), None);
+        assert_eq!(formatter.parse("2020/W52/20210101.jpg"), None);
+
+        let url = FilenameFormatter::new("https://example.com/{isoyear}/W{ww}/{day:02}.jpg").unwrap();
+        assert_eq!(url.format_url(&test_date(2021, 1, 1)), "https://example.com/2020/W53/01.jpg");
+        assert!(details_of("W{wk}.jpg").contains("是否应为 `{ww}`"));
+    }
+
+    #[test]
+    fn test_day_of_year_placeholder() {
+        let formatter = FilenameFormatter::new("{yyyy}/{doy}.jpg").unwrap();
+        for (date, expected) in [
+            (test_date(2024, 1, 1), "2024/001.jpg"),
+            (test_date(2024, 2, 29), "2024/060.jpg"),
+            // 闰年 3 月 1 日起比平年多一天
+            (test_date(2024, 3, 1), "2024/061.jpg"),
+            (test_date(2023, 3, 1), "2023/060.jpg"),
+            (test_date(2024, 6, 15), "2024/167.jpg"),
+            (test_date(2023, 12, 31), "2023/365.jpg"),
+            (test_date(2024, 12, 31), "2024/366.jpg"),
+        ] {
+            assert_eq!(formatter.format(&date), expected);
+            assert_eq!(formatter.parse(expected), Some(date));
+        }
+        assert_eq!(formatter.parse("2023/366.jpg"), None);
+        assert_eq!(formatter.parse("2024/000.jpg"), None);
+
+        let url = FilenameFormatter::new("https://example.com/{year}/{doy:4}_{doy:1}.jpg").unwrap();
+        assert_eq!(url.format_url(&test_date(2024, 1, 5)), "https://example.com/2024/0005_5.jpg");
+
+        // 同时包含月、日时年内第几天必须一致
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}_{doy}.jpg").unwrap();
+        assert_eq!(formatter.parse("20240615_167.jpg"), Some(test_date(2024, 6, 15)));
+        assert_eq!(formatter.parse("20240615_168.jpg"), None);
+    }
+
+    #[test]
+    fn test_weekday_placeholders() {
+        let formatter =
+            FilenameFormatter::new("images/{weekday}/{yyyy}-{mm}-{dd}_{Weekday}_{wd}_{wd_num}.jpg")
+                .unwrap();
+        let monday = test_date(2024, 6, 17);
+        let name = formatter.format(&monday);
+        assert_eq!(name, "images/monday/2024-06-17_Monday_mon_1.jpg");
+        assert_eq!(formatter.parse(&name), Some(monday));
+
+        let sunday = test_date(2024, 6, 23);
+        let name = formatter.format(&sunday);
+        assert_eq!(name, "images/sunday/2024-06-23_Sunday_sun_7.jpg");
+        assert_eq!(formatter.parse(&name), Some(sunday));
+
+        // 星期与日期不一致时不是该格式生成的名称
+        assert_eq!(
+            formatter.parse("images/tuesday/2024-06-17_Monday_mon_1.jpg"),
+            None
+        );
+        let url = FilenameFormatter::new("https://example.com/images/{weekday}/{year}-{month:02}-{day:02}.jpg")
+            .unwrap();
+        assert_eq!(
+            url.format_url(&test_date(2024, 6, 19)),
+            "https://example.com/images/wednesday/2024-06-19.jpg"
+        );
+        assert!(details_of("{wekday}.jpg").contains("是否应为 `{weekday}`"));
+    }
+
+    #[test]
+    fn test_month_name_placeholders() {
+        let formatter = FilenameFormatter::new("{yyyy}/{month_name}/{Mmm}_{mmm}_{m}_{dd}.jpg").unwrap();
+        let expected = [
+            "January/Jan_jan_1", "February/Feb_feb_2", "March/Mar_mar_3", "April/Apr_apr_4",
+            "May/May_may_5", "June/Jun_jun_6", "July/Jul_jul_7", "August/Aug_aug_8",
+            "September/Sep_sep_9", "October/Oct_oct_10", "November/Nov_nov_11",
+            "December/Dec_dec_12",
+        ];
+        for (month, expected) in (1..=12).zip(expected) {
+            let date = test_date(2024, month, 15);
+            let name = formatter.format(&date);
+            assert_eq!(name, format!("2024/{}_15.jpg", expected));
+            assert_eq!(formatter.parse(&name), Some(date));
+        }
+        // 月份名称与数字月份不一致时不是该格式生成的名称
+        assert_eq!(formatter.parse("2024/June/Jul_jul_7_15.jpg"), None);
+
+        let url = FilenameFormatter::new("https://example.com/{year}/{month_name}/{day:02}.jpg").unwrap();
+        assert_eq!(url.format_url(&test_date(2024, 6, 15)), "https://example.com/2024/June/15.jpg");
+    }
+
+    #[test]
+    fn test_localized_month_names() {
+        let names: Vec<String> = (1..=12).map(|month| format!("{}月", month)).collect();
+        let formatter = FilenameFormatter::new("{yyyy}/{month_name}/{dd}_{Mmm}.jpg")
+            .unwrap()
+            .with_month_names(&names)
+            .unwrap();
+        for month in 1..=12 {
+            let date = test_date(2024, month, 5);
+            let name = formatter.format(&date);
+            assert_eq!(name, format!("2024/{}月/05_{}.jpg", month, &MONTH_NAMES[month as usize - 1][..3]));
+            assert_eq!(formatter.parse(&name), Some(date));
+        }
+        // 11月 与 1月 共享后缀，匹配时不会混淆
+        assert_eq!(formatter.parse("2024/11月/05_Nov.jpg"), Some(test_date(2024, 11, 5)));
+        assert_eq!(formatter.parse("2024/June/05_Jun.jpg"), None);
+
+        let url = formatter.with_format("https://example.com/{month_name}/{dd}.jpg").unwrap();
+        assert_eq!(url.format_url(&test_date(2024, 6, 15)), "https://example.com/6%E6%9C%88/15.jpg");
+
+        let err = FilenameFormatter::new("{month_name}.jpg").unwrap().with_month_names(&names[..11]);
+        assert!(matches!(err, Err(AppError::FilenameFormatError { .. })));
+    }
+
+    #[test]
+    fn test_lunar_placeholders() {
+        let formatter =
+            FilenameFormatter::new("{lunar_year}/{lunar_month}/{yyyy}{mm}{dd}_{lunar_day}.jpg").unwrap();
+        for (date, expected) in [
+            // 2024 年春节为正月初一，前一天仍属农历 2023 年腊月
+            (test_date(2024, 2, 10), "2024/01/20240210_01.jpg"),
+            (test_date(2024, 2, 9), "2023/12/20240209_30.jpg"),
+            // 2020 年闰四月
+            (test_date(2020, 5, 23), "2020/r04/20200523_01.jpg"),
+            (test_date(2020, 4, 23), "2020/04/20200423_01.jpg"),
+            (test_date(2024, 9, 17), "2024/08/20240917_15.jpg"),
+        ] {
+            assert_eq!(formatter.format(&date), expected);
+            assert_eq!(formatter.parse(expected), Some(date));
+        }
+        assert_eq!(formatter.parse("2020/04/20200523_01.jpg"), None);
+
+        // 超出农历数据表范围时保留占位符
+        assert_eq!(
+            formatter.format(&test_date(1980, 1, 1)),
+            "{lunar_year}/{lunar_month}/19800101_{lunar_day}.jpg"
+        );
+    }
+
+    fn details_of(format: &str) -> String {
+        match FilenameFormatter::new(format) {
+            Err(AppError::FilenameFormatError { details, .. }) => details,
+            other => panic!("{} 应被拒绝: {:?}", format, other.map(|f| f.format_str().to_string())),
+        }
+    }
+
+    #[test]
+    fn test_case_modifiers() {
+        let formatter =
+            FilenameFormatter::new("{wd:upper}/{yyyy}-{mm}-{dd}_{weekday:title}_{month_name:lower}_{mmm:upper}.jpg")
+                .unwrap();
+        let monday = test_date(2024, 6, 17);
+        let name = formatter.format(&monday);
+        assert_eq!(name, "MON/2024-06-17_Monday_june_JUN.jpg");
+        assert_eq!(formatter.parse(&name), Some(monday));
+        assert_eq!(formatter.parse("mon/2024-06-17_Monday_june_JUN.jpg"), None);
+        assert_eq!(formatter.parse("MON/2024-06-17_Monday_June_JUN.jpg"), None);
+
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}_{month_name:upper}.jpg")
+            .unwrap()
+            .with_month_names(&MONTH_NAMES.map(|name| format!("{}é", name)))
+            .unwrap();
+        let name = formatter.format(&monday);
+        assert_eq!(name, "20240617_JUNEÉ.jpg");
+        assert_eq!(formatter.parse(&name), Some(monday));
+
+        let url = FilenameFormatter::new("https://example.com/{Weekday:upper}/{yyyy}{mm}{dd}.jpg").unwrap();
+        assert_eq!(url.format_url(&monday), "https://example.com/MONDAY/20240617.jpg");
+
+        let details = details_of;
+        assert!(details("{wd:uper}.jpg").contains("未知修饰符 `uper`，是否应为 `{wd:upper}`"));
+        assert!(details("{weekday:reverse}.jpg").contains("支持的修饰符: `upper`、`lower`、`title`"));
+        assert!(details("{mm:upper}.jpg").contains("`{mm}` 不是文本占位符，不能使用大小写修饰符 `upper`"));
+        assert!(details("{wd_num:lower}.jpg").contains("不是文本占位符"));
+    }
+
+    #[test]
+    fn test_unknown_placeholder() {
+        let details = details_of;
+        assert!(details("{yyy}{mm}{dd}.jpg").contains("`{yyy}`，是否应为 `{yyyy}`"));
+        assert!(details("{y}{m}{d}.jpg").contains("`{y}`，是否应为 `{yy}`"));
+        assert!(details("{YYYY}{mm}{dd}.jpg").contains("是否应为 `{yyyy}`"));
+        assert!(details("{year}{mnth:02}{dd}.jpg").contains("是否应为 `{month:02}`"));
+        assert!(details("{year}{month:xx}{dd}.jpg").contains("是否应为 `{month:02}`"));
+        assert!(details("{d:02}.jpg").contains("是否应为 `{dd:02}`"));
+        let details = details("{timestamp}.jpg");
+        assert!(details.contains("未知占位符 `{timestamp}`"));
+        assert!(details.contains("{yyyy}、{year}"));
+
+        for format in ["{year:4}{month:1}{day:03}.jpg", "https://example.com/{year}/{month:02}/{day:02}.jpg"] {
+            assert!(FilenameFormatter::new(format).is_ok(), "{}", format);
+        }
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("yyy", "yyyy"), 1);
+        assert_eq!(edit_distance("mnth", "month"), 1);
+        assert_eq!(edit_distance("", "day"), 3);
+        assert_eq!(edit_distance("day", "day"), 0);
+        assert_eq!(closest_name("timestamp", &PLACEHOLDER_NAMES), None);
     }
 
     #[test]
@@ -226,6 +1733,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_url_percent_encoding() {
+        use percent_encoding::percent_decode_str;
+        use reqwest::Url;
+
+        let names: Vec<String> =
+            ["一月", "二月", "三月", "四月", "五月", "六 月", "七月", "八月", "九月", "十月", "十一月", "十二月"]
+                .map(String::from)
+                .to_vec();
+        let formatter = FilenameFormatter::new(
+            "https://example.com/图片 库/{month_name}/{dd} 日.jpg?size=large&tag={Weekday}&m={month_name}",
+        )
+        .unwrap()
+        .with_month_names(&names)
+        .unwrap();
+        let url = formatter.format_url(&test_date(2024, 6, 15));
+        assert!(url.is_ascii(), "{}", url);
+
+        // reqwest 解析后不再改写，路径段和查询参数解码后与原值一致
+        let parsed = Url::parse(&url).unwrap();
+        assert_eq!(parsed.as_str(), url);
+        let segments: Vec<String> = parsed
+            .path_segments()
+            .unwrap()
+            .map(|segment| percent_decode_str(segment).decode_utf8().unwrap().into_owned())
+            .collect();
+        assert_eq!(segments, ["图片 库", "六 月", "15 日.jpg"]);
+        let query: Vec<(String, String)> = parsed.query_pairs().into_owned().collect();
+        assert_eq!(
+            query,
+            [("size", "large"), ("tag", "Saturday"), ("m", "六 月")].map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+
+        // 取值中的结构字符被编码，模板中已有的 `%XX` 保持不变
+        let names: Vec<String> = (1..=12).map(|month| format!("{}&m=?#", month)).collect();
+        let formatter = FilenameFormatter::new("https://example.com/a%20b/{month_name}.jpg")
+            .unwrap()
+            .with_month_names(&names)
+            .unwrap();
+        assert_eq!(
+            formatter.format_url(&test_date(2024, 6, 15)),
+            "https://example.com/a%20b/6%26m%3D%3F%23.jpg"
+        );
+
+        // 文件名不编码
+        assert_eq!(formatter.format(&test_date(2024, 6, 15)), "https://example.com/a%20b/6&m=?#.jpg");
+
+        // 未替换的占位符保留原样并报错
+        let formatter = FilenameFormatter::new("https://example.com/{lunar_month}/{dd}.jpg").unwrap();
+        assert_eq!(formatter.format_url(&test_date(1989, 6, 1)), "https://example.com/{lunar_month}/01.jpg");
+        assert!(formatter.try_format_url(&test_date(1989, 6, 1), &FormatContext::default()).is_err());
+    }
+
     #[test]
     fn test_empty_format_string() {
         let result = FilenameFormatter::new("");
@@ -265,4 +1825,321 @@ mod tests {
         let date = test_date(2024, 6, 5);
         assert_eq!(formatter.format(&date), "005.jpg");
     }
+
+    #[test]
+    fn test_year_width() {
+        let format = |format: &str, date: NaiveDate| FilenameFormatter::new(format).unwrap().format(&date);
+        let date = test_date(2024, 6, 5);
+        assert_eq!(format("{year:04}", date), "2024");
+        assert_eq!(format("{year:6}", date), "002024");
+        assert_eq!(format("{year:2}", date), format("{yy}", date));
+        assert_eq!(format("{year:3}", date), "024");
+        assert_eq!(format("{year:1}", date), "4");
+        assert_eq!(format("{year:0}", date), "2024");
+
+        // 公元 1 世纪和公元前的年份
+        let ancient = test_date(5, 3, 1);
+        assert_eq!(format("{year}", ancient), "5");
+        assert_eq!(format("{year:04}", ancient), "0005");
+        assert_eq!(format("{year:2}", ancient), "05");
+        let bc = test_date(-44, 3, 15);
+        assert_eq!(format("{year:04}", bc), "-044");
+        assert_eq!(format("{year:2}", bc), format("{yy}", bc));
+        assert_eq!(format("{year:3}", bc), "044");
+
+        // 截断的年份按 2000 年之后解析
+        let formatter = FilenameFormatter::new("{year:2}{mm}{dd}.jpg").unwrap();
+        assert_eq!(formatter.parse("240605.jpg"), Some(date));
+        let formatter = FilenameFormatter::new("{year:6}{mm}{dd}.jpg").unwrap();
+        assert_eq!(formatter.parse("0020240605.jpg"), Some(date));
+        assert_eq!(formatter.parse("20240605.jpg"), None);
+    }
+
+    #[test]
+    fn test_short_placeholder_width() {
+        let formatter = FilenameFormatter::new("{yyyy}-{mm:3}-{dd:3}.jpg").unwrap();
+        let date = test_date(2024, 6, 5);
+        assert_eq!(formatter.format(&date), "2024-006-005.jpg");
+        assert_eq!(formatter.parse("2024-006-005.jpg"), Some(date));
+        assert_eq!(formatter.parse("2024-06-05.jpg"), None);
+        assert_eq!(FilenameFormatter::new("{mm:1}{dd:1}").unwrap().format(&date), "65");
+    }
+
+    #[test]
+    fn test_validate_path_rejects_hostile_formats() {
+        for format in [
+            "../../etc/{yyyy}.jpg",
+            "{yyyy}/../../{mm}{dd}.jpg",
+            "..",
+            "/etc/{yyyy}{mm}{dd}.jpg",
+            "\\\\server\\share\\{yyyy}.jpg",
+            "//server/share/{yyyy}.jpg",
+            "C:\\Windows\\{yyyy}.jpg",
+            "c:{yyyy}.jpg",
+            "{yyyy}\\..\\..\\{dd}.jpg",
+        ] {
+            let formatter = FilenameFormatter::new(format).unwrap();
+            let err = formatter.validate_path().unwrap_err();
+            assert!(
+                matches!(err, AppError::FilenameFormatError { .. }),
+                "{} 应被拒绝",
+                format
+            );
+        }
+
+        for format in ["{yyyy}{mm}{dd}.jpg", "{mm}/{yyyy}{mm}{dd}.jpg", "..{yyyy}..jpg", "./{yyyy}.jpg"] {
+            FilenameFormatter::new(format).unwrap().validate_path().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let formats = [
+            "{yyyy}{mm}{dd}.jpg",
+            "{year}_{month}_{day}.png",
+            "{yy}{mm}{dd}.jpg",
+            "{year}_{month:02}_{day:02}.png",
+            "photo_{yyyy}-{m}-{d}.jpg",
+            "{yy}/{mm}/{dd}.jpg",
+            "{year:04}-{doy}.jpg",
+            "{yyyy}_{doy:1}.jpg",
+            "{yyyy}_{month_name}_{dd}.jpg",
+            "{dd}{Mmm}{yyyy}.jpg",
+            "{yyyy}-{mmm}-{d}.jpg",
+            "{isoyear}-W{ww}/{yyyy}{mm}{dd}.jpg",
+            "{yyyy}{mm}{dd}_{weekday}_{Weekday}_{wd}_{wd_num}.jpg",
+            "{yyyy}{mm}{dd}_{lunar_year}{lunar_month}{lunar_day}.jpg",
+        ];
+        let dates = [test_date(2024, 1, 5), test_date(2024, 12, 31), test_date(2001, 6, 15)];
+
+        for format in formats {
+            let formatter = FilenameFormatter::new(format).unwrap();
+            formatter.ensure_parseable().unwrap();
+            for date in dates {
+                let name = formatter.format(&date);
+                assert_eq!(formatter.parse(&name), Some(date), "{} -> {}", format, name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_non_matching_names() {
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        assert_eq!(formatter.parse("20240615.jpg.part"), None);
+        assert_eq!(formatter.parse("notes.txt"), None);
+        assert_eq!(formatter.parse("20241315.jpg"), None);
+        assert_eq!(formatter.parse("2024061.jpg"), None);
+    }
+
+    #[test]
+    fn test_parse_requires_full_date() {
+        let formatter = FilenameFormatter::new("{mm}{dd}.jpg").unwrap();
+        assert_eq!(formatter.parse("0615.jpg"), None);
+    }
+
+    #[test]
+    fn test_ext_placeholder() {
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.{ext}").unwrap();
+        let date = test_date(2024, 6, 15);
+        assert_eq!(formatter.format(&date), "20240615.jpg");
+        let context = FormatContext { ext: Some("png"), ..Default::default() };
+        assert_eq!(formatter.format_with(&date, &context), "20240615.png");
+        assert_eq!(formatter.default_ext(), Some("jpg"));
+        assert_eq!(formatter.parse("20240615.webp"), Some(date));
+        formatter.validate_path().unwrap();
+
+        let formatter = formatter.with_default_ext("webp");
+        assert_eq!(formatter.format(&date), "20240615.webp");
+        assert_eq!(FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap().default_ext(), None);
+
+        // {ext} 只能作为末尾的扩展名
+        for format in ["{ext}/{yyyy}{mm}{dd}.jpg", "{yyyy}{mm}{dd}.{ext}.bak", "{yyyy}{mm}{dd}_{ext}"] {
+            assert!(FilenameFormatter::new(format).unwrap().validate_path().is_err(), "{}", format);
+        }
+    }
+
+    #[test]
+    fn test_urlhash_placeholder() {
+        let date = test_date(2024, 6, 15);
+        let url = "https://example.com/2024/06/15.jpg";
+        let hash = crate::fileops::sha256_bytes(url.as_bytes());
+        let context = FormatContext { url: Some(url), ..Default::default() };
+
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}_{urlhash}.jpg").unwrap();
+        assert_eq!(formatter.format_with(&date, &context), format!("20240615_{}.jpg", &hash[..8]));
+        // 没有 URL 也没有 URL 模板时保留原样
+        assert_eq!(formatter.format(&date), "20240615_{urlhash}.jpg");
+
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}_{urlhash:12}.jpg").unwrap();
+        let name = formatter.format_with(&date, &context);
+        assert_eq!(name, format!("20240615_{}.jpg", &hash[..12]));
+        assert_eq!(formatter.parse(&name), Some(date));
+        assert_eq!(formatter.parse("20240615_abc.jpg"), None);
+
+        // 按 URL 模板生成该日期的 URL 后计算
+        let template = FilenameFormatter::new("https://example.com/{yyyy}/{mm}/{dd}.jpg").unwrap();
+        let formatter = formatter.with_url_template(template.clone()).unwrap();
+        assert_eq!(formatter.format(&date), name);
+
+        // URL 模板不能包含 {urlhash}
+        let err = template.with_url_template(formatter).unwrap_err().to_string();
+        assert!(err.contains("不能用于 URL 模板"), "{}", err);
+
+        for format in ["{urlhash:0}.jpg", "{urlhash:65}.jpg", "{urlhash:x}.jpg"] {
+            let err = FilenameFormatter::new(format).unwrap_err().to_string();
+            assert!(err.contains("是否应为 `{urlhash:8}`"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_try_format_rejects_unresolved_placeholders() {
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}_{lunar_month}{lunar_day}.jpg").unwrap();
+        let context = FormatContext::default();
+        assert_eq!(
+            formatter.try_format(&test_date(2024, 2, 10), &context).unwrap(),
+            "20240210_0101.jpg"
+        );
+
+        // 超出农历数据表范围
+        let err = formatter.try_format(&test_date(1989, 6, 1), &context).unwrap_err();
+        assert!(matches!(err, AppError::FilenameFormatError { .. }));
+        let err = err.to_string();
+        assert!(err.contains("1989-06-01") && err.contains("{lunar_month}、{lunar_day}"), "{}", err);
+        // 宽松的 format 保留原样
+        assert_eq!(formatter.format(&test_date(1989, 6, 1)), "19890601_{lunar_month}{lunar_day}.jpg");
+    }
+
+    #[test]
+    fn test_check_file_name_chars() {
+        assert!(check_file_name_chars("2024/20240615.jpg").is_ok());
+        assert!(check_file_name_chars("2024/六月/15.jpg").is_ok());
+        assert!(check_file_name_chars("console.jpg").is_ok());
+
+        // 不允许的字符和控制字符
+        for c in ILLEGAL_FILENAME_CHARS.into_iter().chain(['\0', '\t', '\u{1f}']) {
+            let name = format!("2024{}06.jpg", c);
+            let err = check_file_name_chars(&name).unwrap_err();
+            assert!(err.contains(&format!("{:?}", c)), "{}", err);
+            assert_eq!(sanitize_file_name(&name), "2024_06.jpg");
+        }
+
+        // 以点号或空格结尾的路径段
+        for name in ["2024./15.jpg", "2024 /15.jpg", "20240615.jpg.", "20240615 .."] {
+            let err = check_file_name_chars(name).unwrap_err();
+            assert!(err.contains("不能以点号或空格结尾"), "{}", err);
+        }
+        assert_eq!(sanitize_file_name("2024./15.jpg"), "2024_/15.jpg");
+        assert_eq!(sanitize_file_name("20240615 .."), "20240615___");
+        assert!(check_file_name_chars("./2024/../20240615.jpg").is_ok());
+        assert_eq!(sanitize_file_name("./20240615.jpg"), "./20240615.jpg");
+
+        // 保留设备名，忽略大小写和扩展名
+        for name in ["CON", "nul.jpg", "2024/Com1.tar.gz", "lpt9 .jpg"] {
+            let err = check_file_name_chars(name).unwrap_err();
+            assert!(err.contains("保留的设备名"), "{}", err);
+        }
+        assert_eq!(sanitize_file_name("nul.jpg"), "nul_.jpg");
+        assert_eq!(sanitize_file_name("2024/AUX"), "2024/AUX_");
+        assert!(check_file_name_chars(&sanitize_file_name("2024/Com1.tar.gz")).is_ok());
+    }
+
+    #[test]
+    fn test_validate_path_rejects_illegal_month_names() {
+        let names: Vec<String> =
+            (1..=12).map(|m| if m == 9 { "Sep?".to_string() } else { format!("M{}", m) }).collect();
+        let formatter =
+            FilenameFormatter::new("{yyyy}/{month_name}/{dd}.jpg").unwrap().with_month_names(&names).unwrap();
+        let err = formatter.validate_path().unwrap_err().to_string();
+        assert!(err.contains("'?'"), "{}", err);
+        assert!(formatter.with_sanitize(true).validate_path().is_ok());
+    }
+
+    #[test]
+    fn test_index_placeholder() {
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}_{n}.jpg").unwrap();
+        let date = test_date(2024, 6, 15);
+        assert!(formatter.uses_index());
+        assert!(!FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap().uses_index());
+        assert!(!formatter.is_literal());
+        assert!(FilenameFormatter::new("failed_downloads.txt").unwrap().is_literal());
+
+        // 下载以外的场景取 1
+        assert_eq!(formatter.format(&date), "20240615_1.jpg");
+        let context = FormatContext { index: Some(12), ..Default::default() };
+        assert_eq!(formatter.format_with(&date, &context), "20240615_12.jpg");
+        let padded = FilenameFormatter::new("{yyyy}-{mm}-{dd}_{n:02}.jpg").unwrap();
+        assert_eq!(padded.format_with(&date, &context), "2024-06-15_12.jpg");
+        assert_eq!(padded.format(&date), "2024-06-15_01.jpg");
+
+        // 序号不影响反向解析出的日期
+        assert_eq!(formatter.parse("20240615_1.jpg"), Some(date));
+        assert_eq!(formatter.parse("20240615_12.jpg"), Some(date));
+        assert_eq!(formatter.parse("20240615_.jpg"), None);
+        assert!(formatter.ensure_parseable().is_ok());
+    }
+
+    #[test]
+    fn test_strftime_style() {
+        let strftime = |format: &str| FilenameFormatter::with_style(format, FormatStyle::Strftime).unwrap();
+        let braces = FilenameFormatter::new("{yyyy}/{mm}/{dd}.jpg").unwrap();
+        let formatter = strftime("%Y/%m/%d.jpg");
+
+        // 与花括号语法和 chrono 的 strftime 输出一致，且可以反向解析
+        let formats = [
+            "%Y%m%d.jpg", "%y-%-m-%-d", "%j_%-j", "%B %b %h", "%A_%u", "%G-W%V", "%F", "100%%_%Y",
+        ];
+        let mut date = test_date(2020, 12, 25);
+        while date <= test_date(2021, 1, 10) {
+            assert_eq!(formatter.format(&date), braces.format(&date));
+            assert_eq!(formatter.parse(&formatter.format(&date)), Some(date));
+            for format in formats {
+                assert_eq!(strftime(format).format(&date), date.format(format).to_string(), "{}", format);
+            }
+            date = date.succ_opt().unwrap();
+        }
+        assert!(strftime("%F.jpg").ensure_parseable().is_ok());
+        assert!(strftime("%Y-%m.jpg").ensure_parseable().is_err());
+
+        // 不能混用两种语法
+        let err = FilenameFormatter::with_style("%Y/{mm}/%d.jpg", FormatStyle::Strftime).unwrap_err();
+        assert!(err.to_string().contains("不能混用"), "{}", err);
+        assert!(FilenameFormatter::new("{yyyy}/%m/{dd}.jpg").unwrap().validate_path().is_err());
+        assert!(FilenameFormatter::new("{yyyy}{mm}{dd}_100%.jpg").unwrap().validate_path().is_ok());
+
+        // 不支持的格式符（包括时间）和末尾单独的 `%`
+        for format in ["%Y%m%d_%H.jpg", "%Y%m%d%", "%Y%m%d%-"] {
+            let err = FilenameFormatter::with_style(format, FormatStyle::Strftime).unwrap_err();
+            assert!(err.to_string().contains("不支持的 strftime 格式符"), "{}", err);
+        }
+
+        // 派生的格式化器沿用相同语法
+        assert_eq!(formatter.with_format("%Y").unwrap().format(&test_date(2024, 6, 15)), "2024");
+    }
+
+    #[test]
+    fn test_ensure_parseable() {
+        let missing = |format: &str| match FilenameFormatter::new(format).unwrap().ensure_parseable() {
+            Ok(()) => Vec::new(),
+            Err(AppError::AmbiguousFilenameFormat { missing, .. }) => missing,
+            Err(e) => panic!("unexpected error: {}", e),
+        };
+
+        assert!(missing("{yy}{Mmm}{d}.jpg").is_empty());
+        assert!(missing("{yyyy}{doy}.jpg").is_empty());
+        assert_eq!(missing("{mm}{dd}.jpg"), ["year"]);
+        assert_eq!(missing("{yyyy}-{mm}.jpg"), ["day"]);
+        assert_eq!(missing("{yyyy}-{dd}.jpg"), ["month"]);
+        assert_eq!(missing("{isoyear}-W{ww}-{wd_num}.jpg"), ["year", "month", "day"]);
+        assert_eq!(missing("wallpaper.jpg"), ["year", "month", "day"]);
+    }
+
+    #[test]
+    fn test_parse_literal_special_characters() {
+        let formatter = FilenameFormatter::new("img(1).{yyyy}{mm}{dd}+[x].jpg").unwrap();
+        assert_eq!(
+            formatter.parse("img(1).20240615+[x].jpg"),
+            Some(test_date(2024, 6, 15))
+        );
+        assert_eq!(formatter.parse("img1x20240615+[x].jpg"), None);
+    }
 }
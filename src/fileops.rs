@@ -3,84 +3,204 @@
 //! 使用 `filetime` 库实现跨平台的文件时间戳设置功能。
 //! 支持修改文件的创建时间和最后修改时间。
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::error::{AppError, Result};
+use crate::filename::FilenameFormatter;
 
-/// 设置文件的时间戳（创建时间和修改时间）
+/// Windows 传统路径长度上限：MAX_PATH (260) 减去创建目录时为 8.3 文件名预留的 12 个字符
+#[cfg(any(target_os = "windows", test))]
+const WINDOWS_MAX_PATH: usize = 248;
+
+/// 返回可用于文件系统调用的路径
+///
+/// Windows 上路径超过传统长度上限时转换为 `\\?\` 扩展长度形式（先解析为规范化的绝对路径），
+/// 避免深层输出目录写入失败（OS error 3）；较短的路径和其他平台原样返回。
+#[cfg(target_os = "windows")]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    if path.as_os_str().len() < WINDOWS_MAX_PATH {
+        return Cow::Borrowed(path);
+    }
+    let extended = std::path::absolute(path)
+        .ok()
+        .and_then(|absolute| extended_length_path(absolute.to_str()?));
+    match extended {
+        Some(extended) => Cow::Owned(PathBuf::from(extended)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// 返回可用于文件系统调用的路径（非 Windows 平台原样返回）
+#[cfg(not(target_os = "windows"))]
+#[inline]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// 将规范化的 Windows 绝对路径转换为 `\\?\` 扩展长度形式
+///
+/// 盘符路径加 `\\?\` 前缀，UNC 路径 `\\server\share` 转为 `\\?\UNC\server\share`，
+/// 已是设备路径时原样返回；`/` 统一替换为 `\`。不是绝对路径时返回 None。
+#[cfg(any(target_os = "windows", test))]
+fn extended_length_path(path: &str) -> Option<String> {
+    let path = path.replace('/', "\\");
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return Some(path);
+    }
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{}", unc));
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        return Some(format!(r"\\?\{}", path));
+    }
+    None
+}
+
+/// 在扩展名前追加 `-n` 的路径，如 `2024/20240615.jpg` -> `2024/20240615-1.jpg`
+pub fn collision_suffix_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+/// 根据修改时间或创建时间判断已有文件属于哪个日期
+///
+/// 写入元数据时修改时间（`mtime_source = "download"` 时为创建时间）设为图片日期的 UTC 零点；
+/// 两者都不是零点（如 `--download-only` 下载的文件）或文件不存在时返回 None
+pub fn stamped_date(path: &Path) -> Option<NaiveDate> {
+    let midnight = |time: Result<Option<DateTime<Utc>>>| {
+        time.ok()
+            .flatten()
+            .filter(|time| time.time() == chrono::NaiveTime::MIN)
+            .map(|time| time.date_naive())
+    };
+    midnight(get_file_mtime(path)).or_else(|| {
+        if CAN_SET_CREATION_TIME {
+            midnight(get_file_ctime(path))
+        } else {
+            None
+        }
+    })
+}
+
+/// `set_file_timestamps` 要设置的时间，为 None 的项保持不变
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimestampOptions {
+    /// 访问时间
+    pub atime: Option<DateTime<Utc>>,
+    /// 修改时间
+    pub mtime: Option<DateTime<Utc>>,
+    /// 创建时间（仅 Windows 和 macOS 支持设置，其他平台忽略）
+    pub created: Option<DateTime<Utc>>,
+}
+
+impl TimestampOptions {
+    /// 访问时间、修改时间和创建时间都设为同一时间
+    pub fn all(datetime: DateTime<Utc>) -> Self {
+        Self {
+            atime: Some(datetime),
+            mtime: Some(datetime),
+            created: Some(datetime),
+        }
+    }
+}
+
+/// 当前平台是否支持设置文件创建时间
+pub const CAN_SET_CREATION_TIME: bool = cfg!(any(target_os = "windows", target_os = "macos"));
+
+/// 设置文件的时间戳
 ///
 /// # 参数
 /// - `path`: 文件路径
-/// - `datetime`: 目标日期时间（UTC）
+/// - `options`: 要设置的访问时间、修改时间和创建时间（UTC）
 ///
 /// # 跨平台说明
-/// - **Windows**: 设置创建时间 和修改时间
-/// - **Unix/Linux**: 设置修改时间 和访问时间（无法设置创建时间）
+/// - **Windows / macOS**: 三项都可以设置
+/// - **Unix/Linux**: 无法设置创建时间，`created` 记录调试日志后忽略
 ///
 /// # 示例
 /// ```
 /// # use chrono::{TimeZone, Utc};
 /// # use std::path::Path;
-/// # use calendar::fileops::set_file_timestamps;
+/// # use calendar::fileops::{set_file_timestamps, TimestampOptions};
 /// // let datetime = Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap();
-/// // set_file_timestamps(Path::new("photo.jpg"), datetime)?;
+/// // set_file_timestamps(Path::new("photo.jpg"), &TimestampOptions::all(datetime))?;
 /// ```
-pub fn set_file_timestamps(path: &Path, datetime: DateTime<Utc>) -> Result<()> {
-    tracing::debug!(
-        "设置文件时间戳: {:?} -> {}",
-        path,
-        datetime.format("%Y-%m-%d %H:%M:%S")
-    );
+pub fn set_file_timestamps(path: &Path, options: &TimestampOptions) -> Result<()> {
+    tracing::debug!("设置文件时间戳: {:?} -> {:?}", path, options);
 
     // 确保文件存在
-    if !path.exists() {
+    if !long_path(path).exists() {
         return Err(AppError::file_error(path, "文件不存在".to_string()));
     }
 
-    // 将 DateTime<Utc> 转换为 FileTime
-    let filetime = datetime_to_filetime(&datetime);
-
-    // 设置文件时间戳
-    #[cfg(target_os = "windows")]
-    {
-        // Windows: 设置创建时间和修改时间
-        filetime::set_file_times(path, filetime, filetime)
-            .map_err(|e| AppError::file_error(path, e.to_string()))?;
-        tracing::debug!("已设置 Windows 文件时间戳（创建时间和修改时间）");
-    }
+    let result = match (options.atime, options.mtime) {
+        (Some(atime), Some(mtime)) => filetime::set_file_times(
+            long_path(path),
+            datetime_to_filetime(&atime),
+            datetime_to_filetime(&mtime),
+        ),
+        (None, Some(mtime)) => filetime::set_file_mtime(long_path(path), datetime_to_filetime(&mtime)),
+        (Some(atime), None) => filetime::set_file_atime(long_path(path), datetime_to_filetime(&atime)),
+        (None, None) => Ok(()),
+    };
+    result.map_err(|e| AppError::file_error(path, e.to_string()))?;
 
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    {
-        // Unix: 设置修改时间和访问时间
-        filetime::set_file_times(path, filetime, filetime)
-            .map_err(|e| AppError::file_error(path, e.to_string()))?;
-        tracing::debug!("已设置 Unix 文件时间戳（修改时间和访问时间）");
+    if let Some(created) = options.created {
+        if CAN_SET_CREATION_TIME {
+            set_file_creation_time(path, created)?;
+        } else {
+            tracing::debug!("当前平台不支持设置创建时间，忽略: {:?}", path);
+        }
     }
 
-    // 获取更新后的时间戳进行验证
-    let updated_metadata = fs::metadata(path)
-        .map_err(|e| AppError::file_error(path, e.to_string()))?;
+    Ok(())
+}
 
-    #[cfg(target_os = "windows")]
-    let updated_mtime = {
-        use std::os::windows::fs::MetadataExt;
-        let creation = updated_metadata.creation_time();
-        let modified = updated_metadata.last_write_time();
-        (creation, modified)
-    };
+/// 设置文件的创建时间
+///
+/// Windows 通过 `SetFileTime`、macOS 通过 `setattrlist` 设置，访问时间和修改时间不变；
+/// 其他平台返回错误（可先检查 `CAN_SET_CREATION_TIME`）
+pub fn set_file_creation_time(path: &Path, datetime: DateTime<Utc>) -> Result<()> {
+    tracing::debug!(
+        "设置文件创建时间: {:?} -> {}",
+        path,
+        datetime.format("%Y-%m-%d %H:%M:%S")
+    );
 
-    #[cfg(any(target_os = "linux", target_os = "macos"))]
-    let updated_mtime = {
-        use std::os::unix::fs::MetadataExt;
-        let mtime = updated_metadata.mtime();
-        mtime
-    };
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        #[cfg(target_os = "macos")]
+        use std::os::macos::fs::FileTimesExt;
+        #[cfg(target_os = "windows")]
+        use std::os::windows::fs::FileTimesExt;
 
-    tracing::trace!("更新后的文件时间戳: {:?}", updated_mtime);
+        // Windows 修改文件时间需要写入属性的权限
+        let times = fs::FileTimes::new().set_created(std::time::SystemTime::from(datetime));
+        fs::OpenOptions::new()
+            .write(true)
+            .open(long_path(path))
+            .and_then(|file| file.set_times(times))
+            .map_err(|e| AppError::file_error(path, e.to_string()))
+    }
 
-    Ok(())
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let _ = datetime;
+        Err(AppError::file_error(path, "当前平台不支持设置创建时间".to_string()))
+    }
 }
 
 /// 仅设置文件的最后修改时间
@@ -95,19 +215,26 @@ pub fn set_file_mtime(path: &Path, datetime: DateTime<Utc>) -> Result<()> {
         datetime.format("%Y-%m-%d %H:%M:%S")
     );
 
-    if !path.exists() {
+    if !long_path(path).exists() {
         return Err(AppError::file_error(path, "文件不存在".to_string()));
     }
 
     let filetime = datetime_to_filetime(&datetime);
 
     // 仅设置修改时间
-    filetime::set_file_mtime(path, filetime)
+    filetime::set_file_mtime(long_path(path), filetime)
         .map_err(|e| AppError::file_error(path, e.to_string()))?;
 
     Ok(())
 }
 
+/// 设置文件的修改时间，访问时间保持不变
+///
+/// 先读取当前访问时间，再与新的修改时间一起通过 `filetime::set_file_times` 写回
+pub fn set_file_mtime_preserving_atime(path: &Path, datetime: DateTime<Utc>) -> Result<()> {
+    FileTimes::read(path)?.with_mtime(datetime).restore(path)
+}
+
 /// 仅设置文件的访问时间
 ///
 /// # 参数
@@ -120,26 +247,57 @@ pub fn set_file_atime(path: &Path, datetime: DateTime<Utc>) -> Result<()> {
         datetime.format("%Y-%m-%d %H:%M:%S")
     );
 
-    if !path.exists() {
+    if !long_path(path).exists() {
         return Err(AppError::file_error(path, "文件不存在".to_string()));
     }
 
     let filetime = datetime_to_filetime(&datetime);
 
     // 仅设置访问时间
-    filetime::set_file_atime(path, filetime)
+    filetime::set_file_atime(long_path(path), filetime)
         .map_err(|e| AppError::file_error(path, e.to_string()))?;
 
     Ok(())
 }
 
+/// 文件的访问时间和修改时间快照
+///
+/// 用于在改写文件内容（如清除 EXIF）前记录时间戳，之后原样恢复
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileTimes {
+    atime: filetime::FileTime,
+    mtime: filetime::FileTime,
+}
+
+impl FileTimes {
+    /// 读取文件当前的访问时间和修改时间
+    pub fn read(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(long_path(path)).map_err(|e| AppError::file_error(path, e.to_string()))?;
+        Ok(Self {
+            atime: filetime::FileTime::from_last_access_time(&metadata),
+            mtime: filetime::FileTime::from_last_modification_time(&metadata),
+        })
+    }
+
+    /// 替换快照中的修改时间，访问时间不变
+    pub fn with_mtime(self, datetime: DateTime<Utc>) -> Self {
+        Self { mtime: datetime_to_filetime(&datetime), ..self }
+    }
+
+    /// 将文件的访问时间和修改时间恢复为快照中的值
+    pub fn restore(&self, path: &Path) -> Result<()> {
+        filetime::set_file_times(long_path(path), self.atime, self.mtime)
+            .map_err(|e| AppError::file_error(path, e.to_string()))
+    }
+}
+
 /// 将 DateTime<Utc> 转换为 filetime::FileTime
 fn datetime_to_filetime(datetime: &DateTime<Utc>) -> filetime::FileTime {
     // 获取 Unix 时间戳（秒）
     let timestamp = datetime.timestamp();
 
     // 获取纳秒部分
-    let nsec = datetime.timestamp_subsec_nanos() as u32;
+    let nsec = datetime.timestamp_subsec_nanos();
 
     filetime::FileTime::from_unix_time(timestamp, nsec)
 }
@@ -152,11 +310,11 @@ fn datetime_to_filetime(datetime: &DateTime<Utc>) -> filetime::FileTime {
 /// # 返回
 /// 返回 Option<DateTime<Utc>>，如果获取失败则为 None
 pub fn get_file_mtime(path: &Path) -> Result<Option<DateTime<Utc>>> {
-    if !path.exists() {
+    if !long_path(path).exists() {
         return Ok(None);
     }
 
-    let metadata = fs::metadata(path)
+    let metadata = fs::metadata(long_path(path))
         .map_err(|e| AppError::file_error(path, e.to_string()))?;
 
     #[cfg(target_os = "windows")]
@@ -177,14 +335,47 @@ pub fn get_file_mtime(path: &Path) -> Result<Option<DateTime<Utc>>> {
     Ok(mtime)
 }
 
-/// 将 Windows FILETIME 转换为 DateTime<Utc>
-#[cfg(target_os = "windows")]
+/// 获取文件的创建时间
+///
+/// Windows 读取 FILETIME 创建时间，其他平台使用 `Metadata::created()`（Linux 需要文件系统支持 statx 的 birth time）
+///
+/// # 返回
+/// 文件不存在，或平台、文件系统不支持创建时间时返回 None
+pub fn get_file_ctime(path: &Path) -> Result<Option<DateTime<Utc>>> {
+    if !long_path(path).exists() {
+        return Ok(None);
+    }
+
+    let metadata = fs::metadata(long_path(path))
+        .map_err(|e| AppError::file_error(path, e.to_string()))?;
+
+    #[cfg(target_os = "windows")]
+    let ctime = {
+        use std::os::windows::fs::MetadataExt;
+        windows_time_to_datetime(metadata.creation_time())
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    let ctime = match metadata.created() {
+        Ok(created) => Some(DateTime::<Utc>::from(created)),
+        Err(e) => {
+            tracing::trace!("不支持读取创建时间: {:?}: {}", path, e);
+            None
+        }
+    };
+
+    Ok(ctime)
+}
+
+/// 将 Windows FILETIME（自 1601-01-01 起的 100 纳秒数）转换为 DateTime<Utc>
+#[cfg(any(target_os = "windows", test))]
 fn windows_time_to_datetime(win_time: u64) -> Option<DateTime<Utc>> {
-    const WINDOWS_TICK: i64 = 10_000_000; // 100 纳秒
-    const SEC_TO_UNIX_EPOCH: i64 = 116_444_73600; // 1601-01-01 到 1970-01-01 的秒数
+    const WINDOWS_TICK: u64 = 10_000_000; // 每秒的 100 纳秒数
+    const SEC_TO_UNIX_EPOCH: i64 = 11_644_473_600; // 1601-01-01 到 1970-01-01 的秒数
 
-    let timestamp = (win_time as i64 / WINDOWS_TICK) - SEC_TO_UNIX_EPOCH;
-    DateTime::from_timestamp(timestamp, 0)
+    let timestamp = (win_time / WINDOWS_TICK) as i64 - SEC_TO_UNIX_EPOCH;
+    let nsec = (win_time % WINDOWS_TICK) as u32 * 100;
+    DateTime::from_timestamp(timestamp, nsec)
 }
 
 /// 创建目录（如果不存在）
@@ -192,8 +383,8 @@ fn windows_time_to_datetime(win_time: u64) -> Option<DateTime<Utc>> {
 /// # 参数
 /// - `path`: 目录路径
 pub fn ensure_dir_exists(path: &Path) -> Result<()> {
-    if !path.exists() {
-        fs::create_dir_all(path)
+    if !long_path(path).exists() {
+        fs::create_dir_all(long_path(path))
             .map_err(|e| AppError::file_error(path, e.to_string()))?;
         tracing::debug!("创建目录: {}", path.display());
     }
@@ -208,7 +399,7 @@ pub fn ensure_dir_exists(path: &Path) -> Result<()> {
 /// # 返回
 /// 返回 true 如果文件存在
 pub fn file_exists(path: &Path) -> bool {
-    path.exists() && path.is_file()
+    long_path(path).is_file()
 }
 
 /// 获取文件大小
@@ -219,11 +410,11 @@ pub fn file_exists(path: &Path) -> bool {
 /// # 返回
 /// 返回文件大小（字节），如果文件不存在则返回 None
 pub fn get_file_size(path: &Path) -> Result<Option<u64>> {
-    if !path.exists() {
+    if !long_path(path).exists() {
         return Ok(None);
     }
 
-    let metadata = fs::metadata(path)
+    let metadata = fs::metadata(long_path(path))
         .map_err(|e| AppError::file_error(path, e.to_string()))?;
 
     Ok(Some(metadata.len()))
@@ -234,14 +425,225 @@ pub fn get_file_size(path: &Path) -> Result<Option<u64>> {
 /// # 参数
 /// - `path`: 文件路径
 pub fn delete_file(path: &Path) -> Result<()> {
-    if path.exists() {
-        fs::remove_file(path)
+    if long_path(path).exists() {
+        fs::remove_file(long_path(path))
             .map_err(|e| AppError::file_error(path, e.to_string()))?;
         tracing::debug!("删除文件: {}", path.display());
     }
     Ok(())
 }
 
+/// 将文件移入系统回收站（Windows 回收站、macOS 废纸篓、Linux `~/.local/share/Trash`）
+///
+/// 当前环境不支持回收站（如网络文件系统、没有桌面环境的服务器）时记录警告并直接删除。
+/// 文件不存在时不做任何操作。
+pub fn trash_file(path: &Path) -> Result<()> {
+    if !long_path(path).exists() {
+        return Ok(());
+    }
+    match trash::delete(path) {
+        Ok(()) => {
+            tracing::debug!("移入回收站: {}", path.display());
+            Ok(())
+        }
+        Err(e) => {
+            tracing::warn!("无法移入回收站，改为直接删除: {}: {}", path.display(), e);
+            delete_file(path)
+        }
+    }
+}
+
+/// 删除图库中的文件：`use_trash` 为 true 时移入回收站，否则直接删除
+pub fn discard_file(path: &Path, use_trash: bool) -> Result<()> {
+    if use_trash {
+        trash_file(path)
+    } else {
+        delete_file(path)
+    }
+}
+
+/// 未完成下载等临时文件的扩展名
+pub const TEMP_FILE_EXTENSIONS: &[&str] = &["part", "partial", "tmp", "crdownload"];
+
+/// 判断是否为临时文件（按扩展名）
+pub fn is_temp_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| TEMP_FILE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// 递归列出目录下的所有文件
+///
+/// 与 [`scan_library`] 一致，不进入指向目录的符号链接（避免链接成环或扫描到输出目录之外）；
+/// 指向文件的链接作为文件列出。
+///
+/// # 参数
+/// - `dir`: 目录路径
+///
+/// # 返回
+/// 按路径排序的文件列表，目录不存在时返回空列表
+pub fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = fs::read_dir(&current)
+            .map_err(|e| AppError::file_error(&current, e.to_string()))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| AppError::file_error(&current, e.to_string()))?;
+            let path = entry.path();
+            let file_type = entry
+                .file_type()
+                .map_err(|e| AppError::file_error(&path, e.to_string()))?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// 同一进程内原子写入临时文件的序号，避免并发写入同一目标时冲突
+static ATOMIC_WRITE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 原子写入使用的临时文件路径：目标同目录下的 `.<文件名>.<pid>-<序号>.tmp`
+///
+/// 扩展名为 `.tmp`，崩溃遗留的临时文件扫描图库时忽略，由 `clean` 清理
+fn atomic_temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    let seq = ATOMIC_WRITE_SEQ.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}.tmp", name, std::process::id(), seq))
+}
+
+/// 原子写入文件
+///
+/// 先写入同目录下的临时文件并同步到磁盘，再重命名覆盖目标文件；
+/// 崩溃时目标文件要么是旧内容要么是新内容，不会留下写了一半的文件。
+/// 写入或重命名失败时删除临时文件。
+///
+/// # 参数
+/// - `path`: 目标文件路径，所在目录必须已存在
+/// - `contents`: 文件内容
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let temp = write_atomic_temp(path, contents)?;
+    commit_atomic_temp(&temp, path)
+}
+
+/// 原子写入的第一步：把内容写入目标同目录下的临时文件并同步到磁盘
+///
+/// 返回临时文件路径，调用方检查内容后用 `commit_atomic_temp` 替换目标文件，
+/// 或用 `discard_atomic_temp` 丢弃；写入失败时删除临时文件。
+pub fn write_atomic_temp(path: &Path, contents: impl AsRef<[u8]>) -> Result<PathBuf> {
+    let temp = atomic_temp_path(path);
+    let result = (|| -> std::io::Result<()> {
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(long_path(&temp))?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()
+    })();
+
+    if let Err(e) = result {
+        discard_atomic_temp(&temp);
+        return Err(AppError::file_error(path, e.to_string()));
+    }
+    Ok(temp)
+}
+
+/// 原子写入的第二步：把临时文件重命名为目标文件，失败时删除临时文件
+pub fn commit_atomic_temp(temp: &Path, path: &Path) -> Result<()> {
+    if let Err(e) = fs::rename(long_path(temp), long_path(path)) {
+        discard_atomic_temp(temp);
+        return Err(AppError::file_error(path, e.to_string()));
+    }
+
+    // 同步目录项，使重命名本身也落盘
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// 删除未提交的临时文件，失败时只记录警告
+pub fn discard_atomic_temp(temp: &Path) {
+    if let Err(remove_error) = fs::remove_file(long_path(temp)) {
+        if remove_error.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!("删除临时文件失败: {}: {}", temp.display(), remove_error);
+        }
+    }
+}
+
+/// 同步文件所在目录的目录项，失败时只记录调试日志（Windows 不支持打开目录）
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if let Err(e) = fs::File::open(dir).and_then(|d| d.sync_all()) {
+            tracing::debug!("同步目录失败: {}: {}", dir.display(), e);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// 进程内 `sync_durable` 累计耗时（纳秒）
+static SYNC_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// 将文件内容和所在目录项同步到磁盘
+///
+/// 用于 `durable_writes`：写入 EXIF、更新时间戳会在原子写入之后再次修改文件，
+/// 同步后即使断电也不会留下空文件或未写完的内容。耗时累计到 `sync_time`
+pub fn sync_durable(path: &Path) -> Result<()> {
+    let start = Instant::now();
+    // Windows 上 FlushFileBuffers 需要写权限
+    #[cfg(windows)]
+    let file = fs::OpenOptions::new().write(true).open(long_path(path));
+    #[cfg(not(windows))]
+    let file = fs::File::open(path);
+    let result = file
+        .and_then(|file| file.sync_all())
+        .map_err(|e| AppError::file_error(path, format!("同步到磁盘失败: {}", e)));
+    if result.is_ok() {
+        sync_parent_dir(path);
+    }
+    SYNC_NANOS.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    result
+}
+
+/// 本进程 `sync_durable` 的累计耗时
+pub fn sync_time() -> Duration {
+    Duration::from_nanos(SYNC_NANOS.load(Ordering::Relaxed))
+}
+
+/// `write_atomic` 的异步版本，在阻塞线程池中执行
+pub async fn write_atomic_async(
+    path: &Path,
+    contents: impl AsRef<[u8]> + Send + 'static,
+) -> Result<()> {
+    let target = path.to_path_buf();
+    tokio::task::spawn_blocking(move || write_atomic(&target, contents))
+        .await
+        .map_err(|e| AppError::file_error(path, e.to_string()))?
+}
+
+/// 异步写入原子写入的临时文件，见 `write_atomic_temp`
+pub async fn write_atomic_temp_async(
+    path: &Path,
+    contents: impl AsRef<[u8]> + Send + 'static,
+) -> Result<PathBuf> {
+    let target = path.to_path_buf();
+    tokio::task::spawn_blocking(move || write_atomic_temp(&target, contents))
+        .await
+        .map_err(|e| AppError::file_error(path, e.to_string()))?
+}
+
 /// 复制文件
 ///
 /// # 参数
@@ -249,18 +651,804 @@ pub fn delete_file(path: &Path) -> Result<()> {
 /// - `dst`: 目标文件路径
 pub fn copy_file(src: &Path, dst: &Path) -> Result<()> {
     ensure_dir_exists(dst.parent().unwrap_or(Path::new(".")))?;
-    fs::copy(src, dst)
+    fs::copy(long_path(src), long_path(dst))
         .map_err(|e| AppError::file_error(dst, e.to_string()))?;
     tracing::debug!("复制文件: {} -> {}", src.display(), dst.display());
     Ok(())
 }
 
+/// 移动文件，自动创建目标目录
+///
+/// 优先使用 `fs::rename`；源和目标位于不同文件系统时退回到复制（保留修改时间并同步到磁盘）后删除源文件。
+///
+/// # 参数
+/// - `src`: 源文件路径
+/// - `dst`: 目标文件路径
+/// - `overwrite`: 目标已存在时是否覆盖
+///
+/// # 错误
+/// 目标已存在且 `overwrite` 为 false 时返回 `AppError::DestinationExists`，不修改任何文件
+pub fn move_file(src: &Path, dst: &Path, overwrite: bool) -> Result<()> {
+    if !overwrite && fs::symlink_metadata(long_path(dst)).is_ok() {
+        return Err(AppError::DestinationExists { path: dst.to_path_buf() });
+    }
+    ensure_dir_exists(dst.parent().unwrap_or(Path::new(".")))?;
+    match fs::rename(long_path(src), long_path(dst)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            tracing::debug!("跨文件系统移动，改为复制: {} -> {}", src.display(), dst.display());
+            copy_durable(src, dst)?;
+            delete_file(src)?;
+        }
+        Err(e) => {
+            return Err(AppError::file_error(
+                src,
+                format!("移动到 {} 失败: {}", dst.display(), e),
+            ));
+        }
+    }
+    tracing::debug!("移动文件: {} -> {}", src.display(), dst.display());
+    Ok(())
+}
+
+/// 跨文件系统移动时的复制步骤
+///
+/// 先复制到目标目录下的临时文件，恢复源文件的访问/修改时间并同步到磁盘后再重命名为目标，
+/// 删除源文件前目标已完整落盘。失败时删除临时文件。
+fn copy_durable(src: &Path, dst: &Path) -> Result<()> {
+    let times = FileTimes::read(src)?;
+    let temp = atomic_temp_path(dst);
+    let result = (|| -> std::io::Result<()> {
+        fs::copy(long_path(src), long_path(&temp))?;
+        filetime::set_file_times(long_path(&temp), times.atime, times.mtime)?;
+        fs::File::open(long_path(&temp))?.sync_all()?;
+        fs::rename(long_path(&temp), long_path(dst))
+    })();
+
+    if let Err(e) = result {
+        if let Err(remove_error) = fs::remove_file(long_path(&temp)) {
+            if remove_error.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("删除临时文件失败: {}: {}", temp.display(), remove_error);
+            }
+        }
+        return Err(AppError::file_error(dst, e.to_string()));
+    }
+    Ok(())
+}
+
+/// 创建链接时实际采用的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStrategy {
+    Hardlink,
+    Symlink,
+    /// 文件系统不支持链接，退回到复制
+    Copy,
+}
+
+impl LinkStrategy {
+    /// 用于输出的中文名称
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hardlink => "硬链接",
+            Self::Symlink => "符号链接",
+            Self::Copy => "复制",
+        }
+    }
+}
+
+/// 在 `dst` 创建指向 `src` 的硬链接，自动创建目标目录
+///
+/// 先在同一目录创建临时链接再重命名，原子地替换已存在的 `dst`；
+/// 文件系统不支持硬链接（如跨文件系统、FAT）时退回到复制。
+///
+/// # 返回
+/// 实际采用的方式
+pub fn hardlink(src: &Path, dst: &Path) -> Result<LinkStrategy> {
+    ensure_dir_exists(dst.parent().unwrap_or(Path::new(".")))?;
+    match replace_atomically(dst, |temp| fs::hard_link(long_path(src), temp)) {
+        Ok(()) => {
+            tracing::debug!("创建硬链接: {} -> {}", dst.display(), src.display());
+            Ok(LinkStrategy::Hardlink)
+        }
+        Err(e) if is_link_unsupported(&e) => {
+            tracing::debug!("无法创建硬链接，改为复制: {}: {}", dst.display(), e);
+            copy_durable(src, dst)?;
+            Ok(LinkStrategy::Copy)
+        }
+        Err(e) => Err(AppError::file_error(dst, format!("创建硬链接失败: {}", e))),
+    }
+}
+
+/// 在 `dst` 创建指向 `src` 的符号链接，自动创建目标目录
+///
+/// `src` 按原样写入链接，相对路径相对于 `dst` 所在目录解析。
+/// 已存在的 `dst` 被原子替换；不支持符号链接（如 Windows 上缺少权限）时退回到复制。
+///
+/// # 返回
+/// 实际采用的方式
+pub fn symlink(src: &Path, dst: &Path) -> Result<LinkStrategy> {
+    ensure_dir_exists(dst.parent().unwrap_or(Path::new(".")))?;
+    let created = replace_atomically(dst, |temp| {
+        #[cfg(unix)]
+        let result = std::os::unix::fs::symlink(src, temp);
+        #[cfg(windows)]
+        let result = std::os::windows::fs::symlink_file(src, temp);
+        result
+    });
+    match created {
+        Ok(()) => {
+            tracing::debug!("创建符号链接: {} -> {}", dst.display(), src.display());
+            Ok(LinkStrategy::Symlink)
+        }
+        Err(e) if is_link_unsupported(&e) => {
+            tracing::debug!("无法创建符号链接，改为复制: {}: {}", dst.display(), e);
+            let resolved = dst.parent().unwrap_or(Path::new(".")).join(src);
+            copy_durable(&resolved, dst)?;
+            Ok(LinkStrategy::Copy)
+        }
+        Err(e) => Err(AppError::file_error(dst, format!("创建符号链接失败: {}", e))),
+    }
+}
+
+/// 在 `dst` 同目录的临时路径上执行 `create`，再重命名为 `dst`；失败时删除临时文件
+fn replace_atomically(
+    dst: &Path,
+    create: impl FnOnce(&Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let temp = atomic_temp_path(dst);
+    let temp = long_path(&temp);
+    let result = create(&temp).and_then(|()| fs::rename(&temp, long_path(dst)));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// 链接失败是否因为文件系统或平台不支持（而非路径错误等），此时可退回到复制
+fn is_link_unsupported(e: &std::io::Error) -> bool {
+    // Windows 上 ERROR_PRIVILEGE_NOT_HELD (1314)：创建符号链接需要开发者模式或管理员权限
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::CrossesDevices
+            | std::io::ErrorKind::Unsupported
+            | std::io::ErrorKind::PermissionDenied
+    ) || (cfg!(windows) && e.raw_os_error() == Some(1314))
+}
+
+/// 删除目录下所有空子目录（自底向上），不删除 `dir` 本身
+///
+/// # 返回
+/// 删除的目录数量
+pub fn remove_empty_dirs(dir: &Path) -> Result<usize> {
+    let mut removed = 0;
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(AppError::file_error(dir, e.to_string())),
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|e| AppError::file_error(dir, e.to_string()))?;
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .map_err(|e| AppError::file_error(&path, e.to_string()))?;
+        if !file_type.is_dir() {
+            continue;
+        }
+
+        removed += remove_empty_dirs(&path)?;
+        let is_empty = fs::read_dir(&path)
+            .map_err(|e| AppError::file_error(&path, e.to_string()))?
+            .next()
+            .is_none();
+        if is_empty {
+            fs::remove_dir(&path).map_err(|e| AppError::file_error(&path, e.to_string()))?;
+            tracing::debug!("删除空目录: {}", path.display());
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// 计算哈希时每次读取的字节数；较大的块减少机械硬盘上的寻道和系统调用次数
+pub const HASH_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// 计算文件内容的 SHA-256（小写十六进制），按块读取，不把整个文件读入内存
+pub fn sha256_file(path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut file = fs::File::open(long_path(path)).map_err(|e| AppError::file_error(path, e.to_string()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| AppError::file_error(path, e.to_string()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// `sha256_file` 的异步版本，在阻塞线程池中执行
+pub async fn sha256_file_async(path: &Path) -> Result<String> {
+    let target = path.to_path_buf();
+    tokio::task::spawn_blocking(move || sha256_file(&target))
+        .await
+        .map_err(|e| AppError::file_error(path, e.to_string()))?
+}
+
+/// 计算内存中数据（如刚下载的响应体）的 SHA-256（小写十六进制）
+pub fn sha256_bytes(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    to_hex(&Sha256::digest(data))
+}
+
+/// 将摘要转换为小写十六进制字符串
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 获取文件的设备号和 inode，用于识别互为硬链接的文件
+///
+/// 非 Unix 平台或读取失败时返回 `None`
+pub fn file_identity(path: &Path) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        fs::metadata(long_path(path)).ok().map(|m| (m.dev(), m.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// 解析八进制权限字符串（如 `"0664"`、`"664"`），返回错误信息
+pub fn parse_file_mode(value: &str) -> std::result::Result<u32, String> {
+    let digits = value.trim();
+    let digits = digits.strip_prefix("0o").unwrap_or(digits);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if !digits.is_empty() && mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("无效的文件权限 '{}'，应为八进制数字（如 0664）", value)),
+    }
+}
+
+/// 按组名查找组 ID，纯数字按组 ID 处理；组不存在时返回错误信息
+#[cfg(unix)]
+pub fn lookup_group(name: &str) -> std::result::Result<u32, String> {
+    if let Ok(gid) = name.parse::<u32>() {
+        return Ok(gid);
+    }
+    let c_name = std::ffi::CString::new(name).map_err(|_| format!("无效的组名 '{}'", name))?;
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        // SAFETY: group 结构体和缓冲区在调用期间有效，getgrnam_r 只写入其中
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut found: *mut libc::group = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getgrnam_r(c_name.as_ptr(), &mut group, buf.as_mut_ptr(), buf.len(), &mut found)
+        };
+        match rc {
+            0 if found.is_null() => return Err(format!("组 '{}' 不存在", name)),
+            0 => return Ok(group.gr_gid),
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            _ => {
+                return Err(format!(
+                    "查找组 '{}' 失败: {}",
+                    name,
+                    std::io::Error::from_raw_os_error(rc)
+                ))
+            }
+        }
+    }
+}
+
+/// 设置文件权限和所属组
+///
+/// Unix 上 `mode` 通过 `PermissionsExt` 设置，`group` 通过 `chown` 设置（只改组，不改所有者）；
+/// 非 Unix 平台不支持，仅记录调试日志。
+///
+/// # 参数
+/// - `mode`: 八进制权限，如 `0o664`
+/// - `group`: 组名或组 ID
+pub fn set_file_permissions(path: &Path, mode: Option<u32>, group: Option<&str>) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if let Some(group) = group {
+            let gid = lookup_group(group).map_err(|details| AppError::file_error(path, details))?;
+            std::os::unix::fs::chown(path, None, Some(gid)).map_err(|e| {
+                let details = if e.kind() == std::io::ErrorKind::PermissionDenied {
+                    format!("没有权限将所属组改为 '{}'（当前用户需要是该组成员或 root）", group)
+                } else {
+                    format!("修改所属组为 '{}' 失败: {}", group, e)
+                };
+                AppError::file_error(path, details)
+            })?;
+        }
+        if let Some(mode) = mode {
+            fs::set_permissions(long_path(path), fs::Permissions::from_mode(mode)).map_err(|e| {
+                AppError::file_error(path, format!("设置文件权限 {:04o} 失败: {}", mode, e))
+            })?;
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        if mode.is_some() || group.is_some() {
+            tracing::debug!("当前平台不支持 file_mode/file_group，跳过: {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// 输出目录锁文件的文件名
+pub const LOCK_FILE_NAME: &str = ".calendar.lock";
+
+/// 输出目录中记录运行状态的文件名
+pub const STATE_FILE_NAME: &str = ".calendar.state";
+
+/// 修改 EXIF 前备份原文件时追加的扩展名（`20240615.jpg` -> `20240615.jpg.orig`）
+pub const BACKUP_EXTENSION: &str = "orig";
+
+/// 文件的备份路径：在原文件名后追加 `.orig`
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(BACKUP_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// 是否为修改 EXIF 前留下的备份文件
+pub fn is_backup_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == BACKUP_EXTENSION)
+}
+
+/// 指向最新图片的链接的文件名主干（扩展名与图片相同，如 `latest.jpg`）
+pub const LATEST_LINK_STEM: &str = "latest";
+
+/// 是否为程序自身在输出目录中维护的文件（锁文件、状态文件、EXIF 备份、`latest` 链接），扫描图库时应跳过
+pub fn is_internal_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == LOCK_FILE_NAME || name == STATE_FILE_NAME)
+        || path.file_stem().is_some_and(|stem| stem == LATEST_LINK_STEM)
+        || is_backup_file(path)
+}
+
+/// 单个顶层子目录的统计
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct SubdirStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// 目录统计信息
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct DirStats {
+    /// 文件总数
+    pub files: usize,
+    /// 文件总大小（字节）
+    pub bytes: u64,
+    /// 扩展名（小写，没有扩展名时为空字符串） -> 文件数量
+    pub per_extension: BTreeMap<String, usize>,
+    /// 顶层子目录名（按年份存放时即年份） -> 统计，直接位于根目录的文件不计入
+    pub per_subdir: BTreeMap<String, SubdirStats>,
+    /// 因权限不足等原因无法读取的目录或文件数量
+    pub skipped: usize,
+}
+
+impl DirStats {
+    /// 计入一个文件，`subdir` 为所属顶层子目录，直接位于根目录时为 None
+    fn add_file(&mut self, path: &Path, size: u64, subdir: Option<&str>) {
+        self.files += 1;
+        self.bytes += size;
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        *self.per_extension.entry(ext).or_default() += 1;
+        if let Some(subdir) = subdir {
+            let totals = self.per_subdir.entry(subdir.to_string()).or_default();
+            totals.files += 1;
+            totals.bytes += size;
+        }
+    }
+}
+
+/// 遍历一次目录，统计文件数量、大小、扩展名分布和各顶层子目录的汇总
+///
+/// 符号链接不跟随也不计入，避免统计到目录树以外的文件或重复统计同一文件；
+/// 无法读取的子目录或文件计入 `skipped`，不中断扫描。
+///
+/// # 返回
+/// 目录不存在时返回空统计；根目录本身无法读取时返回错误
+pub fn dir_stats(path: &Path) -> Result<DirStats> {
+    let mut stats = DirStats::default();
+    if !path.is_dir() {
+        return Ok(stats);
+    }
+
+    // (目录, 所属顶层子目录)；打开目录推迟到出栈时，避免同时持有大量目录句柄
+    let mut pending: Vec<(PathBuf, Option<String>)> = vec![(path.to_path_buf(), None)];
+    while let Some((dir, subdir)) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if subdir.is_none() => {
+                return Err(AppError::file_error(path, e.to_string()));
+            }
+            Err(e) => {
+                tracing::warn!("无法读取目录，跳过: {}: {}", dir.display(), e);
+                stats.skipped += 1;
+                continue;
+            }
+        };
+        for entry in entries {
+            let Ok(entry) = entry else {
+                stats.skipped += 1;
+                continue;
+            };
+            let entry_path = entry.path();
+            let Ok(metadata) = fs::symlink_metadata(&entry_path) else {
+                stats.skipped += 1;
+                continue;
+            };
+
+            if metadata.is_dir() {
+                let subdir = subdir
+                    .clone()
+                    .unwrap_or_else(|| entry.file_name().to_string_lossy().into_owned());
+                stats.per_subdir.entry(subdir.clone()).or_default();
+                pending.push((entry_path, Some(subdir)));
+                continue;
+            }
+            if !metadata.is_file() {
+                continue;
+            }
+
+            stats.add_file(&entry_path, metadata.len(), subdir.as_deref());
+        }
+    }
+
+    Ok(stats)
+}
+
+/// 相册软件在图片旁生成的附属文件扩展名，扫描图库时跳过
+pub const SIDECAR_EXTENSIONS: &[&str] = &["xmp", "aae"];
+
+/// 是否为附属元数据文件（按扩展名，如 `IMG_0001.xmp`、`20240615.jpg.aae`）
+pub fn is_sidecar_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| SIDECAR_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// 图库扫描得到的单个文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    /// 按文件名格式解析出的日期，不符合格式时为 None
+    pub date: Option<NaiveDate>,
+    /// 文件大小（字节），符号链接为链接目标的大小
+    pub size: u64,
+    /// 最后修改时间，文件系统不支持时为 None
+    pub mtime: Option<DateTime<Utc>>,
+    /// 是否位于目录格式和文件名格式为其日期生成的位置，没有日期时为 false
+    pub in_place: bool,
+}
+
+/// 逐个遍历输出目录中的文件，将文件名反向解析为日期
+///
+/// 按需读取目录，不预先收集整个文件列表，适合大型图库；返回顺序不确定。
+/// 跳过内部文件（锁文件、状态文件、EXIF 备份）、临时文件和附属文件；
+/// 子目录层级（年份目录、格式中 `/` 产生的月份目录）任意嵌套，日期按 `formatter` 的路径层级从文件相对路径的末尾解析。
+/// 不跟随指向目录的符号链接，避免链接成环或扫描到目录树以外的文件；指向文件的符号链接按链接目标计入。
+/// 无法读取的目录或文件记录警告后跳过。
+///
+/// # 参数
+/// - `output_dir`: 图库根目录，不存在时不产生任何条目
+/// - `formatter`: 文件名格式
+/// - `dir_format`: 文件名之上的目录格式（按年份存放时为 `{yyyy}`，直接存放在根目录时为 None），用于计算 `in_place`
+pub fn scan_library<'a>(
+    output_dir: &'a Path,
+    formatter: &'a FilenameFormatter,
+    dir_format: Option<&'a FilenameFormatter>,
+) -> impl Iterator<Item = LibraryEntry> + 'a {
+    LibraryScan::new(output_dir, formatter, dir_format, None)
+}
+
+/// 与 [`scan_library`] 相同，在同一次遍历中统计输出目录的磁盘占用
+///
+/// 磁盘占用与 [`dir_stats`] 口径一致：包括图库扫描跳过的内部文件、临时文件和附属文件，
+/// 不计入符号链接；无法读取的目录或条目计入 `skipped`。
+pub fn scan_library_with_disk_stats(
+    output_dir: &Path,
+    formatter: &FilenameFormatter,
+    dir_format: Option<&FilenameFormatter>,
+) -> (Vec<LibraryEntry>, DirStats) {
+    let mut scan = LibraryScan::new(output_dir, formatter, dir_format, Some(DirStats::default()));
+    let entries = scan.by_ref().collect();
+    (entries, scan.disk.unwrap_or_default())
+}
+
+/// `scan_library` 的迭代状态：待读取的目录栈和正在读取的目录，各自带有所属的顶层子目录
+struct LibraryScan<'a> {
+    output_dir: &'a Path,
+    formatter: &'a FilenameFormatter,
+    dir_format: Option<&'a FilenameFormatter>,
+    pending: Vec<(PathBuf, Option<String>)>,
+    current: Option<(fs::ReadDir, Option<String>)>,
+    /// 同时统计的磁盘占用，不需要时为 None
+    disk: Option<DirStats>,
+}
+
+impl<'a> LibraryScan<'a> {
+    fn new(
+        output_dir: &'a Path,
+        formatter: &'a FilenameFormatter,
+        dir_format: Option<&'a FilenameFormatter>,
+        disk: Option<DirStats>,
+    ) -> Self {
+        LibraryScan {
+            output_dir,
+            formatter,
+            dir_format,
+            pending: if output_dir.is_dir() { vec![(output_dir.to_path_buf(), None)] } else { Vec::new() },
+            current: None,
+            disk,
+        }
+    }
+
+    /// 统计磁盘占用时记录一个无法读取的目录或条目
+    fn skip(&mut self) {
+        if let Some(disk) = &mut self.disk {
+            disk.skipped += 1;
+        }
+    }
+
+    /// 文件相对路径是否与按日期生成的路径一致，格式包含 `{ext}` 时忽略扩展名
+    fn in_place(&self, path: &Path, date: &NaiveDate) -> bool {
+        let relative = path.strip_prefix(self.output_dir).unwrap_or(path);
+        let Ok(name) = self.formatter.check_file_name(self.formatter.format(date)) else {
+            return false;
+        };
+        let mut expected = PathBuf::new();
+        if let Some(dir_format) = self.dir_format {
+            expected.push(dir_format.format(date));
+        }
+        expected.push(name);
+        if self.formatter.default_ext().is_some() {
+            relative.with_extension("") == expected.with_extension("")
+        } else {
+            relative == expected
+        }
+    }
+}
+
+impl Iterator for LibraryScan<'_> {
+    type Item = LibraryEntry;
+
+    fn next(&mut self) -> Option<LibraryEntry> {
+        loop {
+            let Some((entries, subdir)) = self.current.as_mut() else {
+                let (dir, subdir) = self.pending.pop()?;
+                match fs::read_dir(&dir) {
+                    Ok(entries) => self.current = Some((entries, subdir)),
+                    Err(e) => {
+                        tracing::warn!("无法读取目录: {}: {}", dir.display(), e);
+                        self.skip();
+                    }
+                }
+                continue;
+            };
+
+            let path = match entries.next() {
+                Some(Ok(entry)) => entry.path(),
+                Some(Err(e)) => {
+                    tracing::warn!("读取目录项失败: {}", e);
+                    self.skip();
+                    continue;
+                }
+                None => {
+                    self.current = None;
+                    continue;
+                }
+            };
+
+            let mut metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    tracing::warn!("无法读取文件信息: {}: {}", path.display(), e);
+                    self.skip();
+                    continue;
+                }
+            };
+            if metadata.is_dir() {
+                let subdir = subdir.clone().unwrap_or_else(|| {
+                    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+                });
+                if let Some(disk) = &mut self.disk {
+                    disk.per_subdir.entry(subdir.clone()).or_default();
+                }
+                self.pending.push((path, Some(subdir)));
+                continue;
+            }
+            if let (Some(disk), true) = (&mut self.disk, metadata.is_file()) {
+                disk.add_file(&path, metadata.len(), subdir.as_deref());
+            }
+            if metadata.file_type().is_symlink() {
+                match fs::metadata(&path) {
+                    Ok(target) if target.is_file() => metadata = target,
+                    _ => continue,
+                }
+            }
+            if !metadata.is_file()
+                || is_internal_file(&path)
+                || is_temp_file(&path)
+                || is_sidecar_file(&path)
+            {
+                continue;
+            }
+
+            let date = crate::library_file_date(self.output_dir, &path, self.formatter);
+            return Some(LibraryEntry {
+                in_place: date.is_some_and(|date| self.in_place(&path, &date)),
+                date,
+                size: metadata.len(),
+                mtime: metadata.modified().ok().map(DateTime::<Utc>::from),
+                path,
+            });
+        }
+    }
+}
+
+/// 输出目录的独占锁（基于 fs2 的建议锁）
+///
+/// 锁在值被丢弃时释放；进程退出（包括 panic 中止）时由操作系统释放。
+/// 锁文件本身不会被删除，避免与正在等待的进程产生竞争。
+#[derive(Debug)]
+pub struct DirLock {
+    file: fs::File,
+    path: PathBuf,
+}
+
+impl DirLock {
+    /// 获取 `dir` 下的锁文件，`wait` 为 true 时阻塞直到锁可用，否则立即返回 [`AppError::Locked`]
+    pub fn acquire(dir: &Path, wait: bool) -> Result<Self> {
+        use fs2::FileExt;
+        use std::io::{Read, Seek, Write};
+
+        ensure_dir_exists(dir)?;
+        let path = dir.join(LOCK_FILE_NAME);
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|e| AppError::file_error(&path, e.to_string()))?;
+
+        if file.try_lock_exclusive().is_err() {
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = holder.trim().to_string();
+            if !wait {
+                return Err(AppError::Locked { details: holder });
+            }
+            tracing::info!("等待其他 calendar 进程释放锁: {}", holder);
+            file.lock_exclusive()
+                .map_err(|e| AppError::file_error(&path, e.to_string()))?;
+        }
+
+        // 记录持有者信息，供其他进程提示
+        let holder = format!(
+            "pid {}，开始于 {}",
+            std::process::id(),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        file.set_len(0)
+            .and_then(|_| file.rewind())
+            .and_then(|_| file.write_all(holder.as_bytes()))
+            .map_err(|e| AppError::file_error(&path, e.to_string()))?;
+        tracing::debug!("获取输出目录锁: {}", path.display());
+
+        Ok(Self { file, path })
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        let _ = fs2::FileExt::unlock(&self.file);
+        tracing::debug!("释放输出目录锁: {}", self.path.display());
+    }
+}
+
+/// 获取路径所在文件系统的可用空间（字节）
+pub fn available_space(path: &Path) -> Result<u64> {
+    fs2::available_space(path).map_err(|e| AppError::file_error(path, e.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use chrono::TimeZone;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn test_dir_lock() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let lock = DirLock::acquire(temp_dir.path(), false).unwrap();
+        let err = DirLock::acquire(temp_dir.path(), false).unwrap_err();
+        assert!(matches!(err, AppError::Locked { .. }));
+        assert!(err.to_string().contains(&format!("pid {}", std::process::id())));
+
+        drop(lock);
+        assert!(DirLock::acquire(temp_dir.path(), false).is_ok());
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), b"abc").unwrap();
+        assert_eq!(
+            sha256_file(file.path()).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_sha256_bytes() {
+        assert_eq!(
+            sha256_bytes(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256_bytes(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sha256_file_async_multiple_chunks() {
+        // 跨越多个读取块，结果应与一次性计算一致
+        let data: Vec<u8> = (0..HASH_CHUNK_SIZE * 2 + 123).map(|i| (i % 251) as u8).collect();
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), &data).unwrap();
+
+        let digest = sha256_file_async(file.path()).await.unwrap();
+        assert_eq!(digest, sha256_bytes(&data));
+        assert_eq!(digest, sha256_file(file.path()).unwrap());
+
+        let empty = NamedTempFile::new().unwrap();
+        assert_eq!(sha256_file(empty.path()).unwrap(), sha256_bytes(b""));
+    }
+
+    #[test]
+    fn test_file_times_restore() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+        fs::write(path, b"before").unwrap();
+        set_file_timestamps(path, &TimestampOptions::all(Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap()))
+            .unwrap();
+
+        let times = FileTimes::read(path).unwrap();
+        fs::write(path, b"after").unwrap();
+        assert_ne!(FileTimes::read(path).unwrap(), times);
+
+        times.restore(path).unwrap();
+        assert_eq!(FileTimes::read(path).unwrap(), times);
+    }
+
     #[test]
     fn test_datetime_to_filetime() {
         let datetime = Utc
@@ -274,7 +1462,7 @@ mod tests {
 
     fn filetime_nanoseconds(ft: filetime::FileTime) -> u32 {
         const NANOS_PER_SEC: u32 = 1_000_000_000;
-        ((ft.nanoseconds() as u32) * NANOS_PER_SEC) / 1_000_000_000
+        (ft.nanoseconds() * NANOS_PER_SEC) / 1_000_000_000
     }
 
     #[test]
@@ -289,10 +1477,40 @@ mod tests {
             .with_ymd_and_hms(2024, 6, 15, 0, 0, 0)
             .unwrap();
 
-        let result = set_file_timestamps(path, datetime);
+        let result = set_file_timestamps(path, &TimestampOptions::all(datetime));
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_set_file_timestamps_partial() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.path();
+        fs::write(path, b"test content").unwrap();
+        let before = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        set_file_timestamps(path, &TimestampOptions::all(before)).unwrap();
+
+        // 只设置修改时间，访问时间不变
+        let june_15 = Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap();
+        let options = TimestampOptions { mtime: Some(june_15), ..Default::default() };
+        set_file_timestamps(path, &options).unwrap();
+        let metadata = fs::metadata(path).unwrap();
+        assert_eq!(get_file_mtime(path).unwrap(), Some(june_15));
+        assert_eq!(
+            filetime::FileTime::from_last_access_time(&metadata),
+            datetime_to_filetime(&before)
+        );
+
+        // 只设置创建时间：不支持的平台忽略，修改时间不变
+        let options = TimestampOptions { created: Some(before), ..Default::default() };
+        set_file_timestamps(path, &options).unwrap();
+        assert_eq!(get_file_mtime(path).unwrap(), Some(june_15));
+        if CAN_SET_CREATION_TIME {
+            assert_eq!(get_file_ctime(path).unwrap(), Some(before));
+        } else {
+            assert!(set_file_creation_time(path, before).is_err());
+        }
+    }
+
     #[test]
     fn test_set_file_timestamps_nonexistent() {
         let path = Path::new("/nonexistent/file.jpg");
@@ -300,10 +1518,131 @@ mod tests {
             .with_ymd_and_hms(2024, 6, 15, 0, 0, 0)
             .unwrap();
 
-        let result = set_file_timestamps(path, datetime);
+        let result = set_file_timestamps(path, &TimestampOptions::all(datetime));
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_collision_suffix_path() {
+        assert_eq!(
+            collision_suffix_path(Path::new("images/2024/20240615.jpg"), 1),
+            PathBuf::from("images/2024/20240615-1.jpg")
+        );
+        assert_eq!(
+            collision_suffix_path(Path::new("images/20240615.tar.gz"), 12),
+            PathBuf::from("images/20240615.tar-12.gz")
+        );
+        assert_eq!(
+            collision_suffix_path(Path::new("images/20240615"), 2),
+            PathBuf::from("images/20240615-2")
+        );
+    }
+
+    #[test]
+    fn test_stamped_date() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("202406.jpg");
+        assert_eq!(stamped_date(&path), None);
+
+        fs::write(&path, b"image").unwrap();
+        let noon = Utc.with_ymd_and_hms(2024, 6, 15, 12, 0, 0).unwrap();
+        set_file_timestamps(&path, &TimestampOptions::all(noon)).unwrap();
+        assert_eq!(stamped_date(&path), None);
+
+        let midnight = Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap();
+        set_file_timestamps(&path, &TimestampOptions::all(midnight)).unwrap();
+        assert_eq!(stamped_date(&path), NaiveDate::from_ymd_opt(2024, 6, 15));
+    }
+
+    #[test]
+    fn test_sync_durable() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        fs::write(&path, b"image").unwrap();
+
+        let before = sync_time();
+        sync_durable(&path).unwrap();
+        assert!(sync_time() >= before);
+        assert_eq!(fs::read(&path).unwrap(), b"image");
+
+        let error = sync_durable(&temp_dir.path().join("missing.jpg")).unwrap_err();
+        assert!(error.to_string().contains("同步到磁盘失败"));
+    }
+
+    #[test]
+    fn test_extended_length_path() {
+        assert_eq!(
+            extended_length_path(r"C:\Users\me\images\2024\20240615.jpg").as_deref(),
+            Some(r"\\?\C:\Users\me\images\2024\20240615.jpg")
+        );
+        assert_eq!(
+            extended_length_path("d:/images/2024/20240615.jpg").as_deref(),
+            Some(r"\\?\d:\images\2024\20240615.jpg")
+        );
+        assert_eq!(
+            extended_length_path(r"\\nas\photos\2024\20240615.jpg").as_deref(),
+            Some(r"\\?\UNC\nas\photos\2024\20240615.jpg")
+        );
+        // 已是扩展长度或设备路径时不重复添加前缀
+        assert_eq!(
+            extended_length_path(r"\\?\C:\images\a.jpg").as_deref(),
+            Some(r"\\?\C:\images\a.jpg")
+        );
+        assert_eq!(
+            extended_length_path(r"\\?\UNC\nas\a.jpg").as_deref(),
+            Some(r"\\?\UNC\nas\a.jpg")
+        );
+        // 相对路径无法转换
+        assert_eq!(extended_length_path(r"images\2024\a.jpg"), None);
+        assert_eq!(extended_length_path("C:images"), None);
+    }
+
+    #[test]
+    fn test_long_path_short_paths_unchanged() {
+        let path = Path::new("images/2024/20240615.jpg");
+        assert!(matches!(long_path(path), Cow::Borrowed(p) if p == path));
+
+        // 非 Windows 平台长路径也原样返回
+        #[cfg(not(target_os = "windows"))]
+        {
+            let long = PathBuf::from("images").join("a".repeat(WINDOWS_MAX_PATH));
+            assert!(matches!(long_path(&long), Cow::Borrowed(p) if p == long));
+        }
+    }
+
+    #[test]
+    fn test_windows_time_to_datetime() {
+        // 1970-01-01 00:00:00 UTC
+        assert_eq!(
+            windows_time_to_datetime(116_444_736_000_000_000),
+            Some(Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap())
+        );
+        // 2024-06-15 00:00:00.5 UTC
+        let expected = Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap()
+            + chrono::Duration::milliseconds(500);
+        assert_eq!(windows_time_to_datetime(133_628_832_005_000_000), Some(expected));
+        // 1601-01-01
+        assert_eq!(
+            windows_time_to_datetime(0),
+            Some(Utc.with_ymd_and_hms(1601, 1, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_get_file_ctime() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        assert_eq!(get_file_ctime(&path).unwrap(), None);
+
+        let before = Utc::now() - chrono::Duration::seconds(5);
+        fs::write(&path, b"data").unwrap();
+        // 文件系统不支持创建时间时为 None；支持时应为刚才创建的时间，不受修改时间影响
+        set_file_mtime(&path, Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap()).unwrap();
+        if let Some(ctime) = get_file_ctime(&path).unwrap() {
+            assert!(ctime >= before, "{} < {}", ctime, before);
+        }
+    }
+
     #[test]
     fn test_get_file_mtime_nonexistent() {
         let path = Path::new("/nonexistent/file.jpg");
@@ -354,4 +1693,410 @@ mod tests {
         assert!(dst.exists());
         assert_eq!(fs::read_to_string(&dst).unwrap(), "test content");
     }
+
+    #[test]
+    fn test_list_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let nested = temp_dir.path().join("2024");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(temp_dir.path().join("a.txt"), b"a").unwrap();
+        fs::write(nested.join("b.jpg"), b"b").unwrap();
+
+        let files = list_files(temp_dir.path()).unwrap();
+        assert_eq!(files, vec![nested.join("b.jpg"), temp_dir.path().join("a.txt")]);
+
+        assert!(list_files(&temp_dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_files_does_not_follow_directory_symlinks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("2024")).unwrap();
+        fs::write(root.join("2024/20240615.jpg"), b"a").unwrap();
+        fs::write(outside.path().join("20240101.jpg"), b"b").unwrap();
+        std::os::unix::fs::symlink(root, root.join("2024/loop")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("linked")).unwrap();
+        std::os::unix::fs::symlink(root.join("2024/20240615.jpg"), root.join("latest.jpg")).unwrap();
+
+        assert_eq!(
+            list_files(root).unwrap(),
+            vec![root.join("2024/20240615.jpg"), root.join("latest.jpg")]
+        );
+    }
+
+    #[test]
+    fn test_is_temp_file() {
+        assert!(is_temp_file(Path::new("images/20240615.jpg.part")));
+        assert!(is_temp_file(Path::new("images/20240615.TMP")));
+        assert!(!is_temp_file(Path::new("images/20240615.jpg")));
+        assert!(!is_temp_file(Path::new("images/README")));
+    }
+
+    #[test]
+    fn test_set_file_mtime_preserving_atime() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path();
+        let atime = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_atime(path, atime).unwrap();
+
+        let datetime = Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap();
+        set_file_mtime_preserving_atime(path, datetime).unwrap();
+
+        let metadata = fs::metadata(path).unwrap();
+        assert_eq!(filetime::FileTime::from_last_access_time(&metadata), atime);
+        assert_eq!(get_file_mtime(path).unwrap(), Some(datetime));
+    }
+
+    #[test]
+    fn test_backup_path() {
+        let backup = backup_path(Path::new("images/2024/20240615.jpg"));
+        assert_eq!(backup, PathBuf::from("images/2024/20240615.jpg.orig"));
+        assert!(is_backup_file(&backup));
+        assert!(is_internal_file(&backup));
+        assert!(!is_backup_file(Path::new("images/2024/20240615.jpg")));
+    }
+
+    #[test]
+    fn test_dir_stats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("2023")).unwrap();
+        fs::create_dir_all(root.join("2024/06")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::write(root.join("2023/20231231.jpg"), b"12345").unwrap();
+        fs::write(root.join("2024/20240101.JPG"), b"123").unwrap();
+        fs::write(root.join("2024/06/20240615.png"), b"12").unwrap();
+        fs::write(root.join("failed_downloads.txt"), b"1").unwrap();
+        fs::write(root.join("README"), b"").unwrap();
+
+        let stats = dir_stats(root).unwrap();
+        assert_eq!(stats.files, 5);
+        assert_eq!(stats.bytes, 11);
+        assert_eq!(stats.skipped, 0);
+        let extensions: Vec<(&str, usize)> =
+            stats.per_extension.iter().map(|(ext, n)| (ext.as_str(), *n)).collect();
+        assert_eq!(extensions, vec![("", 1), ("jpg", 2), ("png", 1), ("txt", 1)]);
+        assert_eq!(stats.per_subdir["2023"], SubdirStats { files: 1, bytes: 5 });
+        assert_eq!(stats.per_subdir["2024"], SubdirStats { files: 2, bytes: 5 });
+        assert_eq!(stats.per_subdir["empty"], SubdirStats::default());
+        assert_eq!(stats.per_subdir.len(), 3);
+
+        // 图库扫描时同步统计的磁盘占用与单独遍历一致
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let (entries, disk) = scan_library_with_disk_stats(root, &formatter, None);
+        assert_eq!(disk, stats);
+        assert_eq!(entries.len(), 5);
+
+        assert_eq!(dir_stats(&root.join("missing")).unwrap(), DirStats::default());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dir_stats_skips_symlinks_and_unreadable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("big.jpg"), vec![0u8; 1000]).unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("2024")).unwrap();
+        fs::write(root.join("2024/20240615.jpg"), b"abc").unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("linked")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("big.jpg"), root.join("latest.jpg")).unwrap();
+
+        let locked = root.join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        fs::write(locked.join("20240101.jpg"), b"x").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // root 用户不受目录权限限制
+        let unreadable = fs::read_dir(&locked).is_err();
+
+        let stats = dir_stats(root).unwrap();
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let (_, disk) = scan_library_with_disk_stats(root, &formatter, None);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(disk, stats);
+
+        assert!(!stats.per_subdir.contains_key("linked"));
+        if unreadable {
+            assert_eq!((stats.files, stats.bytes, stats.skipped), (1, 3, 1));
+        } else {
+            assert_eq!((stats.files, stats.bytes, stats.skipped), (2, 4, 0));
+        }
+    }
+
+    #[test]
+    fn test_scan_library() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let month_dir = root.join("2024/06");
+        fs::create_dir_all(&month_dir).unwrap();
+        fs::create_dir_all(root.join("2023/12")).unwrap();
+        fs::create_dir_all(root.join("2023/06")).unwrap();
+        fs::create_dir_all(root.join("empty/nested")).unwrap();
+
+        let files: &[(&str, &[u8])] = &[
+            ("2024/06/20240601.jpg", b"abc"),
+            ("2023/12/20231231.jpg", b"abcdef"),
+            // 能解析日期，但年份目录不符
+            ("2023/06/20240605.jpg", b"x"),
+            // 不符合格式：日期为 None
+            ("2024/06/IMG_0001.jpg", b"x"),
+            ("2024/20240602.jpg", b"x"),
+            ("notes.txt", b"x"),
+            // 跳过：临时文件、EXIF 备份、附属文件、内部文件
+            ("2024/06/20240603.jpg.part", b"x"),
+            ("2024/06/20240601.jpg.orig", b"x"),
+            ("2024/06/20240601.xmp", b"x"),
+            ("2024/06/20240601.jpg.aae", b"x"),
+            (LOCK_FILE_NAME, b""),
+            (STATE_FILE_NAME, b"{}"),
+        ];
+        for (name, content) in files {
+            fs::write(root.join(name), content).unwrap();
+        }
+        let mtime = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        set_file_mtime(&month_dir.join("20240601.jpg"), mtime).unwrap();
+
+        // 格式中的 `/` 表示月份目录，年份目录不参与解析，只用于判断位置
+        let formatter = FilenameFormatter::new("{mm}/{yyyy}{mm}{dd}.jpg").unwrap();
+        let dir_format = FilenameFormatter::new("{yyyy}").unwrap();
+        let mut entries: Vec<LibraryEntry> = scan_library(root, &formatter, Some(&dir_format)).collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let summary: Vec<(PathBuf, Option<NaiveDate>, bool)> = entries
+            .iter()
+            .map(|e| (e.path.strip_prefix(root).unwrap().to_path_buf(), e.date, e.in_place))
+            .collect();
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        assert_eq!(
+            summary,
+            vec![
+                (PathBuf::from("2023/06/20240605.jpg"), date(2024, 6, 5), false),
+                (PathBuf::from("2023/12/20231231.jpg"), date(2023, 12, 31), true),
+                (PathBuf::from("2024/06/20240601.jpg"), date(2024, 6, 1), true),
+                (PathBuf::from("2024/06/IMG_0001.jpg"), None, false),
+                (PathBuf::from("2024/20240602.jpg"), None, false),
+                (PathBuf::from("notes.txt"), None, false),
+            ]
+        );
+        assert_eq!(entries[1].size, 6);
+        assert_eq!(entries[2].size, 3);
+        assert_eq!(entries[2].mtime, Some(mtime));
+
+        // 直接存放在根目录的布局下，年份目录中的文件不在原位
+        assert!(scan_library(root, &formatter, None).all(|e| !e.in_place));
+
+        // 不存在的目录不产生条目
+        assert_eq!(scan_library(&root.join("missing"), &formatter, None).count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_library_does_not_follow_directory_symlinks() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("20240101.jpg"), b"outside").unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("2024")).unwrap();
+        fs::write(root.join("2024/20240615.jpg"), b"abc").unwrap();
+        // 指回根目录的链接成环，指向目录树以外的链接不应被扫描
+        std::os::unix::fs::symlink(root, root.join("2024/loop")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.join("linked")).unwrap();
+        // 指向文件的链接（如 dedupe --link symlink 的结果）按链接目标计入
+        std::os::unix::fs::symlink(root.join("2024/20240615.jpg"), root.join("2024/20240616.jpg")).unwrap();
+
+        let formatter = FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let mut found: Vec<(PathBuf, u64)> = scan_library(root, &formatter, None)
+            .map(|e| (e.path.strip_prefix(root).unwrap().to_path_buf(), e.size))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![(PathBuf::from("2024/20240615.jpg"), 3), (PathBuf::from("2024/20240616.jpg"), 3)]
+        );
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_existing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("failed_downloads.txt");
+        fs::write(&path, b"old content that is longer").unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        // 只剩目标文件，没有遗留临时文件
+        assert_eq!(list_files(temp_dir.path()).unwrap(), vec![path]);
+    }
+
+    #[test]
+    fn test_write_atomic_removes_temp_on_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        // 目标是非空目录时重命名失败
+        let target = temp_dir.path().join("20240615.jpg");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("keep"), b"x").unwrap();
+
+        assert!(write_atomic(&target, b"data").is_err());
+        let leftovers: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|p| p != &target)
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+
+        // 崩溃遗留的临时文件按临时文件处理，扫描图库时忽略
+        let stale = atomic_temp_path(&temp_dir.path().join("20240616.jpg"));
+        assert!(is_temp_file(&stale));
+        assert_ne!(stale, atomic_temp_path(&temp_dir.path().join("20240616.jpg")));
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_async() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        write_atomic_async(&path, vec![1u8, 2, 3]).await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_file_mode() {
+        assert_eq!(parse_file_mode("0664"), Ok(0o664));
+        assert_eq!(parse_file_mode("664"), Ok(0o664));
+        assert_eq!(parse_file_mode("0o2775"), Ok(0o2775));
+        assert!(parse_file_mode("0899").is_err());
+        assert!(parse_file_mode("17777").is_err());
+        assert!(parse_file_mode("").is_err());
+        assert!(parse_file_mode("rw-rw-r--").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_file_permissions() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        fs::write(&path, b"data").unwrap();
+
+        // 改为文件当前所属组总是允许的
+        let gid = fs::metadata(&path).unwrap().gid().to_string();
+        set_file_permissions(&path, Some(0o664), Some(&gid)).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o7777, 0o664);
+
+        let err = set_file_permissions(&path, None, Some("no-such-group-calendar")).unwrap_err();
+        assert!(err.to_string().contains("不存在"));
+        set_file_permissions(&path, None, None).unwrap();
+    }
+
+    #[test]
+    fn test_discard_file_without_trash() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("20240615.jpg");
+        fs::write(&path, b"data").unwrap();
+
+        discard_file(&path, false).unwrap();
+        assert!(!path.exists());
+        // 文件已不存在时不报错
+        discard_file(&path, false).unwrap();
+        trash_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hardlink_replaces_existing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("2024/20240615.jpg");
+        let dst = temp_dir.path().join("links/latest.jpg");
+        fs::create_dir_all(src.parent().unwrap()).unwrap();
+        fs::write(&src, b"new").unwrap();
+        fs::create_dir_all(dst.parent().unwrap()).unwrap();
+        fs::write(&dst, b"old").unwrap();
+
+        let strategy = hardlink(&src, &dst).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), b"new");
+        if strategy == LinkStrategy::Hardlink {
+            assert_eq!(file_identity(&dst), file_identity(&src));
+        }
+        // 只剩目标文件，没有遗留临时链接
+        assert_eq!(list_files(&temp_dir.path().join("links")).unwrap(), vec![dst]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_relative_and_replace() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("2024")).unwrap();
+        fs::write(root.join("2024/20240614.jpg"), b"a").unwrap();
+        fs::write(root.join("2024/20240615.jpg"), b"b").unwrap();
+        let link = root.join("latest.jpg");
+
+        assert_eq!(symlink(Path::new("2024/20240614.jpg"), &link).unwrap(), LinkStrategy::Symlink);
+        assert_eq!(fs::read(&link).unwrap(), b"a");
+
+        symlink(Path::new("2024/20240615.jpg"), &link).unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("2024/20240615.jpg"));
+        assert_eq!(fs::read(&link).unwrap(), b"b");
+        assert!(is_internal_file(&link));
+    }
+
+    #[test]
+    fn test_move_file_overwrite() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("a.jpg");
+        let dst = temp_dir.path().join("b.jpg");
+        fs::write(&src, b"new").unwrap();
+        fs::write(&dst, b"old").unwrap();
+
+        let err = move_file(&src, &dst, false).unwrap_err();
+        assert!(matches!(err, AppError::DestinationExists { ref path } if path == &dst));
+        assert_eq!(fs::read(&src).unwrap(), b"new");
+        assert_eq!(fs::read(&dst).unwrap(), b"old");
+
+        move_file(&src, &dst, true).unwrap();
+        assert!(!src.exists());
+        assert_eq!(fs::read(&dst).unwrap(), b"new");
+
+        // 源文件不存在属于 IO 错误
+        let err = move_file(&src, &temp_dir.path().join("c.jpg"), false).unwrap_err();
+        assert!(matches!(err, AppError::FileError { .. }));
+    }
+
+    #[test]
+    fn test_copy_durable_preserves_mtime() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("a.jpg");
+        let dst = temp_dir.path().join("b.jpg");
+        fs::write(&src, b"data").unwrap();
+        let mtime = Utc.with_ymd_and_hms(2024, 6, 15, 0, 0, 0).unwrap();
+        set_file_mtime(&src, mtime).unwrap();
+
+        copy_durable(&src, &dst).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), b"data");
+        assert_eq!(get_file_mtime(&dst).unwrap(), Some(mtime));
+        // 不留下临时文件
+        assert_eq!(list_files(temp_dir.path()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_move_file_and_remove_empty_dirs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("old/2024/a.jpg");
+        let dst = temp_dir.path().join("new/2024/06/a.jpg");
+        fs::create_dir_all(src.parent().unwrap()).unwrap();
+        fs::write(&src, b"data").unwrap();
+
+        move_file(&src, &dst, false).unwrap();
+        assert!(!src.exists());
+        assert_eq!(fs::read(&dst).unwrap(), b"data");
+
+        assert_eq!(remove_empty_dirs(temp_dir.path()).unwrap(), 2);
+        assert!(!temp_dir.path().join("old").exists());
+        assert!(dst.exists());
+    }
 }
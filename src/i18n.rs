@@ -0,0 +1,383 @@
+//! 控制台输出的语言选择与消息目录
+//!
+//! 语言按 `--lang` 参数、配置项 `lang`、`LC_ALL`/`LC_MESSAGES`/`LANG` 环境变量的顺序确定，
+//! 均未指定时使用中文。日志内容不做翻译，只覆盖统计摘要、进度条和错误信息。
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 控制台输出语言
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    /// 中文（默认）
+    #[default]
+    Zh,
+    /// 英文
+    En,
+}
+
+/// 当前使用的语言（`Lang as u8`）
+static CURRENT: AtomicU8 = AtomicU8::new(Lang::Zh as u8);
+
+/// 设置当前进程的输出语言
+pub fn set_lang(lang: Lang) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+
+/// 获取当前进程的输出语言
+pub fn current() -> Lang {
+    match CURRENT.load(Ordering::Relaxed) {
+        x if x == Lang::En as u8 => Lang::En,
+        _ => Lang::Zh,
+    }
+}
+
+impl Lang {
+    /// 根据 locale 字符串（如 `zh_CN.UTF-8`、`en_US`）推断语言
+    ///
+    /// `C`、`POSIX` 和空值不表达语言偏好，返回 `None`；其他非中文 locale 使用英文
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let locale = locale.trim().to_ascii_lowercase();
+        let name = locale.split(['.', '@']).next().unwrap_or_default();
+        match name {
+            "" | "c" | "posix" => None,
+            _ if name.starts_with("zh") => Some(Lang::Zh),
+            _ => Some(Lang::En),
+        }
+    }
+
+    /// 从 `LC_ALL`、`LC_MESSAGES`、`LANG` 环境变量推断语言，按 POSIX 优先级取第一个非空值
+    pub fn from_env() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_locale(&value))
+    }
+}
+
+impl std::str::FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "zh" => Ok(Lang::Zh),
+            "en" => Ok(Lang::En),
+            _ => Err(format!("不支持的语言 '{}'，可选值: zh, en", s)),
+        }
+    }
+}
+
+/// 消息目录：统计摘要、进度条和错误前缀中的固定文本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    /// 错误信息前缀
+    ErrorPrefix,
+    /// run 命令统计标题
+    DownloadSummary,
+    /// process 命令统计标题
+    ProcessSummary,
+    /// retry 命令统计标题
+    RetrySummary,
+    /// verify --redownload 统计标题
+    RedownloadSummary,
+    /// touch 命令统计标题
+    MetadataSummary,
+    /// 统计摘要：总数量
+    Total,
+    /// 统计摘要：成功
+    Succeeded,
+    /// 统计摘要：失败
+    Failed,
+    /// 统计摘要：跳过
+    Skipped,
+    /// 统计摘要：因 --fail-fast 或 --max-duration 未处理
+    Cancelled,
+    /// 统计摘要：元数据已是最新、跳过重写的文件数
+    MetadataUpToDate,
+    /// 统计摘要：元数据更新失败的文件数
+    MetadataFailed,
+    /// 元数据失败记录文件位置（参数：文件路径）
+    MetadataFailuresSaved,
+    /// 重新写入元数据的提示
+    MetadataRetryHint,
+    /// 统计摘要：成功率
+    SuccessRate,
+    /// 统计摘要：日期范围（参数：起始日期、结束日期）
+    DateRange,
+    /// --max-duration 时间预算用尽（参数：已处理日期数、总日期数、百分比）
+    TimeBudgetExhausted,
+    /// 失败日期记录文件位置（参数：文件路径）
+    FailedDatesSaved,
+    /// 重新处理失败日期的提示
+    RetryHint,
+    /// run 更新配置文件中的起始日期（参数：原日期、新日期）
+    StartDateUpdating,
+    /// 配置文件已更新（参数：文件路径）
+    ConfigUpdated,
+    /// status 命令统计标题
+    StatusSummary,
+    /// status 摘要：日期范围内应有的文件数
+    Expected,
+    /// status 摘要：已存在的文件数
+    Present,
+    /// status 摘要：缺失的日期数
+    Missing,
+    /// status 缺失日期列表标题
+    MissingDates,
+    /// 缺失日期记录文件位置（参数：文件路径）
+    MissingDatesSaved,
+    /// verify 命令统计标题
+    VerifySummary,
+    /// verify 摘要：已检查的文件数
+    Checked,
+    /// verify 摘要：有效文件数
+    Valid,
+    /// verify 摘要：无效文件数
+    Invalid,
+    /// verify 摘要：文件名不符合格式的文件数
+    Unrecognized,
+    /// verify/repair 无效文件列表标题
+    InvalidFiles,
+    /// repair --dry-run 统计标题
+    RepairCheckSummary,
+    /// repair 命令统计标题
+    RepairSummary,
+    /// repair 摘要：已检查的文件数
+    RepairChecked,
+    /// repair 摘要：待修复的文件数
+    RepairPending,
+    /// repair 摘要：已修复的文件数
+    RepairFixed,
+    /// repair 摘要：重新下载仍然失败的文件数
+    RepairStillFailed,
+    /// repair 摘要：无法确定日期的文件数
+    RepairUndated,
+    /// repair --dry-run 未下载的提示
+    RepairDryRun,
+    /// 进度条样式模板
+    ProgressTemplate,
+    /// 进度条消息：成功（参数：日期）
+    ProgressSucceeded,
+    /// 进度条消息：失败（参数：日期）
+    ProgressFailed,
+    /// 进度条消息：跳过（参数：日期）
+    ProgressSkipped,
+    /// 进度条结束消息
+    ProgressDone,
+}
+
+impl Msg {
+    /// 当前语言下的文本
+    pub fn text(self) -> &'static str {
+        self.in_lang(current())
+    }
+
+    /// 指定语言下的文本
+    pub fn in_lang(self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::Zh => self.zh(),
+            Lang::En => self.en(),
+        }
+    }
+
+    /// 用参数依次替换当前语言文本中的 `{}`
+    pub fn format(self, args: &[&dyn fmt::Display]) -> String {
+        fill_template(self.text(), args)
+    }
+
+    fn zh(self) -> &'static str {
+        match self {
+            Msg::ErrorPrefix => "错误",
+            Msg::DownloadSummary => "下载统计",
+            Msg::ProcessSummary => "处理统计",
+            Msg::RetrySummary => "重试统计",
+            Msg::RedownloadSummary => "重新下载统计",
+            Msg::MetadataSummary => "元数据更新统计",
+            Msg::Total => "总数量:     ",
+            Msg::Succeeded => "成功:       ",
+            Msg::Failed => "失败:       ",
+            Msg::Skipped => "跳过:       ",
+            Msg::Cancelled => "未处理:     ",
+            Msg::MetadataUpToDate => "元数据已是最新: ",
+            Msg::MetadataFailed => "元数据失败: ",
+            Msg::MetadataFailuresSaved => "元数据更新失败的日期已保存到: {}",
+            Msg::MetadataRetryHint => "可使用以下命令重新写入元数据:",
+            Msg::SuccessRate => "成功率:     ",
+            Msg::DateRange => "日期范围:   {} 到 {}",
+            Msg::TimeBudgetExhausted => {
+                "时间预算用尽 (--max-duration): 已处理 {}/{} 个日期 ({}%)，其余日期留待下次运行"
+            }
+            Msg::FailedDatesSaved => "失败的日期已保存到: {}",
+            Msg::RetryHint => "可使用以下命令重新处理:",
+            Msg::StartDateUpdating => "更新配置文件中的起始日期: {} -> {}",
+            Msg::ConfigUpdated => "配置文件已更新: {}",
+            Msg::StatusSummary => "图库状态",
+            Msg::Expected => "应有数量:   ",
+            Msg::Present => "已存在:     ",
+            Msg::Missing => "缺失:       ",
+            Msg::MissingDates => "缺失日期:",
+            Msg::MissingDatesSaved => "缺失日期已写入: {}",
+            Msg::VerifySummary => "校验结果",
+            Msg::Checked => "已检查:     ",
+            Msg::Valid => "有效:       ",
+            Msg::Invalid => "无效:       ",
+            Msg::Unrecognized => "未识别:     ",
+            Msg::InvalidFiles => "无效文件:",
+            Msg::RepairCheckSummary => "修复检查",
+            Msg::RepairSummary => "修复统计",
+            Msg::RepairChecked => "已检查:       ",
+            Msg::RepairPending => "待修复:       ",
+            Msg::RepairFixed => "已修复:       ",
+            Msg::RepairStillFailed => "仍然失败:     ",
+            Msg::RepairUndated => "无法确定日期: ",
+            Msg::RepairDryRun => "(--dry-run) 未下载任何文件",
+            Msg::ProgressTemplate => {
+                "[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} \
+                 成功: {green} 失败: {red} 跳过: {yellow}"
+            }
+            Msg::ProgressSucceeded => "成功: {}",
+            Msg::ProgressFailed => "失败: {}",
+            Msg::ProgressSkipped => "跳过: {}",
+            Msg::ProgressDone => "完成",
+        }
+    }
+
+    fn en(self) -> &'static str {
+        match self {
+            Msg::ErrorPrefix => "error",
+            Msg::DownloadSummary => "Download summary",
+            Msg::ProcessSummary => "Process summary",
+            Msg::RetrySummary => "Retry summary",
+            Msg::RedownloadSummary => "Redownload summary",
+            Msg::MetadataSummary => "Metadata summary",
+            Msg::Total => "Total:        ",
+            Msg::Succeeded => "Succeeded:    ",
+            Msg::Failed => "Failed:       ",
+            Msg::Skipped => "Skipped:      ",
+            Msg::Cancelled => "Deferred:     ",
+            Msg::MetadataUpToDate => "Metadata up to date: ",
+            Msg::MetadataFailed => "Metadata failed: ",
+            Msg::MetadataFailuresSaved => "Dates with metadata failures saved to: {}",
+            Msg::MetadataRetryHint => "Rewrite their metadata with:",
+            Msg::SuccessRate => "Success rate: ",
+            Msg::DateRange => "Date range:   {} to {}",
+            Msg::TimeBudgetExhausted => {
+                "Time budget exhausted (--max-duration): processed {}/{} dates ({}%); the rest are left for the next run"
+            }
+            Msg::FailedDatesSaved => "Failed dates saved to: {}",
+            Msg::RetryHint => "Reprocess them with:",
+            Msg::StartDateUpdating => "Updating start_date in the config file: {} -> {}",
+            Msg::ConfigUpdated => "Config file updated: {}",
+            Msg::StatusSummary => "Library status",
+            Msg::Expected => "Expected:     ",
+            Msg::Present => "Present:      ",
+            Msg::Missing => "Missing:      ",
+            Msg::MissingDates => "Missing dates:",
+            Msg::MissingDatesSaved => "Missing dates written to: {}",
+            Msg::VerifySummary => "Verify summary",
+            Msg::Checked => "Checked:      ",
+            Msg::Valid => "Valid:        ",
+            Msg::Invalid => "Invalid:      ",
+            Msg::Unrecognized => "Unrecognized: ",
+            Msg::InvalidFiles => "Invalid files:",
+            Msg::RepairCheckSummary => "Repair check",
+            Msg::RepairSummary => "Repair summary",
+            Msg::RepairChecked => "Checked:      ",
+            Msg::RepairPending => "To repair:    ",
+            Msg::RepairFixed => "Repaired:     ",
+            Msg::RepairStillFailed => "Still failed: ",
+            Msg::RepairUndated => "Undated:      ",
+            Msg::RepairDryRun => "(--dry-run) nothing was downloaded",
+            Msg::ProgressTemplate => {
+                "[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} \
+                 ok: {green} failed: {red} skipped: {yellow}"
+            }
+            Msg::ProgressSucceeded => "ok: {}",
+            Msg::ProgressFailed => "failed: {}",
+            Msg::ProgressSkipped => "skipped: {}",
+            Msg::ProgressDone => "done",
+        }
+    }
+}
+
+/// 依次用参数替换模板中的 `{}`，多余的占位符原样保留
+fn fill_template(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        match args.next() {
+            Some(arg) => out.push_str(&arg.to_string()),
+            None => out.push_str("{}"),
+        }
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+impl fmt::Display for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lang_from_locale() {
+        assert_eq!(Lang::from_locale("zh_CN.UTF-8"), Some(Lang::Zh));
+        assert_eq!(Lang::from_locale("zh_TW"), Some(Lang::Zh));
+        assert_eq!(Lang::from_locale("en_US.UTF-8"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("de_DE@euro"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("C"), None);
+        assert_eq!(Lang::from_locale("C.UTF-8"), None);
+        assert_eq!(Lang::from_locale("POSIX"), None);
+        assert_eq!(Lang::from_locale(""), None);
+    }
+
+    #[test]
+    fn test_lang_from_str() {
+        assert_eq!("zh".parse::<Lang>().unwrap(), Lang::Zh);
+        assert_eq!("EN".parse::<Lang>().unwrap(), Lang::En);
+        assert!("fr".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_msg_catalog() {
+        assert_eq!(Msg::DownloadSummary.in_lang(Lang::Zh), "下载统计");
+        assert_eq!(Msg::DownloadSummary.in_lang(Lang::En), "Download summary");
+        assert_eq!(
+            fill_template(Msg::DateRange.in_lang(Lang::En), &[&"2024-06-01", &"2024-06-30"]),
+            "Date range:   2024-06-01 to 2024-06-30"
+        );
+        assert_eq!(fill_template("{} / {}", &[&1]), "1 / {}");
+        // 同一摘要中的英文标签等宽，数值对齐
+        let labels = [
+            Msg::Total, Msg::Expected, Msg::Present, Msg::Missing, Msg::Checked, Msg::Valid,
+            Msg::Invalid, Msg::Unrecognized, Msg::RepairChecked, Msg::RepairPending,
+            Msg::RepairFixed, Msg::RepairStillFailed, Msg::RepairUndated,
+        ];
+        assert!(labels.iter().all(|label| label.in_lang(Lang::En).len() == 14));
+        assert_eq!(
+            fill_template(Msg::TimeBudgetExhausted.in_lang(Lang::Zh), &[&3, &4, &"75.0"]),
+            "时间预算用尽 (--max-duration): 已处理 3/4 个日期 (75.0%)，其余日期留待下次运行"
+        );
+        // 进度条模板在两种语言下都能被 indicatif 解析
+        for lang in [Lang::Zh, Lang::En] {
+            assert!(indicatif::ProgressStyle::default_bar()
+                .template(Msg::ProgressTemplate.in_lang(lang))
+                .is_ok());
+        }
+        // 统计摘要的标签等宽，保证数值对齐
+        let labels = [Msg::Total, Msg::Succeeded, Msg::Failed, Msg::SuccessRate];
+        let widths: Vec<_> = labels.iter().map(|m| m.in_lang(Lang::En).len()).collect();
+        assert!(widths.windows(2).all(|w| w[0] == w[1]));
+    }
+}
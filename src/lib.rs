@@ -3,21 +3,28 @@ mod error;
 
 // 模块导出
 pub mod cli;
+pub mod color;
 pub mod config;
 pub mod downloader;
 pub mod exif;
 pub mod filename;
 pub mod fileops;
+pub mod i18n;
+pub mod lunar;
+pub mod progress;
 pub mod validator;
 
 // 重新导出常用类型
 pub use error::{AppError, Result, RetryableError};
 
-use chrono::{NaiveDate, Utc};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 /// 下载统计信息
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct DownloadStats {
     pub total: usize,
     pub succeeded: usize,
@@ -25,6 +32,25 @@ pub struct DownloadStats {
     pub skipped: usize,
     pub failed_dates: Vec<String>,
     pub succeeded_dates: Vec<String>,
+    pub skipped_dates: Vec<String>,
+    /// 每个日期的处理结果
+    pub results: Vec<DateResult>,
+    /// EXIF 或文件时间戳更新失败的日期及原因
+    pub metadata_failures: Vec<(String, String)>,
+    /// 已存在且 EXIF 日期和修改时间已是最新、跳过重写的文件数量
+    pub metadata_up_to_date: usize,
+    /// 因 `--fail-fast` 或 `--max-duration` 未处理完成的日期数量（不计入失败）
+    pub cancelled: usize,
+}
+
+/// 单个日期的处理结果
+#[derive(Debug, Clone, Serialize)]
+pub struct DateResult {
+    pub date: String,
+    /// downloaded、skipped 或 failed
+    pub status: &'static str,
+    pub path: Option<PathBuf>,
+    pub error: Option<String>,
 }
 
 impl DownloadStats {
@@ -53,6 +79,42 @@ impl DownloadStats {
         self.skipped += 1;
     }
 
+    pub fn record_skip_with_date(&mut self, date: &str) {
+        self.skipped += 1;
+        self.skipped_dates.push(date.to_string());
+    }
+
+    /// 记录单个日期的处理结果，同时更新对应的计数
+    pub fn record_result(&mut self, date: &str, result: &ProcessResult) {
+        let (status, error) = match result {
+            ProcessResult::Downloaded(_) => {
+                self.record_success_with_date(date);
+                ("downloaded", None)
+            }
+            ProcessResult::AlreadyExists(_) => {
+                self.record_skip_with_date(date);
+                ("skipped", None)
+            }
+            ProcessResult::Failed(e) => {
+                self.record_failure(date);
+                ("failed", Some(e.clone()))
+            }
+        };
+
+        self.results.push(DateResult {
+            date: date.to_string(),
+            status,
+            path: result.path().cloned(),
+            error,
+        });
+    }
+
+    /// 记录元数据（EXIF、文件时间戳）更新失败，不影响成功/失败计数
+    pub fn record_metadata_failure(&mut self, date: &str, reason: &str) {
+        self.metadata_failures
+            .push((date.to_string(), reason.to_string()));
+    }
+
     pub fn success_rate(&self) -> f64 {
         if self.total == 0 {
             return 0.0;
@@ -71,6 +133,30 @@ impl DownloadStats {
             .filter_map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
             .max()
     }
+
+    /// 获取从日期列表开头起连续完成的最后一个日期
+    ///
+    /// 成功和跳过（文件已存在）都视为完成；遇到第一个失败或未记录结果的日期即停止。
+    /// 用于自动推进 `start_date`，确保不会越过失败日期留下的空洞。
+    pub fn contiguous_success_end(&self, dates: &[NaiveDate]) -> Option<NaiveDate> {
+        let completed: HashSet<&str> = self
+            .succeeded_dates
+            .iter()
+            .chain(self.skipped_dates.iter())
+            .map(String::as_str)
+            .collect();
+        let failed: HashSet<&str> = self.failed_dates.iter().map(String::as_str).collect();
+
+        let mut end = None;
+        for date in dates {
+            let date_str = date_utils::format_date(date);
+            if failed.contains(date_str.as_str()) || !completed.contains(date_str.as_str()) {
+                break;
+            }
+            end = Some(*date);
+        }
+        end
+    }
 }
 
 /// 文件处理结果
@@ -107,6 +193,77 @@ pub mod date_utils {
         })
     }
 
+    /// 解析命令行中的日期参数，返回其表示的日期范围（单日时首尾相同）
+    ///
+    /// 除 `YYYY-MM-DD` 外还支持相对 `today` 的关键字：
+    /// - `today`、`yesterday`
+    /// - `N-days-ago`：N 天前
+    /// - `last-N-days`：包含今天在内的最近 N 天
+    pub fn resolve_date_arg(input: &str, today: NaiveDate) -> Result<(NaiveDate, NaiveDate)> {
+        let keyword = input.trim().to_lowercase();
+        let invalid = |details: &str| AppError::InvalidDate {
+            input: input.to_string(),
+            details: details.to_string(),
+        };
+        let days_before = |days: &str| -> Result<NaiveDate> {
+            let days: u64 = days.parse().map_err(|_| invalid("无效的天数"))?;
+            today
+                .checked_sub_days(chrono::Days::new(days))
+                .ok_or_else(|| invalid("日期超出范围"))
+        };
+
+        if keyword == "today" {
+            return Ok((today, today));
+        }
+        if keyword == "yesterday" {
+            let date = days_before("1")?;
+            return Ok((date, date));
+        }
+        if let Some(days) = keyword
+            .strip_suffix("-days-ago")
+            .or_else(|| keyword.strip_suffix("-day-ago"))
+        {
+            let date = days_before(days)?;
+            return Ok((date, date));
+        }
+        if let Some(days) = keyword.strip_prefix("last-").and_then(|rest| {
+            rest.strip_suffix("-days").or_else(|| rest.strip_suffix("-day"))
+        }) {
+            let count: u64 = days.parse().map_err(|_| invalid("无效的天数"))?;
+            if count == 0 {
+                return Err(invalid("天数必须大于 0"));
+            }
+            let start = days_before(&(count - 1).to_string())?;
+            return Ok((start, today));
+        }
+
+        let date = parse_date(input)?;
+        Ok((date, date))
+    }
+
+    /// 解析 `--dates` 中的日期表达式，在 [`resolve_date_arg`] 的基础上支持 `起始..结束` 闭区间
+    ///
+    /// 区间两端可以使用 `resolve_date_arg` 接受的任意写法，起始晚于结束时报错
+    pub fn resolve_date_expr(input: &str, today: NaiveDate) -> Result<(NaiveDate, NaiveDate)> {
+        let Some((start, end)) = input.split_once("..") else {
+            return resolve_date_arg(input, today);
+        };
+
+        let start = resolve_date_arg(start.trim(), today)?.0;
+        let end = resolve_date_arg(end.trim(), today)?.1;
+        if start > end {
+            return Err(AppError::InvalidDate {
+                input: input.to_string(),
+                details: format!(
+                    "起始日期 {} 晚于结束日期 {}，范围应写作 起始..结束",
+                    format_date(&start),
+                    format_date(&end)
+                ),
+            });
+        }
+        Ok((start, end))
+    }
+
     /// 格式化日期为 YYYY-MM-DD
     pub fn format_date(date: &NaiveDate) -> String {
         date.format("%Y-%m-%d").to_string()
@@ -117,6 +274,132 @@ pub mod date_utils {
         Utc::now().date_naive()
     }
 
+    /// 解析月份字符串 (格式: YYYY-MM)，返回该月的第一天和最后一天
+    pub fn parse_month(month_str: &str) -> Result<(NaiveDate, NaiveDate)> {
+        let invalid = |details: &str| AppError::InvalidDate {
+            input: month_str.to_string(),
+            details: details.to_string(),
+        };
+
+        let (year, month) = month_str
+            .split_once('-')
+            .ok_or_else(|| invalid("月份格式应为 YYYY-MM"))?;
+        if year.len() != 4 || month.len() != 2 {
+            return Err(invalid("月份格式应为 YYYY-MM"));
+        }
+        let year: i32 = year.parse().map_err(|_| invalid("无效的年份"))?;
+        let month: u32 = month.parse().map_err(|_| invalid("无效的月份"))?;
+
+        let first = NaiveDate::from_ymd_opt(year, month, 1).ok_or_else(|| invalid("无效的月份"))?;
+        let next_month = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)
+        };
+        let last = next_month
+            .and_then(|d| d.pred_opt())
+            .ok_or_else(|| invalid("日期超出范围"))?;
+
+        Ok((first, last))
+    }
+
+    /// 解析年份字符串 (格式: YYYY)，返回该年的第一天和最后一天
+    ///
+    /// 最后一天不晚于 `today`，避免请求未来的日期；整年都在 `today` 之后时返回错误
+    pub fn parse_year(year_str: &str, today: NaiveDate) -> Result<(NaiveDate, NaiveDate)> {
+        let invalid = |details: String| AppError::InvalidDate {
+            input: year_str.to_string(),
+            details,
+        };
+
+        if year_str.len() != 4 {
+            return Err(invalid("年份格式应为 YYYY".to_string()));
+        }
+        let year: i32 = year_str
+            .parse()
+            .map_err(|_| invalid("无效的年份".to_string()))?;
+
+        let first = NaiveDate::from_ymd_opt(year, 1, 1)
+            .ok_or_else(|| invalid("无效的年份".to_string()))?;
+        let last = NaiveDate::from_ymd_opt(year, 12, 31)
+            .ok_or_else(|| invalid("无效的年份".to_string()))?;
+
+        if first > today {
+            return Err(invalid(format!("年份晚于今天 ({})", format_date(&today))));
+        }
+
+        Ok((first, last.min(today)))
+    }
+
+    /// 解析时间间隔 (如 `90s`、`30m`、`24h`、`1d`)，必须大于 0
+    pub fn parse_duration(input: &str) -> Result<std::time::Duration> {
+        let invalid = || {
+            AppError::argument_error(format!(
+                "无效的时间间隔 '{}'，应为正整数加单位 s、m、h 或 d，如 24h",
+                input
+            ))
+        };
+
+        let input = input.trim();
+        let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+        let (number, unit) = input.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let seconds = match unit {
+            "s" => Some(number),
+            "m" => number.checked_mul(60),
+            "h" => number.checked_mul(3600),
+            "d" => number.checked_mul(86400),
+            _ => None,
+        }
+        .filter(|secs| *secs > 0)
+        .ok_or_else(invalid)?;
+
+        Ok(std::time::Duration::from_secs(seconds))
+    }
+
+    /// 计算 `now` 之后下一次到达每日时刻 `at` 的时间（本地时间）
+    ///
+    /// `now` 恰好等于当天的 `at` 时返回第二天
+    pub fn next_run_time(
+        now: chrono::NaiveDateTime,
+        at: chrono::NaiveTime,
+    ) -> chrono::NaiveDateTime {
+        let today = now.date().and_time(at);
+        if today > now {
+            today
+        } else {
+            today + chrono::Duration::days(1)
+        }
+    }
+
+    /// 解析按行列出的日期（如失败日期记录文件）
+    ///
+    /// 每行取第一个字段作为日期，忽略空行和 `#` 注释，兼容 `日期<TAB>原因` 格式。
+    /// 结果按时间排序并去重；格式错误时报告所在行号。
+    pub fn parse_date_lines(content: &str) -> Result<Vec<NaiveDate>> {
+        let mut dates = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let token = line.split_whitespace().next().unwrap_or(line);
+            let date = NaiveDate::parse_from_str(token, "%Y-%m-%d").map_err(|e| {
+                AppError::InvalidDate {
+                    input: token.to_string(),
+                    details: format!("第 {} 行: {}", index + 1, e),
+                }
+            })?;
+            dates.push(date);
+        }
+
+        dates.sort();
+        dates.dedup();
+        Ok(dates)
+    }
+
     /// 生成交间范围的所有日期
     pub fn date_range(start: NaiveDate, end: NaiveDate) -> Vec<NaiveDate> {
         let mut dates = Vec::new();
@@ -129,19 +412,620 @@ pub mod date_utils {
     }
 }
 
-/// 构建年份目录路径
-pub fn build_year_path(base_dir: &Path, year: i32) -> PathBuf {
+/// 构建年份目录路径，目录不存在时创建
+///
+/// # 错误
+/// 无法创建目录（如输出目录没有写权限）时返回文件操作错误
+pub fn build_year_path(base_dir: &Path, year: i32) -> Result<PathBuf> {
     let year_dir = base_dir.join(year.to_string());
-    std::fs::create_dir_all(&year_dir).unwrap_or_else(|e| {
-        tracing::warn!("Failed to create directory {:?}: {}", year_dir, e);
+    std::fs::create_dir_all(&year_dir).map_err(|e| {
+        AppError::file_error(&year_dir, format!("无法创建年份目录: {}", e))
+    })?;
+    Ok(year_dir)
+}
+
+/// 构建日期对应文件所在的目录
+///
+/// `year_subdirs` 为 true 时按年份归档到 `base_dir/<year>`，否则直接使用 `base_dir`
+pub fn build_date_dir(base_dir: &Path, date: &NaiveDate, year_subdirs: bool) -> Result<PathBuf> {
+    if year_subdirs {
+        build_year_path(base_dir, date.year())
+    } else {
+        Ok(base_dir.to_path_buf())
+    }
+}
+
+/// 日期对应文件所在目录的路径，与 [`build_date_dir`] 相同但不创建目录
+///
+/// 用于试运行和只读命令（如 list、url）计算路径，不在输出目录中留下空的年份目录
+pub fn date_dir_path(base_dir: &Path, date: &NaiveDate, year_subdirs: bool) -> PathBuf {
+    if year_subdirs {
+        base_dir.join(date.year().to_string())
+    } else {
+        base_dir.to_path_buf()
+    }
+}
+
+/// 与 [`build_date_dir`] 一致的目录格式，用于 [`fileops::scan_library`] 判断文件是否在原位
+///
+/// 按年份存放时为 `{yyyy}`，直接存放在根目录时为 `None`
+pub fn dir_formatter(year_subdirs: bool) -> Result<Option<filename::FilenameFormatter>> {
+    year_subdirs.then(|| filename::FilenameFormatter::new("{yyyy}")).transpose()
+}
+
+/// 构建日期对应文件的完整路径
+///
+/// 与下载时保存文件的位置一致：`build_date_dir` 目录下按 `formatter` 生成文件名，
+/// 文件名包含文件系统不允许的字符时按 [`check_file_name`](filename::FilenameFormatter::check_file_name) 报错或清理。
+/// 格式包含 `{ext}` 时，默认扩展名的文件不存在则依次查找其他已知扩展名的文件，
+/// 都不存在时返回默认扩展名的路径。
+pub fn build_file_path(
+    base_dir: &Path,
+    date: &NaiveDate,
+    formatter: &filename::FilenameFormatter,
+    year_subdirs: bool,
+) -> Result<PathBuf> {
+    let name = formatter.check_file_name(formatter.format(date))?;
+    let path = build_date_dir(base_dir, date, year_subdirs)?.join(name);
+    Ok(find_existing_ext(path, formatter))
+}
+
+/// 与 [`build_file_path`] 相同，但不创建年份目录
+///
+/// 用于 rename/migrate 的计划和只读命令；真正写入文件时由
+/// [`fileops::move_file`] 等创建目标的上级目录。
+pub fn plan_file_path(
+    base_dir: &Path,
+    date: &NaiveDate,
+    formatter: &filename::FilenameFormatter,
+    year_subdirs: bool,
+) -> Result<PathBuf> {
+    let name = formatter.check_file_name(formatter.format(date))?;
+    let path = date_dir_path(base_dir, date, year_subdirs).join(name);
+    Ok(find_existing_ext(path, formatter))
+}
+
+/// 与 [`build_file_path`] 相同，文件名按给定的上下文（如实际请求的 URL）生成
+///
+/// 使用 [`FilenameFormatter::try_format`](filename::FilenameFormatter::try_format)，
+/// 文件名中仍有未替换的占位符时返回错误，用于确定下载的保存路径。
+pub fn build_file_path_with(
+    base_dir: &Path,
+    date: &NaiveDate,
+    formatter: &filename::FilenameFormatter,
+    context: &filename::FormatContext,
+    year_subdirs: bool,
+) -> Result<PathBuf> {
+    let name = formatter.check_file_name(formatter.try_format(date, context)?)?;
+    let path = build_date_dir(base_dir, date, year_subdirs)?.join(name);
+    Ok(find_existing_ext(path, formatter))
+}
+
+/// 格式包含 `{ext}` 且默认扩展名的文件不存在时，查找其他已知扩展名的已有文件
+fn find_existing_ext(path: PathBuf, formatter: &filename::FilenameFormatter) -> PathBuf {
+    if formatter.default_ext().is_none() || fileops::file_exists(&path) {
+        return path;
+    }
+    filename::KNOWN_EXTENSIONS
+        .iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|candidate| fileops::file_exists(candidate))
+        .unwrap_or(path)
+}
+
+/// 检查日期范围内每个日期的保存路径（相对输出目录）互不相同
+///
+/// 发现冲突时返回 [`AppError::DuplicateFilenames`]，给出第一组冲突的两个日期。
+pub fn ensure_unique_paths(
+    dates: impl IntoIterator<Item = NaiveDate>,
+    formatter: &filename::FilenameFormatter,
+    year_subdirs: bool,
+) -> Result<()> {
+    let mut seen: HashMap<String, NaiveDate> = HashMap::new();
+    for date in dates {
+        let name = formatter.check_file_name(formatter.format(&date))?;
+        let path = if year_subdirs { format!("{}/{}", date.year(), name) } else { name };
+        match seen.entry(path) {
+            Entry::Occupied(entry) => {
+                return Err(AppError::DuplicateFilenames {
+                    format: formatter.format_str().to_string(),
+                    first: date_utils::format_date(entry.get()),
+                    second: date_utils::format_date(&date),
+                    path: entry.key().clone(),
+                });
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(date);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 检查文件路径解析符号链接后仍位于输出目录内，防止文件名格式把文件写到输出目录以外
+///
+/// 尚不存在的目录按最近一级已存在的上级目录解析。
+pub fn ensure_within_output_dir(
+    output_dir: &Path,
+    path: &Path,
+    formatter: &filename::FilenameFormatter,
+) -> Result<()> {
+    let base = resolve_existing_prefix(output_dir);
+    let dir = resolve_existing_prefix(path.parent().unwrap_or(output_dir));
+    if dir.starts_with(&base) {
+        return Ok(());
+    }
+    Err(AppError::FilenameFormatError {
+        format: formatter.format_str().to_string(),
+        details: format!("生成的路径 {} 不在输出目录 {} 内", path.display(), output_dir.display()),
+    })
+}
+
+/// 将路径中已存在的最长前缀规范化（解析符号链接），再拼接其余部分
+///
+/// `..` 在其之前的部分解析后再回退一级，不存在的部分按字面回退
+fn resolve_existing_prefix(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                let current = if normalized.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    normalized.as_path()
+                };
+                if let Ok(resolved) = current.canonicalize() {
+                    normalized = resolved;
+                }
+                normalized.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+
+    let path = normalized.as_path();
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        let candidate = if existing.as_os_str().is_empty() { Path::new(".") } else { existing };
+        if let Ok(resolved) = candidate.canonicalize() {
+            return rest.iter().rev().fold(resolved, |acc, name| acc.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// 将输出目录中的文件反向解析为日期
+///
+/// 按文件相对 `output_dir` 路径的最后几级（与格式中的 `/` 层级一致）进行匹配，
+/// 不符合文件名格式时返回 `None`。下面扫描整个图库的函数会先调用
+/// [`FilenameFormatter::ensure_parseable`](filename::FilenameFormatter::ensure_parseable)，
+/// 格式缺少年、月、日时直接返回错误，而不是把所有文件都当作无法识别。
+pub fn library_file_date(
+    output_dir: &Path,
+    path: &Path,
+    formatter: &filename::FilenameFormatter,
+) -> Option<NaiveDate> {
+    let depth = formatter.path_depth();
+    let relative = path.strip_prefix(output_dir).unwrap_or(path);
+    let components: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if components.len() < depth {
+        return None;
+    }
+
+    formatter.parse(&components[components.len() - depth..].join("/"))
+}
+
+/// 扫描输出目录，将符合文件名格式的文件映射回日期
+///
+/// 无法解析的文件会被忽略。
+pub fn library_dates(
+    output_dir: &Path,
+    formatter: &filename::FilenameFormatter,
+) -> Result<BTreeMap<NaiveDate, PathBuf>> {
+    Ok(library_dates_with_unrecognized(output_dir, formatter)?.0)
+}
+
+/// 扫描输出目录，将符合文件名格式的文件映射回日期，并返回无法解析日期的文件
+///
+/// 内部文件（锁文件、状态文件、EXIF 备份等）、临时文件和附属文件不计入未识别文件；
+/// 同一日期有多个文件时取路径最小的一个。
+pub fn library_dates_with_unrecognized(
+    output_dir: &Path,
+    formatter: &filename::FilenameFormatter,
+) -> Result<(BTreeMap<NaiveDate, PathBuf>, Vec<PathBuf>)> {
+    formatter.ensure_parseable()?;
+    let mut dates: BTreeMap<NaiveDate, PathBuf> = BTreeMap::new();
+    let mut unrecognized = Vec::new();
+
+    for entry in fileops::scan_library(output_dir, formatter, None) {
+        match entry.date {
+            Some(date) => match dates.entry(date) {
+                std::collections::btree_map::Entry::Vacant(slot) => {
+                    slot.insert(entry.path);
+                }
+                std::collections::btree_map::Entry::Occupied(mut slot) => {
+                    if entry.path < *slot.get() {
+                        slot.insert(entry.path);
+                    }
+                }
+            },
+            None => unrecognized.push(entry.path),
+        }
+    }
+
+    unrecognized.sort();
+    Ok((dates, unrecognized))
+}
+
+/// 图库中的单个文件
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibraryFile {
+    pub date: NaiveDate,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// 单个年份的文件统计
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct YearStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// 图库统计信息
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct LibraryStats {
+    /// 符合文件名格式的文件数量
+    pub total_files: usize,
+    /// 符合文件名格式的文件总大小（字节）
+    pub total_bytes: u64,
+    /// 不符合文件名格式的文件数量
+    pub unrecognized: usize,
+    /// 按年份统计
+    pub per_year: BTreeMap<i32, YearStats>,
+    pub earliest: Option<NaiveDate>,
+    pub latest: Option<NaiveDate>,
+    /// 最早和最晚日期之间缺失的天数
+    pub missing_days: usize,
+    /// 最早和最晚日期之间连续缺失的区间数量
+    pub gaps: usize,
+    pub largest: Option<LibraryFile>,
+    pub smallest: Option<LibraryFile>,
+    /// 输出目录中全部文件的磁盘占用（含未识别、临时和内部文件），以及因权限不足跳过的条目
+    pub disk: fileops::DirStats,
+}
+
+/// 统计图库的文件数量、大小和日期覆盖情况，以及输出目录的磁盘占用
+pub fn library_stats(
+    output_dir: &Path,
+    formatter: &filename::FilenameFormatter,
+) -> Result<LibraryStats> {
+    formatter.ensure_parseable()?;
+    // 图库文件和磁盘占用在同一次遍历中统计
+    let (mut entries, disk) = fileops::scan_library_with_disk_stats(output_dir, formatter, None);
+    let mut stats = LibraryStats { disk, ..Default::default() };
+    let mut dates = BTreeSet::new();
+
+    // 按路径排序，大小相同时最大/最小文件的结果保持确定
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    for entry in entries {
+        let Some(date) = entry.date else {
+            stats.unrecognized += 1;
+            continue;
+        };
+
+        let (path, size) = (entry.path, entry.size);
+        stats.total_files += 1;
+        stats.total_bytes += size;
+        let year = stats.per_year.entry(date.year()).or_default();
+        year.files += 1;
+        year.bytes += size;
+        dates.insert(date);
+
+        let file = LibraryFile { date, path, size };
+        if stats.largest.as_ref().is_none_or(|f| size > f.size) {
+            stats.largest = Some(file.clone());
+        }
+        if stats.smallest.as_ref().is_none_or(|f| size < f.size) {
+            stats.smallest = Some(file);
+        }
+    }
+
+    stats.earliest = dates.first().copied();
+    stats.latest = dates.last().copied();
+    let mut previous: Option<NaiveDate> = None;
+    for date in &dates {
+        if let Some(previous) = previous {
+            let missing = (*date - previous).num_days() as usize - 1;
+            if missing > 0 {
+                stats.missing_days += missing;
+                stats.gaps += 1;
+            }
+        }
+        previous = Some(*date);
+    }
+
+    Ok(stats)
+}
+
+/// 列出图库中日期早于 `cutoff` 的文件，按日期排序
+///
+/// 只包含符合文件名格式的文件，无法解析日期的文件和临时文件不会出现在结果中。
+pub fn library_files_before(
+    output_dir: &Path,
+    formatter: &filename::FilenameFormatter,
+    cutoff: NaiveDate,
+) -> Result<Vec<LibraryFile>> {
+    formatter.ensure_parseable()?;
+    let files = fileops::scan_library(output_dir, formatter, None)
+        .filter_map(|entry| {
+            let date = entry.date.filter(|date| *date < cutoff)?;
+            Some(LibraryFile { date, path: entry.path, size: entry.size })
+        });
+    let mut files: Vec<LibraryFile> = files.collect();
+
+    files.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.path.cmp(&b.path)));
+    Ok(files)
+}
+
+/// 一组内容完全相同的文件
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub sha256: String,
+    /// 单个文件的大小（字节）
+    pub size: u64,
+    /// 按日期排序，第一个为保留的文件
+    pub files: Vec<LibraryFile>,
+    /// 折叠为一个文件后可释放的空间，已互为硬链接的文件不重复计算
+    pub recoverable: u64,
+}
+
+/// 查找图库中内容完全相同的文件
+///
+/// 先按文件大小筛选候选，再并行计算 SHA-256 分组。只处理符合文件名格式的普通文件，
+/// 符号链接会被忽略。返回的分组按最早日期排序。
+pub fn find_duplicates(
+    output_dir: &Path,
+    formatter: &filename::FilenameFormatter,
+) -> Result<Vec<DuplicateGroup>> {
+    formatter.ensure_parseable()?;
+    let mut by_size: BTreeMap<u64, Vec<LibraryFile>> = BTreeMap::new();
+    for entry in fileops::scan_library(output_dir, formatter, None) {
+        let Some(date) = entry.date else {
+            continue;
+        };
+        let is_symlink = std::fs::symlink_metadata(&entry.path)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if is_symlink {
+            continue;
+        }
+        let size = entry.size;
+        by_size.entry(size).or_default().push(LibraryFile { date, path: entry.path, size });
+    }
+
+    let candidates: Vec<LibraryFile> = by_size
+        .into_values()
+        .filter(|files| files.len() > 1)
+        .flatten()
+        .collect();
+
+    // 按线程数切分候选文件并行计算哈希
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = candidates.len().div_ceil(threads).max(1);
+    let hashes: Vec<Result<String>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = candidates
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|file| fileops::sha256_file(&file.path))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("哈希线程异常退出"))
+            .collect()
     });
-    year_dir
+
+    let mut by_hash: BTreeMap<(u64, String), Vec<LibraryFile>> = BTreeMap::new();
+    for (file, hash) in candidates.into_iter().zip(hashes) {
+        by_hash.entry((file.size, hash?)).or_default().push(file);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|((size, sha256), mut files)| {
+            files.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.path.cmp(&b.path)));
+            let mut identities = HashSet::new();
+            let distinct = files
+                .iter()
+                .filter(|f| fileops::file_identity(&f.path).is_none_or(|id| identities.insert(id)))
+                .count() as u64;
+            DuplicateGroup {
+                sha256,
+                size,
+                files,
+                recoverable: size * distinct.saturating_sub(1),
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.files[0].date.cmp(&b.files[0].date));
+
+    Ok(groups)
+}
+
+/// 计划中的一次文件移动
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedMove {
+    /// 从文件名解析出的日期
+    pub date: NaiveDate,
+    /// 当前路径
+    pub source: PathBuf,
+    /// 目标路径
+    pub target: PathBuf,
+}
+
+/// 图库重命名/迁移计划
+#[derive(Debug, Default)]
+pub struct RenamePlan {
+    /// 可以直接移动的文件
+    pub renames: Vec<PlannedMove>,
+    /// 已在目标位置、无需改动的文件
+    pub unchanged: Vec<PathBuf>,
+    /// 无法按文件名格式解析的文件
+    pub unrecognized: Vec<PathBuf>,
+    /// 目标路径已存在的文件
+    pub existing: Vec<PlannedMove>,
+    /// 多个文件映射到同一目标的文件
+    pub duplicates: Vec<PlannedMove>,
+}
+
+impl RenamePlan {
+    /// 冲突（目标已存在或重复映射）的文件数量
+    pub fn conflict_count(&self) -> usize {
+        self.existing.len() + self.duplicates.len()
+    }
+}
+
+/// 按旧文件名格式解析输出目录中的文件，计算按新格式和目录布局存放时的路径
+///
+/// `from` 与 `to` 相同时即为按当前布局整理目录。临时文件不参与；
+/// 存在冲突的文件不会出现在 `renames` 中，避免覆盖已有文件。
+pub fn plan_renames(
+    output_dir: &Path,
+    from: &filename::FilenameFormatter,
+    to: &filename::FilenameFormatter,
+    year_subdirs: bool,
+) -> Result<RenamePlan> {
+    from.ensure_parseable()?;
+    let mut plan = RenamePlan::default();
+    let mut targets: BTreeMap<PathBuf, Vec<(NaiveDate, PathBuf)>> = BTreeMap::new();
+
+    for entry in fileops::scan_library(output_dir, from, None) {
+        let path = entry.path;
+        match entry.date {
+            Some(date) => {
+                let target = plan_file_path(output_dir, &date, to, year_subdirs)?;
+                if target == path {
+                    plan.unchanged.push(path);
+                } else {
+                    targets.entry(target).or_default().push((date, path));
+                }
+            }
+            None => plan.unrecognized.push(path),
+        }
+    }
+
+    // 扫描顺序不确定，排序使计划稳定
+    plan.unchanged.sort();
+    plan.unrecognized.sort();
+    for (target, mut sources) in targets {
+        sources.sort();
+        let duplicate = sources.len() > 1;
+        let exists = target.exists();
+        for (date, source) in sources {
+            let planned = PlannedMove { date, source, target: target.clone() };
+            if duplicate {
+                plan.duplicates.push(planned);
+            } else if exists {
+                plan.existing.push(planned);
+            } else {
+                plan.renames.push(planned);
+            }
+        }
+    }
+
+    Ok(plan)
+}
+
+/// 更新输出目录中指向最新日期图片的 `latest.<扩展名>` 链接
+///
+/// 符号链接使用相对于输出目录的路径，移动整个输出目录后仍然有效；
+/// 文件系统不支持链接时退回到复制。已存在的链接被原子替换。
+///
+/// # 返回
+/// 链接路径和实际采用的方式，图库中没有符合格式的文件时返回 None
+pub fn update_latest_link(
+    output_dir: &Path,
+    formatter: &filename::FilenameFormatter,
+    kind: config::LatestLink,
+) -> Result<Option<(PathBuf, fileops::LinkStrategy)>> {
+    let dates = library_dates(output_dir, formatter)?;
+    let Some((_, latest)) = dates.last_key_value() else {
+        return Ok(None);
+    };
+
+    let mut link = output_dir.join(fileops::LATEST_LINK_STEM);
+    if let Some(ext) = latest.extension() {
+        link.set_extension(ext);
+    }
+    let strategy = match kind {
+        config::LatestLink::Symlink => {
+            let target = latest.strip_prefix(output_dir).unwrap_or(latest);
+            fileops::symlink(target, &link)?
+        }
+        config::LatestLink::Hardlink => fileops::hardlink(latest, &link)?,
+    };
+    Ok(Some((link, strategy)))
+}
+
+/// 记录在输出目录中的运行状态（`run` 每次推进后更新）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, serde::Deserialize)]
+pub struct RunState {
+    /// 从起始日期开始连续成功的最后一天
+    pub last_success: Option<NaiveDate>,
+}
+
+impl RunState {
+    /// 读取输出目录中的状态文件，文件不存在时返回默认值
+    pub fn load(output_dir: &Path) -> Result<Self> {
+        let path = output_dir.join(fileops::STATE_FILE_NAME);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(AppError::file_error(&path, e.to_string())),
+        };
+        serde_json::from_str(&content).map_err(|e| AppError::file_error(&path, e.to_string()))
+    }
+
+    /// 写入输出目录中的状态文件
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        fileops::ensure_dir_exists(output_dir)?;
+        let path = output_dir.join(fileops::STATE_FILE_NAME);
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| AppError::file_error(&path, e.to_string()))?;
+        fileops::write_atomic(&path, content)
+    }
+
+    /// 记录新的成功日期，只向后推进；返回状态是否发生变化
+    pub fn advance(&mut self, date: NaiveDate) -> bool {
+        if self.last_success.is_some_and(|last| last >= date) {
+            return false;
+        }
+        self.last_success = Some(date);
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Datelike;
 
     #[test]
     fn test_parse_date_valid() {
@@ -184,4 +1068,668 @@ mod tests {
         assert_eq!(stats.skipped, 1);
         assert_eq!(stats.success_rate(), 40.0);
     }
+
+    fn dates(start: &str, end: &str) -> Vec<NaiveDate> {
+        date_utils::date_range(
+            date_utils::parse_date(start).unwrap(),
+            date_utils::parse_date(end).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_contiguous_success_end_all_success() {
+        let range = dates("2024-06-01", "2024-06-03");
+        let mut stats = DownloadStats::new(range.len());
+        stats.record_success_with_date("2024-06-01");
+        stats.record_skip_with_date("2024-06-02");
+        stats.record_success_with_date("2024-06-03");
+
+        assert_eq!(
+            stats.contiguous_success_end(&range),
+            Some(date_utils::parse_date("2024-06-03").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_contiguous_success_end_stops_before_failure() {
+        let range = dates("2024-06-01", "2024-06-10");
+        let mut stats = DownloadStats::new(range.len());
+        for day in ["2024-06-01", "2024-06-02", "2024-06-04", "2024-06-10"] {
+            stats.record_success_with_date(day);
+        }
+        stats.record_failure("2024-06-03");
+
+        // 虽然 06-10 成功，但 06-03 失败，只能推进到 06-02
+        assert_eq!(stats.latest_success_date(), Some(date_utils::parse_date("2024-06-10").unwrap()));
+        assert_eq!(
+            stats.contiguous_success_end(&range),
+            Some(date_utils::parse_date("2024-06-02").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_contiguous_success_end_skips_count_as_success() {
+        let range = dates("2024-06-01", "2024-06-04");
+        let mut stats = DownloadStats::new(range.len());
+        stats.record_skip_with_date("2024-06-01");
+        stats.record_skip_with_date("2024-06-02");
+        stats.record_success_with_date("2024-06-03");
+        stats.record_failure("2024-06-04");
+
+        assert_eq!(
+            stats.contiguous_success_end(&range),
+            Some(date_utils::parse_date("2024-06-03").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_contiguous_success_end_first_date_failed() {
+        let range = dates("2024-06-01", "2024-06-03");
+        let mut stats = DownloadStats::new(range.len());
+        stats.record_failure("2024-06-01");
+        stats.record_success_with_date("2024-06-02");
+        stats.record_success_with_date("2024-06-03");
+
+        assert_eq!(stats.contiguous_success_end(&range), None);
+    }
+
+    #[test]
+    fn test_contiguous_success_end_missing_result() {
+        // 任务异常导致没有记录结果的日期同样视为中断
+        let range = dates("2024-06-01", "2024-06-03");
+        let mut stats = DownloadStats::new(range.len());
+        stats.record_success_with_date("2024-06-01");
+        stats.record_success_with_date("2024-06-03");
+
+        assert_eq!(
+            stats.contiguous_success_end(&range),
+            Some(date_utils::parse_date("2024-06-01").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_build_date_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let date = date_utils::parse_date("2024-06-15").unwrap();
+
+        assert_eq!(
+            build_date_dir(temp_dir.path(), &date, true).unwrap(),
+            temp_dir.path().join("2024")
+        );
+        assert!(temp_dir.path().join("2024").is_dir());
+        assert_eq!(build_date_dir(temp_dir.path(), &date, false).unwrap(), temp_dir.path());
+    }
+
+    #[test]
+    fn test_build_year_path_error() {
+        // 输出目录所在位置是普通文件，无法创建年份目录
+        let temp_dir = tempfile::tempdir().unwrap();
+        let blocker = temp_dir.path().join("images");
+        std::fs::write(&blocker, b"").unwrap();
+
+        let err = build_year_path(&blocker, 2024).unwrap_err();
+        assert!(matches!(err, AppError::FileError { ref path, .. } if path == &blocker.join("2024")));
+
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let date = date_utils::parse_date("2024-06-15").unwrap();
+        assert!(build_file_path(&blocker, &date, &formatter, true).is_err());
+    }
+
+    #[test]
+    fn test_ensure_unique_paths() {
+        let june = date_utils::date_range(
+            date_utils::parse_date("2024-06-01").unwrap(),
+            date_utils::parse_date("2024-06-30").unwrap(),
+        );
+        let daily = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        assert!(ensure_unique_paths(june.iter().copied(), &daily, false).is_ok());
+
+        let monthly = filename::FilenameFormatter::new("{yyyy}{mm}.jpg").unwrap();
+        let err = ensure_unique_paths(june.iter().copied(), &monthly, false).unwrap_err();
+        assert!(err.is_config_error());
+        assert!(
+            err.to_string().contains("2024-06-01 和 2024-06-02 都映射到 202406.jpg"),
+            "{}",
+            err
+        );
+
+        // 不含年份的格式按年份目录区分
+        let years = date_utils::date_range(
+            date_utils::parse_date("2023-01-01").unwrap(),
+            date_utils::parse_date("2024-12-31").unwrap(),
+        );
+        let no_year = filename::FilenameFormatter::new("{mm}{dd}.jpg").unwrap();
+        assert!(ensure_unique_paths(years.iter().copied(), &no_year, true).is_ok());
+        let err = ensure_unique_paths(years.iter().copied(), &no_year, false).unwrap_err();
+        assert!(err.to_string().contains("2023-01-01 和 2024-01-01 都映射到 0101.jpg"), "{}", err);
+    }
+
+    #[test]
+    fn test_build_file_path_illegal_chars() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let date = date_utils::parse_date("2024-06-15").unwrap();
+        let formatter = filename::FilenameFormatter::new("{yyyy}-{mm}-{dd} {Weekday}?.jpg").unwrap();
+
+        let err = build_file_path(temp_dir.path(), &date, &formatter, false).unwrap_err();
+        assert!(matches!(err, AppError::FilenameFormatError { .. }));
+        assert!(err.to_string().contains("'?'"), "{}", err);
+
+        let formatter = formatter.with_sanitize(true);
+        assert_eq!(
+            build_file_path(temp_dir.path(), &date, &formatter, false).unwrap(),
+            temp_dir.path().join("2024-06-15 Saturday_.jpg")
+        );
+    }
+
+    #[test]
+    fn test_record_result() {
+        let mut stats = DownloadStats::new(3);
+        stats.record_result("2024-06-01", &ProcessResult::Downloaded(PathBuf::from("a.jpg")));
+        stats.record_result("2024-06-02", &ProcessResult::AlreadyExists(PathBuf::from("b.jpg")));
+        stats.record_result("2024-06-03", &ProcessResult::Failed("HTTP 404".to_string()));
+
+        assert_eq!((stats.succeeded, stats.skipped, stats.failed), (1, 1, 1));
+        assert_eq!(stats.failed_dates, vec!["2024-06-03".to_string()]);
+        assert_eq!(stats.results.len(), 3);
+        assert_eq!(stats.results[0].status, "downloaded");
+        assert_eq!(stats.results[1].path, Some(PathBuf::from("b.jpg")));
+        assert_eq!(stats.results[2].error.as_deref(), Some("HTTP 404"));
+        assert_eq!(stats.results[2].path, None);
+    }
+
+    #[test]
+    fn test_parse_date_lines() {
+        let content = "# 失败的日期\n2024-06-03\n\n2024-06-01\tHTTP 404\n2024-06-03 超时\n";
+        let dates = date_utils::parse_date_lines(content).unwrap();
+        assert_eq!(
+            dates,
+            vec![
+                date_utils::parse_date("2024-06-01").unwrap(),
+                date_utils::parse_date("2024-06-03").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_date_lines_reports_line_number() {
+        let content = "2024-06-01\n2024-13-01\n";
+        let err = date_utils::parse_date_lines(content).unwrap_err();
+        assert!(err.to_string().contains("第 2 行"));
+        assert!(err.to_string().contains("2024-13-01"));
+    }
+
+    #[test]
+    fn test_library_dates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let year_dir = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&year_dir).unwrap();
+        std::fs::write(year_dir.join("20240601.jpg"), b"a").unwrap();
+        std::fs::write(year_dir.join("20240603.jpg"), b"b").unwrap();
+        std::fs::write(year_dir.join("20240603.jpg.part"), b"c").unwrap();
+        std::fs::write(temp_dir.path().join("failed_downloads.txt"), b"d").unwrap();
+
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let dates = library_dates(temp_dir.path(), &formatter).unwrap();
+
+        assert_eq!(
+            dates.keys().copied().collect::<Vec<_>>(),
+            vec![
+                date_utils::parse_date("2024-06-01").unwrap(),
+                date_utils::parse_date("2024-06-03").unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_library_dates_with_unrecognized() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let year_dir = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&year_dir).unwrap();
+        std::fs::write(year_dir.join("20240601.jpg"), b"a").unwrap();
+        std::fs::write(year_dir.join("20240601.jpg.orig"), b"b").unwrap();
+        std::fs::write(year_dir.join("20240602.jpg.part"), b"c").unwrap();
+        std::fs::write(year_dir.join("IMG_0001.jpg"), b"d").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), b"e").unwrap();
+
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let (dates, unrecognized) =
+            library_dates_with_unrecognized(temp_dir.path(), &formatter).unwrap();
+
+        assert_eq!(dates.len(), 1);
+        assert_eq!(
+            dates.get(&date_utils::parse_date("2024-06-01").unwrap()),
+            Some(&year_dir.join("20240601.jpg"))
+        );
+        assert_eq!(
+            unrecognized,
+            vec![year_dir.join("IMG_0001.jpg"), temp_dir.path().join("notes.txt")]
+        );
+    }
+
+    #[test]
+    fn test_ensure_within_output_dir() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_dir = temp_dir.path().join("images");
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+
+        // 输出目录和年份目录尚不存在
+        ensure_within_output_dir(&output_dir, &output_dir.join("2024/20240615.jpg"), &formatter)
+            .unwrap();
+
+        let err = ensure_within_output_dir(
+            &output_dir,
+            &output_dir.join("../outside/20240615.jpg"),
+            &formatter,
+        )
+        .unwrap_err();
+        assert!(matches!(err, AppError::FilenameFormatError { .. }));
+
+        #[cfg(unix)]
+        {
+            // 指向输出目录以外的符号链接目录
+            let outside = temp_dir.path().join("outside");
+            std::fs::create_dir_all(&outside).unwrap();
+            std::fs::create_dir_all(&output_dir).unwrap();
+            std::os::unix::fs::symlink(&outside, output_dir.join("2024")).unwrap();
+            assert!(ensure_within_output_dir(
+                &output_dir,
+                &output_dir.join("2024/20240615.jpg"),
+                &formatter
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_update_latest_link() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        assert_eq!(
+            update_latest_link(root, &formatter, config::LatestLink::Hardlink).unwrap(),
+            None
+        );
+
+        std::fs::create_dir_all(root.join("2024")).unwrap();
+        std::fs::write(root.join("2024/20240601.jpg"), b"a").unwrap();
+        std::fs::write(root.join("2024/20240603.jpg"), b"b").unwrap();
+
+        let (link, _) = update_latest_link(root, &formatter, config::LatestLink::Hardlink)
+            .unwrap()
+            .unwrap();
+        assert_eq!(link, root.join("latest.jpg"));
+        assert_eq!(std::fs::read(&link).unwrap(), b"b");
+
+        // 链接本身不计入图库
+        std::fs::write(root.join("2024/20240605.jpg"), b"c").unwrap();
+        let (dates, unrecognized) = library_dates_with_unrecognized(root, &formatter).unwrap();
+        assert_eq!(dates.len(), 3);
+        assert!(unrecognized.is_empty());
+
+        #[cfg(unix)]
+        {
+            let (link, strategy) = update_latest_link(root, &formatter, config::LatestLink::Symlink)
+                .unwrap()
+                .unwrap();
+            assert_eq!(strategy, fileops::LinkStrategy::Symlink);
+            assert_eq!(std::fs::read_link(&link).unwrap(), PathBuf::from("2024/20240605.jpg"));
+            assert_eq!(std::fs::read(&link).unwrap(), b"c");
+        }
+    }
+
+    #[test]
+    fn test_plan_renames() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let year_dir = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&year_dir).unwrap();
+        std::fs::write(year_dir.join("20240601.jpg"), b"a").unwrap();
+        std::fs::write(year_dir.join("20240602.jpg"), b"b").unwrap();
+        std::fs::write(year_dir.join("20240603.jpg.part"), b"c").unwrap();
+        std::fs::write(year_dir.join("owspace_2024-06-02.jpg"), b"d").unwrap();
+        std::fs::write(year_dir.join("notes.txt"), b"e").unwrap();
+
+        let from = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let to = filename::FilenameFormatter::new("owspace_{yyyy}-{mm}-{dd}.jpg").unwrap();
+        let plan = plan_renames(temp_dir.path(), &from, &to, true).unwrap();
+
+        assert_eq!(
+            plan.renames,
+            vec![PlannedMove {
+                date: date_utils::parse_date("2024-06-01").unwrap(),
+                source: year_dir.join("20240601.jpg"),
+                target: year_dir.join("owspace_2024-06-01.jpg"),
+            }]
+        );
+        assert_eq!(plan.existing.len(), 1);
+        assert_eq!(plan.existing[0].source, year_dir.join("20240602.jpg"));
+        assert_eq!(plan.unrecognized.len(), 2);
+        assert!(plan.unchanged.is_empty());
+        assert!(plan.duplicates.is_empty());
+
+        // 根目录下的文件改名到年份目录时，生成计划不创建目录
+        std::fs::write(temp_dir.path().join("20230601.jpg"), b"f").unwrap();
+        let plan = plan_renames(temp_dir.path(), &from, &to, true).unwrap();
+        assert!(plan
+            .renames
+            .iter()
+            .any(|m| m.target == temp_dir.path().join("2023/owspace_2023-06-01.jpg")));
+        assert!(!temp_dir.path().join("2023").exists());
+    }
+
+    #[test]
+    fn test_plan_renames_duplicate_targets() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let year_dir = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&year_dir).unwrap();
+        // 同一日期的文件同时存在于年份子目录和输出目录根下
+        std::fs::write(year_dir.join("20240601.jpg"), b"a").unwrap();
+        std::fs::write(temp_dir.path().join("20240601.jpg"), b"b").unwrap();
+
+        let from = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let to = filename::FilenameFormatter::new("{yyyy}-{mm}-{dd}.jpg").unwrap();
+        let plan = plan_renames(temp_dir.path(), &from, &to, true).unwrap();
+
+        assert!(plan.renames.is_empty());
+        assert_eq!(plan.duplicates.len(), 2);
+        assert!(plan.duplicates.iter().all(|m| m.target == year_dir.join("2024-06-01.jpg")));
+    }
+
+    #[test]
+    fn test_plan_renames_layout_migration() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let year_dir = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&year_dir).unwrap();
+        std::fs::write(year_dir.join("20240601.jpg"), b"a").unwrap();
+        std::fs::write(temp_dir.path().join("20240602.jpg"), b"b").unwrap();
+
+        // 格式不变、启用年份子目录时，只移动不在年份目录中的文件
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let plan = plan_renames(temp_dir.path(), &formatter, &formatter, true).unwrap();
+        assert_eq!(plan.unchanged, vec![year_dir.join("20240601.jpg")]);
+        assert_eq!(plan.renames.len(), 1);
+        assert_eq!(plan.renames[0].target, year_dir.join("20240602.jpg"));
+
+        // 试运行只生成计划，不创建目标年份目录
+        std::fs::write(temp_dir.path().join("20230601.jpg"), b"c").unwrap();
+        let plan = plan_renames(temp_dir.path(), &formatter, &formatter, true).unwrap();
+        assert_eq!(plan.renames.len(), 2);
+        assert!(!temp_dir.path().join("2023").exists());
+        std::fs::remove_file(temp_dir.path().join("20230601.jpg")).unwrap();
+
+        // 改为按月分目录
+        let formatter = filename::FilenameFormatter::new("{mm}/{yyyy}{mm}{dd}.jpg").unwrap();
+        let from = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let plan = plan_renames(temp_dir.path(), &from, &formatter, true).unwrap();
+        assert_eq!(plan.renames.len(), 2);
+        assert!(plan.renames.iter().all(|m| m.target.starts_with(year_dir.join("06"))));
+    }
+
+    #[test]
+    fn test_library_stats() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir_2023 = temp_dir.path().join("2023");
+        let dir_2024 = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&dir_2023).unwrap();
+        std::fs::create_dir_all(&dir_2024).unwrap();
+        std::fs::write(dir_2023.join("20231231.jpg"), b"abcd").unwrap();
+        std::fs::write(dir_2024.join("20240101.jpg"), b"ab").unwrap();
+        std::fs::write(dir_2024.join("20240105.jpg"), b"abcdef").unwrap();
+        std::fs::write(dir_2024.join("20240107.jpg"), b"abc").unwrap();
+        std::fs::write(dir_2024.join("20240108.jpg.part"), b"x").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), b"y").unwrap();
+
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let stats = library_stats(temp_dir.path(), &formatter).unwrap();
+
+        assert_eq!(stats.total_files, 4);
+        assert_eq!(stats.total_bytes, 15);
+        // 临时文件与其他扫描一样跳过，只有 notes.txt 计为未识别
+        assert_eq!(stats.unrecognized, 1);
+        assert_eq!(stats.per_year[&2023], YearStats { files: 1, bytes: 4 });
+        assert_eq!(stats.per_year[&2024], YearStats { files: 3, bytes: 11 });
+        assert_eq!(stats.earliest, Some(date_utils::parse_date("2023-12-31").unwrap()));
+        assert_eq!(stats.latest, Some(date_utils::parse_date("2024-01-07").unwrap()));
+        // 缺失 01-02..01-04 和 01-06
+        assert_eq!(stats.missing_days, 4);
+        assert_eq!(stats.gaps, 2);
+        assert_eq!(stats.largest.unwrap().path, dir_2024.join("20240105.jpg"));
+        assert_eq!(stats.smallest.unwrap().path, dir_2024.join("20240101.jpg"));
+        // 磁盘占用统计全部文件
+        assert_eq!((stats.disk.files, stats.disk.bytes, stats.disk.skipped), (6, 17, 0));
+        assert_eq!(stats.disk.per_extension["part"], 1);
+        assert_eq!(stats.disk.per_subdir["2024"], fileops::SubdirStats { files: 4, bytes: 12 });
+    }
+
+    #[test]
+    fn test_library_files_before() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir_2023 = temp_dir.path().join("2023");
+        let dir_2024 = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&dir_2023).unwrap();
+        std::fs::create_dir_all(&dir_2024).unwrap();
+        std::fs::write(dir_2023.join("20231231.jpg"), b"abcd").unwrap();
+        std::fs::write(dir_2024.join("20240101.jpg"), b"ab").unwrap();
+        std::fs::write(dir_2024.join("20240102.jpg"), b"abc").unwrap();
+        std::fs::write(dir_2023.join("20230101.jpg.part"), b"x").unwrap();
+        std::fs::write(dir_2023.join("holiday.jpg"), b"y").unwrap();
+
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let cutoff = date_utils::parse_date("2024-01-02").unwrap();
+        let files = library_files_before(temp_dir.path(), &formatter, cutoff).unwrap();
+
+        let paths: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![dir_2023.join("20231231.jpg"), dir_2024.join("20240101.jpg")]);
+        assert_eq!(files.iter().map(|f| f.size).sum::<u64>(), 6);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir_2024 = temp_dir.path().join("2024");
+        std::fs::create_dir_all(&dir_2024).unwrap();
+        std::fs::write(dir_2024.join("20240101.jpg"), b"same").unwrap();
+        std::fs::write(dir_2024.join("20240108.jpg"), b"same").unwrap();
+        std::fs::write(dir_2024.join("20240103.jpg"), b"same").unwrap();
+        // 大小相同但内容不同
+        std::fs::write(dir_2024.join("20240102.jpg"), b"diff").unwrap();
+        std::fs::write(dir_2024.join("20240104.jpg"), b"other").unwrap();
+        // 无法解析日期的文件不参与
+        std::fs::write(dir_2024.join("copy.jpg"), b"same").unwrap();
+
+        let formatter = filename::FilenameFormatter::new("{yyyy}{mm}{dd}.jpg").unwrap();
+        let groups = find_duplicates(temp_dir.path(), &formatter).unwrap();
+
+        assert_eq!(groups.len(), 1);
+        let paths: Vec<_> = groups[0].files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                dir_2024.join("20240101.jpg"),
+                dir_2024.join("20240103.jpg"),
+                dir_2024.join("20240108.jpg"),
+            ]
+        );
+        assert_eq!(groups[0].size, 4);
+        assert_eq!(groups[0].recoverable, 8);
+    }
+
+    #[test]
+    fn test_run_state_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let date = |s: &str| date_utils::parse_date(s).unwrap();
+
+        // 状态文件不存在时为空状态
+        let mut state = RunState::load(temp_dir.path()).unwrap();
+        assert_eq!(state.last_success, None);
+
+        assert!(state.advance(date("2024-06-10")));
+        // 只向后推进
+        assert!(!state.advance(date("2024-06-01")));
+        assert!(!state.advance(date("2024-06-10")));
+        state.save(temp_dir.path()).unwrap();
+
+        let loaded = RunState::load(temp_dir.path()).unwrap();
+        assert_eq!(loaded.last_success, Some(date("2024-06-10")));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        use std::time::Duration;
+
+        assert_eq!(date_utils::parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(date_utils::parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(date_utils::parse_duration("24h").unwrap(), Duration::from_secs(86400));
+        assert_eq!(date_utils::parse_duration("2d").unwrap(), Duration::from_secs(172800));
+        assert!(date_utils::parse_duration("0h").is_err());
+        assert!(date_utils::parse_duration("24").is_err());
+        assert!(date_utils::parse_duration("h").is_err());
+        assert!(date_utils::parse_duration("1w").is_err());
+    }
+
+    #[test]
+    fn test_next_run_time() {
+        let at = chrono::NaiveTime::from_hms_opt(8, 30, 0).unwrap();
+        let datetime = |s: &str| {
+            chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+        };
+
+        assert_eq!(
+            date_utils::next_run_time(datetime("2024-06-15 07:00:00"), at),
+            datetime("2024-06-15 08:30:00")
+        );
+        assert_eq!(
+            date_utils::next_run_time(datetime("2024-06-15 08:30:00"), at),
+            datetime("2024-06-16 08:30:00")
+        );
+        assert_eq!(
+            date_utils::next_run_time(datetime("2024-12-31 23:00:00"), at),
+            datetime("2025-01-01 08:30:00")
+        );
+    }
+
+    #[test]
+    fn test_parse_month() {
+        let (first, last) = date_utils::parse_month("2024-06").unwrap();
+        assert_eq!(date_utils::format_date(&first), "2024-06-01");
+        assert_eq!(date_utils::format_date(&last), "2024-06-30");
+
+        let (_, last) = date_utils::parse_month("2024-12").unwrap();
+        assert_eq!(date_utils::format_date(&last), "2024-12-31");
+    }
+
+    #[test]
+    fn test_parse_month_february_leap_year() {
+        let (_, last) = date_utils::parse_month("2024-02").unwrap();
+        assert_eq!(date_utils::format_date(&last), "2024-02-29");
+
+        let (_, last) = date_utils::parse_month("2023-02").unwrap();
+        assert_eq!(date_utils::format_date(&last), "2023-02-28");
+
+        let (_, last) = date_utils::parse_month("1900-02").unwrap();
+        assert_eq!(date_utils::format_date(&last), "1900-02-28");
+    }
+
+    #[test]
+    fn test_parse_month_invalid() {
+        assert!(date_utils::parse_month("2024-13").is_err());
+        assert!(date_utils::parse_month("2024-00").is_err());
+        assert!(date_utils::parse_month("2024-6").is_err());
+        assert!(date_utils::parse_month("2024/06").is_err());
+        assert!(date_utils::parse_month("2024-06-01").is_err());
+    }
+
+    #[test]
+    fn test_parse_year() {
+        let today = date_utils::parse_date("2026-10-16").unwrap();
+
+        let (first, last) = date_utils::parse_year("2023", today).unwrap();
+        assert_eq!(date_utils::format_date(&first), "2023-01-01");
+        assert_eq!(date_utils::format_date(&last), "2023-12-31");
+
+        // 闰年的天数
+        let (first, last) = date_utils::parse_year("2024", today).unwrap();
+        assert_eq!(date_utils::date_range(first, last).len(), 366);
+    }
+
+    #[test]
+    fn test_parse_year_clamps_to_today() {
+        let today = date_utils::parse_date("2026-10-16").unwrap();
+
+        let (first, last) = date_utils::parse_year("2026", today).unwrap();
+        assert_eq!(date_utils::format_date(&first), "2026-01-01");
+        assert_eq!(last, today);
+
+        assert!(date_utils::parse_year("2027", today).is_err());
+    }
+
+    #[test]
+    fn test_parse_year_invalid() {
+        let today = date_utils::parse_date("2026-10-16").unwrap();
+        assert!(date_utils::parse_year("24", today).is_err());
+        assert!(date_utils::parse_year("20x4", today).is_err());
+        assert!(date_utils::parse_year("2024-01", today).is_err());
+    }
+
+    #[test]
+    fn test_resolve_date_arg_keywords() {
+        let today = date_utils::parse_date("2024-03-02").unwrap();
+        let date = |s: &str| date_utils::parse_date(s).unwrap();
+
+        assert_eq!(date_utils::resolve_date_arg("today", today).unwrap(), (today, today));
+        assert_eq!(
+            date_utils::resolve_date_arg("yesterday", today).unwrap(),
+            (date("2024-03-01"), date("2024-03-01"))
+        );
+        assert_eq!(
+            date_utils::resolve_date_arg("3-days-ago", today).unwrap(),
+            (date("2024-02-28"), date("2024-02-28"))
+        );
+        assert_eq!(
+            date_utils::resolve_date_arg("last-7-days", today).unwrap(),
+            (date("2024-02-25"), today)
+        );
+        assert_eq!(
+            date_utils::resolve_date_arg("last-1-day", today).unwrap(),
+            (today, today)
+        );
+    }
+
+    #[test]
+    fn test_resolve_date_arg_plain_date() {
+        let today = date_utils::parse_date("2024-03-02").unwrap();
+        let date = date_utils::parse_date("2023-06-15").unwrap();
+        assert_eq!(date_utils::resolve_date_arg("2023-06-15", today).unwrap(), (date, date));
+    }
+
+    #[test]
+    fn test_resolve_date_arg_invalid() {
+        let today = date_utils::parse_date("2024-03-02").unwrap();
+        assert!(date_utils::resolve_date_arg("tomorrow", today).is_err());
+        assert!(date_utils::resolve_date_arg("x-days-ago", today).is_err());
+        assert!(date_utils::resolve_date_arg("last-0-days", today).is_err());
+        assert!(date_utils::resolve_date_arg("2024-13-01", today).is_err());
+    }
+
+    #[test]
+    fn test_resolve_date_expr_range() {
+        let today = date_utils::parse_date("2024-03-02").unwrap();
+        let date = |s: &str| date_utils::parse_date(s).unwrap();
+
+        assert_eq!(
+            date_utils::resolve_date_expr("2024-06-01..2024-06-10", today).unwrap(),
+            (date("2024-06-01"), date("2024-06-10"))
+        );
+        assert_eq!(
+            date_utils::resolve_date_expr("2024-02-28..today", today).unwrap(),
+            (date("2024-02-28"), today)
+        );
+        assert_eq!(
+            date_utils::resolve_date_expr("2024-06-15", today).unwrap(),
+            (date("2024-06-15"), date("2024-06-15"))
+        );
+
+        let err = date_utils::resolve_date_expr("2024-06-10..2024-06-01", today).unwrap_err();
+        assert!(err.to_string().contains("晚于"));
+        assert!(date_utils::resolve_date_expr("2024-06-01..", today).is_err());
+    }
 }
@@ -0,0 +1,201 @@
+//! 农历（阴历）转换
+//!
+//! 基于 1990–2100 年的农历数据表将公历日期转换为农历日期，用于 `{lunar_year}`、
+//! `{lunar_month}`、`{lunar_day}` 占位符。
+
+use chrono::NaiveDate;
+
+/// 数据表覆盖的第一个农历年
+const FIRST_YEAR: i32 = 1990;
+
+/// 1990–2100 年每个农历年的数据
+///
+/// - 低 4 位：闰月月份，0 表示没有闰月
+/// - 第 4–15 位：十二月到正月是否为大月（30 天），第 15 位对应正月
+/// - 第 16 位：闰月是否为大月
+const LUNAR_INFO: [u32; 111] = [
+    0x04af5, 0x04970, 0x064b0, 0x074a3, 0x0ea50, 0x06b58, 0x05ac0, 0x0ab60, 0x096d5, 0x092e0, // 1990-1999
+    0x0c960, 0x0d954, 0x0d4a0, 0x0da50, 0x07552, 0x056a0, 0x0abb7, 0x025d0, 0x092d0, 0x0cab5, // 2000-2009
+    0x0a950, 0x0b4a0, 0x0baa4, 0x0ad50, 0x055d9, 0x04ba0, 0x0a5b0, 0x15176, 0x052b0, 0x0a930, // 2010-2019
+    0x07954, 0x06aa0, 0x0ad50, 0x05b52, 0x04b60, 0x0a6e6, 0x0a4e0, 0x0d260, 0x0ea65, 0x0d530, // 2020-2029
+    0x05aa0, 0x076a3, 0x096d0, 0x04afb, 0x04ad0, 0x0a4d0, 0x1d0b6, 0x0d250, 0x0d520, 0x0dd45, // 2030-2039
+    0x0b5a0, 0x056d0, 0x055b2, 0x049b0, 0x0a577, 0x0a4b0, 0x0aa50, 0x1b255, 0x06d20, 0x0ada0, // 2040-2049
+    0x14b63, 0x09370, 0x049f8, 0x04970, 0x064b0, 0x168a6, 0x0ea50, 0x06b20, 0x1a6c4, 0x0aae0, // 2050-2059
+    0x092e0, 0x0d2e3, 0x0c960, 0x0d557, 0x0d4a0, 0x0da50, 0x05d55, 0x056a0, 0x0a6d0, 0x055d4, // 2060-2069
+    0x052d0, 0x0a9b8, 0x0a950, 0x0b4a0, 0x0b6a6, 0x0ad50, 0x055a0, 0x0aba4, 0x0a5b0, 0x052b0, // 2070-2079
+    0x0b273, 0x06930, 0x07337, 0x06aa0, 0x0ad50, 0x14b55, 0x04b60, 0x0a570, 0x054e4, 0x0d160, // 2080-2089
+    0x0e968, 0x0d520, 0x0daa0, 0x16aa6, 0x056d0, 0x04ae0, 0x0a9d4, 0x0a2d0, 0x0d150, 0x0f252, // 2090-2099
+    0x0d520, // 2100
+];
+
+/// 农历月份名称
+const MONTH_NAMES: [&str; 12] =
+    ["正", "二", "三", "四", "五", "六", "七", "八", "九", "十", "冬", "腊"];
+
+/// 农历日期
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LunarDate {
+    /// 农历年（正月初一所在的公历年）
+    pub year: i32,
+    /// 月份（1–12）
+    pub month: u32,
+    /// 日（1–30）
+    pub day: u32,
+    /// 是否为闰月
+    pub is_leap_month: bool,
+}
+
+impl LunarDate {
+    /// 将公历日期转换为农历日期，超出 1990 年正月初一到 2100 年末的范围时返回 None
+    ///
+    /// # 示例
+    /// ```
+    /// # use chrono::NaiveDate;
+    /// # use calendar::lunar::LunarDate;
+    /// let lunar = LunarDate::from_solar(&NaiveDate::from_ymd_opt(2024, 2, 10).unwrap()).unwrap();
+    /// assert_eq!(lunar.to_chinese(), "正月初一");
+    /// ```
+    pub fn from_solar(date: &NaiveDate) -> Option<Self> {
+        // 1990 年正月初一
+        let base = NaiveDate::from_ymd_opt(FIRST_YEAR, 1, 27).unwrap();
+        let mut offset = (*date - base).num_days();
+        if offset < 0 {
+            return None;
+        }
+
+        for (year, &info) in (FIRST_YEAR..).zip(LUNAR_INFO.iter()) {
+            let days = i64::from(year_days(info));
+            if offset >= days {
+                offset -= days;
+                continue;
+            }
+
+            let leap = info & 0xf;
+            for month in 1..=12 {
+                let days = i64::from(month_days(info, month));
+                if offset < days {
+                    return Some(Self::new(year, month, offset, false));
+                }
+                offset -= days;
+
+                if month == leap {
+                    let days = i64::from(leap_month_days(info));
+                    if offset < days {
+                        return Some(Self::new(year, month, offset, true));
+                    }
+                    offset -= days;
+                }
+            }
+        }
+        None
+    }
+
+    fn new(year: i32, month: u32, offset: i64, is_leap_month: bool) -> Self {
+        Self { year, month, day: offset as u32 + 1, is_leap_month }
+    }
+
+    /// 两位月份，闰月加 `r` 前缀，如 `04`、`r04`
+    pub fn month_code(&self) -> String {
+        let prefix = if self.is_leap_month { "r" } else { "" };
+        format!("{}{:02}", prefix, self.month)
+    }
+
+    /// 中文农历月日，如 `正月初一`、`闰四月十五`、`腊月廿九`
+    pub fn to_chinese(&self) -> String {
+        const DIGITS: [&str; 10] = ["", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+        let day = match self.day {
+            10 => "初十".to_string(),
+            20 => "二十".to_string(),
+            30 => "三十".to_string(),
+            day => {
+                let tens = ["初", "十", "廿"][(day / 10) as usize];
+                format!("{}{}", tens, DIGITS[(day % 10) as usize])
+            }
+        };
+        let leap = if self.is_leap_month { "闰" } else { "" };
+        format!("{}{}月{}", leap, MONTH_NAMES[self.month as usize - 1], day)
+    }
+}
+
+/// 第 `month` 个普通月份（1–12）的天数
+fn month_days(info: u32, month: u32) -> u32 {
+    if info & (0x10000 >> month) != 0 {
+        30
+    } else {
+        29
+    }
+}
+
+/// 闰月天数，没有闰月时为 0
+fn leap_month_days(info: u32) -> u32 {
+    match (info & 0xf, info & 0x10000) {
+        (0, _) => 0,
+        (_, 0) => 29,
+        _ => 30,
+    }
+}
+
+/// 农历年总天数
+fn year_days(info: u32) -> u32 {
+    (1..=12).map(|month| month_days(info, month)).sum::<u32>() + leap_month_days(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lunar(year: i32, month: u32, day: u32) -> LunarDate {
+        LunarDate::from_solar(&NaiveDate::from_ymd_opt(year, month, day).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_spring_festivals() {
+        for (year, month, day) in [
+            (1990, 1, 27),
+            (2000, 2, 5),
+            (2010, 2, 14),
+            (2020, 1, 25),
+            (2021, 2, 12),
+            (2023, 1, 22),
+            (2024, 2, 10),
+            (2025, 1, 29),
+            (2030, 2, 3),
+            (2050, 1, 23),
+            (2100, 2, 9),
+        ] {
+            let date = lunar(year, month, day);
+            assert_eq!(
+                (date.year, date.month, date.day, date.is_leap_month),
+                (year, 1, 1, false),
+                "{}-{}-{}",
+                year,
+                month,
+                day
+            );
+        }
+    }
+
+    #[test]
+    fn test_known_conversions() {
+        assert_eq!(lunar(2024, 2, 10).to_chinese(), "正月初一");
+        assert_eq!(lunar(2024, 2, 9).to_chinese(), "腊月三十");
+        assert_eq!(lunar(2024, 2, 9).year, 2023);
+        assert_eq!(lunar(2024, 9, 17).to_chinese(), "八月十五");
+        assert_eq!(lunar(2023, 6, 22).to_chinese(), "五月初五");
+        assert_eq!(lunar(2000, 1, 1).to_chinese(), "冬月廿五");
+
+        // 闰月
+        let leap = lunar(2020, 5, 23);
+        assert_eq!(leap.to_chinese(), "闰四月初一");
+        assert_eq!(leap.month_code(), "r04");
+        assert_eq!(lunar(2020, 4, 23).month_code(), "04");
+        assert_eq!(lunar(2023, 3, 22).to_chinese(), "闰二月初一");
+        assert_eq!(lunar(2023, 4, 20).to_chinese(), "三月初一");
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert_eq!(LunarDate::from_solar(&NaiveDate::from_ymd_opt(1990, 1, 26).unwrap()), None);
+        assert_eq!(LunarDate::from_solar(&NaiveDate::from_ymd_opt(2101, 2, 1).unwrap()), None);
+    }
+}
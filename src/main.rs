@@ -2,21 +2,51 @@
 //!
 //! 负责解析命令行参数、加载配置、执行下载任务和显示结果。
 
-use chrono::NaiveDate;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use chrono::{Datelike, NaiveDate};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use calendar::cli::{Cli, Command};
-use calendar::config::Config;
+use calendar::cli::{Cli, Command, ExifCommand};
+use calendar::config::{Config, OnCollision};
+use calendar::color;
 use calendar::date_utils;
 use calendar::downloader::Downloader;
-use calendar::{AppError, Result};
+use calendar::exif;
+use calendar::filename::FilenameFormatter;
+use calendar::fileops;
+use calendar::i18n::{self, Lang, Msg};
+use calendar::progress;
+use calendar::validator::{ImageValidator, ValidationResult};
+use calendar::{AppError, DownloadStats, Result, RunState};
 
 use clap::Parser;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+/// 退出码：所有日期成功或跳过
+const EXIT_SUCCESS: i32 = 0;
+/// 退出码：意外错误
+const EXIT_ERROR: i32 = 1;
+/// 退出码：部分日期失败（或存在无效文件）
+const EXIT_PARTIAL_FAILURE: i32 = 2;
+/// 退出码：配置文件或命令行参数错误
+const EXIT_CONFIG_ERROR: i32 = 3;
 
 /// 设置日志记录
-fn setup_tracing(log_level: &str) {
+///
+/// 控制台日志写到标准错误，进度条和统计结果留在标准输出；
+/// 指定 `log_file` 时再增加一个按天轮转、带时间戳的文件日志层。
+/// `log_format` 为 `json` 时两者都输出 JSON 行，包含时间、级别、target 和结构化字段；
+/// `ansi` 为 false 时控制台日志不使用颜色
+fn setup_tracing(
+    log_level: &str,
+    log_format: &str,
+    log_file: Option<&Path>,
+    ansi: bool,
+) -> Result<()> {
     let level_filter = match log_level {
         "trace" => tracing::Level::TRACE,
         "debug" => tracing::Level::DEBUG,
@@ -25,47 +55,268 @@ fn setup_tracing(log_level: &str) {
         "error" => tracing::Level::ERROR,
         _ => tracing::Level::INFO,
     };
+    let json = log_format == "json";
+
+    let console_layer = if json {
+        tracing_subscriber::fmt::layer()
+            .json()
+            .with_target(true)
+            .with_writer(|| progress::LogWriter)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .without_time()
+            .with_ansi(ansi)
+            .with_writer(|| progress::LogWriter)
+            .boxed()
+    };
+
+    let file_layer = match log_file {
+        Some(path) => {
+            let writer = open_log_file(path)?;
+            Some(if json {
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_target(true)
+                    .with_writer(writer)
+                    .boxed()
+            } else {
+                tracing_subscriber::fmt::layer()
+                    .with_target(false)
+                    .with_ansi(false)
+                    .with_writer(writer)
+                    .boxed()
+            })
+        }
+        None => None,
+    };
 
-    tracing_subscriber::fmt()
-        .with_max_level(level_filter)
-        .with_target(false)
-        .without_time()
+    tracing_subscriber::registry()
+        .with(console_layer)
+        .with(file_layer)
+        .with(LevelFilter::from_level(level_filter))
         .init();
+
+    Ok(())
+}
+
+/// 打开按天轮转的日志文件，实际文件名为 `<文件名>.YYYY-MM-DD`
+fn open_log_file(path: &Path) -> Result<RollingFileAppender> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| AppError::file_error(path, "日志文件路径缺少文件名"))?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(file_name.to_string_lossy())
+        .build(dir)
+        .map_err(|e| AppError::file_error(path, format!("无法打开日志文件: {}", e)))
 }
 
 /// 保存失败下载日期到文件
-fn save_failed_downloads(
-    failed_dates: &[String],
-    output_dir: &Path,
-) -> Result<std::path::PathBuf> {
-    let log_path = output_dir.join("failed_downloads.txt");
+fn save_failed_downloads(failed_dates: &[String], log_path: &Path) -> Result<()> {
+    if let Some(parent) = log_path.parent() {
+        fileops::ensure_dir_exists(parent)?;
+    }
+
+    let content: String = failed_dates.iter().map(|date| format!("{}\n", date)).collect();
+    fileops::write_atomic(log_path, content)
+}
+
+/// 打印统计结果
+fn print_stats(title: Msg, stats: &DownloadStats) {
+    println!("\n========== {} ==========", title);
+    println!("{}{}", Msg::Total, stats.total);
+    println!("{}{}", Msg::Succeeded, color::success(stats.succeeded));
+    println!("{}{}", Msg::Failed, color::failure_count(stats.failed));
+    println!("{}{}", Msg::Skipped, color::skipped(stats.skipped));
+    if stats.cancelled > 0 {
+        println!("{}{}", Msg::Cancelled, color::skipped(stats.cancelled));
+    }
+    if stats.metadata_up_to_date > 0 {
+        println!("{}{}", Msg::MetadataUpToDate, stats.metadata_up_to_date);
+    }
+    if !stats.metadata_failures.is_empty() {
+        println!("{}{}", Msg::MetadataFailed, color::failure(stats.metadata_failures.len()));
+    }
+    println!("{}{:.1}%", Msg::SuccessRate, stats.success_rate());
+}
 
-    let mut file = File::create(&log_path)
-        .map_err(|e: std::io::Error| AppError::file_error(&log_path, e.to_string()))?;
+/// `--fail-fast` 停止后输出导致停止的失败原因
+fn report_fail_fast(stats: &DownloadStats) {
+    if let Some(failure) = stats.results.iter().find(|r| r.error.is_some()) {
+        eprintln!(
+            "错误: {} 处理失败，已停止 (--fail-fast): {}",
+            color::failure(&failure.date),
+            failure.error.as_deref().unwrap_or_default()
+        );
+    }
+}
+
+/// 记录并提示失败的日期
+///
+/// 按配置的 `failed_log_path` 写入失败日期（为空时不写入），`quiet` 为 false 时打印重新处理的命令。
+/// 返回写入的记录文件路径。
+fn report_failed_dates(
+    config: &Config,
+    failed_dates: &[String],
+    quiet: bool,
+) -> Result<Option<PathBuf>> {
+    if failed_dates.is_empty() {
+        return Ok(None);
+    }
 
-    for date in failed_dates {
-        writeln!(file, "{}", date)
-            .map_err(|e| AppError::file_error(&log_path, e.to_string()))?;
+    let log_path = config.failed_log_path(&date_utils::today())?;
+    if let Some(log_path) = &log_path {
+        save_failed_downloads(failed_dates, log_path)?;
+        if !quiet {
+            println!("\n{}", Msg::FailedDatesSaved.format(&[&log_path.display()]));
+        }
+    }
+    if !quiet {
+        println!("{}", Msg::RetryHint);
+        println!("  cargo run -- process --dates {}", color::failure(failed_dates.join(",")));
     }
 
     Ok(log_path)
 }
 
+/// 记录并提示元数据（EXIF、文件时间戳）更新失败的日期
+///
+/// 写入 `output_dir/metadata_failures.txt`（每行 `日期<TAB>原因`），`quiet` 为 false 时打印修复命令。
+/// 返回写入的记录文件路径。
+fn report_metadata_failures(
+    config: &Config,
+    failures: &[(String, String)],
+    quiet: bool,
+) -> Result<Option<PathBuf>> {
+    if failures.is_empty() {
+        return Ok(None);
+    }
+
+    let log_path = config.metadata_failed_log_path();
+    let lines: Vec<String> = failures
+        .iter()
+        .map(|(date, reason)| format!("{}\t{}", date, reason.replace(['\r', '\n'], " ")))
+        .collect();
+    save_failed_downloads(&lines, &log_path)?;
+    if !quiet {
+        println!("\n{}", Msg::MetadataFailuresSaved.format(&[&log_path.display()]));
+        println!("{}", Msg::MetadataRetryHint);
+        println!(
+            "  cargo run -- process --metadata-only --dates-file {}",
+            color::failure(log_path.display())
+        );
+    }
+
+    Ok(Some(log_path))
+}
+
+/// 输出 run/process 命令的 JSON 结果
+fn print_json_report(
+    command: &str,
+    dates: &[NaiveDate],
+    stats: &DownloadStats,
+    failed_log: Option<&Path>,
+    started: Instant,
+    new_start_date: Option<NaiveDate>,
+) {
+    let report = serde_json::json!({
+        "command": command,
+        "start_date": dates.iter().min().map(date_utils::format_date),
+        "end_date": dates.iter().max().map(date_utils::format_date),
+        "stats": stats,
+        "success_rate": stats.success_rate(),
+        "failed_log": failed_log.map(|p| p.display().to_string()),
+        "new_start_date": new_start_date.as_ref().map(date_utils::format_date),
+        "elapsed_secs": started.elapsed().as_secs_f64(),
+    });
+    println!("{}", report);
+}
+
+/// `--overwrite` 将覆盖大量已存在文件时请求确认
+///
+/// 已存在的文件数量超过 `overwrite_confirm_threshold` 时，在终端上询问 y/N；
+/// 非交互环境必须使用 `--yes`，否则中止
+fn confirm_overwrite(
+    config: &Config,
+    downloader: &Downloader,
+    dates: &[NaiveDate],
+    yes: bool,
+) -> Result<()> {
+    if yes {
+        return Ok(());
+    }
+
+    let mut existing = 0;
+    for date in dates {
+        if fileops::file_exists(&downloader.build_path(date)?) {
+            existing += 1;
+        }
+    }
+    if existing <= config.overwrite_confirm_threshold {
+        return Ok(());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err(AppError::argument_error(format!(
+            "--overwrite 将覆盖 {} 个已存在的文件，非交互环境请使用 --yes 确认",
+            existing
+        )));
+    }
+
+    eprint!("--overwrite 将覆盖 {} 个已存在的文件，是否继续? [y/N] ", existing);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(AppError::argument_error("已取消覆盖"))
+    }
+}
+
 /// 执行 run 命令（批量下载）
 async fn run_command(
     config_path: &Path,
     config: &Config,
     cli_defaults: calendar::config::ConfigWithDefaults,
-) -> Result<()> {
+) -> Result<DownloadStats> {
     tracing::info!("执行 run 命令");
+    let started = Instant::now();
+    let base_url = cli_defaults.base_url_override.as_deref().unwrap_or(&config.base_url);
 
     // 获取有效的起始和结束日期
-    let start_date = config.get_effective_start_date(&cli_defaults.start_date_override)?;
+    let output_dir = Path::new(&config.output_dir);
+    let mut state = RunState::load(output_dir)?;
+    let start_date = if cli_defaults.since_last_success {
+        let last_success = state.last_success.ok_or_else(|| {
+            AppError::argument_error(format!(
+                "{} 中尚无成功记录，请先不带 --since-last-success 执行一次 run",
+                output_dir.join(fileops::STATE_FILE_NAME).display()
+            ))
+        })?;
+        last_success.succ_opt().unwrap_or(last_success)
+    } else {
+        config.get_effective_start_date(&cli_defaults.start_date_override)?
+    };
     let end_date = match config.get_effective_end_date(&cli_defaults.end_date)? {
         Some(d) => d,
         None => date_utils::today(),
     };
 
+    if let (true, Some(last_success)) = (cli_defaults.since_last_success, state.last_success) {
+        tracing::info!(
+            "--since-last-success: 上次成功日期 {}，忽略配置中的 start_date={}",
+            date_utils::format_date(&last_success),
+            date_utils::format_date(&config.start_date)
+        );
+    }
     tracing::info!(
         "日期范围: {} 到 {}",
         date_utils::format_date(&start_date),
@@ -75,6 +326,13 @@ async fn run_command(
     // 生成日期列表
     let dates = date_utils::date_range(start_date, end_date);
     tracing::info!("待处理日期数量: {}", dates.len());
+    // 默认 on_collision = error 时，文件名不能区分所有日期则在开始下载前中止
+    if config.on_collision == OnCollision::Error {
+        calendar::ensure_unique_paths(dates.iter().copied(), &config.filename_formatter()?, config.year_subdirs)?;
+    }
+    if let Some(budget) = cli_defaults.max_duration {
+        tracing::info!("时间预算: {} 秒 (--max-duration)", budget.as_secs());
+    }
 
     // 创建下载器（使用重试配置）
     let retry_config = config.retry_config();
@@ -83,79 +341,215 @@ async fn run_command(
         retry_config.max_retries,
         retry_config.base_delay_ms
     );
-    let downloader = Downloader::with_retry_config(config, retry_config)?;
+    // --json 和 --progress json 都占用标准输出，不输出文字统计
+    let text_output = !cli_defaults.json && !cli_defaults.progress_json;
+    let download_config = config.with_cli_overrides(&cli_defaults);
+    let downloader = Downloader::with_retry_config(&download_config, retry_config)?
+        .with_progress(text_output && !cli_defaults.quiet)
+        .with_progress_events(cli_defaults.progress_json)
+        .with_force_metadata(cli_defaults.force_metadata)
+        .with_fail_fast(cli_defaults.fail_fast)
+        .with_deadline(cli_defaults.max_duration.map(|budget| started + budget));
+
+    if cli_defaults.overwrite {
+        confirm_overwrite(config, &downloader, &dates, cli_defaults.yes)?;
+    }
 
     // 执行批量下载
     let stats = downloader
         .download_batch(
-            &config.base_url,
+            base_url,
             &dates,
-            config.max_concurrent,
+            download_config.max_concurrent,
             cli_defaults.overwrite,
             cli_defaults.download_only,
         )
         .await;
 
     // 打印统计结果
-    println!("\n========== 下载统计 ==========");
-    println!("总数量:     {}", stats.total);
-    println!("成功:       {}", stats.succeeded);
-    println!("失败:       {}", stats.failed);
-    println!("跳过:       {}", stats.skipped);
-    println!("成功率:     {:.1}%", stats.success_rate());
-
-    // 保存失败的日期
-    if !stats.failed_dates.is_empty() {
-        let log_path = save_failed_downloads(&stats.failed_dates, Path::new(&config.output_dir))?;
-        println!("\n失败的日期已保存到: {}", log_path.display());
-        println!("可使用以下命令重新处理:");
+    if text_output {
+        print_stats(Msg::DownloadSummary, &stats);
         println!(
-            "  cargo run -- process --dates {}",
-            stats.failed_dates.join(",")
+            "{}",
+            Msg::DateRange.format(&[
+                &date_utils::format_date(&start_date),
+                &date_utils::format_date(&end_date),
+            ])
         );
+        if cli_defaults.max_duration.is_some() && stats.cancelled > 0 {
+            let covered = stats.total - stats.cancelled;
+            let percent = format!("{:.1}", covered as f64 * 100.0 / stats.total as f64);
+            println!("{}", Msg::TimeBudgetExhausted.format(&[&covered, &stats.total, &percent]));
+        }
     }
 
-    // 更新配置文件中的 start_date
-    // 优先使用最新成功下载的日期，如果没有则使用结束日期
-    let should_update = if let Some(latest_date) = stats.latest_success_date() {
-        // 只在用户未通过命令行指定 start_date 时才更新
-        if cli_defaults.start_date_override.is_none() && latest_date > config.start_date {
-            Some(latest_date)
-        } else {
-            None
-        }
-    } else {
-        // 如果没有成功下载（全部跳过），使用结束日期更新
-        // 条件：用户未指定 start_date，且没有失败，且日期范围有效
-        if cli_defaults.start_date_override.is_none() && stats.failed == 0 && stats.skipped > 0 {
-            // 获取实际处理的结束日期
-            let end_date = match config.get_effective_end_date(&cli_defaults.end_date) {
-                Ok(Some(d)) => d,
-                Ok(None) => date_utils::today(),
-                Err(_) => return Ok(()),
-            };
-            if end_date > config.start_date {
-                Some(end_date)
-            } else {
-                None
+    if let Some(kind) = config.fileops.latest_link {
+        let formatter = config.filename_formatter()?;
+        match calendar::update_latest_link(output_dir, &formatter, kind) {
+            Ok(Some((link, strategy))) => {
+                tracing::info!("更新最新图片链接（{}）: {}", strategy.as_str(), link.display());
             }
-        } else {
-            None
+            Ok(None) => {}
+            Err(e) => tracing::warn!("更新最新图片链接失败: {}", e),
         }
+    }
+
+    // 保存失败的日期
+    let failed_log = report_failed_dates(config, &stats.failed_dates, !text_output)?;
+    report_metadata_failures(config, &stats.metadata_failures, !text_output)?;
+
+    // 更新配置文件中的 start_date 和状态文件中的上次成功日期
+    // 只推进到从起始日期开始连续成功（含跳过）的最后一天，失败日期之后的成功不计入，
+    // 保证失败的日期会在下次 run 时重新尝试。用户通过命令行指定 start_date 时不更新。
+    let contiguous_end = if cli_defaults.start_date_override.is_none() {
+        stats.contiguous_success_end(&dates)
+    } else {
+        None
     };
+    if let Some(date) = contiguous_end {
+        if state.advance(date) {
+            state.save(output_dir)?;
+        }
+    }
+    // 使用 --output-dir 时下载到其他目录，不改动配置文件
+    let should_update = contiguous_end
+        .filter(|latest_date| *latest_date > config.start_date)
+        .filter(|_| !config.output_dir_overridden());
 
     if let Some(new_date) = should_update {
-        println!("\n更新配置文件中的起始日期: {} -> {}",
-            date_utils::format_date(&config.start_date),
-            date_utils::format_date(&new_date)
-        );
+        if text_output {
+            println!(
+                "\n{}",
+                Msg::StartDateUpdating.format(&[
+                    &date_utils::format_date(&config.start_date),
+                    &date_utils::format_date(&new_date),
+                ])
+            );
+        }
 
         // 创建可变配置副本并更新
         let mut config_clone = config.clone();
         config_clone.update_start_date(new_date, config_path)?;
-        println!("配置文件已更新: {}", config_path.display());
+        if text_output {
+            println!("{}", Msg::ConfigUpdated.format(&[&config_path.display()]));
+        }
+    }
+
+    if cli_defaults.json {
+        print_json_report(
+            "run",
+            &[start_date, end_date],
+            &stats,
+            failed_log.as_deref(),
+            started,
+            should_update,
+        );
+    }
+
+    Ok(stats)
+}
+
+/// 等待 Ctrl-C 或 SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "无法监听 SIGTERM，只响应 Ctrl-C");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// watch 命令的单个周期：重新读取配置文件后执行 run
+async fn watch_cycle(config_path: PathBuf, output_dir: Option<PathBuf>) {
+    let started = Instant::now();
+    let result = async {
+        let config = load_config(&config_path, output_dir.as_deref())?;
+        let _lock = fileops::DirLock::acquire(Path::new(&config.output_dir), false)?;
+        let mut cli_defaults = config.merge_cli_defaults(Some(&Command::default_run()));
+        cli_defaults.quiet = true;
+        // 有成功记录时从上次成功之后继续，而不是每个周期都从配置的 start_date 扫描
+        cli_defaults.since_last_success =
+            RunState::load(Path::new(&config.output_dir))?.last_success.is_some();
+        run_command(&config_path, &config, cli_defaults).await
+    }
+    .await;
+
+    match result {
+        Err(e @ AppError::Locked { .. }) => tracing::warn!(error = %e, "跳过本周期"),
+        Ok(stats) => tracing::info!(
+            succeeded = stats.succeeded,
+            failed = stats.failed,
+            skipped = stats.skipped,
+            elapsed_secs = started.elapsed().as_secs(),
+            "本周期运行完成"
+        ),
+        Err(e) => tracing::error!(error = %e, "本周期运行失败"),
+    }
+}
+
+/// 执行 watch 命令（常驻，按计划定时执行 run）
+async fn watch_command(
+    config_path: &Path,
+    output_dir: Option<&Path>,
+    at: Option<chrono::NaiveTime>,
+    interval: std::time::Duration,
+) -> Result<()> {
+    let interval = chrono::Duration::from_std(interval)
+        .map_err(|_| AppError::argument_error("--interval 超出范围"))?;
+    let now = || chrono::Local::now().naive_local();
+    let mut next = match at {
+        Some(at) => date_utils::next_run_time(now(), at),
+        None => now(),
+    };
+    tracing::info!("执行 watch 命令，间隔 {} 秒", interval.num_seconds());
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut running: Option<tokio::task::JoinHandle<()>> = None;
+
+    loop {
+        tracing::info!("下一次运行时间: {}", next.format("%Y-%m-%d %H:%M:%S"));
+        let wait = (next - now()).to_std().unwrap_or_default();
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = &mut shutdown => break,
+        }
+
+        if running.as_ref().is_some_and(|handle| !handle.is_finished()) {
+            tracing::warn!("上一周期仍在运行，跳过本次运行");
+        } else {
+            running = Some(tokio::spawn(watch_cycle(
+                config_path.to_path_buf(),
+                output_dir.map(Path::to_path_buf),
+            )));
+        }
+
+        // 按间隔推进；错过的周期（如系统休眠）不补跑
+        while next <= now() {
+            next += interval;
+        }
     }
 
+    tracing::info!("收到退出信号");
+    if let Some(handle) = running.filter(|handle| !handle.is_finished()) {
+        tracing::info!("等待当前周期结束");
+        let _ = handle.await;
+    }
     Ok(())
 }
 
@@ -164,8 +558,10 @@ async fn process_command(
     config: &Config,
     cli_defaults: calendar::config::ConfigWithDefaults,
     dates: &[String],
-) -> Result<()> {
+) -> Result<DownloadStats> {
     tracing::info!("执行 process 命令，处理 {} 个日期", dates.len());
+    let started = Instant::now();
+    let base_url = cli_defaults.base_url_override.as_deref().unwrap_or(&config.base_url);
 
     // 解析日期列表
     let parsed_dates: Result<Vec<NaiveDate>> = dates
@@ -177,104 +573,1694 @@ async fn process_command(
 
     // 创建下载器（使用重试配置）
     let retry_config = config.retry_config();
-    let downloader = Downloader::with_retry_config(config, retry_config)?;
+    // --json 和 --progress json 都占用标准输出，不输出文字统计
+    let text_output = !cli_defaults.json && !cli_defaults.progress_json;
+    let download_config = config.with_cli_overrides(&cli_defaults);
+    let downloader = Downloader::with_retry_config(&download_config, retry_config)?
+        .with_progress(text_output && !cli_defaults.quiet)
+        .with_progress_events(cli_defaults.progress_json)
+        .with_force_metadata(cli_defaults.force_metadata)
+        .with_fail_fast(cli_defaults.fail_fast);
+
+    if cli_defaults.overwrite && !cli_defaults.metadata_only {
+        confirm_overwrite(config, &downloader, &parsed_dates, cli_defaults.yes)?;
+    }
 
     // 执行处理
     let stats = downloader
         .process_dates(
-            &config.base_url,
+            base_url,
             &parsed_dates,
+            cli_defaults.concurrency_override.unwrap_or(1),
             cli_defaults.overwrite,
             cli_defaults.metadata_only,
         )
         .await;
 
     // 打印统计结果
-    println!("\n========== 处理统计 ==========");
-    println!("总数量:     {}", stats.total);
-    println!("成功:       {}", stats.succeeded);
-    println!("失败:       {}", stats.failed);
-    println!("跳过:       {}", stats.skipped);
-    println!("成功率:     {:.1}%", stats.success_rate());
+    if text_output {
+        print_stats(Msg::ProcessSummary, &stats);
+        // --reverse 时日期按从新到旧排列
+        if let (Some(first), Some(last)) = (parsed_dates.iter().min(), parsed_dates.iter().max()) {
+            println!(
+                "{}",
+                Msg::DateRange.format(&[&date_utils::format_date(first), &date_utils::format_date(last)])
+            );
+        }
+    }
 
     // 保存失败的日期
-    if !stats.failed_dates.is_empty() {
-        let log_path = save_failed_downloads(&stats.failed_dates, Path::new(&config.output_dir))?;
-        println!("\n失败的日期已保存到: {}", log_path.display());
-        println!("可使用以下命令重新处理:");
-        println!(
-            "  cargo run -- process --dates {}",
-            stats.failed_dates.join(",")
-        );
+    let failed_log = report_failed_dates(config, &stats.failed_dates, !text_output)?;
+    report_metadata_failures(config, &stats.metadata_failures, !text_output)?;
+
+    if cli_defaults.json {
+        print_json_report("process", &parsed_dates, &stats, failed_log.as_deref(), started, None);
     }
 
-    Ok(())
+    Ok(stats)
 }
 
-/// 主函数
-#[tokio::main]
-async fn main() -> Result<()> {
-    // 解析命令行参数
-    let cli = Cli::parse();
+/// 执行 retry 命令（重新处理失败日期记录文件中的日期）
+///
+/// 下载流程与 process 相同；未指定 `--file` 时使用按今天日期解析的 `failed_log_path`，
+/// 路径含日期占位符时无法确定要重试哪一天的记录，必须指定 `--file`
+async fn retry_command(
+    config: &Config,
+    cli_defaults: calendar::config::ConfigWithDefaults,
+    file: Option<&Path>,
+) -> Result<DownloadStats> {
+    let log_path = match file {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let template = config.failed_log_template().ok_or_else(|| {
+                AppError::argument_error("failed_log_path 配置为空，请使用 --file 指定失败日期记录文件")
+            })?;
+            if !FilenameFormatter::new(template)?.is_literal() {
+                return Err(AppError::argument_error(format!(
+                    "failed_log_path 含日期占位符 ({})，请使用 --file 指定要重试的失败日期记录文件",
+                    template
+                )));
+            }
+            config.failed_log_path(&date_utils::today())?.ok_or_else(|| {
+                AppError::argument_error("failed_log_path 配置为空，请使用 --file 指定失败日期记录文件")
+            })?
+        }
+    };
+    tracing::info!("执行 retry 命令，读取失败日期: {}", log_path.display());
+    let started = Instant::now();
+    let base_url = cli_defaults.base_url_override.as_deref().unwrap_or(&config.base_url);
 
-    // 设置日志
-    setup_tracing(&cli.log_level);
+    let content = std::fs::read_to_string(&log_path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            AppError::file_error(&log_path, "失败日期记录文件不存在，可使用 --file 指定其他记录文件")
+        } else {
+            AppError::file_error(&log_path, e.to_string())
+        }
+    })?;
+    let mut dates = date_utils::parse_date_lines(&content)?;
+    if cli_defaults.reverse {
+        dates.reverse();
+    }
 
-    tracing::info!("Calendar 图片下载器启动");
-    tracing::debug!("日志级别: {}", cli.log_level);
+    // --json 和 --progress json 都占用标准输出，不输出文字统计
+    let text_output = !cli_defaults.json && !cli_defaults.progress_json;
+    if dates.is_empty() {
+        if text_output {
+            println!("没有需要重试的日期: {}", log_path.display());
+        }
+        let stats = DownloadStats::default();
+        if cli_defaults.json {
+            print_json_report("retry", &dates, &stats, Some(&log_path), started, None);
+        }
+        return Ok(stats);
+    }
+    tracing::info!("待重试日期数量: {}", dates.len());
 
-    // 加载配置文件
-    let config_path = cli.config.as_path();
-    let config = Config::from_file(config_path)?.apply_env_overrides();
+    let retry_config = config.retry_config();
+    let download_config = config.with_cli_overrides(&cli_defaults);
+    let downloader = Downloader::with_retry_config(&download_config, retry_config)?
+        .with_progress(text_output && !cli_defaults.quiet)
+        .with_progress_events(cli_defaults.progress_json)
+        .with_force_metadata(cli_defaults.force_metadata)
+        .with_fail_fast(cli_defaults.fail_fast);
 
-    tracing::info!(
-        "配置加载完成: start_date={}, max_concurrent={}",
-        date_utils::format_date(&config.start_date),
-        config.max_concurrent
+    if cli_defaults.overwrite && !cli_defaults.metadata_only {
+        confirm_overwrite(config, &downloader, &dates, cli_defaults.yes)?;
+    }
+
+    let stats = downloader
+        .process_dates(
+            base_url,
+            &dates,
+            cli_defaults.concurrency_override.unwrap_or(1),
+            cli_defaults.overwrite,
+            cli_defaults.metadata_only,
+        )
+        .await;
+
+    if text_output {
+        print_stats(Msg::RetrySummary, &stats);
+    }
+    report_metadata_failures(config, &stats.metadata_failures, !text_output)?;
+
+    // 改写记录文件，只保留仍然失败的日期，使重复执行 retry 逐步收敛
+    let remaining_log = if stats.failed_dates.is_empty() {
+        fileops::delete_file(&log_path)?;
+        if text_output {
+            println!("\n{}，已删除: {}", color::success("所有日期均已处理成功"), log_path.display());
+        }
+        None
+    } else {
+        let mut still_failed = stats.failed_dates.clone();
+        still_failed.sort();
+        save_failed_downloads(&still_failed, &log_path)?;
+        if text_output {
+            println!(
+                "\n仍有 {} 个日期失败，已写回: {}",
+                color::failure(still_failed.len()),
+                log_path.display()
+            );
+        }
+        Some(log_path.as_path())
+    };
+
+    if cli_defaults.json {
+        print_json_report("retry", &dates, &stats, remaining_log, started, None);
+    }
+
+    Ok(stats)
+}
+
+/// 执行 status 命令（统计图库中缺失的日期）
+fn status_command(config: &Config, write_dates: Option<&Path>, json: bool) -> Result<()> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    let start_date = config.start_date;
+    let end_date = date_utils::today();
+    tracing::info!("执行 status 命令，扫描目录: {}", output_dir.display());
+
+    let existing = calendar::library_dates(output_dir, &formatter)?;
+    let expected = date_utils::date_range(start_date, end_date);
+    let missing: Vec<NaiveDate> = expected
+        .iter()
+        .filter(|date| !existing.contains_key(date))
+        .copied()
+        .collect();
+    let present = expected.len() - missing.len();
+
+    // 按月份分组缺失日期
+    let mut missing_by_month: BTreeMap<String, Vec<NaiveDate>> = BTreeMap::new();
+    for date in &missing {
+        missing_by_month
+            .entry(date.format("%Y-%m").to_string())
+            .or_default()
+            .push(*date);
+    }
+
+    if let Some(path) = write_dates {
+        let lines: Vec<String> = missing.iter().map(date_utils::format_date).collect();
+        save_failed_downloads(&lines, path)?;
+        tracing::info!("缺失日期已写入: {}", path.display());
+    }
+
+    if json {
+        let by_month: BTreeMap<&String, Vec<String>> = missing_by_month
+            .iter()
+            .map(|(month, dates)| (month, dates.iter().map(date_utils::format_date).collect()))
+            .collect();
+        let report = serde_json::json!({
+            "output_dir": config.output_dir,
+            "start_date": date_utils::format_date(&start_date),
+            "end_date": date_utils::format_date(&end_date),
+            "expected": expected.len(),
+            "present": present,
+            "missing_count": missing.len(),
+            "missing": missing.iter().map(date_utils::format_date).collect::<Vec<_>>(),
+            "missing_by_month": by_month,
+        });
+        println!("{}", report);
+        return Ok(());
+    }
+
+    println!("\n========== {} ==========", Msg::StatusSummary);
+    println!(
+        "{}",
+        Msg::DateRange.format(&[
+            &date_utils::format_date(&start_date),
+            &date_utils::format_date(&end_date),
+        ])
     );
+    println!("{}{}", Msg::Expected, expected.len());
+    println!("{}{}", Msg::Present, present);
+    println!("{}{}", Msg::Missing, missing.len());
 
-    // 根据子命令执行相应操作
-    match &cli.command {
-        Some(Command::Config { validate }) => {
-            if *validate {
-                println!("✓ 配置文件验证通过: {}", config_path.display());
-                println!("\n配置信息:");
-                println!("  起始日期: {}", date_utils::format_date(&config.start_date));
-                println!("  输出目录: {}", config.output_dir);
-                println!("  基础 URL: {}", config.base_url);
-                println!("  文件名格式: {}", config.filename_format);
-                println!("  最大并发数: {}", config.max_concurrent);
-                println!("  超时时间: {} 秒", config.timeout);
-                println!("  最大重试次数: {}", config.max_retries);
-            }
+    if !missing_by_month.is_empty() {
+        println!("\n{}", Msg::MissingDates);
+        for (month, dates) in &missing_by_month {
+            let days: Vec<String> = dates.iter().map(|d| d.format("%d").to_string()).collect();
+            println!("  {} ({}): {}", month, dates.len(), days.join(", "));
         }
-        Some(Command::Run {
-            start_date: _,
-            end_date: _,
-            overwrite: _,
-            download_only: _,
-        }) => {
-            let cli_defaults = config.merge_cli_defaults(cli.command.as_ref());
-            run_command(config_path, &config, cli_defaults).await?;
-        }
-        Some(Command::Process {
-            date: _,
-            dates: _,
-            overwrite: _,
-            metadata_only: _,
-        }) => {
-            let dates = cli.command.as_ref().unwrap().get_dates()?;
-            let cli_defaults = config.merge_cli_defaults(cli.command.as_ref());
-            process_command(&config, cli_defaults, &dates).await?;
+    }
+
+    if let Some(path) = write_dates {
+        println!("\n{}", Msg::MissingDatesSaved.format(&[&path.display()]));
+    }
+
+    Ok(())
+}
+
+/// 执行 verify 命令（校验输出目录中的图片）
+async fn verify_command(config: &Config, delete_invalid: bool, redownload: bool) -> Result<()> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    tracing::info!("执行 verify 命令，扫描目录: {}", output_dir.display());
+
+    let mut checked = 0;
+    let mut unrecognized = 0;
+    let mut invalid: Vec<(PathBuf, NaiveDate, String)> = Vec::new();
+
+    for entry in fileops::scan_library(output_dir, &formatter, None) {
+        // 只校验符合文件名格式的文件，其余文件（失败记录等）不属于图库
+        let Some(date) = entry.date else {
+            unrecognized += 1;
+            continue;
+        };
+
+        checked += 1;
+        if let ValidationResult::Invalid(reason) =
+            ImageValidator::validate_existing(&entry.path, &config.allowed_content_types)?
+        {
+            invalid.push((entry.path, date, reason));
         }
-        None => {
-            // 默认执行 run 命令
-            tracing::info!("未指定命令，默认执行 run 命令");
-            let cli_defaults = config.merge_cli_defaults(cli.command.as_ref());
-            run_command(config_path, &config, cli_defaults).await?;
+    }
+    invalid.sort();
+
+    println!("\n========== {} ==========", Msg::VerifySummary);
+    println!("{}{}", Msg::Checked, checked);
+    println!("{}{}", Msg::Valid, color::success(checked - invalid.len()));
+    println!("{}{}", Msg::Invalid, color::failure_count(invalid.len()));
+    println!("{}{}", Msg::Unrecognized, color::skipped(unrecognized));
+
+    if invalid.is_empty() {
+        return Ok(());
+    }
+
+    println!("\n{}", Msg::InvalidFiles);
+    for (path, date, reason) in &invalid {
+        println!(
+            "  {}  {}  {}",
+            date_utils::format_date(date),
+            path.display(),
+            color::failure(reason)
+        );
+    }
+
+    if delete_invalid {
+        for (path, _, _) in &invalid {
+            fileops::discard_file(path, config.fileops.use_trash)?;
         }
+        println!("\n已{} {} 个无效文件", config.fileops.discard_verb(), invalid.len());
+    }
+
+    let mut remaining = if delete_invalid { 0 } else { invalid.len() };
+
+    if redownload {
+        let mut dates: Vec<NaiveDate> = invalid.iter().map(|(_, date, _)| *date).collect();
+        dates.sort();
+        dates.dedup();
+
+        let downloader = Downloader::with_retry_config(config, config.retry_config())?;
+        let stats = downloader
+            .process_dates(&config.base_url, &dates, 1, true, false)
+            .await;
+        print_stats(Msg::RedownloadSummary, &stats);
+
+        remaining = stats.failed;
+        report_failed_dates(config, &stats.failed_dates, false)?;
+    }
+
+    if remaining > 0 {
+        return Err(AppError::InvalidFiles { count: remaining });
     }
 
-    tracing::info!("程序执行完成");
     Ok(())
 }
+
+/// 执行 repair 命令（查找无效文件并重新下载）
+///
+/// 文件名可解析出日期的无效文件按日期重新下载并覆盖；文件名不符合格式的图片无法确定日期，
+/// 只报告不处理。返回仍未修复的文件数量
+async fn repair_command(config: &Config, dry_run: bool, quiet: bool) -> Result<usize> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    tracing::info!("执行 repair 命令，扫描目录: {}", output_dir.display());
+
+    let dir_formatter = calendar::dir_formatter(config.year_subdirs)?;
+    let mut checked = 0;
+    // (路径, 日期, 是否在原位, 原因)
+    let mut damaged: Vec<(PathBuf, NaiveDate, bool, String)> = Vec::new();
+    let mut undated: Vec<(PathBuf, String)> = Vec::new();
+
+    for entry in fileops::scan_library(output_dir, &formatter, dir_formatter.as_ref()) {
+        let path = entry.path;
+        // 文件名不符合格式的非图片文件（失败记录等）不属于图库
+        if entry.date.is_none() && !calendar::validator::has_image_extension(&path) {
+            continue;
+        }
+
+        checked += 1;
+        if let ValidationResult::Invalid(reason) =
+            ImageValidator::validate_existing(&path, &config.allowed_content_types)?
+        {
+            match entry.date {
+                Some(date) => damaged.push((path, date, entry.in_place, reason)),
+                None => undated.push((path, reason)),
+            }
+        }
+    }
+    damaged.sort();
+    undated.sort();
+
+    if !damaged.is_empty() || !undated.is_empty() {
+        println!("\n{}", Msg::InvalidFiles);
+        for (path, date, _, reason) in &damaged {
+            println!(
+                "  {}  {}  {}",
+                date_utils::format_date(date),
+                path.display(),
+                color::failure(reason)
+            );
+        }
+        for (path, reason) in &undated {
+            println!("  {:<10}  {}  {}", "-", path.display(), color::failure(reason));
+        }
+    }
+
+    let mut dates: Vec<NaiveDate> = damaged.iter().map(|(_, date, _, _)| *date).collect();
+    dates.sort();
+    dates.dedup();
+
+    if dry_run || dates.is_empty() {
+        println!("\n========== {} ==========", Msg::RepairCheckSummary);
+        println!("{}{}", Msg::RepairChecked, checked);
+        println!("{}{}", Msg::RepairPending, color::failure_count(damaged.len()));
+        println!("{}{}", Msg::RepairUndated, color::failure_count(undated.len()));
+        if dry_run && !dates.is_empty() {
+            println!("\n{}", Msg::RepairDryRun);
+        }
+        return Ok(damaged.len() + undated.len());
+    }
+
+    // 走正常的下载流程，覆盖已存在的无效文件
+    let downloader = Downloader::with_retry_config(config, config.retry_config())?
+        .with_progress(!quiet);
+    let stats = downloader
+        .process_dates(&config.base_url, &dates, 1, true, false)
+        .await;
+
+    // 文件名与当前格式生成的路径不一致时（如年份子目录设置变化），修复后删除原无效文件
+    let repaired: HashSet<&str> = stats.succeeded_dates.iter().map(String::as_str).collect();
+    for (path, date, in_place, _) in &damaged {
+        if repaired.contains(date_utils::format_date(date).as_str()) && !in_place {
+            fileops::discard_file(path, config.fileops.use_trash)?;
+        }
+    }
+
+    println!("\n========== {} ==========", Msg::RepairSummary);
+    println!("{}{}", Msg::RepairChecked, checked);
+    println!("{}{}", Msg::RepairFixed, color::success(stats.succeeded));
+    println!("{}{}", Msg::RepairStillFailed, color::failure_count(stats.failed));
+    println!("{}{}", Msg::RepairUndated, color::failure_count(undated.len()));
+
+    report_failed_dates(config, &stats.failed_dates, false)?;
+
+    Ok(stats.failed + undated.len())
+}
+
+/// 判断文件是否为失败日期记录文件（含元数据失败记录）
+///
+/// `failed_log_path` 含日期占位符时，按模板反向解析以识别历史记录文件
+fn is_failed_log(config: &Config, output_dir: &Path, path: &Path) -> Result<bool> {
+    if config.metadata_failed_log_path() == path {
+        return Ok(true);
+    }
+    let Some(template) = config.failed_log_template() else {
+        return Ok(false);
+    };
+    if config.failed_log_path(&date_utils::today())?.as_deref() == Some(path) {
+        return Ok(true);
+    }
+
+    let formatter = config.localized(FilenameFormatter::new(template)?)?;
+    Ok(calendar::library_file_date(output_dir, path, &formatter).is_some())
+}
+
+/// 执行 stats 命令（统计图库）
+fn stats_command(config: &Config, json: bool) -> Result<()> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    tracing::info!("执行 stats 命令，扫描目录: {}", output_dir.display());
+
+    let stats = calendar::library_stats(output_dir, &formatter)?;
+
+    if json {
+        println!("{}", serde_json::to_string(&stats).unwrap_or_default());
+        return Ok(());
+    }
+
+    let format_date = |date: &Option<NaiveDate>| {
+        date.as_ref()
+            .map(date_utils::format_date)
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    println!("\n========== 图库统计 ==========");
+    println!("文件数量:   {}", stats.total_files);
+    println!("总大小:     {} 字节", stats.total_bytes);
+    println!("未识别:     {}", stats.unrecognized);
+    println!("最早日期:   {}", format_date(&stats.earliest));
+    println!("最晚日期:   {}", format_date(&stats.latest));
+    println!("缺失天数:   {}（{} 段）", stats.missing_days, stats.gaps);
+    if let Some(file) = &stats.largest {
+        println!("最大文件:   {} ({} 字节)", file.path.display(), file.size);
+    }
+    if let Some(file) = &stats.smallest {
+        println!("最小文件:   {} ({} 字节)", file.path.display(), file.size);
+    }
+
+    if !stats.per_year.is_empty() {
+        println!("\n按年份:");
+        for (year, year_stats) in &stats.per_year {
+            println!("  {}: {} 个文件, {} 字节", year, year_stats.files, year_stats.bytes);
+        }
+    }
+
+    println!("\n磁盘占用:   {} 个文件, {} 字节", stats.disk.files, stats.disk.bytes);
+    if !stats.disk.per_extension.is_empty() {
+        let extensions: Vec<String> = stats
+            .disk
+            .per_extension
+            .iter()
+            .map(|(ext, count)| format!("{}: {}", if ext.is_empty() { "(无)" } else { ext }, count))
+            .collect();
+        println!("按扩展名:   {}", extensions.join(", "));
+    }
+    if stats.disk.skipped > 0 {
+        println!("无法读取:   {}", color::failure(stats.disk.skipped));
+    }
+
+    Ok(())
+}
+
+/// 执行 clean 命令（清理输出目录中的垃圾文件）
+fn clean_command(
+    config: &Config,
+    dry_run: bool,
+    keep_unrecognized: bool,
+    backups: bool,
+) -> Result<()> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    tracing::info!("执行 clean 命令，扫描目录: {}", output_dir.display());
+
+    let mut junk: Vec<(PathBuf, String)> = Vec::new();
+    for path in fileops::list_files(output_dir)? {
+        if backups && fileops::is_backup_file(&path) {
+            junk.push((path, "EXIF 备份".to_string()));
+            continue;
+        }
+        if fileops::is_internal_file(&path) {
+            continue;
+        }
+        if fileops::is_temp_file(&path) {
+            junk.push((path, "临时文件".to_string()));
+        } else if calendar::library_file_date(output_dir, &path, &formatter).is_some() {
+            if let ValidationResult::Invalid(reason) =
+                ImageValidator::validate_existing(&path, &config.allowed_content_types)?
+            {
+                junk.push((path, reason));
+            }
+        } else if !keep_unrecognized && !is_failed_log(config, output_dir, &path)? {
+            junk.push((path, "不符合文件名格式".to_string()));
+        }
+    }
+
+    if junk.is_empty() {
+        println!("没有需要清理的文件: {}", output_dir.display());
+        return Ok(());
+    }
+
+    let mut reclaimed = 0;
+    let verb = config.fileops.discard_verb();
+    println!("\n{}以下文件:", if dry_run { format!("将{}", verb) } else { verb.to_string() });
+    for (path, reason) in &junk {
+        let size = fileops::get_file_size(path)?.unwrap_or(0);
+        println!("  {}  ({} 字节, {})", path.display(), size, reason);
+        if !dry_run {
+            fileops::discard_file(path, config.fileops.use_trash)?;
+        }
+        reclaimed += size;
+    }
+
+    println!("\n========== 清理统计 ==========");
+    println!("文件数量:   {}", junk.len());
+    println!("释放空间:   {} 字节", reclaimed);
+    if dry_run {
+        println!("（试运行，未{}任何文件）", verb);
+    }
+
+    Ok(())
+}
+
+/// 执行 prune 命令（删除保留期之外的旧文件）
+fn prune_command(
+    config: &Config,
+    keep_days: Option<u32>,
+    before: Option<&str>,
+    dry_run: bool,
+) -> Result<()> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    let cutoff = match (keep_days, before) {
+        (_, Some(before)) => date_utils::parse_date(before)?,
+        (Some(days), None) => date_utils::today()
+            .checked_sub_days(chrono::Days::new(u64::from(days) - 1))
+            .ok_or_else(|| AppError::argument_error("--keep-days 超出范围"))?,
+        (None, None) => {
+            return Err(AppError::argument_error("必须指定 --keep-days 或 --before 参数"))
+        }
+    };
+    tracing::info!(
+        "执行 prune 命令，删除 {} 之前的文件，扫描目录: {}",
+        date_utils::format_date(&cutoff),
+        output_dir.display()
+    );
+
+    let files = calendar::library_files_before(output_dir, &formatter, cutoff)?;
+    if files.is_empty() {
+        println!("没有早于 {} 的文件: {}", date_utils::format_date(&cutoff), output_dir.display());
+        return Ok(());
+    }
+
+    let mut reclaimed = 0;
+    let verb = config.fileops.discard_verb();
+    println!("\n{}以下文件:", if dry_run { format!("将{}", verb) } else { verb.to_string() });
+    for file in &files {
+        println!(
+            "  {}  {}  ({} 字节)",
+            date_utils::format_date(&file.date),
+            file.path.display(),
+            file.size
+        );
+        if !dry_run {
+            fileops::discard_file(&file.path, config.fileops.use_trash)?;
+        }
+        reclaimed += file.size;
+    }
+
+    let removed_dirs = if dry_run { 0 } else { fileops::remove_empty_dirs(output_dir)? };
+
+    println!("\n========== 保留期清理统计 ==========");
+    println!("截止日期:   {}（不含）", date_utils::format_date(&cutoff));
+    println!("文件数量:   {}", files.len());
+    println!("释放空间:   {} 字节", reclaimed);
+    println!("删除空目录: {}", removed_dirs);
+    if dry_run {
+        println!("（试运行，未{}任何文件）", verb);
+    }
+
+    Ok(())
+}
+
+/// 将重复文件替换为指向保留文件的链接
+fn replace_with_link(keep: &Path, duplicate: &Path, kind: &str) -> Result<()> {
+    let strategy = if kind == "sym" {
+        let target = std::fs::canonicalize(keep)
+            .map_err(|e| AppError::file_error(keep, e.to_string()))?;
+        fileops::symlink(&target, duplicate)?
+    } else {
+        fileops::hardlink(keep, duplicate)?
+    };
+    if strategy == fileops::LinkStrategy::Copy {
+        tracing::warn!("文件系统不支持链接，已改为复制，未释放空间: {}", duplicate.display());
+    }
+    Ok(())
+}
+
+/// 执行 dedupe 命令（查找并折叠重复文件）
+fn dedupe_command(config: &Config, link: Option<&str>, delete: bool, dry_run: bool) -> Result<()> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    tracing::info!("执行 dedupe 命令，扫描目录: {}", output_dir.display());
+
+    let groups = calendar::find_duplicates(output_dir, &formatter)?;
+    if groups.is_empty() {
+        println!("没有重复的文件: {}", output_dir.display());
+        return Ok(());
+    }
+
+    let apply = !dry_run && (link.is_some() || delete);
+    let action = match (link, delete) {
+        (Some("sym"), _) => "替换为符号链接",
+        (Some(_), _) => "替换为硬链接",
+        (None, true) => config.fileops.discard_verb(),
+        (None, false) => "重复",
+    };
+
+    let mut duplicates = 0;
+    for group in &groups {
+        let (keep, rest) = group.files.split_first().expect("重复分组至少包含两个文件");
+        println!(
+            "\n{}  {} 个文件, 每个 {} 字节",
+            &group.sha256[..12],
+            group.files.len(),
+            group.size
+        );
+        println!("  {}  {}（保留）", date_utils::format_date(&keep.date), keep.path.display());
+
+        let keep_id = fileops::file_identity(&keep.path);
+        for file in rest {
+            duplicates += 1;
+            // 已经是保留文件的硬链接时无需再处理
+            let already_linked = keep_id.is_some() && fileops::file_identity(&file.path) == keep_id;
+            let note = if already_linked { "已是硬链接" } else { action };
+            println!("  {}  {}（{}）", date_utils::format_date(&file.date), file.path.display(), note);
+
+            if !apply || (already_linked && link == Some("hard")) {
+                continue;
+            }
+            match link {
+                Some(kind) => replace_with_link(&keep.path, &file.path, kind)?,
+                None => fileops::discard_file(&file.path, config.fileops.use_trash)?,
+            }
+        }
+    }
+
+    println!("\n========== 去重统计 ==========");
+    println!("重复分组:   {}", groups.len());
+    println!("重复文件:   {}", duplicates);
+    let recoverable: u64 = groups.iter().map(|g| g.recoverable).sum();
+    if apply {
+        println!("释放空间:   {} 字节", recoverable);
+    } else {
+        println!("可释放空间: {} 字节", recoverable);
+    }
+    if !apply {
+        if dry_run {
+            println!("（试运行，未修改任何文件）");
+        } else {
+            println!("（仅报告，使用 --link hard|sym 或 --delete 折叠重复文件）");
+        }
+    }
+
+    Ok(())
+}
+
+/// 执行 rename 命令（按新的文件名格式重命名已有文件）
+///
+/// 返回是否存在冲突的文件
+fn rename_command(config: &Config, from: &str, to: Option<&str>, dry_run: bool) -> Result<bool> {
+    let output_dir = Path::new(&config.output_dir);
+    let to = to.unwrap_or(&config.filename_format);
+    let from_formatter = config.formatter(from)?;
+    let to_formatter = config.formatter(to)?;
+    tracing::info!("执行 rename 命令: {} -> {}，扫描目录: {}", from, to, output_dir.display());
+
+    let plan =
+        calendar::plan_renames(output_dir, &from_formatter, &to_formatter, config.year_subdirs)?;
+
+    if !plan.renames.is_empty() {
+        println!("\n{}", if dry_run { "将重命名以下文件:" } else { "重命名以下文件:" });
+        for planned in &plan.renames {
+            println!("  {} -> {}", planned.source.display(), planned.target.display());
+            if !dry_run {
+                fileops::move_file(&planned.source, &planned.target, false)?;
+            }
+        }
+    }
+
+    print_conflicts(&plan);
+
+    println!("\n========== 重命名统计 ==========");
+    println!("重命名:     {}", plan.renames.len());
+    println!("无需改动:   {}", plan.unchanged.len());
+    println!("未识别:     {}", plan.unrecognized.len());
+    println!("冲突:       {}", plan.conflict_count());
+    if dry_run {
+        println!("（试运行，未修改任何文件）");
+    }
+    if !dry_run && !plan.renames.is_empty() && to != config.filename_format {
+        println!("\n请将配置文件中的 filename_format 更新为: {}", to);
+    }
+
+    Ok(plan.conflict_count() > 0)
+}
+
+/// 列出重命名/迁移计划中目标冲突的文件
+fn print_conflicts(plan: &calendar::RenamePlan) {
+    if plan.conflict_count() == 0 {
+        return;
+    }
+    println!("\n目标冲突，未移动:");
+    for planned in &plan.existing {
+        println!(
+            "  {} -> {}（目标已存在）",
+            planned.source.display(),
+            planned.target.display()
+        );
+    }
+    for planned in &plan.duplicates {
+        println!(
+            "  {} -> {}（多个文件映射到同一目标）",
+            planned.source.display(),
+            planned.target.display()
+        );
+    }
+}
+
+/// 执行 migrate 命令（按当前目录布局整理已有文件）
+///
+/// 返回是否存在未移动的冲突文件
+fn migrate_command(config: &Config, dry_run: bool, force: bool) -> Result<bool> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+    tracing::info!("执行 migrate 命令，扫描目录: {}", output_dir.display());
+
+    let mut plan = calendar::plan_renames(output_dir, &formatter, &formatter, config.year_subdirs)?;
+    if force {
+        // --force 时覆盖已存在的目标；重复映射无法确定保留哪一个，仍视为冲突
+        plan.renames.append(&mut plan.existing);
+        plan.renames.sort_by(|a, b| a.source.cmp(&b.source));
+    }
+
+    // 年份 -> (移动, 冲突)
+    let mut per_year: BTreeMap<i32, (usize, usize)> = BTreeMap::new();
+
+    if !plan.renames.is_empty() {
+        println!("\n{}", if dry_run { "将移动以下文件:" } else { "移动以下文件:" });
+        for planned in &plan.renames {
+            println!("  {} -> {}", planned.source.display(), planned.target.display());
+            if !dry_run {
+                fileops::move_file(&planned.source, &planned.target, force)?;
+            }
+            per_year.entry(planned.date.year()).or_default().0 += 1;
+        }
+    }
+    for planned in plan.existing.iter().chain(&plan.duplicates) {
+        per_year.entry(planned.date.year()).or_default().1 += 1;
+    }
+
+    print_conflicts(&plan);
+
+    let removed_dirs = if dry_run || plan.renames.is_empty() {
+        0
+    } else {
+        fileops::remove_empty_dirs(output_dir)?
+    };
+
+    println!("\n========== 迁移统计 ==========");
+    for (year, (moved, conflicts)) in &per_year {
+        println!("  {}: 移动 {}, 冲突 {}", year, moved, conflicts);
+    }
+    println!("移动:       {}", plan.renames.len());
+    println!("无需改动:   {}", plan.unchanged.len());
+    println!("未识别:     {}", plan.unrecognized.len());
+    println!("冲突:       {}", plan.conflict_count());
+    println!("删除空目录: {}", removed_dirs);
+    if dry_run {
+        println!("（试运行，未修改任何文件）");
+    } else if !plan.existing.is_empty() {
+        println!("\n可使用 --force 覆盖已存在的目标文件");
+    }
+
+    Ok(plan.conflict_count() > 0)
+}
+
+/// 执行 touch 命令（为已有文件重新写入元数据，不访问网络）
+async fn touch_command(
+    config: &Config,
+    cli_defaults: calendar::config::ConfigWithDefaults,
+    all: bool,
+    concurrency: Option<usize>,
+) -> Result<DownloadStats> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+
+    let mut unrecognized = Vec::new();
+    let files: Vec<(NaiveDate, PathBuf)> = if all {
+        let (dates, skipped) =
+            calendar::library_dates_with_unrecognized(output_dir, &formatter)?;
+        unrecognized = skipped;
+        dates.into_iter().collect()
+    } else {
+        let start_date = config.get_effective_start_date(&cli_defaults.start_date_override)?;
+        let end_date = config
+            .get_effective_end_date(&cli_defaults.end_date)?
+            .unwrap_or_else(date_utils::today);
+        date_utils::date_range(start_date, end_date)
+            .into_iter()
+            .map(|date| {
+                let path = calendar::plan_file_path(output_dir, &date, &formatter, config.year_subdirs)?;
+                Ok((date, path))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let concurrency = concurrency.unwrap_or(config.max_concurrent);
+    tracing::info!("执行 touch 命令，处理 {} 个日期，并发数 {}", files.len(), concurrency);
+
+    let stats = Downloader::touch_files(files, concurrency, &config.exif, &config.fileops).await;
+
+    print_stats(Msg::MetadataSummary, &stats);
+
+    if !stats.skipped_dates.is_empty() {
+        let mut missing = stats.skipped_dates.clone();
+        missing.sort();
+        println!("\n文件不存在的日期 ({}):", missing.len());
+        for date in &missing {
+            println!("  {}", date);
+        }
+    }
+
+    if !unrecognized.is_empty() {
+        println!("\n无法从文件名解析日期的文件 ({}):", unrecognized.len());
+        for path in &unrecognized {
+            println!("  {}", path.display());
+        }
+    }
+
+    if !stats.failed_dates.is_empty() {
+        let mut failed = stats.failed_dates.clone();
+        failed.sort();
+        println!("\n更新失败的日期: {}", failed.join(", "));
+    }
+
+    Ok(stats)
+}
+
+/// 执行 list 命令（列出日期范围内的 URL 和保存路径，不访问网络）
+fn list_command(
+    config: &Config,
+    cli_defaults: calendar::config::ConfigWithDefaults,
+    format: &str,
+    missing_only: bool,
+) -> Result<()> {
+    let start_date = config.get_effective_start_date(&cli_defaults.start_date_override)?;
+    let end_date = config
+        .get_effective_end_date(&cli_defaults.end_date)?
+        .unwrap_or_else(date_utils::today);
+    let downloader = Downloader::new(config)?;
+
+    // (日期, URL, 保存路径, 是否已存在)
+    let entries: Vec<(String, String, PathBuf, bool)> =
+        date_utils::date_range(start_date, end_date)
+            .into_iter()
+            .map(|date| {
+                let url = downloader.build_url(&config.base_url, &date);
+                let url = calendar::config::redact_secrets(&url);
+                let path = downloader.build_path(&date)?;
+                let exists = fileops::file_exists(&path);
+                Ok((date_utils::format_date(&date), url, path, exists))
+            })
+            .filter(|entry| !missing_only || entry.as_ref().map_or(true, |(_, _, _, exists)| !exists))
+            .collect::<Result<_>>()?;
+
+    match format {
+        "json" => {
+            let items: Vec<_> = entries
+                .iter()
+                .map(|(date, url, path, exists)| {
+                    serde_json::json!({ "date": date, "url": url, "path": path, "exists": exists })
+                })
+                .collect();
+            println!("{}", serde_json::Value::Array(items));
+        }
+        "tsv" => {
+            println!("date\turl\tpath\texists");
+            for (date, url, path, exists) in &entries {
+                println!("{}\t{}\t{}\t{}", date, url, path.display(), exists);
+            }
+        }
+        _ => {
+            for (date, url, path, exists) in &entries {
+                let mark = if *exists { "已存在" } else { "缺失" };
+                println!("{}  {}  {}  {}", date, mark, url, path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 执行 url 命令（打印日期对应的下载 URL）
+///
+/// 输出完整的 URL（不隐藏插值的敏感值），便于直接交给 curl 等工具调试
+fn url_command(config: &Config, dates: &[String], show_path: bool) -> Result<()> {
+    let downloader = Downloader::new(config)?;
+
+    for date in dates {
+        let date = date_utils::parse_date(date)?;
+        let url = downloader.build_url(&config.base_url, &date);
+        if show_path {
+            println!("{}\t{}", url, downloader.build_path(&date)?.display());
+        } else {
+            println!("{}", url);
+        }
+    }
+
+    Ok(())
+}
+
+/// 执行 exif show 命令（查看文件的日期元数据）
+fn exif_show_command(
+    config: &Config,
+    path: Option<&Path>,
+    date: Option<&str>,
+    json: bool,
+) -> Result<()> {
+    let output_dir = Path::new(&config.output_dir);
+    let formatter = config.filename_formatter()?;
+
+    // 确定文件路径和期望日期：指定日期时按配置定位文件，否则从文件名推断日期
+    let (path, expected) = match (path, date) {
+        (_, Some(date)) => {
+            let date = date_utils::parse_date(date)?;
+            let path = calendar::plan_file_path(output_dir, &date, &formatter, config.year_subdirs)?;
+            (path, Some(date))
+        }
+        (Some(path), None) => {
+            let expected = calendar::library_file_date(output_dir, path, &formatter).or_else(|| {
+                path.file_name()
+                    .and_then(|name| formatter.parse(&name.to_string_lossy()))
+            });
+            (path.to_path_buf(), expected)
+        }
+        (None, None) => return Err(AppError::argument_error("必须指定文件路径或 --date 参数")),
+    };
+
+    if !fileops::file_exists(&path) {
+        return Err(AppError::file_error(&path, "文件不存在"));
+    }
+
+    let info = exif::get_exif_info(&path)?;
+    let dates = info.dates();
+    let mtime = fileops::get_file_mtime(&path)?;
+    let ctime = fileops::get_file_ctime(&path)?;
+
+    // 逐项对比期望日期，None 表示无法比较
+    let matches = |value: Option<NaiveDate>| -> Option<bool> {
+        Some(value? == expected?)
+    };
+    let fields: Vec<(&str, Option<String>, Option<bool>)> = vec![
+        (
+            "DateTimeOriginal",
+            info.date_time_original.clone(),
+            matches(dates.date_time_original.map(|dt| dt.date())),
+        ),
+        (
+            "CreateDate",
+            info.create_date.clone(),
+            matches(dates.create_date.map(|dt| dt.date())),
+        ),
+        (
+            "ModifyDate",
+            info.modify_date.clone(),
+            matches(dates.modify_date.map(|dt| dt.date())),
+        ),
+        ("Artist", info.artist.clone(), None),
+        (
+            "ImageDescription",
+            info.image_description.clone(),
+            matches(
+                info.image_description
+                    .as_deref()
+                    .and_then(|d| date_utils::parse_date(d.trim()).ok()),
+            ),
+        ),
+        ("Copyright", info.copyright.clone(), None),
+        ("OffsetTimeOriginal", info.offset_time_original.clone(), None),
+        ("SubSecTimeOriginal", info.sub_sec_time_original.clone(), None),
+        (
+            "GPSPosition",
+            info.gps_latitude.zip(info.gps_longitude).map(|(lat, lon)| match info.gps_altitude {
+                Some(alt) => format!("{:.6}, {:.6}, {} m", lat, lon, alt),
+                None => format!("{:.6}, {:.6}", lat, lon),
+            }),
+            None,
+        ),
+        ("UserComment", info.user_comment.clone(), None),
+        (
+            "SensitiveTags",
+            (!info.sensitive_tags.is_empty()).then(|| info.sensitive_tags.join(", ")),
+            None,
+        ),
+        (
+            "FileModifyTime",
+            mtime.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+            matches(mtime.map(|t| t.date_naive())),
+        ),
+        // 创建时间通常是文件写入磁盘的时间，仅展示不对比
+        (
+            "FileCreateTime",
+            ctime.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+            None,
+        ),
+    ];
+
+    let mismatches: Vec<&str> = fields
+        .iter()
+        .filter(|(_, _, ok)| *ok == Some(false))
+        .map(|(name, _, _)| *name)
+        .collect();
+
+    if json {
+        let values: BTreeMap<&str, &Option<String>> =
+            fields.iter().map(|(name, value, _)| (*name, value)).collect();
+        let report = serde_json::json!({
+            "path": path.display().to_string(),
+            "expected_date": expected.as_ref().map(date_utils::format_date),
+            "fields": values,
+            "mismatches": mismatches,
+        });
+        println!("{}", report);
+        return Ok(());
+    }
+
+    println!("文件:     {}", path.display());
+    match &expected {
+        Some(date) => println!("期望日期: {}", date_utils::format_date(date)),
+        None => println!("期望日期: (无法从文件名推断)"),
+    }
+    println!();
+
+    for (name, value, ok) in &fields {
+        let mark = match ok {
+            Some(true) => color::success("✓").to_string(),
+            Some(false) => color::failure("✗ 与期望日期不一致").to_string(),
+            None => String::new(),
+        };
+        let line = format!("  {:<20}{:<28}{}", name, value.as_deref().unwrap_or("(无)"), mark);
+        println!("{}", line.trim_end());
+    }
+
+    Ok(())
+}
+
+/// 确定 exif strip 要处理的文件：指定的路径、按日期定位的文件或输出目录中的全部文件
+fn strip_targets(
+    config: &Config,
+    paths: &[PathBuf],
+    dates: Option<&[String]>,
+    all: bool,
+) -> Result<Vec<PathBuf>> {
+    let output_dir = Path::new(&config.output_dir);
+
+    if all {
+        return Ok(fileops::list_files(output_dir)?
+            .into_iter()
+            .filter(|path| !fileops::is_internal_file(path) && !fileops::is_temp_file(path))
+            .filter(|path| exif::supports_exif(path))
+            .collect());
+    }
+
+    if let Some(dates) = dates {
+        let formatter = config.filename_formatter()?;
+        let today = date_utils::today();
+        let mut files = Vec::new();
+        for value in dates {
+            let (start, end) = date_utils::resolve_date_expr(value, today)?;
+            for date in date_utils::date_range(start, end) {
+                let path = calendar::plan_file_path(output_dir, &date, &formatter, config.year_subdirs)?;
+                if fileops::file_exists(&path) {
+                    files.push(path);
+                } else {
+                    tracing::warn!(date = %date, path = %path.display(), "文件不存在，跳过");
+                }
+            }
+        }
+        return Ok(files);
+    }
+
+    for path in paths {
+        if !fileops::file_exists(path) {
+            return Err(AppError::file_error(path, "文件不存在"));
+        }
+    }
+    Ok(paths.to_vec())
+}
+
+/// 执行 exif strip 命令，返回处理失败的文件数
+///
+/// 默认清除后恢复文件原有的访问和修改时间，`reset_mtime` 为 true 时保留改写产生的新时间
+fn exif_strip_command(
+    files: &[PathBuf],
+    keep_dates: bool,
+    reset_mtime: bool,
+    dry_run: bool,
+) -> Result<usize> {
+    tracing::info!("执行 exif strip 命令，检查 {} 个文件", files.len());
+
+    let mut stripped = 0;
+    let mut unchanged = 0;
+    let mut failed = 0;
+
+    for path in files {
+        if !exif::has_embedded_metadata(path) {
+            unchanged += 1;
+            continue;
+        }
+        if dry_run {
+            println!("  {}", path.display());
+            stripped += 1;
+            continue;
+        }
+
+        let result = fileops::FileTimes::read(path).and_then(|times| {
+            let modified = exif::strip_metadata(path, keep_dates)?;
+            if modified && !reset_mtime {
+                times.restore(path)?;
+            }
+            Ok(modified)
+        });
+        match result {
+            Ok(true) => stripped += 1,
+            Ok(false) => unchanged += 1,
+            Err(e) => {
+                eprintln!("{} {}: {}", color::failure("✗"), path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("\n========== 元数据清除统计 ==========");
+    println!("已检查:       {}", files.len());
+    if dry_run {
+        println!("待清除:       {}", stripped);
+    } else {
+        println!("已清除:       {}", color::success(stripped));
+    }
+    println!("无元数据:     {}", unchanged);
+    println!("失败:         {}", color::failure_count(failed));
+    if dry_run && stripped > 0 {
+        println!("\n(--dry-run) 未修改任何文件");
+    }
+
+    Ok(failed)
+}
+
+/// doctor 命令要求的最低可用磁盘空间（字节）
+const DOCTOR_MIN_FREE_SPACE: u64 = 100 * 1024 * 1024;
+
+/// 打印单项检查结果，失败时附带修复建议；返回是否通过
+fn report_check(name: &str, result: std::result::Result<String, String>, hint: &str) -> bool {
+    match result {
+        Ok(detail) => {
+            println!("{} {}: {}", color::success("✓"), name, detail);
+            true
+        }
+        Err(detail) => {
+            println!("{} {}: {}", color::failure("✗"), name, detail);
+            println!("    建议: {}", hint);
+            false
+        }
+    }
+}
+
+/// 加载配置并校验文件名格式、URL 模板和 User-Agent
+fn load_checked_config(config_path: &Path, output_dir: Option<&Path>) -> Result<(Config, Downloader)> {
+    let config = load_config(config_path, output_dir)?;
+    config.filename_formatter()?.validate_path()?;
+    config.formatter(&config.base_url)?;
+    if config.max_concurrent == 0 {
+        return Err(AppError::config_error(config_path, "max_concurrent 必须大于 0"));
+    }
+    let downloader = Downloader::new(&config)?;
+    Ok((config, downloader))
+}
+
+/// 在输出目录中创建并删除探测文件
+fn check_output_dir(output_dir: &Path) -> std::result::Result<String, String> {
+    let probe = output_dir.join(".calendar-doctor-probe");
+    fileops::ensure_dir_exists(output_dir).map_err(|e| e.to_string())?;
+    std::fs::write(&probe, b"probe").map_err(|e| format!("{}: {}", probe.display(), e))?;
+    fileops::delete_file(&probe).map_err(|e| e.to_string())?;
+    Ok(output_dir.display().to_string())
+}
+
+/// 解析 URL 中的主机名
+async fn check_dns(url: &str) -> std::result::Result<String, String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("无效的 URL: {}", e))?;
+    let host = parsed.host_str().ok_or("URL 中缺少主机名")?.to_string();
+    let port = parsed.port_or_known_default().unwrap_or(80);
+
+    let lookup_host = host.clone();
+    let addrs = tokio::task::spawn_blocking(move || {
+        std::net::ToSocketAddrs::to_socket_addrs(&(lookup_host.as_str(), port))
+            .map(|addrs| addrs.collect::<Vec<_>>())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{}: {}", host, e))?;
+
+    match addrs.first() {
+        Some(addr) => Ok(format!("{} -> {}", host, addr.ip())),
+        None => Err(format!("{}: 没有解析到地址", host)),
+    }
+}
+
+/// 请求今天的 URL，报告状态码和延迟
+async fn check_url(downloader: &Downloader, url: &str) -> std::result::Result<String, String> {
+    let shown_url = calendar::config::redact_secrets(url);
+    let started = Instant::now();
+    let status = downloader.probe_url(url).await.map_err(|e| e.to_string())?;
+    let detail = format!("{} 返回 {}，耗时 {} ms", shown_url, status, started.elapsed().as_millis());
+    if status.is_success() {
+        Ok(detail)
+    } else {
+        Err(detail)
+    }
+}
+
+/// 在临时目录生成一张 1x1 JPEG，写入并读回 EXIF 日期
+fn check_exif_roundtrip() -> std::result::Result<String, String> {
+    let dir = std::env::temp_dir().join(format!("calendar-doctor-{}", std::process::id()));
+    let path = dir.join("probe.jpg");
+    let date = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+
+    let result = (|| {
+        fileops::ensure_dir_exists(&dir).map_err(|e| e.to_string())?;
+        image::RgbImage::new(1, 1)
+            .save_with_format(&path, image::ImageFormat::Jpeg)
+            .map_err(|e| format!("生成测试图片失败: {}", e))?;
+        exif::set_exif_datetime(&path, &date.and_hms_opt(0, 0, 0).unwrap(), &Default::default(), None)
+            .map_err(|e| e.to_string())?;
+        match exif::get_exif_datetime(&path).map_err(|e| e.to_string())? {
+            Some(read_back) if read_back == date => Ok("写入并读回 DateTimeOriginal 成功".to_string()),
+            other => Err(format!("读回的日期不一致: {:?}", other)),
+        }
+    })();
+
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// 检查输出目录所在磁盘的可用空间
+fn check_disk_space(output_dir: &Path) -> std::result::Result<String, String> {
+    let available = fileops::available_space(output_dir).map_err(|e| e.to_string())?;
+    let detail = format!("可用 {} MB", available / 1024 / 1024);
+    if available >= DOCTOR_MIN_FREE_SPACE {
+        Ok(detail)
+    } else {
+        Err(format!("{}，低于 {} MB", detail, DOCTOR_MIN_FREE_SPACE / 1024 / 1024))
+    }
+}
+
+/// 执行 doctor 命令（环境和连通性检查），返回退出码
+async fn doctor_command(config_path: &Path, output_dir: Option<&Path>) -> i32 {
+    println!("\n========== 环境检查 ==========");
+
+    let (config, downloader) = match load_checked_config(config_path, output_dir) {
+        Ok(loaded) => {
+            report_check("配置文件", Ok(config_path.display().to_string()), "");
+            loaded
+        }
+        Err(e) => {
+            report_check(
+                "配置文件",
+                Err(e.to_string()),
+                "对照 README 的配置项说明修正配置文件，可用 `calendar config --validate` 查看解析结果",
+            );
+            return EXIT_CONFIG_ERROR;
+        }
+    };
+
+    let output_dir = Path::new(&config.output_dir);
+    let url = downloader.build_url(&config.base_url, &date_utils::today());
+
+    let checks = [
+        report_check(
+            "输出目录可写",
+            check_output_dir(output_dir),
+            "确认 output_dir 路径正确，且当前用户对其有写权限",
+        ),
+        report_check(
+            "DNS 解析",
+            check_dns(&url).await,
+            "检查网络连接和 DNS 设置，确认 base_url 中的域名拼写正确",
+        ),
+        report_check(
+            "URL 访问",
+            check_url(&downloader, &url).await,
+            "检查 base_url 模板和网络代理；404 也可能表示今天的图片尚未发布",
+        ),
+        report_check(
+            "EXIF 写入",
+            check_exif_roundtrip(),
+            "确认临时目录可写；EXIF 写入失败时可暂时使用 --download-only",
+        ),
+        report_check(
+            "磁盘空间",
+            check_disk_space(output_dir),
+            "清理输出目录所在磁盘，或将 output_dir 指向空间更大的磁盘",
+        ),
+    ];
+
+    let failed = checks.iter().filter(|ok| !**ok).count();
+    if failed == 0 {
+        println!("\n所有检查均已通过");
+        EXIT_SUCCESS
+    } else {
+        println!("\n{} 项检查未通过", failed);
+        EXIT_ERROR
+    }
+}
+
+/// 执行 man 命令（生成 man 手册）
+fn man_command(out_dir: Option<&Path>) -> Result<()> {
+    let pages = calendar::cli::render_man_pages()?;
+
+    match out_dir {
+        Some(dir) => {
+            fileops::ensure_dir_exists(dir)?;
+            for (name, content) in &pages {
+                let path = dir.join(name);
+                std::fs::write(&path, content)
+                    .map_err(|e| AppError::file_error(&path, e.to_string()))?;
+                println!("{}", path.display());
+            }
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            for (_, content) in &pages {
+                stdout.write_all(content)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 根据统计结果计算退出码
+///
+/// 有日期失败时返回 `EXIT_PARTIAL_FAILURE`；`strict` 为 true 时元数据更新失败也算作失败
+fn stats_exit_code(stats: &DownloadStats, strict: bool) -> i32 {
+    if stats.failed > 0 || (strict && !stats.metadata_failures.is_empty()) {
+        EXIT_PARTIAL_FAILURE
+    } else {
+        EXIT_SUCCESS
+    }
+}
+
+/// 根据错误类型计算退出码
+fn error_exit_code(error: &AppError) -> i32 {
+    match error {
+        AppError::InvalidFiles { .. } => EXIT_PARTIAL_FAILURE,
+        e if e.is_config_error() => EXIT_CONFIG_ERROR,
+        _ => EXIT_ERROR,
+    }
+}
+
+/// 主函数
+#[tokio::main]
+async fn main() {
+    // 解析命令行参数（参数错误使用配置错误退出码，避免与部分失败混淆）
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(if e.use_stderr() { EXIT_CONFIG_ERROR } else { EXIT_SUCCESS });
+        }
+    };
+
+    // 选择输出语言（--lang > 配置中的 lang > LANG 环境变量 > 中文）
+    let lang = cli
+        .lang
+        .or_else(|| Config::peek_lang(&cli.config))
+        .or_else(Lang::from_env)
+        .unwrap_or_default();
+    i18n::set_lang(lang);
+    color::init(cli.no_color);
+
+    // 设置日志（命令行 --log-file 优先于配置中的 log_file）
+    let log_file = cli.log_file.clone().or_else(|| Config::peek_log_file(&cli.config));
+    if let Err(e) = setup_tracing(
+        cli.effective_log_level(),
+        &cli.log_format,
+        log_file.as_deref(),
+        color::log_colors_enabled(cli.no_color),
+    ) {
+        eprintln!("{}: {}", Msg::ErrorPrefix, e);
+        std::process::exit(error_exit_code(&e));
+    }
+
+    let exit_code = match run_app(&cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}: {}", Msg::ErrorPrefix, e);
+            error_exit_code(&e)
+        }
+    };
+
+    tracing::info!("程序执行完成");
+    std::process::exit(exit_code);
+}
+
+/// 加载配置文件并应用环境变量和 `--output-dir` 覆盖
+fn load_config(config_path: &Path, output_dir: Option<&Path>) -> Result<Config> {
+    let config = Config::from_file(config_path)?.apply_env_overrides();
+    let Some(dir) = output_dir else {
+        return Ok(config);
+    };
+    fileops::ensure_dir_exists(dir)?;
+    tracing::warn!(
+        "使用命令行指定的输出目录 {}（配置文件中为 {}），不会更新配置文件中的 start_date",
+        dir.display(),
+        config.output_dir
+    );
+    Ok(config.with_output_dir(dir))
+}
+
+/// 执行命令行指定的操作，返回退出码
+async fn run_app(cli: &Cli) -> Result<i32> {
+    tracing::info!("Calendar 图片下载器启动");
+    tracing::debug!("日志级别: {}", cli.effective_log_level());
+
+    let config_path = cli.config.as_path();
+
+    match &cli.command {
+        // doctor 命令自行加载配置，把配置错误作为一项检查结果报告
+        Some(Command::Doctor) => return Ok(doctor_command(config_path, cli.output_dir.as_deref()).await),
+        // man 命令不需要配置文件
+        Some(Command::Man { out_dir }) => {
+            man_command(out_dir.as_deref())?;
+            return Ok(EXIT_SUCCESS);
+        }
+        // completions 命令不需要配置文件
+        Some(Command::Completions { shell }) => {
+            std::io::stdout().lock().write_all(&calendar::cli::render_completions(*shell))?;
+            return Ok(EXIT_SUCCESS);
+        }
+        _ => {}
+    }
+
+    // 加载配置文件
+    let config = load_config(config_path, cli.output_dir.as_deref())?;
+    // 未指定子命令时默认执行 run
+    let default_run = Command::default_run();
+    let command = cli.command.as_ref().unwrap_or_else(|| {
+        tracing::info!("未指定命令，默认执行 run 命令");
+        &default_run
+    });
+
+    let mut cli_defaults = config.merge_cli_defaults(Some(command));
+    cli_defaults.quiet = cli.quiet;
+    let strict = cli_defaults.strict;
+    let fail_fast = cli_defaults.fail_fast;
+    // run/process/retry 会写入输出目录和失败记录，同一时间只允许一个进程执行
+    let acquire_lock = || fileops::DirLock::acquire(Path::new(&config.output_dir), cli_defaults.wait);
+
+    // 命令行指定的 URL 模板在开始下载前校验，避免格式错误时静默回退
+    if let Some(base_url) = &cli_defaults.base_url_override {
+        config.formatter(base_url)?;
+        tracing::info!("使用命令行指定的 base_url: {}", base_url);
+    }
+    if let Some(timeout) = cli_defaults.timeout_override {
+        tracing::info!("使用命令行指定的超时时间: {} 秒", timeout);
+    }
+    if let Some(concurrency) = cli_defaults.concurrency_override {
+        calendar::config::validate_concurrency(concurrency)?;
+        tracing::info!("使用命令行指定的并发数: {}", concurrency);
+    }
+
+    tracing::info!(
+        "配置加载完成: start_date={}, max_concurrent={}",
+        date_utils::format_date(&config.start_date),
+        cli_defaults.concurrency_override.unwrap_or(config.max_concurrent)
+    );
+
+    // 根据子命令执行相应操作
+    let exit_code = match command {
+        Command::Doctor | Command::Man { .. } | Command::Completions { .. } => {
+            unreachable!("在加载配置前处理")
+        }
+        Command::Config { validate } => {
+            if *validate {
+                let formatter = config.filename_formatter()?;
+                formatter.validate_path()?;
+                if config.on_collision == OnCollision::Error {
+                    let dates = date_utils::date_range(config.start_date, date_utils::today());
+                    calendar::ensure_unique_paths(dates, &formatter, config.year_subdirs)?;
+                }
+                // 展示插值前的模板，避免输出环境变量中的敏感值
+                let output_dir = match &cli.output_dir {
+                    Some(dir) => format!("{}（命令行 --output-dir）", dir.display()),
+                    None => config.redacted().output_dir,
+                };
+                let config = config.redacted();
+                println!("{} 配置文件验证通过: {}", color::success("✓"), config_path.display());
+                println!("\n配置信息:");
+                println!("  起始日期: {}", date_utils::format_date(&config.start_date));
+                println!("  输出目录: {}", output_dir);
+                println!("  基础 URL: {}", config.base_url);
+                println!("  文件名格式: {}", config.filename_format);
+                println!("  最大并发数: {}", config.max_concurrent);
+                println!("  User-Agent: {}", config.user_agent);
+                println!("  超时时间: {} 秒", config.timeout);
+                println!("  最大重试次数: {}", config.max_retries);
+
+                if !config.deprecated_keys().is_empty() {
+                    println!("\n已弃用的配置项（请更新配置文件）:");
+                    for (old_key, new_key) in config.deprecated_keys() {
+                        println!("  {} -> {}", old_key, new_key);
+                    }
+                }
+            }
+            EXIT_SUCCESS
+        }
+        Command::Run { .. } => {
+            let _lock = acquire_lock()?;
+            let stats = run_command(config_path, &config, cli_defaults).await?;
+            if fail_fast {
+                report_fail_fast(&stats);
+            }
+            stats_exit_code(&stats, strict)
+        }
+        Command::Watch { at, interval } => {
+            watch_command(config_path, cli.output_dir.as_deref(), *at, *interval).await?;
+            EXIT_SUCCESS
+        }
+        Command::Process { .. } => {
+            let dates = command.get_dates()?;
+            let _lock = acquire_lock()?;
+            let stats = process_command(&config, cli_defaults, &dates).await?;
+            if fail_fast {
+                report_fail_fast(&stats);
+            }
+            stats_exit_code(&stats, strict)
+        }
+        Command::Status { write_dates, json } => {
+            status_command(&config, write_dates.as_deref(), *json)?;
+            EXIT_SUCCESS
+        }
+        Command::Verify {
+            delete_invalid,
+            redownload,
+        } => {
+            // 只读校验不加锁，删除或重新下载时与其他写入者互斥
+            let _lock = if *delete_invalid || *redownload { Some(acquire_lock()?) } else { None };
+            verify_command(&config, *delete_invalid, *redownload).await?;
+            EXIT_SUCCESS
+        }
+        Command::Repair { dry_run, .. } => {
+            let _lock = if *dry_run { None } else { Some(acquire_lock()?) };
+            let remaining = repair_command(&config, *dry_run, cli_defaults.quiet).await?;
+            if remaining > 0 {
+                EXIT_PARTIAL_FAILURE
+            } else {
+                EXIT_SUCCESS
+            }
+        }
+        Command::Stats { json } => {
+            stats_command(&config, *json)?;
+            EXIT_SUCCESS
+        }
+        Command::Clean {
+            dry_run,
+            keep_unrecognized,
+            backups,
+        } => {
+            let _lock = if *dry_run { None } else { Some(acquire_lock()?) };
+            clean_command(&config, *dry_run, *keep_unrecognized, *backups)?;
+            EXIT_SUCCESS
+        }
+        Command::Prune {
+            keep_days,
+            before,
+            dry_run,
+        } => {
+            let _lock = if *dry_run { None } else { Some(acquire_lock()?) };
+            prune_command(&config, *keep_days, before.as_deref(), *dry_run)?;
+            EXIT_SUCCESS
+        }
+        Command::Dedupe {
+            link,
+            delete,
+            dry_run,
+        } => {
+            let _lock = if *dry_run { None } else { Some(acquire_lock()?) };
+            dedupe_command(&config, link.as_deref(), *delete, *dry_run)?;
+            EXIT_SUCCESS
+        }
+        Command::Rename { from, to, dry_run } => {
+            let _lock = if *dry_run { None } else { Some(acquire_lock()?) };
+            if rename_command(&config, from, to.as_deref(), *dry_run)? {
+                EXIT_PARTIAL_FAILURE
+            } else {
+                EXIT_SUCCESS
+            }
+        }
+        Command::Migrate { dry_run, force } => {
+            let _lock = if *dry_run { None } else { Some(acquire_lock()?) };
+            if migrate_command(&config, *dry_run, *force)? {
+                EXIT_PARTIAL_FAILURE
+            } else {
+                EXIT_SUCCESS
+            }
+        }
+        Command::Touch {
+            all, concurrency, ..
+        } => {
+            let _lock = acquire_lock()?;
+            let stats = touch_command(&config, cli_defaults, *all, *concurrency).await?;
+            stats_exit_code(&stats, strict)
+        }
+        Command::List {
+            format,
+            missing_only,
+            ..
+        } => {
+            list_command(&config, cli_defaults, format, *missing_only)?;
+            EXIT_SUCCESS
+        }
+        Command::Url { path, .. } => {
+            let dates = command.get_dates()?;
+            url_command(&config, &dates, *path)?;
+            EXIT_SUCCESS
+        }
+        Command::Exif {
+            command: ExifCommand::Show { path, date, json },
+        } => {
+            exif_show_command(&config, path.as_deref(), date.as_deref(), *json)?;
+            EXIT_SUCCESS
+        }
+        Command::Exif {
+            command:
+                ExifCommand::Strip {
+                    paths,
+                    date,
+                    all,
+                    keep_dates,
+                    also_reset_mtime,
+                    dry_run,
+                },
+        } => {
+            let _lock = if *dry_run { None } else { Some(acquire_lock()?) };
+            let files = strip_targets(&config, paths, date.as_deref(), *all)?;
+            let failed = exif_strip_command(&files, *keep_dates, *also_reset_mtime, *dry_run)?;
+            if failed > 0 {
+                EXIT_PARTIAL_FAILURE
+            } else {
+                EXIT_SUCCESS
+            }
+        }
+        Command::Retry { file, .. } => {
+            let _lock = acquire_lock()?;
+            let stats = retry_command(&config, cli_defaults, file.as_deref()).await?;
+            if fail_fast {
+                report_fail_fast(&stats);
+            }
+            stats_exit_code(&stats, strict)
+        }
+    };
+
+    Ok(exit_code)
+}
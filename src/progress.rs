@@ -0,0 +1,120 @@
+//! 进度条与日志输出协调
+//!
+//! 进度条绘制在标准输出，日志写到标准错误。两者指向同一终端时，
+//! 日志写入前通过 `ProgressBar::suspend` 暂时擦除进度条，避免日志行撕裂进度条。
+//! `--progress json` 时不显示进度条，改为在标准输出逐行输出 [`ProgressEvent`]。
+
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 当前正在显示的进度条
+static ACTIVE_BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// 创建绘制到标准输出的进度条
+///
+/// `visible` 为 false 时返回隐藏的进度条；可见的进度条会被登记，
+/// 供 [`LogWriter`] 在输出日志时暂停
+pub fn create_bar(len: u64, visible: bool) -> ProgressBar {
+    if !visible {
+        return ProgressBar::hidden();
+    }
+
+    let bar = ProgressBar::with_draw_target(Some(len), ProgressDrawTarget::stdout());
+    *ACTIVE_BAR.lock().unwrap_or_else(|e| e.into_inner()) = Some(bar.clone());
+    bar
+}
+
+/// 结束进度条并取消登记
+pub fn finish_bar(bar: &ProgressBar, message: &'static str) {
+    bar.finish_with_message(message);
+    let mut active = ACTIVE_BAR.lock().unwrap_or_else(|e| e.into_inner());
+    if active.as_ref().is_some_and(|b| b.is_finished()) {
+        *active = None;
+    }
+}
+
+/// 写入标准错误的日志输出，写入期间暂停当前进度条
+///
+/// 用于 `tracing_subscriber::fmt().with_writer(|| LogWriter)`
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let active = ACTIVE_BAR.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match active {
+            Some(bar) if !bar.is_finished() => bar.suspend(|| io::stderr().write_all(buf))?,
+            _ => io::stderr().write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// `--progress json` 输出的进度事件，每个事件序列化为一行 JSON
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum ProgressEvent<'a> {
+    /// 开始处理
+    Start { total: usize },
+    /// 单个日期完成（`result` 为 downloaded 或 skipped）
+    Done {
+        date: &'a str,
+        result: &'a str,
+        path: Option<&'a Path>,
+        /// EXIF 或文件时间戳更新失败的原因
+        #[serde(skip_serializing_if = "Option::is_none")]
+        metadata_error: Option<&'a str>,
+    },
+    /// 单个日期失败
+    Failed { date: &'a str, reason: &'a str },
+    /// 全部结束，数值与 `DownloadStats` 一致
+    Summary {
+        total: usize,
+        succeeded: usize,
+        failed: usize,
+        skipped: usize,
+        cancelled: usize,
+        metadata_failures: usize,
+        metadata_up_to_date: usize,
+    },
+}
+
+/// 在标准输出写入一行进度事件并立即刷新
+pub fn emit_event(event: &ProgressEvent) {
+    let Ok(line) = serde_json::to_string(event) else {
+        return;
+    };
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_event_json() {
+        let json = |event: &ProgressEvent| serde_json::to_string(event).unwrap();
+
+        assert_eq!(json(&ProgressEvent::Start { total: 365 }), r#"{"event":"start","total":365}"#);
+        assert_eq!(
+            json(&ProgressEvent::Done {
+                date: "2024-06-15",
+                result: "downloaded",
+                path: Some(Path::new("images/2024/20240615.jpg")),
+                metadata_error: None,
+            }),
+            r#"{"event":"done","date":"2024-06-15","result":"downloaded","path":"images/2024/20240615.jpg"}"#
+        );
+        assert_eq!(
+            json(&ProgressEvent::Failed { date: "2024-06-16", reason: "HTTP 404" }),
+            r#"{"event":"failed","date":"2024-06-16","reason":"HTTP 404"}"#
+        );
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! 用于验证下载的图片是否完整和有效。
 
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use crate::error::{AppError, Result};
 
@@ -14,6 +15,182 @@ pub enum ValidationResult {
     Invalid(String),
 }
 
+/// 非图片类型（PDF、视频等）的文件大小上限：2GB
+const MAX_MEDIA_FILE_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// 支持的图片扩展名
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "tiff", "tif"];
+
+/// 常见非图片媒体的扩展名及对应的 Content-Type
+const MEDIA_TYPES: &[(&str, &str)] = &[
+    ("pdf", "application/pdf"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mov", "video/quicktime"),
+    ("mp3", "audio/mpeg"),
+];
+
+/// 识别文件格式时读取的文件头字节数
+const SIGNATURE_LEN: u64 = 16;
+
+/// 检查 JPEG 结束标记时读取的文件末尾字节数
+///
+/// 有些编码器会在结束标记之后追加元数据或填充，因此检查末尾一段而不只是最后两个字节
+const EOI_SCAN_LEN: u64 = 4096;
+
+/// JPEG 结束标记（EOI）
+const JPEG_EOI: [u8; 2] = [0xFF, 0xD9];
+
+/// 按文件头识别的图片格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    WebP,
+    Bmp,
+    Tiff,
+}
+
+impl ImageFormat {
+    /// 扩展名（忽略大小写）对应的格式
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "png" => Some(Self::Png),
+            "gif" => Some(Self::Gif),
+            "webp" => Some(Self::WebP),
+            "bmp" => Some(Self::Bmp),
+            "tiff" | "tif" => Some(Self::Tiff),
+            _ => None,
+        }
+    }
+
+    /// 根据文件开头的魔数识别格式，无法识别时返回 `None`
+    ///
+    /// # 示例
+    /// ```
+    /// # use calendar::validator::ImageFormat;
+    /// assert_eq!(ImageFormat::detect(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(ImageFormat::Jpeg));
+    /// assert_eq!(ImageFormat::detect(b"<!DOCTYPE html>"), None);
+    /// ```
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if header.len() >= 12 && header.starts_with(b"RIFF") && &header[8..12] == b"WEBP" {
+            Some(Self::WebP)
+        } else if header.starts_with(b"BM") {
+            Some(Self::Bmp)
+        } else if header.starts_with(b"II*\0") || header.starts_with(b"MM\0*") {
+            Some(Self::Tiff)
+        } else {
+            None
+        }
+    }
+
+    /// 格式名称，用于错误信息
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Jpeg => "JPEG",
+            Self::Png => "PNG",
+            Self::Gif => "GIF",
+            Self::WebP => "WebP",
+            Self::Bmp => "BMP",
+            Self::Tiff => "TIFF",
+        }
+    }
+}
+
+/// 读取文件开头的若干字节，文件较短时返回全部内容
+fn read_header(path: &Path) -> Result<Vec<u8>> {
+    let file = std::fs::File::open(path).map_err(|e| AppError::file_error(path, e.to_string()))?;
+    let mut header = Vec::with_capacity(SIGNATURE_LEN as usize);
+    file.take(SIGNATURE_LEN)
+        .read_to_end(&mut header)
+        .map_err(|e| AppError::file_error(path, e.to_string()))?;
+    Ok(header)
+}
+
+/// JPEG 文件末尾一段中是否有结束标记
+///
+/// 结束标记之后可能有填充字节或编码器追加的元数据，只要这一段中出现 `FF D9` 即视为完整。
+/// 扫描数据中的 `FF` 后只会跟 `00` 或复位标记，因此被截断的文件末尾不会出现 `FF D9`。
+fn has_jpeg_eoi(path: &Path, file_size: u64) -> Result<bool> {
+    let io_error = |e: std::io::Error| AppError::file_error(path, e.to_string());
+    let mut file = std::fs::File::open(path).map_err(io_error)?;
+    let start = file_size.saturating_sub(EOI_SCAN_LEN);
+    file.seek(SeekFrom::Start(start)).map_err(io_error)?;
+    let mut tail = Vec::with_capacity((file_size - start) as usize);
+    file.read_to_end(&mut tail).map_err(io_error)?;
+    Ok(tail.windows(2).any(|window| window == JPEG_EOI))
+}
+
+/// 文件扩展名是否为支持的图片格式（忽略大小写）
+pub fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// 判断 Content-Type 是否在允许列表中
+///
+/// 支持 `image/*` 形式的通配符，忽略大小写和 `; charset=...` 等参数。
+pub fn content_type_allowed(content_type: &str, allowed: &[String]) -> bool {
+    let mime = essence(content_type);
+    allowed.iter().any(|pattern| {
+        let pattern = pattern.trim().to_lowercase();
+        if pattern == "*/*" || pattern == "*" {
+            return true;
+        }
+        match pattern.strip_suffix("/*") {
+            Some(main_type) => mime
+                .split('/')
+                .next()
+                .is_some_and(|t| t == main_type),
+            None => mime == pattern,
+        }
+    })
+}
+
+/// 判断 Content-Type 是否为图片类型
+pub fn is_image_content_type(content_type: &str) -> bool {
+    essence(content_type).starts_with("image/")
+}
+
+/// Content-Type 对应的 `{ext}` 扩展名，不是常见图片或媒体类型时返回 `None`
+pub fn content_type_extension(content_type: &str) -> Option<&'static str> {
+    let mime = essence(content_type);
+    match mime.as_str() {
+        "image/jpeg" | "image/jpg" | "image/pjpeg" => Some("jpg"),
+        "image/png" => Some("png"),
+        "image/webp" => Some("webp"),
+        "image/gif" => Some("gif"),
+        _ => MEDIA_TYPES.iter().find(|(_, media)| *media == mime).map(|(ext, _)| *ext),
+    }
+}
+
+/// 文件扩展名对应的非图片媒体 Content-Type（忽略大小写），图片或未知扩展名返回 `None`
+pub fn media_content_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    MEDIA_TYPES
+        .iter()
+        .find(|(media_ext, _)| *media_ext == ext)
+        .map(|(_, content_type)| *content_type)
+}
+
+/// 提取 MIME 类型主体（去掉参数并转为小写）
+fn essence(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase()
+}
+
 /// 图片验证器
 pub struct ImageValidator;
 
@@ -26,6 +203,17 @@ impl ImageValidator {
     /// # 返回
     /// 返回验证结果
     pub fn validate(path: &Path) -> Result<ValidationResult> {
+        Self::validate_as(path, path)
+    }
+
+    /// 验证图片文件，按 `name` 的扩展名检查格式
+    ///
+    /// 用于验证尚未重命名为目标文件名的临时文件
+    ///
+    /// # 参数
+    /// - `path`: 实际读取的文件路径
+    /// - `name`: 决定预期格式的文件名（通常为目标路径）
+    pub fn validate_as(path: &Path, name: &Path) -> Result<ValidationResult> {
         // 检查文件是否存在
         if !path.exists() {
             return Ok(ValidationResult::Invalid("文件不存在".to_string()));
@@ -40,10 +228,9 @@ impl ImageValidator {
         }
 
         // 检查文件扩展名
-        if let Some(ext) = path.extension() {
+        if let Some(ext) = name.extension() {
             let ext_lower = ext.to_string_lossy().to_lowercase();
-            let valid_extensions = ["jpg", "jpeg", "png", "gif", "webp", "bmp", "tiff", "tif"];
-            if !valid_extensions.contains(&ext_lower.as_str()) {
+            if !IMAGE_EXTENSIONS.contains(&ext_lower.as_str()) {
                 return Ok(ValidationResult::Invalid(format!("不支持的文件格式: {}", ext_lower)));
             }
         }
@@ -57,8 +244,80 @@ impl ImageValidator {
             return Ok(ValidationResult::Invalid("文件过大".to_string()));
         }
 
+        // 检查文件头与扩展名是否一致，避免把 HTML 错误页面等当作图片；没有扩展名时只要求能识别
+        let expected = name
+            .extension()
+            .and_then(|ext| ImageFormat::from_extension(&ext.to_string_lossy()));
+        let detected = ImageFormat::detect(&read_header(path)?);
+        let expected_name = expected.map_or("图片", ImageFormat::name);
+        match detected {
+            None => {
+                return Ok(ValidationResult::Invalid(format!(
+                    "文件内容不是图片: 应为 {}，实际为无法识别的格式",
+                    expected_name
+                )));
+            }
+            Some(detected) if expected.is_some_and(|expected| expected != detected) => {
+                return Ok(ValidationResult::Invalid(format!(
+                    "文件内容与扩展名不符: 应为 {}，实际为 {}",
+                    expected_name,
+                    detected.name()
+                )));
+            }
+            Some(_) => {}
+        }
+
+        // 下载中断的 JPEG 缺少末尾的结束标记
+        if expected == Some(ImageFormat::Jpeg) && !has_jpeg_eoi(path, file_size)? {
+            return Ok(ValidationResult::Invalid("JPEG 文件被截断".to_string()));
+        }
+
         Ok(ValidationResult::Valid)
     }
+
+    /// 验证输出目录中已有的文件
+    ///
+    /// 扩展名对应的非图片类型（PDF、视频等）在 `allowed` 允许列表中时，
+    /// 按 [`validate_download`](Self::validate_download) 的放宽规则检查，避免把正常下载的文件当作损坏的图片；
+    /// 其他文件按图片验证。
+    pub fn validate_existing(path: &Path, allowed: &[String]) -> Result<ValidationResult> {
+        match media_content_type(path) {
+            Some(ct) if content_type_allowed(ct, allowed) => Self::validate_download(path, Some(ct)),
+            _ => Self::validate(path),
+        }
+    }
+
+    /// 根据响应的 Content-Type 验证下载的文件
+    ///
+    /// 图片类型（或未知类型）使用 `validate` 的完整规则；
+    /// 其他类型（PDF、视频等）只检查文件存在且非空，并放宽大小上限。
+    pub fn validate_download(path: &Path, content_type: Option<&str>) -> Result<ValidationResult> {
+        Self::validate_download_as(path, path, content_type)
+    }
+
+    /// 根据响应的 Content-Type 验证下载的文件，按 `name` 的扩展名检查图片格式
+    pub fn validate_download_as(
+        path: &Path,
+        name: &Path,
+        content_type: Option<&str>,
+    ) -> Result<ValidationResult> {
+        match content_type {
+            Some(ct) if !is_image_content_type(ct) => {
+                let file_size = match std::fs::metadata(path) {
+                    Ok(metadata) => metadata.len(),
+                    Err(_) => return Ok(ValidationResult::Invalid("文件不存在".to_string())),
+                };
+                if file_size == 0 {
+                    return Ok(ValidationResult::Invalid("文件为空".to_string()));
+                }
+                if file_size > MAX_MEDIA_FILE_SIZE {
+                    return Ok(ValidationResult::Invalid("文件过大".to_string()));
+                }
+                Ok(ValidationResult::Valid)
+            }
+            _ => Self::validate_as(path, name),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -91,13 +350,209 @@ mod tests {
         assert!(matches!(result.unwrap(), ValidationResult::Invalid(_)));
     }
 
+    /// 以指定文件头开头、以 JPEG 结束标记结尾、总长 2KB 的文件
+    fn fixture(suffix: &str, header: &[u8]) -> NamedTempFile {
+        let mut data = header.to_vec();
+        data.resize(2046, 0);
+        data.extend_from_slice(&JPEG_EOI);
+        fixture_data(suffix, &data)
+    }
+
+    fn fixture_data(suffix: &str, data: &[u8]) -> NamedTempFile {
+        let temp_file = NamedTempFile::with_suffix(suffix).unwrap();
+        std::fs::write(temp_file.path(), data).unwrap();
+        temp_file
+    }
+
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00];
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR";
+    const WEBP: &[u8] = b"RIFF\x24\x08\0\0WEBPVP8 ";
+
     #[test]
     fn test_validate_valid_size_file() {
-        let temp_file = NamedTempFile::with_suffix(".jpg").unwrap();
-        let data = vec![0u8; 2048]; // 2KB
-        std::fs::write(temp_file.path(), data).unwrap();
+        let temp_file = fixture(".jpg", JPEG);
         let result = ImageValidator::validate(temp_file.path());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), ValidationResult::Valid);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_validate_signatures() {
+        let fixtures: [(&str, &[u8]); 9] = [
+            (".jpg", JPEG),
+            (".JPEG", JPEG),
+            (".png", PNG),
+            (".gif", b"GIF87a\x10\0\x10\0"),
+            (".gif", b"GIF89a\x10\0\x10\0"),
+            (".webp", WEBP),
+            (".bmp", b"BM\x00\x08\0\0"),
+            (".tif", b"II*\0\x08\0\0\0"),
+            (".tiff", b"MM\0*\0\0\0\x08"),
+        ];
+        for (suffix, header) in fixtures {
+            let temp_file = fixture(suffix, header);
+            assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), ValidationResult::Valid, "{}", suffix);
+        }
+
+        // 扩展名与内容不符
+        let temp_file = fixture(".jpg", PNG);
+        assert_eq!(
+            ImageValidator::validate(temp_file.path()).unwrap(),
+            ValidationResult::Invalid("文件内容与扩展名不符: 应为 JPEG，实际为 PNG".to_string())
+        );
+        let temp_file = fixture(".png", WEBP);
+        assert_eq!(
+            ImageValidator::validate(temp_file.path()).unwrap(),
+            ValidationResult::Invalid("文件内容与扩展名不符: 应为 PNG，实际为 WebP".to_string())
+        );
+
+        // 以 .jpg 保存的 HTML 错误页面
+        let temp_file = fixture(".jpg", b"<!DOCTYPE html><html><body>404 Not Found");
+        assert_eq!(
+            ImageValidator::validate(temp_file.path()).unwrap(),
+            ValidationResult::Invalid("文件内容不是图片: 应为 JPEG，实际为无法识别的格式".to_string())
+        );
+
+        // RIFF 容器但不是 WebP（如 WAV）
+        let temp_file = fixture(".webp", b"RIFF\x24\x08\0\0WAVEfmt ");
+        assert!(matches!(ImageValidator::validate(temp_file.path()).unwrap(), ValidationResult::Invalid(_)));
+
+        // 没有扩展名时只要求能识别
+        let temp_file = fixture("", PNG);
+        assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), ValidationResult::Valid);
+    }
+
+    /// 2KB 以上的 JPEG 扫描数据（不含 `FF D9`）
+    fn jpeg_body(len: usize) -> Vec<u8> {
+        let mut data = JPEG.to_vec();
+        data.extend((0..len).map(|i| (i % 251) as u8 & 0x7F));
+        data
+    }
+
+    #[test]
+    fn test_validate_jpeg_eoi() {
+        // 完整的文件
+        let mut data = jpeg_body(8192);
+        data.extend_from_slice(&JPEG_EOI);
+        let temp_file = fixture_data(".jpg", &data);
+        assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), ValidationResult::Valid);
+
+        // 结束标记之后有填充和追加的元数据
+        data.extend(std::iter::repeat_n(0u8, 512));
+        data.extend_from_slice(b"trailing metadata");
+        let temp_file = fixture_data(".jpeg", &data);
+        assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), ValidationResult::Valid);
+
+        // 下载中断，缺少结束标记（包括末尾恰好是 FF 的情况）
+        let truncated = ValidationResult::Invalid("JPEG 文件被截断".to_string());
+        let temp_file = fixture_data(".jpg", &jpeg_body(8192));
+        assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), truncated);
+        let mut data = jpeg_body(8192);
+        data.push(0xFF);
+        let temp_file = fixture_data(".jpg", &data);
+        assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), truncated);
+
+        // 结束标记早于末尾 4KB 时视为截断后追加的数据
+        let mut data = jpeg_body(2048);
+        data.extend_from_slice(&JPEG_EOI);
+        data.extend(std::iter::repeat_n(0x20u8, EOI_SCAN_LEN as usize));
+        let temp_file = fixture_data(".jpg", &data);
+        assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), truncated);
+
+        // 其他格式不检查
+        let mut data = PNG.to_vec();
+        data.resize(8192, 1);
+        let temp_file = fixture_data(".png", &data);
+        assert_eq!(ImageValidator::validate(temp_file.path()).unwrap(), ValidationResult::Valid);
+    }
+
+    #[test]
+    fn test_read_header_reads_prefix_only() {
+        let temp_file = fixture(".jpg", JPEG);
+        assert_eq!(read_header(temp_file.path()).unwrap().len(), SIGNATURE_LEN as usize);
+
+        let mut short = NamedTempFile::new().unwrap();
+        short.write_all(b"GIF").unwrap();
+        assert_eq!(read_header(short.path()).unwrap(), b"GIF");
+        assert_eq!(ImageFormat::detect(b"GIF"), None);
+    }
+
+    #[test]
+    fn test_has_image_extension() {
+        assert!(has_image_extension(Path::new("2024/20240101.jpg")));
+        assert!(has_image_extension(Path::new("20240101.JPEG")));
+        assert!(!has_image_extension(Path::new("failed_downloads.txt")));
+        assert!(!has_image_extension(Path::new("README")));
+    }
+
+    #[test]
+    fn test_content_type_allowed() {
+        let allowed = vec![
+            "image/*".to_string(),
+            "application/pdf".to_string(),
+            "video/mp4".to_string(),
+        ];
+        assert!(content_type_allowed("image/jpeg", &allowed));
+        assert!(content_type_allowed("IMAGE/PNG", &allowed));
+        assert!(content_type_allowed("application/pdf", &allowed));
+        assert!(content_type_allowed("video/mp4; codecs=avc1", &allowed));
+        assert!(!content_type_allowed("text/html; charset=utf-8", &allowed));
+        assert!(!content_type_allowed("video/webm", &allowed));
+    }
+
+    #[test]
+    fn test_is_image_content_type() {
+        assert!(is_image_content_type("image/jpeg"));
+        assert!(!is_image_content_type("application/pdf"));
+    }
+
+    #[test]
+    fn test_content_type_extension() {
+        assert_eq!(content_type_extension("image/jpeg"), Some("jpg"));
+        assert_eq!(content_type_extension("Image/WebP; charset=binary"), Some("webp"));
+        assert_eq!(content_type_extension("image/png"), Some("png"));
+        assert_eq!(content_type_extension("image/gif"), Some("gif"));
+        assert_eq!(content_type_extension("image/bmp"), None);
+        assert_eq!(content_type_extension("application/pdf"), Some("pdf"));
+        assert_eq!(content_type_extension("Video/MP4"), Some("mp4"));
+        assert_eq!(content_type_extension("application/octet-stream"), None);
+    }
+
+    #[test]
+    fn test_validate_download_non_image() {
+        let temp_file = NamedTempFile::with_suffix(".pdf").unwrap();
+        std::fs::write(temp_file.path(), b"%PDF-1.4").unwrap();
+
+        // PDF 不受图片扩展名和最小尺寸限制
+        let result = ImageValidator::validate_download(temp_file.path(), Some("application/pdf"));
+        assert_eq!(result.unwrap(), ValidationResult::Valid);
+
+        // 按图片规则验证则不通过
+        let result = ImageValidator::validate_download(temp_file.path(), Some("image/jpeg"));
+        assert!(matches!(result.unwrap(), ValidationResult::Invalid(_)));
+    }
+
+    #[test]
+    fn test_validate_existing() {
+        let pdf = fixture_data(".PDF", b"%PDF-1.4");
+        let images = vec!["image/*".to_string()];
+        let with_pdf = vec!["image/*".to_string(), "application/pdf".to_string()];
+
+        // 只有允许的非图片类型才放宽检查
+        assert_eq!(
+            ImageValidator::validate_existing(pdf.path(), &with_pdf).unwrap(),
+            ValidationResult::Valid
+        );
+        assert!(matches!(
+            ImageValidator::validate_existing(pdf.path(), &images).unwrap(),
+            ValidationResult::Invalid(_)
+        ));
+
+        // 图片仍按图片规则验证
+        let jpg = fixture_data(".jpg", b"%PDF-1.4");
+        assert!(matches!(
+            ImageValidator::validate_existing(jpg.path(), &with_pdf).unwrap(),
+            ValidationResult::Invalid(_)
+        ));
+    }
+}